@@ -1,2 +1,5 @@
+pub mod retry;
+pub mod source;
+pub mod time;
 #[macro_use]
 pub mod util;
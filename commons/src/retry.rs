@@ -0,0 +1,162 @@
+use std::{
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use tokio::time::sleep;
+use tracing as log;
+
+/// The decision for the error of a failed attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retry {
+    /// Return the error
+    Stop,
+    /// Try again after the backoff of the policy
+    Backoff,
+    /// Try again right away, since the operation already waited, such as for a rate limit
+    Immediately,
+}
+
+impl From<bool> for Retry {
+    fn from(retry: bool) -> Self {
+        if retry {
+            Self::Backoff
+        } else {
+            Self::Stop
+        }
+    }
+}
+
+/// How often an operation is attempted, and how long to wait between the attempts.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Backoff after the first attempt, which doubles with each attempt
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Fraction of the backoff which is randomized, so clients which failed together don't retry together
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// The backoff after the failed attempt, starting at 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let backoff = self.min_backoff.saturating_mul(factor).min(self.max_backoff);
+        if self.jitter <= 0.0 {
+            return backoff;
+        }
+
+        // Each RandomState has random keys, which is enough to spread out the retries
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        backoff.mul_f64(1.0 - self.jitter.min(1.0) * random)
+    }
+}
+
+/// Runs the operation until it succeeds, `retry_on` decides to stop, or the attempts are used up.
+///
+/// The error of the last attempt is returned.
+pub async fn retry<T, E, F, Fut, P, R>(policy: &RetryPolicy, mut op: F, mut retry_on: P) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: FnMut(&E) -> R,
+    R: Into<Retry>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match op().await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+
+        let decision = retry_on(&error).into();
+        if decision == Retry::Stop || attempt >= policy.max_attempts {
+            return Err(error);
+        }
+        if decision == Retry::Backoff {
+            let backoff = policy.backoff(attempt);
+            log::warn!("Retrying in {:.1} seconds...", backoff.as_secs_f32());
+            sleep(backoff).await;
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 4,
+        min_backoff: Duration::from_secs(1),
+        max_backoff: Duration::from_secs(5),
+        jitter: 0.0,
+    };
+
+    #[test]
+    fn test_backoff() {
+        let backoffs: Vec<u64> = (1..=5).map(|attempt| POLICY.backoff(attempt).as_secs()).collect();
+        assert_eq!(backoffs, vec![1, 2, 4, 5, 5]);
+
+        let policy = RetryPolicy { jitter: 0.5, ..POLICY };
+        for attempt in 1..=5 {
+            let backoff = policy.backoff(attempt);
+            assert!(backoff <= POLICY.backoff(attempt) && backoff >= POLICY.backoff(attempt) / 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let policy = RetryPolicy {
+            min_backoff: Duration::ZERO,
+            ..POLICY
+        };
+
+        let attempts = &Cell::new(0);
+        let result = retry(
+            &policy,
+            || async move {
+                attempts.set(attempts.get() + 1);
+                if attempts.get() < 3 {
+                    Err("server error")
+                } else {
+                    Ok(attempts.get())
+                }
+            },
+            |_| true,
+        )
+        .await;
+        assert_eq!(result, Ok(3));
+
+        attempts.set(0);
+        let result: Result<(), _> = retry(
+            &policy,
+            || async move {
+                attempts.set(attempts.get() + 1);
+                Err("rate limited")
+            },
+            |_| Retry::Immediately,
+        )
+        .await;
+        assert_eq!(result, Err("rate limited"));
+        assert_eq!(attempts.get(), 4);
+
+        attempts.set(0);
+        let result: Result<(), _> = retry(
+            &policy,
+            || async move {
+                attempts.set(attempts.get() + 1);
+                Err("not found")
+            },
+            |error| *error != "not found",
+        )
+        .await;
+        assert_eq!(result, Err("not found"));
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+
+/// A platform independent snapshot of a live stream.
+#[derive(Clone, Debug)]
+pub struct LiveStream {
+    /// The platform specific id of the stream
+    pub id: Box<str>,
+    /// The platform specific id of the channel
+    pub user_id: Box<str>,
+    /// The channel identifier, as configured by the user
+    pub user_login: Box<str>,
+    /// The display name of the channel
+    pub user_name: Box<str>,
+    pub title: Box<str>,
+    /// The game or category, empty if unknown
+    pub category: Box<str>,
+    /// The resolved preview image, empty if unavailable
+    pub thumbnail_url: Box<str>,
+    pub started_at: eos::DateTime,
+    pub viewer_count: u32,
+}
+
+/// The recording of a stream, once it ended.
+#[derive(Clone, Debug)]
+pub struct SourceVideo {
+    pub id: Box<str>,
+    pub title: Box<str>,
+    pub url: Box<str>,
+    /// The resolved preview image, empty if unavailable
+    pub thumbnail_url: Box<str>,
+    /// The length of the video in seconds
+    pub duration: u64,
+}
+
+/// A platform which can be polled for live streams.
+#[async_trait]
+pub trait StreamSource: Send + Sync {
+    /// The display name of the platform, such as "YouTube"
+    fn platform(&self) -> &'static str;
+
+    /// The brand color of the platform, for the embeds of its notifications
+    fn color(&self) -> u32;
+
+    /// The link to the stream of the channel, for its notifications.
+    fn stream_url(&self, channel: &str, stream_id: &str) -> String;
+
+    /// Fetches all currently live streams for the provided channels.
+    async fn get_live_streams(&self, channels: &[Box<str>]) -> anyhow::Result<Vec<LiveStream>>;
+
+    /// Fetches the recording of an ended stream, or `None` if the platform did not keep one.
+    async fn get_video(&self, _channel: &str, _stream_id: &str) -> anyhow::Result<Option<SourceVideo>> {
+        Ok(None)
+    }
+
+    /// Downloads the preview image of a stream or video, which fails for platforms without downloads.
+    async fn get_thumbnail(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        anyhow::bail!("Thumbnail downloads are not supported by {}: {url}", self.platform())
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::{self, Display};
+
+use serde::Deserialize;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A UTC offset like `+02:00`, since named time zones would require a time zone database.
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(try_from = "String")]
+pub struct UtcOffset {
+    /// Offset in minutes, east of UTC
+    minutes: i32,
+}
+
+impl UtcOffset {
+    pub const fn minutes(&self) -> i32 {
+        self.minutes
+    }
+}
+
+impl TryFrom<String> for UtcOffset {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.eq_ignore_ascii_case("utc") || value == "Z" {
+            return Ok(Self::default());
+        }
+
+        let invalid = || format!("invalid utc offset {value:?}, expected a value like \"+02:00\"");
+        let (sign, offset) = match value.chars().next() {
+            Some('+') => (1, value.trim_start_matches('+')),
+            Some('-') => (-1, value.trim_start_matches('-')),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 14 || minutes >= 60 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            minutes: sign * (hours * 60 + minutes),
+        })
+    }
+}
+
+impl Display for UtcOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.minutes < 0 { '-' } else { '+' };
+        let (hours, minutes) = (self.minutes.abs() / 60, self.minutes.abs() % 60);
+        match (hours, minutes) {
+            (0, 0) => write!(f, "UTC"),
+            (hours, 0) => write!(f, "UTC{sign}{hours}"),
+            (hours, minutes) => write!(f, "UTC{sign}{hours}:{minutes:02}"),
+        }
+    }
+}
+
+/// How dates and durations are written, which depends on the language of the notifications.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum DateStyle {
+    /// Like `Jan 5, 2023, 2:05 PM` and `2h 5m`
+    #[default]
+    English,
+    /// Like `5.1.2023, 14:05` and `2 h 5 min`
+    Dotted,
+    /// Like `05/01/2023 14:05` and `2 h 5 min`
+    Slashed,
+}
+
+/// Writes the times and durations of notifications in the display timezone.
+///
+/// Without an offset, times are written as discord timestamps, which every client shows in its own timezone.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TimeFormat {
+    pub offset: Option<UtcOffset>,
+    pub style: DateStyle,
+}
+
+impl TimeFormat {
+    /// The date and time of the unix timestamp, like `Jan 5, 2023, 2:05 PM UTC+2`.
+    pub fn datetime(&self, timestamp: i64) -> String {
+        let Some(offset) = self.offset else {
+            return format!("<t:{timestamp}:F>");
+        };
+
+        let local = timestamp + i64::from(offset.minutes) * 60;
+        let (year, month, day) = civil_from_days(local.div_euclid(86400));
+        let seconds = local.rem_euclid(86400);
+        let (hour, minute) = (seconds / 3600, seconds / 60 % 60);
+        match self.style {
+            DateStyle::English => {
+                let (hour, suffix) = match hour {
+                    0 => (12, "AM"),
+                    1..=11 => (hour, "AM"),
+                    12 => (12, "PM"),
+                    _ => (hour - 12, "PM"),
+                };
+                let month = MONTHS[month as usize - 1];
+                format!("{month} {day}, {year}, {hour}:{minute:02} {suffix} {offset}")
+            }
+            DateStyle::Dotted => format!("{day}.{month}.{year}, {hour:02}:{minute:02} {offset}"),
+            DateStyle::Slashed => format!("{day:02}/{month:02}/{year} {hour:02}:{minute:02} {offset}"),
+        }
+    }
+
+    /// The duration in seconds, like `2h 5m`.
+    ///
+    /// Without a display timezone, durations keep the format of twitch, like `02h05m03s`.
+    pub fn duration(&self, seconds: u32) -> String {
+        let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+        match (self.offset, self.style, hours) {
+            (None, ..) => format!("{hours:02}h{minutes:02}m{:02}s", seconds % 60),
+            (_, DateStyle::English, 0) => format!("{minutes}m"),
+            (_, DateStyle::English, _) => format!("{hours}h {minutes}m"),
+            (_, _, 0) => format!("{minutes} min"),
+            (_, _, _) => format!("{hours} h {minutes} min"),
+        }
+    }
+}
+
+/// The year, month, and day of the days since the unix epoch, in the proleptic gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset() {
+        let offset = |text: &str| UtcOffset::try_from(text.to_owned());
+        assert_eq!(offset("UTC").map(|offset| offset.minutes()), Ok(0));
+        assert_eq!(offset("+02:00").map(|offset| offset.minutes()), Ok(120));
+        assert_eq!(offset("-5").map(|offset| offset.minutes()), Ok(-300));
+        assert!(offset("Europe/Berlin").is_err());
+        assert!(offset("+25").is_err());
+
+        assert_eq!(UtcOffset { minutes: 120 }.to_string(), "UTC+2");
+        assert_eq!(UtcOffset { minutes: -330 }.to_string(), "UTC-5:30");
+        assert_eq!(UtcOffset::default().to_string(), "UTC");
+    }
+
+    #[test]
+    fn test_format() {
+        // 2023-01-01T00:00:00Z
+        let timestamp = 1_672_531_200;
+        let format = |offset: Option<i32>, style| TimeFormat {
+            offset: offset.map(|minutes| UtcOffset { minutes }),
+            style,
+        };
+
+        assert_eq!(format(None, DateStyle::English).datetime(timestamp), "<t:1672531200:F>");
+        assert_eq!(
+            format(Some(120), DateStyle::English).datetime(timestamp),
+            "Jan 1, 2023, 2:00 AM UTC+2"
+        );
+        assert_eq!(
+            format(Some(-300), DateStyle::English).datetime(timestamp),
+            "Dec 31, 2022, 7:00 PM UTC-5"
+        );
+        assert_eq!(
+            format(Some(60), DateStyle::Dotted).datetime(timestamp + 3600 * 13),
+            "1.1.2023, 14:00 UTC+1"
+        );
+        assert_eq!(
+            format(Some(0), DateStyle::Slashed).datetime(timestamp + 86400 * 40),
+            "10/02/2023 00:00 UTC"
+        );
+
+        assert_eq!(format(None, DateStyle::English).duration(3723), "01h02m03s");
+        assert_eq!(format(Some(0), DateStyle::English).duration(3723), "1h 2m");
+        assert_eq!(format(Some(0), DateStyle::Dotted).duration(300), "5 min");
+    }
+}
@@ -1,7 +1,7 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::{Deserialize, Serialize};
-use std::{num::NonZeroU64, ops::Add};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+use std::{borrow::Cow, num::NonZeroU64, ops::Add, time::Duration};
 
 #[macro_export]
 macro_rules! resolve {
@@ -27,6 +27,11 @@ impl Timestamp {
             ))
         }
     }
+
+    #[inline]
+    pub const fn as_secs(&self) -> u64 {
+        self.0.get()
+    }
 }
 
 impl Add<u64> for Timestamp {
@@ -37,6 +42,65 @@ impl Add<u64> for Timestamp {
     }
 }
 
+/// Parses a duration like `90s`, `5m`, or `1h30m`.
+///
+/// Supported units are `s`, `m`, `h`, and `d`. Every number needs a unit.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut total = 0u64;
+    let mut value: Option<u64> = None;
+    for c in text.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            value = Some(value.unwrap_or(0).checked_mul(10)?.checked_add(u64::from(digit))?);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return None,
+        };
+        total = total.checked_add(value.take()?.checked_mul(unit)?)?;
+    }
+
+    if value.is_some() {
+        return None;
+    }
+    Some(Duration::from_secs(total))
+}
+
+/// A duration in the config, written like `"90s"` or `"5m"`.
+///
+/// Plain numbers are minutes, for compatibility with older configs.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct ConfigDuration(pub Duration);
+
+impl<'de> Deserialize<'de> for ConfigDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Minutes(u64),
+            Text(Box<str>),
+        }
+
+        match Value::deserialize(deserializer)? {
+            Value::Minutes(minutes) => Ok(Self(Duration::from_secs(minutes * 60))),
+            Value::Text(text) => parse_duration(&text).map(Self).ok_or_else(|| {
+                D::Error::custom(format!(
+                    "invalid duration {text:?}, expected a value like \"90s\" or \"5m\""
+                ))
+            }),
+        }
+    }
+}
+
 // [0x1f600,0x1f603,0x1f604,...,0x1f1f3]
 const EMOJI_LIST: [u32; 9280] = include!("../../emojis.txt");
 
@@ -52,6 +116,50 @@ pub fn sanitize_link_title(text: &str) -> String {
         .to_owned()
 }
 
+/// How strictly user-controlled text, such as game names, is escaped in messages.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum EscapeMode {
+    /// Send the text as is
+    #[serde(rename = "none")]
+    None,
+    /// Only prevent mentions, like `@everyone`
+    #[serde(rename = "mentions")]
+    Mentions,
+    /// Prevent mentions and show markdown as plain text
+    #[default]
+    #[serde(rename = "markdown")]
+    Markdown,
+}
+
+/// Escapes the text so it shows up as written in discord messages, instead of being rendered as markdown.
+///
+/// Mentions are broken up with a zero-width space, since escaping does not reliably prevent pings.
+pub fn escape_markdown(text: &str, mode: EscapeMode) -> Cow<'_, str> {
+    if mode == EscapeMode::None || !text.contains(|c: char| c == '@' || is_markdown(c)) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '@' => escaped.push_str("@\u{200B}"),
+            c if mode == EscapeMode::Markdown && is_markdown(c) => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+const fn is_markdown(c: char) -> bool {
+    matches!(
+        c,
+        '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '<' | '#' | '-' | '[' | ']' | '(' | ')'
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,4 +187,45 @@ mod tests {
             "MORE LIKE SPLIT-LATE!!!!"
         )
     }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("90"), None);
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_config_duration() {
+        let parse = |json: &str| serde_json::from_str::<ConfigDuration>(json).map(|d| d.0.as_secs());
+        assert_eq!(parse("2").unwrap(), 120);
+        assert_eq!(parse(r#""90s""#).unwrap(), 90);
+        assert!(parse(r#""soon""#).is_err());
+    }
+
+    #[test]
+    fn test_escape_markdown() {
+        let text = "**Elden Ring** @everyone [click](https://example.com)";
+        assert_eq!(escape_markdown(text, EscapeMode::None), text);
+        assert_eq!(
+            escape_markdown(text, EscapeMode::Mentions),
+            "**Elden Ring** @\u{200B}everyone [click](https://example.com)"
+        );
+        assert_eq!(
+            escape_markdown(text, EscapeMode::Markdown),
+            "\\*\\*Elden Ring\\*\\* @\u{200B}everyone \\[click\\]\\(https://example.com\\)"
+        );
+        assert_eq!(
+            escape_markdown("cool_streamer", EscapeMode::Markdown),
+            r"cool\_streamer"
+        );
+        assert!(matches!(
+            escape_markdown("Just Chatting", EscapeMode::Markdown),
+            Cow::Borrowed(_)
+        ));
+    }
 }
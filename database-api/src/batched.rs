@@ -0,0 +1,217 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::time::sleep;
+use tracing as log;
+
+use super::*;
+use crate::locks::KeyLocks;
+
+#[derive(Default)]
+struct BatchState {
+    /// When each key was last written to the inner database
+    written: HashMap<String, Instant>,
+    /// The latest documents which were not written yet, by key
+    pending: HashMap<String, Value>,
+}
+
+/// Limits the writes of each key to one per interval, by delaying frequent updates of the same document.
+///
+/// The first write of a key is passed through immediately, later writes within the interval only keep the
+/// latest document, which is written by [`flush`](Self::flush). Reads return the pending document, if any.
+pub struct BatchedDatabase<D> {
+    inner: D,
+    interval: Duration,
+    state: Mutex<BatchState>,
+    locks: KeyLocks,
+}
+
+impl<D: Database> BatchedDatabase<D> {
+    /// Delays writes of the same key within the interval, or writes everything immediately for a zero interval.
+    pub fn new(inner: D, interval: Duration) -> Self {
+        Self {
+            inner,
+            interval,
+            state: Mutex::default(),
+            locks: KeyLocks::default(),
+        }
+    }
+
+    pub const fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Writes the pending documents whose interval has passed, or all pending documents if `all` is set.
+    ///
+    /// Documents which could not be written stay pending, so the next flush tries again.
+    pub async fn flush(&self, all: bool) -> Result<(), DatabaseError> {
+        let due: Vec<String> = {
+            let state = self.state.lock().expect("Batch state poisoned");
+            let now = Instant::now();
+            state
+                .pending
+                .keys()
+                .filter(|key| all || state.written.get(*key).map_or(true, |t| now - *t >= self.interval))
+                .cloned()
+                .collect()
+        };
+
+        let mut result = Ok(());
+        for key in due {
+            // Deletes hold the same lock, so a deleted document is not written back
+            let _guard = self.locks.lock(&key).await;
+            let pending = {
+                let state = self.state.lock().expect("Batch state poisoned");
+                state.pending.get(&key).cloned()
+            };
+            let Some(document) = pending else {
+                continue;
+            };
+
+            if let Err(e) = self.inner.save(&key, &document).await {
+                result = Err(e);
+                continue;
+            }
+
+            let mut state = self.state.lock().expect("Batch state poisoned");
+            // A newer document which was saved during the write stays pending
+            if state.pending.get(&key) == Some(&document) {
+                state.pending.remove(&key);
+            }
+            state.written.insert(key, Instant::now());
+        }
+        result
+    }
+
+    /// Writes the document to the inner database if the interval of the key has passed, or keeps it pending.
+    ///
+    /// The caller has to hold the lock of the key, so a pending document can't be written back by a flush.
+    async fn write(&self, key: &str, document: Value) -> Result<(), DatabaseError> {
+        {
+            let mut state = self.state.lock().expect("Batch state poisoned");
+            let now = Instant::now();
+            let due = state.written.get(key).map_or(true, |t| now - *t >= self.interval);
+            if !due {
+                state.pending.insert(key.to_owned(), document);
+                return Ok(());
+            }
+
+            state.written.insert(key.to_owned(), now);
+            state.pending.remove(key);
+        }
+
+        self.inner.save(key, &document).await
+    }
+
+    /// Writes the delayed documents once their interval has passed, failed writes are tried again later.
+    pub async fn run(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+
+        loop {
+            sleep(self.interval).await;
+            if let Err(e) = self.flush(false).await {
+                log::error!("Failed to write delayed cache entries: {e}");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<D: Database> Database for BatchedDatabase<D> {
+    async fn save<V>(&self, key: &str, document: &V) -> Result<(), DatabaseError>
+    where
+        V: Serialize + Send + Sync,
+    {
+        let document = serde_json::to_value(document)?;
+        let _guard = self.locks.lock(key).await;
+        self.write(key, document).await
+    }
+
+    async fn read<V>(&self, key: &str) -> Result<V, DatabaseError>
+    where
+        V: DeserializeOwned + Send + Sync,
+    {
+        let pending = {
+            let state = self.state.lock().expect("Batch state poisoned");
+            state.pending.get(key).cloned()
+        };
+
+        match pending {
+            Some(document) => Ok(serde_json::from_value(document)?),
+            None => self.inner.read(key).await,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), DatabaseError> {
+        let _guard = self.locks.lock(key).await;
+        {
+            let mut state = self.state.lock().expect("Batch state poisoned");
+            state.pending.remove(key);
+            state.written.remove(key);
+        }
+
+        self.inner.delete(key).await
+    }
+
+    async fn update<V, F>(&self, key: &str, f: F) -> Result<V, DatabaseError>
+    where
+        V: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce(Option<V>) -> V + Send,
+    {
+        let _guard = self.locks.lock(key).await;
+        let current = match self.read(key).await {
+            Ok(value) => Some(value),
+            Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        let value = f(current);
+        self.write(key, serde_json::to_value(&value)?).await?;
+        Ok(value)
+    }
+
+    async fn claim(&self, key: &str, owner: &str, lease: Duration) -> Result<bool, DatabaseError> {
+        // Claims have to be visible to the other processes immediately, so they are never delayed
+        self.inner.claim(key, owner, lease).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_batched() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("strumbot-batched-test-{}-{nanos}", std::process::id()));
+        let files = FileDatabase::new(root.to_string_lossy().into_owned());
+        files.setup().await.unwrap();
+
+        let db = BatchedDatabase::new(files, Duration::from_secs(60));
+        db.save("watcher", &1).await.unwrap();
+        db.save("watcher", &2).await.unwrap();
+        db.save("watcher", &3).await.unwrap();
+
+        assert_eq!(db.inner().read::<u32>("watcher").await.unwrap(), 1);
+        assert_eq!(db.read::<u32>("watcher").await.unwrap(), 3);
+
+        db.flush(false).await.unwrap();
+        assert_eq!(db.inner().read::<u32>("watcher").await.unwrap(), 1);
+        db.flush(true).await.unwrap();
+        assert_eq!(db.inner().read::<u32>("watcher").await.unwrap(), 3);
+
+        db.delete("watcher").await.unwrap();
+        assert!(db.read::<u32>("watcher").await.is_err());
+    }
+}
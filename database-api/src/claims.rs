@@ -0,0 +1,64 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// The owner of a key, which holds it until the lease expires.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Claim {
+    pub owner: Box<str>,
+    /// Unix timestamp in seconds
+    pub expires_at: u64,
+}
+
+impl Claim {
+    pub fn new(owner: &str, lease: Duration) -> Self {
+        Self {
+            owner: owner.into(),
+            expires_at: unix_now() + lease.as_secs(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= unix_now()
+    }
+
+    /// The claim after the owner tried to claim the key, which only changes if the current claim is
+    /// held by the same owner or expired.
+    pub fn renew(current: Option<Self>, owner: &str, lease: Duration) -> Self {
+        match current {
+            Some(claim) if claim.owner.as_ref() != owner && !claim.is_expired() => claim,
+            _ => Self::new(owner, lease),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renew() {
+        let lease = Duration::from_secs(60);
+        let first = Claim::renew(None, "replica-a", lease);
+        assert_eq!(first.owner.as_ref(), "replica-a");
+
+        let denied = Claim::renew(Some(first.clone()), "replica-b", lease);
+        assert_eq!(denied, first);
+
+        let expired = Claim {
+            owner: "replica-a".into(),
+            expires_at: 0,
+        };
+        assert!(expired.is_expired());
+        assert_eq!(
+            Claim::renew(Some(expired), "replica-b", lease).owner.as_ref(),
+            "replica-b"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use super::*;
+use crate::locks::{locked_update, KeyLocks};
+
+/// The object safe part of [`Database`], which stores the documents as JSON values.
+#[async_trait]
+pub trait DocumentStore: Send + Sync {
+    async fn save_value(&self, key: &str, document: &Value) -> Result<(), DatabaseError>;
+
+    async fn read_value(&self, key: &str) -> Result<Value, DatabaseError>;
+
+    async fn delete_value(&self, key: &str) -> Result<(), DatabaseError>;
+
+    async fn claim_key(&self, key: &str, owner: &str, lease: Duration) -> Result<bool, DatabaseError>;
+}
+
+#[async_trait]
+impl<D: Database> DocumentStore for D {
+    async fn save_value(&self, key: &str, document: &Value) -> Result<(), DatabaseError> {
+        self.save(key, document).await
+    }
+
+    async fn read_value(&self, key: &str) -> Result<Value, DatabaseError> {
+        self.read(key).await
+    }
+
+    async fn delete_value(&self, key: &str) -> Result<(), DatabaseError> {
+        self.delete(key).await
+    }
+
+    async fn claim_key(&self, key: &str, owner: &str, lease: Duration) -> Result<bool, DatabaseError> {
+        self.claim(key, owner, lease).await
+    }
+}
+
+/// A database of any type, for applications which let the user choose the implementation.
+///
+/// The documents are converted to JSON values, before they are passed to the inner database.
+#[derive(Clone)]
+pub struct DynDatabase {
+    inner: Arc<dyn DocumentStore>,
+    locks: Arc<KeyLocks>,
+}
+
+impl DynDatabase {
+    pub fn new<D: Database + 'static>(inner: Arc<D>) -> Self {
+        Self {
+            inner,
+            locks: Arc::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Database for DynDatabase {
+    async fn save<V>(&self, key: &str, document: &V) -> Result<(), DatabaseError>
+    where
+        V: Serialize + Send + Sync,
+    {
+        let document = serde_json::to_value(document)?;
+        self.inner.save_value(key, &document).await
+    }
+
+    async fn read<V>(&self, key: &str) -> Result<V, DatabaseError>
+    where
+        V: DeserializeOwned + Send + Sync,
+    {
+        let document = self.inner.read_value(key).await?;
+        Ok(serde_json::from_value(document)?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), DatabaseError> {
+        self.inner.delete_value(key).await
+    }
+
+    async fn update<V, F>(&self, key: &str, f: F) -> Result<V, DatabaseError>
+    where
+        V: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce(Option<V>) -> V + Send,
+    {
+        locked_update(self, &self.locks, key, f).await
+    }
+
+    async fn claim(&self, key: &str, owner: &str, lease: Duration) -> Result<bool, DatabaseError> {
+        // The inner database knows whether the claim is shared with other processes
+        self.inner.claim_key(key, owner, lease).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dynamic() {
+        let root = std::env::temp_dir().join("strumbot-dynamic-test");
+        let files = FileDatabase::new(root.to_string_lossy().into_owned());
+        files.setup().await.unwrap();
+
+        let db = DynDatabase::new(Arc::new(files));
+        db.save("watcher", &vec![1, 2, 3]).await.unwrap();
+        assert_eq!(db.read::<Vec<u32>>("watcher").await.unwrap(), vec![1, 2, 3]);
+
+        let value = db
+            .update("watcher", |v: Option<Vec<u32>>| {
+                let mut v = v.unwrap_or_default();
+                v.push(4);
+                v
+            })
+            .await
+            .unwrap();
+        assert_eq!(value, vec![1, 2, 3, 4]);
+        assert_eq!(
+            db.inner.read_value("watcher").await.unwrap(),
+            serde_json::json!([1, 2, 3, 4])
+        );
+
+        db.delete("watcher").await.unwrap();
+        assert!(db.read::<Vec<u32>>("watcher").await.is_err());
+    }
+}
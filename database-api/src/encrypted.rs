@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::*;
+use crate::locks::{locked_update, KeyLocks};
+
+const ALGORITHM: &str = "aes-256-gcm";
+
+/// A document as it is stored by the inner database, with the nonce and ciphertext in base64.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EncryptedDocument {
+    encrypted: Box<str>,
+    nonce: String,
+    data: String,
+}
+
+/// Documents written before the encryption was enabled are still readable.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored<V> {
+    Encrypted(EncryptedDocument),
+    Plain(V),
+}
+
+fn invalid(message: &str) -> DatabaseError {
+    DatabaseError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, message))
+}
+
+/// Encrypts the documents of another database with AES-256-GCM.
+///
+/// Without a key, the documents are passed through unchanged.
+pub struct EncryptedDatabase<D> {
+    inner: D,
+    cipher: Option<Aes256Gcm>,
+    locks: KeyLocks,
+}
+
+impl<D: Database> EncryptedDatabase<D> {
+    /// The environment variable with the base64 encoded 256-bit key.
+    pub const KEY_VARIABLE: &'static str = "STRUMBOT_CACHE_KEY";
+
+    /// Encrypts the documents with the 32 byte key, if provided.
+    pub fn new(inner: D, key: Option<&[u8]>) -> Result<Self, DatabaseError> {
+        let cipher = match key {
+            Some(key) => Some(Aes256Gcm::new_from_slice(key).map_err(|_| invalid("Cache key must be 32 bytes"))?),
+            None => None,
+        };
+        Ok(Self {
+            inner,
+            cipher,
+            locks: KeyLocks::default(),
+        })
+    }
+
+    /// Encrypts the documents with the key from [`KEY_VARIABLE`](Self::KEY_VARIABLE), if it is set.
+    pub fn from_env(inner: D) -> Result<Self, DatabaseError> {
+        match std::env::var(Self::KEY_VARIABLE) {
+            Ok(key) => {
+                let key = STANDARD
+                    .decode(key.trim())
+                    .map_err(|_| invalid("Cache key must be base64 encoded"))?;
+                Self::new(inner, Some(&key))
+            }
+            Err(_) => Self::new(inner, None),
+        }
+    }
+
+    pub const fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    pub const fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+}
+
+#[async_trait]
+impl<D: Database> Database for EncryptedDatabase<D> {
+    async fn save<V>(&self, key: &str, document: &V) -> Result<(), DatabaseError>
+    where
+        V: Serialize + Send + Sync,
+    {
+        let Some(ref cipher) = self.cipher else {
+            return self.inner.save(key, document).await;
+        };
+
+        let json = serde_json::to_vec(document)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let data = cipher
+            .encrypt(&nonce, json.as_slice())
+            .map_err(|_| invalid("Failed to encrypt document"))?;
+
+        let document = EncryptedDocument {
+            encrypted: ALGORITHM.into(),
+            nonce: STANDARD.encode(nonce),
+            data: STANDARD.encode(data),
+        };
+        self.inner.save(key, &document).await
+    }
+
+    async fn read<V>(&self, key: &str) -> Result<V, DatabaseError>
+    where
+        V: DeserializeOwned + Send + Sync,
+    {
+        let Some(ref cipher) = self.cipher else {
+            return self.inner.read(key).await;
+        };
+
+        let document = match self.inner.read::<Stored<V>>(key).await? {
+            Stored::Encrypted(document) => document,
+            Stored::Plain(value) => return Ok(value),
+        };
+
+        if document.encrypted.as_ref() != ALGORITHM {
+            return Err(invalid("Unsupported encryption"));
+        }
+
+        let nonce = STANDARD.decode(document.nonce).map_err(|_| invalid("Invalid nonce"))?;
+        if nonce.len() != 12 {
+            return Err(invalid("Invalid nonce"));
+        }
+        let data = STANDARD
+            .decode(document.data)
+            .map_err(|_| invalid("Invalid ciphertext"))?;
+        let json = cipher
+            .decrypt(Nonce::from_slice(&nonce), data.as_slice())
+            .map_err(|_| invalid("Failed to decrypt document, the key might have changed"))?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), DatabaseError> {
+        self.inner.delete(key).await
+    }
+
+    async fn update<V, F>(&self, key: &str, f: F) -> Result<V, DatabaseError>
+    where
+        V: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce(Option<V>) -> V + Send,
+    {
+        locked_update(self, &self.locks, key, f).await
+    }
+
+    async fn claim(&self, key: &str, owner: &str, lease: Duration) -> Result<bool, DatabaseError> {
+        // Claims only contain the owner, so they are not encrypted and the inner database can compare them
+        self.inner.claim(key, owner, lease).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_encrypted() {
+        let root = std::env::temp_dir().join("strumbot-encrypted-test");
+        let files = FileDatabase::new(root.to_string_lossy().into_owned());
+        files.setup().await.unwrap();
+        files.save("plain", &vec![1, 2, 3]).await.unwrap();
+
+        let db = EncryptedDatabase::new(files, Some(&[7; 32])).unwrap();
+        db.save("secret", &"twitch token").await.unwrap();
+
+        let stored: EncryptedDocument = db.inner().read("secret").await.unwrap();
+        assert_eq!(stored.encrypted.as_ref(), ALGORITHM);
+        assert!(!stored.data.contains("twitch"));
+        assert_eq!(db.read::<String>("secret").await.unwrap(), "twitch token");
+        assert_eq!(db.read::<Vec<u32>>("plain").await.unwrap(), vec![1, 2, 3]);
+
+        let other = EncryptedDatabase::new(FileDatabase::new(db.inner().root().to_owned()), Some(&[8; 32])).unwrap();
+        assert!(other.read::<String>("secret").await.is_err());
+        assert!(EncryptedDatabase::new(FileDatabase::new(String::new()), Some(&[0; 16])).is_err());
+    }
+}
@@ -17,6 +17,23 @@ impl Display for DatabaseError {
 
 impl std::error::Error for DatabaseError {}
 
+impl DatabaseError {
+    /// Whether the operation might succeed when it is retried, such as an interrupted write.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DatabaseError::Io(e) => is_transient(e),
+            DatabaseError::Serde(_) => false,
+        }
+    }
+}
+
+pub(crate) fn is_transient(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
 impl From<std::io::Error> for DatabaseError {
     fn from(e: std::io::Error) -> Self {
         DatabaseError::Io(e)
@@ -1,16 +1,55 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    time::Duration,
+};
+
 use async_trait::async_trait;
+use commons::retry::{retry, RetryPolicy};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::fs;
+use tokio::{fs, io::AsyncWriteExt, time::sleep};
 
 use super::*;
+use crate::{error::is_transient, locks::KeyLocks};
+
+/// The first bytes of a gzip stream, which can't be the start of a JSON document
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Lock files older than this are left over by a crashed process
+const STALE_LOCK: Duration = Duration::from_secs(10);
+/// Retries of reads and writes which failed temporarily, such as on a busy network drive
+const IO_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    min_backoff: Duration::from_millis(100),
+    max_backoff: Duration::from_secs(1),
+    jitter: 0.2,
+};
 
 pub struct FileDatabase {
     root: String,
+    compress: bool,
+    locks: KeyLocks,
 }
 
 impl FileDatabase {
-    pub const fn new(root: String) -> Self {
-        FileDatabase { root }
+    pub fn new(root: String) -> Self {
+        FileDatabase {
+            root,
+            compress: false,
+            locks: KeyLocks::default(),
+        }
+    }
+
+    /// Writes the documents with gzip compression.
+    ///
+    /// Files are read according to their content, so this can be changed without losing the existing files.
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// The directory which contains the documents.
+    pub fn root(&self) -> &str {
+        &self.root
     }
 
     pub async fn setup(&self) -> Result<(), std::io::Error> {
@@ -19,6 +58,46 @@ impl FileDatabase {
             res => res,
         }
     }
+
+    /// Creates the lock file of the key, which waits while another process holds it.
+    async fn lock_file(&self, key: &str) -> Result<String, DatabaseError> {
+        let path = format!("{}/{}.lock", self.root, key);
+        for _ in 0..200 {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path).await {
+                Ok(_) => return Ok(path),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .await
+                        .ok()
+                        .and_then(|meta| meta.modified().ok())
+                        .and_then(|modified| modified.elapsed().ok())
+                        .map_or(false, |age| age > STALE_LOCK);
+                    if stale {
+                        drop(fs::remove_file(&path).await);
+                    } else {
+                        sleep(Duration::from_millis(50)).await;
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(std::io::Error::new(ErrorKind::TimedOut, format!("Timed out waiting for lock of {key}")).into())
+    }
+
+    async fn write(&self, key: &str, data: &[u8]) -> Result<(), DatabaseError> {
+        // Write to a different file to avoid crash corruption
+        let name = format!("{}/{}-part.json", self.root, key);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // The cache contains access tokens, so only the owner may read it
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&name).await?;
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        // Move it to the right name when done (atomic)
+        Ok(fs::rename(&name, format!("{}/{}.json", self.root, key)).await?)
+    }
 }
 
 #[async_trait]
@@ -27,23 +106,137 @@ impl Database for FileDatabase {
     where
         V: Serialize + Send + Sync,
     {
-        let json = serde_json::to_string(&document)?;
-        // Write to a different file to avoid crash corruption
-        let name = format!("{}/{}-part.json", self.root, key);
-        fs::write(&name, json).await?;
-        // Move it to the right name when done (atomic)
-        Ok(fs::rename(&name, format!("{}/{}.json", self.root, key)).await?)
+        let mut json = serde_json::to_vec(&document)?;
+        if self.compress {
+            let mut encoder = GzEncoder::new(Vec::with_capacity(json.len() / 4), Compression::default());
+            encoder.write_all(&json)?;
+            json = encoder.finish()?;
+        }
+        retry(&IO_RETRY, || self.write(key, &json), DatabaseError::is_transient).await
     }
 
     async fn read<'de, V>(&'de self, key: &str) -> Result<V, DatabaseError>
     where
         V: DeserializeOwned + Send + Sync,
     {
-        let file = fs::read(format!("{}/{}.json", self.root, key)).await?;
+        let path = format!("{}/{}.json", self.root, key);
+        let file = retry(&IO_RETRY, || fs::read(&path), is_transient).await?;
+        if file.starts_with(&GZIP_MAGIC) {
+            let mut json = Vec::with_capacity(file.len() * 4);
+            GzDecoder::new(file.as_slice()).read_to_end(&mut json)?;
+            return Ok(serde_json::from_slice(&json)?);
+        }
         Ok(serde_json::from_slice(&file)?)
     }
 
     async fn delete(&self, key: &str) -> Result<(), DatabaseError> {
         Ok(fs::remove_file(format!("{}/{}.json", self.root, key)).await?)
     }
+
+    async fn update<V, F>(&self, key: &str, f: F) -> Result<V, DatabaseError>
+    where
+        V: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce(Option<V>) -> V + Send,
+    {
+        // The key locks only apply to this process, other processes sharing the directory see the lock file
+        let _guard = self.locks.lock(key).await;
+        let lock = self.lock_file(key).await?;
+        let result = async {
+            let current = match self.read(key).await {
+                Ok(value) => Some(value),
+                Err(DatabaseError::Io(e)) if e.kind() == ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            };
+            let value = f(current);
+            self.save(key, &value).await?;
+            Ok(value)
+        }
+        .await;
+
+        drop(fs::remove_file(&lock).await);
+        result
+    }
+
+    async fn claim(&self, key: &str, owner: &str, lease: Duration) -> Result<bool, DatabaseError> {
+        let claim = self.update(key, |current| Claim::renew(current, owner, lease)).await?;
+        Ok(claim.owner.as_ref() == owner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A new directory for each test run, so runs in parallel don't share their files.
+    fn test_root(name: &str) -> String {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("strumbot-{name}-test-{}-{nanos}", std::process::id()));
+        root.to_string_lossy().into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_compression() {
+        let root = test_root("compression");
+        let plain = FileDatabase::new(root.clone());
+        plain.setup().await.unwrap();
+        plain.save("plain", &"Elden Ring".repeat(100)).await.unwrap();
+
+        let compressed = FileDatabase::new(root.clone()).with_compression(true);
+        compressed.save("compressed", &"Elden Ring".repeat(100)).await.unwrap();
+
+        let file = fs::read(format!("{root}/compressed.json")).await.unwrap();
+        assert!(file.starts_with(&GZIP_MAGIC));
+        assert!(file.len() < 1000);
+        assert_eq!(
+            plain.read::<String>("compressed").await.unwrap(),
+            "Elden Ring".repeat(100)
+        );
+        assert_eq!(
+            compressed.read::<String>("plain").await.unwrap(),
+            "Elden Ring".repeat(100)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let db = std::sync::Arc::new(FileDatabase::new(test_root("update")));
+        db.setup().await.unwrap();
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let db = std::sync::Arc::clone(&db);
+                tokio::spawn(async move { db.update("counter", |count: Option<u32>| count.unwrap_or(0) + 1).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(db.read::<u32>("counter").await.unwrap(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_claim() {
+        let root = test_root("claim");
+        let first = FileDatabase::new(root.clone());
+        first.setup().await.unwrap();
+
+        // Two processes sharing the same directory
+        let second = FileDatabase::new(root);
+        let lease = Duration::from_secs(60);
+        assert!(first.claim("claim-stream", "replica-a", lease).await.unwrap());
+        assert!(!second.claim("claim-stream", "replica-b", lease).await.unwrap());
+        assert!(first.claim("claim-stream", "replica-a", lease).await.unwrap());
+
+        // Expired claims are taken over
+        first
+            .save("claim-stream", &Claim::new("replica-a", Duration::ZERO))
+            .await
+            .unwrap();
+        assert!(second.claim("claim-stream", "replica-b", lease).await.unwrap());
+        assert!(!first.claim("claim-stream", "replica-a", lease).await.unwrap());
+    }
 }
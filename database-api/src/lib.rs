@@ -1,11 +1,22 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
 use serde::{de::DeserializeOwned, Serialize};
 
+pub use batched::*;
+pub use claims::*;
+pub use dynamic::*;
+pub use encrypted::*;
 pub use error::*;
 pub use file::*;
 
+mod batched;
+mod claims;
+mod dynamic;
+mod encrypted;
 mod error;
 mod file;
+mod locks;
 
 #[async_trait]
 pub trait Database: Send + Sync {
@@ -18,4 +29,25 @@ pub trait Database: Send + Sync {
         V: DeserializeOwned + Send + Sync;
 
     async fn delete(&self, key: &str) -> Result<(), DatabaseError>;
+
+    /// Reads the document, applies the function, and saves the result, returning the new document.
+    ///
+    /// The function receives `None` if the document does not exist yet.
+    /// Other updates of the same key wait until this update is saved, so concurrent updates don't overwrite each other.
+    async fn update<V, F>(&self, key: &str, f: F) -> Result<V, DatabaseError>
+    where
+        V: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce(Option<V>) -> V + Send;
+
+    /// Claims the key for the owner until the lease expires, and returns whether the owner holds the claim.
+    ///
+    /// The claim is granted if the key is not claimed, the previous lease expired, or the owner already holds it,
+    /// which renews the lease. Databases which are shared by several processes have to implement this as an atomic
+    /// compare-and-set, since the default implementation is only atomic within this process.
+    async fn claim(&self, key: &str, owner: &str, lease: Duration) -> Result<bool, DatabaseError> {
+        let claim = self
+            .update(key, |current: Option<Claim>| Claim::renew(current, owner, lease))
+            .await?;
+        Ok(claim.owner.as_ref() == owner)
+    }
 }
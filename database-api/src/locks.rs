@@ -0,0 +1,46 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use super::*;
+
+/// Locks for individual keys, to run updates of the same document one after another.
+#[derive(Default)]
+pub(crate) struct KeyLocks {
+    locks: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+}
+
+impl KeyLocks {
+    pub async fn lock(&self, key: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().expect("Key locks poisoned");
+            // Locks which are not held or awaited by anyone else can be dropped
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            Arc::clone(locks.entry(key.to_owned()).or_default())
+        };
+        lock.lock_owned().await
+    }
+}
+
+/// Implements [`Database::update`] with the read and save of the database, while holding the lock of the key.
+pub(crate) async fn locked_update<D, V, F>(db: &D, locks: &KeyLocks, key: &str, f: F) -> Result<V, DatabaseError>
+where
+    D: Database,
+    V: Serialize + DeserializeOwned + Send + Sync,
+    F: FnOnce(Option<V>) -> V + Send,
+{
+    let _guard = locks.lock(key).await;
+    let current = match db.read(key).await {
+        Ok(value) => Some(value),
+        Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let value = f(current);
+    db.save(key, &value).await?;
+    Ok(value)
+}
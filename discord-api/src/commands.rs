@@ -1,34 +1,101 @@
 use hashbrown::HashMap;
-use std::{str::FromStr, sync::Arc};
-use twilight_util::builder::command::StringBuilder;
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::sleep,
+};
+use twilight_util::builder::command::{CommandBuilder, IntegerBuilder, StringBuilder};
 
 use tracing as log;
-use twilight_gateway::{Config as ShardConfig, Event, EventTypeFlags, Intents, Shard, ShardId};
-use twilight_http::Client;
+use twilight_gateway::{
+    error::ReceiveMessageErrorType, Config as ShardConfig, Event, EventTypeFlags, Intents, Session, Shard, ShardId,
+};
+use twilight_http::{client::InteractionClient, request::application::interaction::CreateFollowup, Client};
 use twilight_model::{
-    application::interaction::{application_command::CommandOptionValue, Interaction, InteractionData},
-    channel::message::MessageFlags,
+    application::{
+        command::CommandType,
+        interaction::{
+            application_command::{CommandData, CommandOptionValue},
+            Interaction, InteractionData,
+        },
+    },
+    channel::message::{
+        component::{ActionRow, Button, ButtonStyle},
+        AllowedMentions, Component, MessageFlags,
+    },
     gateway::payload::incoming::Ready,
-    http::interaction::{InteractionResponse, InteractionResponseData, InteractionResponseType},
+    guild::{Permissions, Role},
+    http::{
+        attachment::Attachment,
+        interaction::{InteractionResponse, InteractionResponseData, InteractionResponseType},
+    },
     id::{
-        marker::{GuildMarker, RoleMarker},
+        marker::{ChannelMarker, GuildMarker, RoleMarker, UserMarker},
         Id,
     },
 };
 
 use commons::resolve;
 
-use crate::config::{DiscordConfig, RoleNameConfig};
+use crate::{
+    config::{DiscordConfig, RoleNameConfig},
+    i18n::{self, Locale},
+    permissions, sync, AppCommand, ClipPeriod, CommandReply, CommandRequest, ExportFormat,
+};
+
+/// The action taken for a member who toggled a notification role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RoleOutcome {
+    Added,
+    Removed,
+    /// The role is not configured or does not exist in the guild
+    NotFound,
+    /// The interaction was incomplete or the role could not be updated
+    Error,
+}
+
+impl RoleOutcome {
+    /// The localized followup for the member.
+    fn message(self, locale: Locale, role_name: &str) -> String {
+        let messages = locale.messages();
+        let template = match self {
+            Self::Added => messages.role_added,
+            Self::Removed => messages.role_removed,
+            Self::NotFound => messages.role_not_found,
+            Self::Error => messages.role_error,
+        };
+        i18n::render(template, &[("role", role_name)])
+    }
+}
 
 pub struct Gateway {
     pub http: Arc<Client>,
     pub config: Arc<DiscordConfig>,
     role_cache: HashMap<String, Id<RoleMarker>>,
+    connected: Arc<AtomicBool>,
+    handler: Option<mpsc::Sender<CommandRequest>>,
+    /// The last ready event, to set up the commands again when a session is resumed
+    ready: Option<Box<Ready>>,
 }
 
 impl Gateway {
     const INTENTS: Intents = Intents::GUILDS;
 
+    /// Initial delay before reconnecting after the connection stopped
+    const MIN_BACKOFF: Duration = Duration::from_secs(1);
+    /// Maximum delay between reconnect attempts
+    const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+    /// Prefix of the custom id for role buttons, followed by the role name
+    const BUTTON_PREFIX: &'static str = "notify:";
+
     const DEFER: InteractionResponse = InteractionResponse {
         kind: InteractionResponseType::DeferredChannelMessageWithSource,
         data: Some(InteractionResponseData {
@@ -50,17 +117,68 @@ impl Gateway {
             http,
             config,
             role_cache: HashMap::new(),
+            connected: Arc::new(AtomicBool::new(false)),
+            handler: None,
+            ready: None,
         }
     }
 
+    /// Enables the application commands, which are forwarded to this channel.
+    pub fn with_handler(mut self, handler: mpsc::Sender<CommandRequest>) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    /// Shared flag which indicates whether the gateway connection is currently ready.
+    pub fn connection_status(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.connected)
+    }
+
+    /// Runs the gateway connection, and reconnects with exponential backoff whenever the shard stops.
+    ///
+    /// Returns the error if the connection can't be recovered, such as for an invalid token or disallowed intents.
     pub async fn run(mut self) -> anyhow::Result<()> {
-        let mut shard = Shard::with_config(
-            ShardId::ONE,
-            ShardConfig::builder(self.http.token().unwrap().into(), Self::INTENTS)
-                .event_types(EventTypeFlags::INTERACTION_CREATE | EventTypeFlags::READY)
-                .build(),
+        let mut session = None;
+        let mut backoff = Self::MIN_BACKOFF;
+
+        loop {
+            let started = Instant::now();
+            let result = self.connect(session).await;
+            self.connected.store(false, Ordering::Relaxed);
+            session = result?;
+
+            // Only back off further if the connection failed quickly again
+            if started.elapsed() > Self::MAX_BACKOFF {
+                backoff = Self::MIN_BACKOFF;
+            }
+
+            log::warn!("Connection terminated, reconnecting in {} seconds", backoff.as_secs());
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(Self::MAX_BACKOFF);
+        }
+    }
+
+    /// Handles the events of a shard until it stops, and returns the session to resume if possible.
+    ///
+    /// Fails for fatal close codes, since reconnecting would not help. If the commands could not be set up,
+    /// no session is returned, so the next connection starts a new session and sets them up again.
+    async fn connect(&mut self, session: Option<Session>) -> anyhow::Result<Option<Session>> {
+        let mut config = ShardConfig::builder(self.http.token().unwrap().into(), Self::INTENTS).event_types(
+            EventTypeFlags::INTERACTION_CREATE
+                | EventTypeFlags::READY
+                | EventTypeFlags::RESUMED
+                | EventTypeFlags::GATEWAY_CLOSE
+                | EventTypeFlags::ROLE_CREATE
+                | EventTypeFlags::ROLE_UPDATE
+                | EventTypeFlags::ROLE_DELETE,
         );
 
+        if let Some(session) = session {
+            config = config.session(session);
+        }
+
+        let mut shard = Shard::with_config(ShardId::ONE, config.build());
+
         log::info!("Connection established");
 
         loop {
@@ -69,23 +187,50 @@ impl Gateway {
                     self.on_interaction(&interaction).await;
                 }
                 Ok(Event::Ready(e)) => {
-                    if !self.on_ready(&e).await {
-                        break;
+                    // Commands are registered again for every new session
+                    let ready = self.on_ready(&e).await;
+                    self.ready = Some(e);
+                    if !ready {
+                        log::warn!("Failed to set up the commands, reconnecting with a new session");
+                        return Ok(None);
                     }
+                    self.connected.store(true, Ordering::Relaxed);
+                }
+                Ok(Event::Resumed) => {
+                    log::info!("Session resumed");
+                    // The commands or roles might have changed while the bot was disconnected
+                    if let Some(e) = self.ready.take() {
+                        let ready = self.on_ready(&e).await;
+                        self.ready = Some(e);
+                        if !ready {
+                            log::warn!("Failed to set up the commands, reconnecting with a new session");
+                            return Ok(None);
+                        }
+                    }
+                    self.connected.store(true, Ordering::Relaxed);
+                }
+                Ok(Event::RoleCreate(e)) => self.on_role_update(e.guild_id, &e.role),
+                Ok(Event::RoleUpdate(e)) => self.on_role_update(e.guild_id, &e.role),
+                Ok(Event::RoleDelete(e)) => self.on_role_delete(e.guild_id, e.role_id).await,
+                Ok(Event::GatewayClose(_)) => {
+                    // The shard reconnects on its own, unless the close is fatal
+                    self.connected.store(false, Ordering::Relaxed);
                 }
                 Err(e) => {
                     log::error!(?e, "error in gateway event stream");
 
+                    // Fatal closes are caused by the token or intents, which don't change by reconnecting
                     if e.is_fatal() {
-                        break;
+                        return Err(e.into());
+                    }
+                    // Reconnects with the backoff of the gateway, instead of retrying right away
+                    if matches!(e.kind(), ReceiveMessageErrorType::Reconnect) {
+                        return Ok(shard.session().cloned());
                     }
                 }
                 _ => {}
             }
         }
-
-        log::info!("Connection terminated");
-        Ok(())
     }
 
     #[inline]
@@ -93,22 +238,154 @@ impl Gateway {
         (name, name)
     }
 
+    /// Restricts an admin command to administrators by default.
+    ///
+    /// With configured admins, the command is visible to everyone and the access is checked by [`Self::is_admin`].
+    fn restrict(&self, command: CommandBuilder) -> CommandBuilder {
+        if self.config.admins.is_some() {
+            command
+        } else {
+            command.default_member_permissions(Permissions::ADMINISTRATOR)
+        }
+    }
+
+    /// Whether the member who invoked the interaction may use the admin commands.
+    fn is_admin(&self, interaction: &Interaction) -> bool {
+        let Some(ref admins) = self.config.admins else {
+            // Discord only shows the commands to administrators, or the members allowed in the server settings
+            return true;
+        };
+
+        let (Some(member), Some(user)) = (interaction.member.as_ref(), interaction.author_id()) else {
+            return false;
+        };
+        let administrator = member
+            .permissions
+            .map_or(false, |permissions| permissions.contains(Permissions::ADMINISTRATOR));
+        administrator || admins.allows(user, &member.roles)
+    }
+
+    /// Builds a command which takes a streamer login and is only visible to administrators by default.
+    fn admin_command(&self, name: &str, description: &str) -> CommandBuilder {
+        let command = CommandBuilder::new(name, description, CommandType::ChatInput).dm_permission(false);
+        self.restrict(command)
+            .option(StringBuilder::new("login", "The twitch login of the streamer").required(true))
+    }
+
+    fn string_option(command: &CommandData, name: &str) -> Option<Box<str>> {
+        command.options.iter().find_map(|option| match option.value {
+            CommandOptionValue::String(ref value) if option.name == name => Some(value.as_str().into()),
+            _ => None,
+        })
+    }
+
+    fn integer_option(command: &CommandData, name: &str) -> Option<i64> {
+        command.options.iter().find_map(|option| match option.value {
+            CommandOptionValue::Integer(value) if option.name == name => Some(value),
+            _ => None,
+        })
+    }
+
     async fn init_roles(&mut self, config: &RoleNameConfig, guild_id: &str) -> anyhow::Result<bool> {
         let guild_id: Id<GuildMarker> = Id::from_str(guild_id)?;
         let role_names = config.values();
 
         let guild = resolve! { self.http.guild(guild_id) }?;
+        let mut roles = Vec::new();
         for role in &guild.roles {
             if role_names.iter().any(|n| role.name.eq_ignore_ascii_case(n)) {
                 self.role_cache.insert(role.name.to_string(), role.id);
+                roles.push(role.id);
             }
         }
 
+        // The /notify command fails for every member, if the bot can't assign the roles
+        match permissions::role_problems(&self.http, &guild, &roles).await {
+            Ok(problems) => problems.iter().for_each(|problem| log::warn!("{problem}")),
+            Err(e) => log::warn!("Could not check the permissions of the bot for the notification roles: {e}"),
+        }
+
         Ok(!self.role_cache.is_empty())
     }
 
+    /// Whether role events of this guild affect the role cache.
+    fn is_role_guild(&self, guild_id: Id<GuildMarker>) -> bool {
+        match self.config.guild_id {
+            Some(ref id) => id.as_ref() == guild_id.to_string(),
+            None => true,
+        }
+    }
+
+    /// Keeps the role cache in sync when a notification role is created or renamed.
+    fn on_role_update(&mut self, guild_id: Id<GuildMarker>, role: &Role) {
+        if !self.is_role_guild(guild_id) {
+            return;
+        }
+
+        // A renamed role might no longer match any of the configured names
+        let before = self.role_cache.len();
+        self.role_cache.retain(|_, id| *id != role.id);
+        let removed = before != self.role_cache.len();
+
+        let role_names = self.config.role_name.values();
+        if role_names
+            .iter()
+            .any(|n| !n.is_empty() && role.name.eq_ignore_ascii_case(n))
+        {
+            log::info!("Using role {:?} ({}) for notifications", role.name, role.id);
+            self.role_cache.insert(role.name.clone(), role.id);
+        } else if removed {
+            log::warn!(
+                "Role {} was renamed to {:?}, which is no longer a notification role",
+                role.id,
+                role.name
+            );
+        }
+    }
+
+    /// Removes a deleted notification role from the cache, and creates it again if configured.
+    async fn on_role_delete(&mut self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) {
+        if !self.is_role_guild(guild_id) {
+            return;
+        }
+
+        let Some(name) = self
+            .role_cache
+            .iter()
+            .find_map(|(name, id)| (*id == role_id).then(|| name.clone()))
+        else {
+            return;
+        };
+
+        self.role_cache.remove(&name);
+        if !self.config.recreate_roles {
+            log::warn!("Notification role {name:?} was deleted, /notify can no longer assign it");
+            return;
+        }
+
+        let response = resolve! {
+            self.http
+                .create_role(guild_id)
+                .name(&name)
+                .mentionable(false)
+                .permissions(Permissions::empty())
+        };
+
+        match response {
+            Ok(role) => {
+                log::warn!(
+                    "Notification role {name:?} was deleted, created it again with id {}",
+                    role.id
+                );
+                self.role_cache.insert(name, role.id);
+            }
+            Err(e) => log::error!("Failed to create deleted notification role {name:?}: {e}"),
+        }
+    }
+
     async fn on_ready(&mut self, event: &Ready) -> bool {
         let r = self.config.role_name.clone();
+        self.role_cache.clear();
 
         // Find role ids
         let has_roles = if let Some(ref id) = self.config.guild_id.clone() {
@@ -156,42 +433,351 @@ impl Gateway {
 
         let choices = r.values().into_iter().filter(|s| !s.is_empty()).map(Self::to_choice);
 
-        let option = StringBuilder::new("role", "The event role to subscribe or unsubscribe")
-            .required(true)
-            .choices(choices)
-            .into();
-
-        let res = self
-            .http
-            .interaction(event.application.id)
-            .create_global_command()
-            .chat_input("notify", "Subscribe or unsubscribe for notifications")
-            .unwrap()
-            .dm_permission(false)
-            .command_options(&[option])
-            .unwrap()
-            .await;
+        let messages = self.config.locale.messages();
+        let mut commands = vec![
+            CommandBuilder::new("notify", messages.notify_description, CommandType::ChatInput)
+                .description_localizations(Locale::localizations(|m| m.notify_description))
+                .dm_permission(false)
+                .option(
+                    StringBuilder::new("role", messages.notify_role_description)
+                        .description_localizations(Locale::localizations(|m| m.notify_role_description))
+                        .required(true)
+                        .choices(choices),
+                )
+                .build(),
+        ];
+
+        if self.handler.is_some() {
+            commands.push(self.admin_command("track", "Start tracking a twitch streamer").build());
+            commands.push(self.admin_command("untrack", "Stop tracking a twitch streamer").build());
+            commands.push(
+                self.restrict(
+                    CommandBuilder::new(
+                        "resync",
+                        "Poll all tracked streamers immediately",
+                        CommandType::ChatInput,
+                    )
+                    .dm_permission(false),
+                )
+                .build(),
+            );
+            commands.push(
+                CommandBuilder::new("status", "Show the currently live streams", CommandType::ChatInput)
+                    .dm_permission(false)
+                    .build(),
+            );
+            commands.push(
+                CommandBuilder::new("vod", "Show the most recent VOD of a streamer", CommandType::ChatInput)
+                    .dm_permission(false)
+                    .option(StringBuilder::new("streamer", "The twitch login of the streamer").required(true))
+                    .build(),
+            );
+            commands.push(
+                CommandBuilder::new(
+                    "schedule",
+                    "Show the scheduled streams of the week",
+                    CommandType::ChatInput,
+                )
+                .dm_permission(false)
+                .build(),
+            );
+            commands.push(
+                self.admin_command("export", "Export the recorded stream history of a twitch streamer")
+                    .option(
+                        StringBuilder::new("format", "The file format (default: csv)")
+                            .choices(ExportFormat::ALL.map(|format| Self::to_choice(format.name()))),
+                    )
+                    .build(),
+            );
+            commands.push(
+                self.restrict(
+                    CommandBuilder::new(
+                        "mute",
+                        "Temporarily suppress the notifications of a streamer",
+                        CommandType::ChatInput,
+                    )
+                    .dm_permission(false),
+                )
+                .option(StringBuilder::new("streamer", "The twitch login of the streamer").required(true))
+                .option(
+                    StringBuilder::new(
+                        "duration",
+                        "How long to mute the streamer, like 2h or 1d (0s to unmute)",
+                    )
+                    .required(true),
+                )
+                .build(),
+            );
+            commands.push(
+                CommandBuilder::new(
+                    "stats",
+                    "Show the stats of the recent streams of a streamer",
+                    CommandType::ChatInput,
+                )
+                .dm_permission(false)
+                .option(StringBuilder::new("streamer", "The twitch login of the streamer").required(true))
+                .option(
+                    IntegerBuilder::new("streams", "The number of recent streams (default: 10)")
+                        .min_value(1)
+                        .max_value(25),
+                )
+                .build(),
+            );
+            commands.push(
+                CommandBuilder::new("clips", "Show the top clips of a streamer", CommandType::ChatInput)
+                    .dm_permission(false)
+                    .option(StringBuilder::new("streamer", "The twitch login of the streamer").required(true))
+                    .option(
+                        StringBuilder::new("period", "The time range of the clips (default: week)")
+                            .choices(ClipPeriod::ALL.map(|period| Self::to_choice(period.name()))),
+                    )
+                    .build(),
+            );
+        }
+
+        let guild_id = self.config.guild_id.as_deref().and_then(|id| Id::from_str(id).ok());
+        let res = sync::sync_commands(&self.http, event.application.id, guild_id, &commands).await;
 
         if let Err(ref e) = res {
-            log::error!("Failed to create command: {}", e);
+            log::error!("Failed to create commands: {}", e);
             return false;
         } else {
-            log::info!("Successfully created notify command!");
+            log::info!("Successfully synchronized {} commands!", commands.len());
+        }
+
+        if let Some(ref channel) = self.config.role_buttons {
+            if let Err(e) = self.init_role_buttons(channel, event.user.id).await {
+                log::error!("Failed to setup role buttons: {}", e);
+            }
         }
 
         true
     }
 
+    fn role_button_components(&self) -> Vec<Component> {
+        let buttons: Vec<Component> = self
+            .config
+            .role_name
+            .values()
+            .into_iter()
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Component::Button(Button {
+                    custom_id: Some(format!("{}{name}", Self::BUTTON_PREFIX)),
+                    disabled: false,
+                    emoji: None,
+                    label: Some(name.to_owned()),
+                    style: ButtonStyle::Secondary,
+                    url: None,
+                })
+            })
+            .collect();
+
+        // Discord allows at most 5 buttons in each row
+        buttons
+            .chunks(5)
+            .map(|row| {
+                Component::ActionRow(ActionRow {
+                    components: row.to_vec(),
+                })
+            })
+            .collect()
+    }
+
+    /// Updates the existing role buttons message of the bot in this channel, or sends a new one.
+    async fn init_role_buttons(&self, channel: &str, self_id: Id<UserMarker>) -> anyhow::Result<()> {
+        let channel_id: Id<ChannelMarker> = Id::from_str(channel)?;
+        let components = self.role_button_components();
+
+        let messages = resolve! { self.http.channel_messages(channel_id).limit(50)? }?;
+        let existing = messages.into_iter().find(|message| {
+            message.author.id == self_id
+                && message.components.iter().any(|row| match row {
+                    Component::ActionRow(row) => row.components.iter().any(|c| match c {
+                        Component::Button(b) => b
+                            .custom_id
+                            .as_deref()
+                            .map_or(false, |id| id.starts_with(Self::BUTTON_PREFIX)),
+                        _ => false,
+                    }),
+                    _ => false,
+                })
+        });
+
+        if let Some(message) = existing {
+            self.http
+                .update_message(channel_id, message.id)
+                .components(Some(&components))?
+                .await?;
+            log::info!("Updated role buttons in channel {}", channel_id);
+        } else {
+            self.http
+                .create_message(channel_id)
+                .content("Click a button to subscribe or unsubscribe for notifications:")?
+                .components(&components)?
+                .await?;
+            log::info!("Created role buttons in channel {}", channel_id);
+        }
+
+        Ok(())
+    }
+
     async fn on_interaction(&self, interaction: &Interaction) -> Option<()> {
-        let InteractionData::ApplicationCommand(command) = interaction.data.as_ref()? else {
-            return None;
+        let command = match interaction.data.as_ref()? {
+            InteractionData::ApplicationCommand(command) => command,
+            InteractionData::MessageComponent(component) => {
+                let role_name = component.custom_id.strip_prefix(Self::BUTTON_PREFIX)?;
+                return self.on_role_button(interaction, role_name).await;
+            }
+            _ => return None,
         };
 
-        if command.name != "notify" {
-            log::warn!("Ignoring unknown command: {}", command.name);
+        let app_command = match command.name.as_str() {
+            "notify" => None,
+            "track" => Some(AppCommand::Track(Self::string_option(command, "login")?)),
+            "untrack" => Some(AppCommand::Untrack(Self::string_option(command, "login")?)),
+            "status" => Some(AppCommand::Status),
+            "resync" => Some(AppCommand::Resync),
+            "schedule" => Some(AppCommand::Schedule),
+            "export" => {
+                let format = match Self::string_option(command, "format") {
+                    Some(name) => ExportFormat::parse(&name)?,
+                    None => ExportFormat::default(),
+                };
+                Some(AppCommand::Export(Self::string_option(command, "login")?, format))
+            }
+            "stats" => {
+                let count = Self::integer_option(command, "streams").map_or(10, |n| n.clamp(1, 25) as u8);
+                Some(AppCommand::Stats(Self::string_option(command, "streamer")?, count))
+            }
+            "mute" => Some(AppCommand::Mute(
+                Self::string_option(command, "streamer")?,
+                Self::string_option(command, "duration")?,
+            )),
+            "vod" => Some(AppCommand::Vod(Self::string_option(command, "streamer")?)),
+            "clips" => {
+                let period = match Self::string_option(command, "period") {
+                    Some(name) => ClipPeriod::parse(&name)?,
+                    None => ClipPeriod::default(),
+                };
+                Some(AppCommand::Clips(Self::string_option(command, "streamer")?, period))
+            }
+            _ => {
+                log::warn!("Ignoring unknown command: {}", command.name);
+                return None;
+            }
+        };
+
+        let client = self.http.interaction(interaction.application_id);
+        let r = client
+            .create_response(interaction.id, &interaction.token, &Self::DEFER)
+            .await;
+        if let Err(e) = r {
+            log::error!("Failed to respond to interaction: {}", e);
+            return None;
+        }
+
+        if app_command.as_ref().map_or(false, AppCommand::is_admin) && !self.is_admin(interaction) {
+            log::warn!("Denied /{} for member without admin access", command.name);
+            self.send_error(interaction, "You are not allowed to use this command.")
+                .await;
+            return None;
+        }
+
+        match app_command {
+            Some(app_command) => self.forward(interaction, app_command).await,
+            None => self.on_notify(interaction, command).await,
+        }
+    }
+
+    /// Forwards the command to the application and sends its reply as followup, once it is ready.
+    async fn forward(&self, interaction: &Interaction, command: AppCommand) -> Option<()> {
+        let (reply, receive) = oneshot::channel();
+        let sent = match self.handler {
+            Some(ref handler) => handler.send(CommandRequest { command, reply }).await.is_ok(),
+            None => false,
+        };
+        if !sent {
+            log::error!("Command handler is no longer running");
+            self.send_error(
+                interaction,
+                "This command is currently unavailable, please try again later.",
+            )
+            .await;
             return None;
         }
 
+        let http = Arc::clone(&self.http);
+        let application_id = interaction.application_id;
+        let token = interaction.token.clone();
+        let allowed_mentions = self.allowed_mentions();
+        tokio::spawn(async move {
+            let mut reply = receive
+                .await
+                .unwrap_or_else(|_| CommandReply::text("Something went wrong, please try again later."));
+
+            let client = http.interaction(application_id);
+            let attachments: Vec<Attachment> = std::mem::take(&mut reply.files)
+                .into_iter()
+                .enumerate()
+                .map(|(id, file)| Attachment::from_bytes(file.filename, file.data, id as u64))
+                .collect();
+
+            let invalid = CommandReply::text("The reply to this command was invalid, check the logs for details.");
+            let mentions = allowed_mentions.as_ref();
+            let followup = match Self::followup(&client, &token, &reply, &attachments, mentions) {
+                Ok(followup) => followup,
+                Err(e) => {
+                    log::error!("Invalid reply to command: {e:?}");
+                    match Self::followup(&client, &token, &invalid, &[], mentions) {
+                        Ok(followup) => followup,
+                        Err(_) => return,
+                    }
+                }
+            };
+
+            if let Err(e) = followup.await {
+                log::error!("Failed to send followup: {}", e);
+            }
+        });
+
+        Some(())
+    }
+
+    /// Sends an ephemeral followup with the error, to the interaction which was already deferred.
+    async fn send_error(&self, interaction: &Interaction, message: &str) {
+        let client = self.http.interaction(interaction.application_id);
+        let res = client
+            .create_followup(&interaction.token)
+            .allowed_mentions(self.allowed_mentions().as_ref())
+            .flags(MessageFlags::EPHEMERAL)
+            .content(message)
+            .expect("Failed to create followup!")
+            .await;
+        if let Err(e) = res {
+            log::error!("Failed to send followup: {}", e);
+        }
+    }
+
+    /// Builds the followup with the reply, which fails if the reply exceeds the discord limits.
+    fn followup<'a>(
+        client: &'a InteractionClient<'a>,
+        token: &'a str,
+        reply: &'a CommandReply,
+        attachments: &'a [Attachment],
+        allowed_mentions: Option<&'a AllowedMentions>,
+    ) -> anyhow::Result<CreateFollowup<'a>> {
+        let mut followup = client.create_followup(token).allowed_mentions(allowed_mentions);
+        if !reply.content.is_empty() {
+            followup = followup.content(&reply.content)?;
+        }
+        if !attachments.is_empty() {
+            followup = followup.attachments(attachments)?;
+        }
+        Ok(followup.embeds(&reply.embeds)?)
+    }
+
+    async fn on_role_button(&self, interaction: &Interaction, role_name: &str) -> Option<()> {
         let client = self.http.interaction(interaction.application_id);
         let r = client
             .create_response(interaction.id, &interaction.token, &Self::DEFER)
@@ -201,46 +787,83 @@ impl Gateway {
             return None;
         }
 
-        let option = command.options.iter().find(|o| o.name == "role")?;
+        let outcome = self.toggle_role(interaction, role_name).await;
+        self.send_role_followup(interaction, role_name, outcome).await;
+        Some(())
+    }
+
+    async fn on_notify(&self, interaction: &Interaction, command: &CommandData) -> Option<()> {
+        let role_name = command.options.iter().find_map(|option| match option.value {
+            CommandOptionValue::String(ref value) if option.name == "role" => Some(value.as_str()),
+            _ => None,
+        });
 
-        let CommandOptionValue::String(ref role_name) = option.value else {
+        let Some(role_name) = role_name else {
+            self.send_role_followup(interaction, "", RoleOutcome::Error).await;
             return None;
         };
 
-        let role = self.role_cache.get(role_name).copied()?;
-        let guild = interaction.guild_id?;
+        let outcome = self.toggle_role(interaction, role_name).await;
+        self.send_role_followup(interaction, role_name, outcome).await;
+        Some(())
+    }
 
-        let member = interaction.member.as_ref().expect("Command without member in a guild");
-        let author = interaction.author().expect("Command without author");
+    /// Adds or removes the role for the member who invoked the interaction.
+    async fn toggle_role(&self, interaction: &Interaction, role_name: &str) -> RoleOutcome {
+        let Some(role) = self.role_cache.get(role_name).copied() else {
+            log::warn!("Cannot toggle unknown role {role_name:?}");
+            return RoleOutcome::NotFound;
+        };
+
+        let (Some(guild), Some(member), Some(author)) =
+            (interaction.guild_id, interaction.member.as_ref(), interaction.author())
+        else {
+            log::warn!("Cannot toggle role {role_name:?} outside of a server");
+            return RoleOutcome::Error;
+        };
 
-        let res = if member.roles.contains(&role) {
-            self.http.remove_guild_member_role(guild, author.id, role).await
+        let (res, outcome) = if member.roles.contains(&role) {
+            let res = self.http.remove_guild_member_role(guild, author.id, role).await;
+            (res, RoleOutcome::Removed)
         } else {
-            self.http.add_guild_member_role(guild, author.id, role).await
+            let res = self.http.add_guild_member_role(guild, author.id, role).await;
+            (res, RoleOutcome::Added)
         };
 
         if let Err(e) = res {
             log::error!("Failed to update member roles: {}", e);
-        } else {
-            log::info!(
-                "Successfully updated member roles! Member: {}#{} Role: {} ({})",
-                author.name,
-                author.discriminator(),
-                role_name,
-                role
-            );
+            return RoleOutcome::Error;
         }
 
+        log::info!(
+            "Successfully updated member roles! Member: {}#{} Role: {} ({}) Action: {:?}",
+            author.name,
+            author.discriminator(),
+            role_name,
+            role,
+            outcome
+        );
+        outcome
+    }
+
+    /// The allowed mentions of followups, which don't ping anyone unless the config trusts the mentions.
+    fn allowed_mentions(&self) -> Option<AllowedMentions> {
+        (!self.config.trusted_mentions).then(AllowedMentions::default)
+    }
+
+    /// Tells the member which action was taken for the role.
+    async fn send_role_followup(&self, interaction: &Interaction, role_name: &str, outcome: RoleOutcome) {
+        let content = outcome.message(self.config.locale, role_name);
+        let client = self.http.interaction(interaction.application_id);
         let res = client
             .create_followup(&interaction.token)
-            .content("Your roles have been updated!")
+            .allowed_mentions(self.allowed_mentions().as_ref())
+            .content(&content)
             .expect("Failed to create followup!")
             .await;
 
         if let Err(e) = res {
             log::error!("Failed to send followup: {}", e);
         }
-
-        Some(())
     }
 }
@@ -1,11 +1,29 @@
+use std::time::Duration;
+
+use commons::{
+    time::{TimeFormat, UtcOffset},
+    util::{ConfigDuration, EscapeMode},
+};
 use serde::Deserialize;
 
-use crate::WebhookParams;
+use twilight_model::id::{
+    marker::{ChannelMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use crate::{
+    i18n::{self, Locale},
+    WebhookParams,
+};
 
 const fn default_true() -> bool {
     true
 }
 
+const fn default_digest_window() -> ConfigDuration {
+    ConfigDuration(Duration::from_secs(300))
+}
+
 #[derive(Deserialize, Default, Clone)]
 pub struct RoleNameConfig {
     #[serde(default)]
@@ -14,15 +32,37 @@ pub struct RoleNameConfig {
     pub vod: Box<str>,
     #[serde(default)]
     pub update: Box<str>,
+    #[serde(default)]
+    pub category: Box<str>,
+    #[serde(default)]
+    pub upcoming: Box<str>,
+    #[serde(default)]
+    pub hype_train: Box<str>,
+    #[serde(default)]
+    pub goal: Box<str>,
+    #[serde(default)]
+    pub video: Box<str>,
+    #[serde(default)]
+    pub milestone: Box<str>,
 }
 
 impl RoleNameConfig {
     pub fn values(&self) -> Vec<&str> {
-        vec![&self.live, &self.vod, &self.update]
+        vec![
+            &self.live,
+            &self.vod,
+            &self.update,
+            &self.category,
+            &self.upcoming,
+            &self.hype_train,
+            &self.goal,
+            &self.video,
+            &self.milestone,
+        ]
     }
 }
 
-#[derive(Deserialize, PartialEq, Eq, Clone, Copy)]
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum EventName {
     #[serde(rename = "live")]
     Live,
@@ -30,6 +70,231 @@ pub enum EventName {
     Vod,
     #[serde(rename = "update")]
     Update,
+    /// A stream went live in one of the watched categories
+    #[serde(rename = "category")]
+    Category,
+    /// A scheduled stream of a tracked streamer starts soon
+    #[serde(rename = "upcoming")]
+    Upcoming,
+    /// A hype train of a tracked streamer started or ended
+    #[serde(rename = "hype_train")]
+    HypeTrain,
+    /// A creator goal of a tracked streamer made progress
+    #[serde(rename = "goal")]
+    Goal,
+    /// A tracked streamer published a highlight or uploaded a video
+    #[serde(rename = "video")]
+    Video,
+    /// A tracked streamer reached a follower or subscriber milestone
+    #[serde(rename = "milestone")]
+    Milestone,
+}
+
+impl EventName {
+    /// The name in the config, which is also the key of its notification role.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Live => "live",
+            Self::Vod => "vod",
+            Self::Update => "update",
+            Self::Category => "category",
+            Self::Upcoming => "upcoming",
+            Self::HypeTrain => "hype_train",
+            Self::Goal => "goal",
+            Self::Video => "video",
+            Self::Milestone => "milestone",
+        }
+    }
+}
+
+/// Who is mentioned by the notifications of an event, an empty object mentions nobody.
+#[derive(Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct MentionConfig {
+    /// Also mention the notification role of the event from `role_name`
+    #[serde(default)]
+    pub role_name: bool,
+    #[serde(default)]
+    pub roles: Vec<Id<RoleMarker>>,
+    #[serde(default)]
+    pub users: Vec<Id<UserMarker>>,
+    #[serde(default)]
+    pub here: bool,
+    #[serde(default)]
+    pub everyone: bool,
+}
+
+/// An entry of `enabled_events`, either the name of the event or an object with its mentions.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum EnabledEvent {
+    Name(EventName),
+    Mention { event: EventName, mention: MentionConfig },
+}
+
+impl EnabledEvent {
+    pub const fn event(&self) -> EventName {
+        match *self {
+            Self::Name(event) | Self::Mention { event, .. } => event,
+        }
+    }
+}
+
+/// The events which are sent to the `stream_notifications` webhook.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(transparent)]
+pub struct EnabledEvents(Vec<EnabledEvent>);
+
+impl EnabledEvents {
+    pub fn contains(&self, event: &EventName) -> bool {
+        self.0.iter().any(|entry| entry.event() == *event)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// The configured mentions of the event, or `None` if it mentions its notification role.
+    pub fn mention(&self, event: EventName) -> Option<&MentionConfig> {
+        self.0.iter().find_map(|entry| match entry {
+            EnabledEvent::Mention { event: name, mention } if *name == event => Some(mention),
+            _ => None,
+        })
+    }
+}
+
+impl From<Vec<EventName>> for EnabledEvents {
+    fn from(events: Vec<EventName>) -> Self {
+        Self(events.into_iter().map(EnabledEvent::Name).collect())
+    }
+}
+
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ChapterFormat {
+    #[default]
+    #[serde(rename = "txt")]
+    Text,
+    #[serde(rename = "json")]
+    Json,
+}
+
+/// Where the profile image of the streamer is shown in the stream notifications.
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum ProfileImage {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    /// As the avatar of the webhook, instead of the `avatar_url`
+    #[serde(rename = "avatar")]
+    Avatar,
+    /// Next to the author of the embed
+    #[serde(rename = "author")]
+    Author,
+}
+
+/// A time of day range like `22:00-08:00`, which may wrap around midnight.
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(try_from = "String")]
+pub struct TimeRange {
+    /// Minute of the day when the range starts, inclusive
+    start: u32,
+    /// Minute of the day when the range ends, exclusive
+    end: u32,
+}
+
+impl TimeRange {
+    pub const fn contains(&self, minute: u32) -> bool {
+        if self.start <= self.end {
+            self.start <= minute && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for TimeRange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid time range {value:?}, expected a value like \"22:00-08:00\"");
+        let parse = |time: &str| -> Option<u32> {
+            let (hours, minutes) = time.trim().split_once(':')?;
+            let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+            (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+        };
+
+        let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+        Ok(Self {
+            start: parse(start).ok_or_else(invalid)?,
+            end: parse(end).ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// How live and update notifications are handled during quiet hours.
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum QuietMode {
+    /// Send the notification without role mentions
+    #[default]
+    #[serde(rename = "silent")]
+    Silent,
+    /// Don't send the notification at all
+    #[serde(rename = "skip")]
+    Skip,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct QuietHoursConfig {
+    /// The offset of the local time to UTC, in which the ranges are written
+    #[serde(default)]
+    pub timezone: UtcOffset,
+    pub ranges: Vec<TimeRange>,
+    #[serde(default)]
+    pub mode: QuietMode,
+}
+
+impl QuietHoursConfig {
+    /// Whether the unix timestamp is within any of the quiet ranges.
+    pub fn is_quiet(&self, timestamp: i64) -> bool {
+        let local = timestamp + i64::from(self.timezone.minutes()) * 60;
+        let minute = (local.rem_euclid(86400) / 60) as u32;
+        self.ranges.iter().any(|range| range.contains(minute))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DigestConfig {
+    /// How long live events are collected before the combined notification is sent
+    #[serde(default = "default_digest_window")]
+    pub window: ConfigDuration,
+}
+
+/// The members who may use the admin commands, in addition to the administrators of the server.
+#[derive(Deserialize, Default, Clone, Debug)]
+pub struct AdminConfig {
+    #[serde(default)]
+    pub roles: Vec<Id<RoleMarker>>,
+    #[serde(default)]
+    pub users: Vec<Id<UserMarker>>,
+}
+
+impl AdminConfig {
+    pub fn allows(&self, user: Id<UserMarker>, roles: &[Id<RoleMarker>]) -> bool {
+        self.users.contains(&user) || roles.iter().any(|role| self.roles.contains(role))
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+pub struct DryRunConfig {
+    /// Log the notifications instead of sending them to discord
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory to write the notifications to, as JSON files
+    #[serde(default)]
+    pub output: Option<Box<str>>,
 }
 
 #[derive(Deserialize, Default, Clone)]
@@ -43,11 +308,102 @@ pub struct DiscordConfig {
     pub show_notify_hints: bool,
     #[serde(default)]
     pub role_name: RoleNameConfig,
-    pub enabled_events: Vec<EventName>,
+    pub enabled_events: EnabledEvents,
     #[serde(default = "default_true")]
     pub enable_command: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub avatar_url: Option<Box<str>>,
+    #[serde(default)]
+    pub chapter_format: ChapterFormat,
+    /// Channel id for a message with buttons to toggle the notification roles
+    #[serde(default)]
+    pub role_buttons: Option<Box<str>>,
+    #[serde(default)]
+    pub locale: Locale,
+    /// Publish notifications to the followers of the announcement channel
+    #[serde(default)]
+    pub crosspost: bool,
+    /// Attach the stream thumbnail to notifications
+    #[serde(default = "default_true")]
+    pub thumbnails: bool,
+    /// Link the thumbnails from twitch instead of downloading and uploading them
+    #[serde(default)]
+    pub link_thumbnails: bool,
+    /// Attach a grid of the box art of each game played to the VOD notification
+    #[serde(default)]
+    pub vod_collage: bool,
+    /// How strictly game names, user names, and marker descriptions are escaped in notifications
+    #[serde(default)]
+    pub escape: EscapeMode,
+    /// Times in which live and update notifications are sent without mentions, or not at all
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHoursConfig>,
+    /// Combine the live notifications of streams which start around the same time into one message
+    #[serde(default)]
+    pub digest: Option<DigestConfig>,
+    /// Validate the setup without sending anything to discord
+    #[serde(default)]
+    pub dry_run: DryRunConfig,
+    /// Create a notification role again, if it is deleted while the bot is running
+    #[serde(default)]
+    pub recreate_roles: bool,
+    /// Roles and users which may use the admin commands, which are otherwise only available to administrators
+    #[serde(default)]
+    pub admins: Option<AdminConfig>,
+    /// Write times in this UTC offset instead of discord timestamps, which every client shows in its own time zone
+    #[serde(default)]
+    pub timezone: Option<UtcOffset>,
+    /// Let mentions in messages without explicit mentions ping, such as mentions in stream titles or command replies
+    #[serde(default)]
+    pub trusted_mentions: bool,
+    /// Show the profile image of the streamer in their stream notifications
+    #[serde(default)]
+    pub profile_image: ProfileImage,
+    /// Name of the webhook for the notifications about a streamer, where `{user}` is the display name of the streamer
+    #[serde(default)]
+    pub username: Option<Box<str>>,
+    /// Forum channel id for the VOD archive, which gets a post with the VOD summary of every stream
+    #[serde(default)]
+    pub vod_forum: Option<Id<ChannelMarker>>,
+    /// Pin the live notification while the stream is live, until the VOD notification is sent
+    #[serde(default)]
+    pub pin_live: bool,
+    /// Edit the live notification with a new stream preview at this interval, while the stream is live
+    #[serde(default)]
+    pub preview_refresh: Option<ConfigDuration>,
+}
+
+impl DiscordConfig {
+    /// How times and durations are written in notifications.
+    pub const fn time_format(&self) -> TimeFormat {
+        TimeFormat {
+            offset: self.timezone,
+            style: self.locale.date_style(),
+        }
+    }
+
+    /// The interval at which the preview of the live notification is refreshed, if enabled.
+    ///
+    /// Every refresh uploads a new image, so the interval is at least 5 minutes.
+    pub fn preview_refresh(&self) -> Option<Duration> {
+        const MIN_INTERVAL: Duration = Duration::from_secs(300);
+        self.preview_refresh.map(|interval| interval.0.max(MIN_INTERVAL))
+    }
+
+    /// The webhook name for a notification about the streamer, or `None` to keep the name of the webhook.
+    ///
+    /// Discord rejects names which contain `discord` or `clyde`, so these keep the name of the webhook too.
+    pub fn username(&self, user: &str) -> Option<String> {
+        let template = self.username.as_deref()?;
+        let name: String = i18n::render(template, &[("user", user)])
+            .trim()
+            .chars()
+            .take(80)
+            .collect();
+        let lowercase = name.to_lowercase();
+        let allowed = !name.is_empty() && !lowercase.contains("discord") && !lowercase.contains("clyde");
+        allowed.then_some(name)
+    }
 }
 
 #[cfg(test)]
@@ -93,10 +449,103 @@ mod tests {
         assert!(discord.enabled_events.contains(&EventName::Live));
         assert!(discord.enabled_events.contains(&EventName::Update));
         assert!(discord.enabled_events.contains(&EventName::Vod));
+        assert!(discord.enabled_events.mention(EventName::Live).is_none());
 
         let role_names = discord.role_name;
         assert_eq!(role_names.live.as_ref(), "live");
         assert_eq!(role_names.update.as_ref(), "new game");
         assert_eq!(role_names.vod.as_ref(), "");
+
+        assert!(discord.chapter_format == ChapterFormat::Text);
+        assert_eq!(discord.role_buttons, None);
+        assert_eq!(discord.locale, Locale::English);
+        assert!(!discord.crosspost);
+        assert!(discord.thumbnails);
+        assert!(!discord.link_thumbnails);
+        assert!(!discord.vod_collage);
+        assert_eq!(discord.escape, EscapeMode::Markdown);
+        assert!(discord.quiet_hours.is_none());
+        assert!(discord.digest.is_none());
+        assert!(!discord.dry_run.enabled);
+        assert!(!discord.recreate_roles);
+        assert!(discord.admins.is_none());
+        assert!(discord.timezone.is_none());
+        assert!(!discord.trusted_mentions);
+        assert_eq!(discord.profile_image, ProfileImage::None);
+        assert_eq!(discord.username("Elajjaz"), None);
+        assert!(discord.vod_forum.is_none());
+        assert!(!discord.pin_live);
+        assert_eq!(discord.preview_refresh(), None);
+    }
+
+    #[test]
+    fn test_admins() {
+        let admins: AdminConfig = serde_json::from_str(r#"{"roles": ["81384788765712384"]}"#).unwrap();
+        assert!(admins.allows(Id::new(1), &[Id::new(2), Id::new(81384788765712384)]));
+        assert!(!admins.allows(Id::new(1), &[Id::new(2)]));
+
+        let admins: AdminConfig = serde_json::from_str(r#"{"users": ["86699011792191488"]}"#).unwrap();
+        assert!(admins.allows(Id::new(86699011792191488), &[]));
+        assert!(!admins.allows(Id::new(1), &[]));
+    }
+
+    #[test]
+    fn test_username() {
+        let mut config = DiscordConfig {
+            username: Some("{user} on twitch".into()),
+            ..DiscordConfig::default()
+        };
+        assert_eq!(config.username("Elajjaz").as_deref(), Some("Elajjaz on twitch"));
+        assert_eq!(config.username("DiscordTV"), None);
+
+        config.username = Some("{user}".into());
+        assert_eq!(config.username(""), None);
+        assert_eq!(config.username(&"x".repeat(100)).map(|name| name.len()), Some(80));
+    }
+
+    #[test]
+    fn test_enabled_events() {
+        let events: EnabledEvents = serde_json::from_str(
+            r#"["live", {"event": "vod", "mention": {}}, {"event": "update", "mention": {"roles": ["81384788765712384"], "here": true}}]"#,
+        )
+        .unwrap();
+
+        assert!(events.contains(&EventName::Live));
+        assert!(events.contains(&EventName::Vod));
+        assert!(!events.contains(&EventName::Category));
+
+        assert!(events.mention(EventName::Live).is_none());
+        assert_eq!(events.mention(EventName::Vod), Some(&MentionConfig::default()));
+        let update = events.mention(EventName::Update).unwrap();
+        assert_eq!(update.roles, vec![Id::new(81384788765712384)]);
+        assert!(update.here && !update.everyone && !update.role_name);
+
+        assert!(serde_json::from_str::<EnabledEvents>(r#"[{"event": "unknown", "mention": {}}]"#).is_err());
+    }
+
+    #[test]
+    fn test_digest() {
+        let digest: DigestConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(digest.window.0.as_secs(), 300);
+        let digest: DigestConfig = serde_json::from_str(r#"{"window": "90s"}"#).unwrap();
+        assert_eq!(digest.window.0.as_secs(), 90);
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let quiet: QuietHoursConfig =
+            serde_json::from_str(r#"{"timezone": "+02:00", "ranges": ["22:00-08:00", "12:30-13:00"], "mode": "skip"}"#)
+                .unwrap();
+        assert_eq!(quiet.mode, QuietMode::Skip);
+
+        // 2023-10-09T21:00:00Z is 23:00 in UTC+2
+        assert!(quiet.is_quiet(1_696_885_200));
+        // 2023-10-09T08:00:00Z is 10:00 in UTC+2
+        assert!(!quiet.is_quiet(1_696_838_400));
+        // 2023-10-09T10:45:00Z is 12:45 in UTC+2
+        assert!(quiet.is_quiet(1_696_848_300));
+
+        assert!(serde_json::from_str::<QuietHoursConfig>(r#"{"ranges": ["25:00-08:00"]}"#).is_err());
+        assert!(serde_json::from_str::<QuietHoursConfig>(r#"{"timezone": "Europe/Berlin", "ranges": []}"#).is_err());
     }
 }
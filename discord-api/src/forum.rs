@@ -0,0 +1,124 @@
+use twilight_http::Client;
+use twilight_model::{
+    channel::{forum::ForumTag, message::AllowedMentions},
+    id::{
+        marker::{ChannelMarker, TagMarker},
+        Id,
+    },
+};
+
+use crate::{SendError, WebhookMessage};
+
+/// Discord rejects threads with more tags than this
+const MAX_TAGS: usize = 5;
+/// Thread names are limited to 100 characters
+const MAX_NAME_LENGTH: usize = 100;
+
+/// A new thread in a forum channel, such as the VOD summary of a stream in the VOD archive.
+#[derive(Clone, Debug, Default)]
+pub struct ForumPost {
+    pub name: String,
+    /// Names of the forum tags to apply, such as the games played, which are matched case-insensitively
+    pub tags: Vec<String>,
+    /// The first message starts the thread, the others are sent as replies
+    pub messages: Vec<WebhookMessage>,
+}
+
+/// Creates the post as a new thread in the forum channel, using the bot token.
+///
+/// The messages of the post never ping anyone, since the archive is not a notification.
+pub(crate) async fn create_post(
+    client: &Client,
+    channel_id: Id<ChannelMarker>,
+    post: &ForumPost,
+) -> Result<(), SendError> {
+    let Some((first, replies)) = post.messages.split_first() else {
+        return Err(SendError::Invalid(anyhow::anyhow!("Forum post without messages")));
+    };
+
+    let forum = client.channel(channel_id).await?.model().await?;
+    let tags = match_tags(forum.available_tags.as_deref().unwrap_or_default(), &post.tags);
+    let name: String = post.name.chars().take(MAX_NAME_LENGTH).collect();
+    let no_mentions = AllowedMentions::default();
+
+    let attachments = first.attachments();
+    let mut request = client
+        .create_forum_thread(channel_id, &name)
+        .applied_tags(&tags)
+        .message()
+        .allowed_mentions(Some(&no_mentions));
+    if !first.content.is_empty() {
+        request = request.content(&first.content).map_err(SendError::invalid)?;
+    }
+    if !first.embeds.is_empty() {
+        request = request.embeds(&first.embeds).map_err(SendError::invalid)?;
+    }
+    if !attachments.is_empty() {
+        request = request.attachments(&attachments).map_err(SendError::invalid)?;
+    }
+    let thread = request.await?.model().await?;
+
+    for message in replies {
+        let attachments = message.attachments();
+        let mut request = client
+            .create_message(thread.channel.id)
+            .allowed_mentions(Some(&no_mentions));
+        if !message.content.is_empty() {
+            request = request.content(&message.content).map_err(SendError::invalid)?;
+        }
+        if !message.embeds.is_empty() {
+            request = request.embeds(&message.embeds).map_err(SendError::invalid)?;
+        }
+        if !attachments.is_empty() {
+            request = request.attachments(&attachments).map_err(SendError::invalid)?;
+        }
+        request.await?;
+    }
+
+    Ok(())
+}
+
+/// The ids of the forum tags with the provided names, in order of the names and at most 5.
+fn match_tags(available: &[ForumTag], names: &[String]) -> Vec<Id<TagMarker>> {
+    let mut tags = Vec::new();
+    for name in names {
+        let tag = available.iter().find(|tag| tag.name.eq_ignore_ascii_case(name.trim()));
+        if let Some(tag) = tag.filter(|tag| !tags.contains(&tag.id)) {
+            tags.push(tag.id);
+        }
+    }
+    tags.truncate(MAX_TAGS);
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(id: u64, name: &str) -> ForumTag {
+        ForumTag {
+            emoji_id: None,
+            emoji_name: None,
+            id: Id::new(id),
+            moderated: false,
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_match_tags() {
+        let available = vec![tag(1, "Dark Souls"), tag(2, "Elden Ring"), tag(3, "Just Chatting")];
+        let names = vec![
+            "elden ring".to_owned(),
+            "Hollow Knight".to_owned(),
+            "Dark Souls".to_owned(),
+            "ELDEN RING".to_owned(),
+        ];
+        assert_eq!(match_tags(&available, &names), vec![Id::new(2), Id::new(1)]);
+        assert!(match_tags(&[], &names).is_empty());
+
+        let available: Vec<ForumTag> = (1..=7).map(|id| tag(id, &format!("Game {id}"))).collect();
+        let names: Vec<String> = (1..=7).map(|id| format!("game {id}")).collect();
+        assert_eq!(match_tags(&available, &names).len(), MAX_TAGS);
+    }
+}
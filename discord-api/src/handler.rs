@@ -0,0 +1,153 @@
+use tokio::sync::oneshot;
+use twilight_model::channel::message::Embed;
+
+use crate::MessageFile;
+
+/// Commands which are handled by the application, rather than the gateway itself.
+#[derive(Debug, Clone)]
+pub enum AppCommand {
+    /// Start tracking the streamer with this login
+    Track(Box<str>),
+    /// Stop tracking the streamer with this login
+    Untrack(Box<str>),
+    /// Show the currently live streams
+    Status,
+    /// Show the most recent VOD of the streamer with this login
+    Vod(Box<str>),
+    /// Show the top clips of the streamer with this login
+    Clips(Box<str>, ClipPeriod),
+    /// Show the scheduled streams of the next 7 days
+    Schedule,
+    /// Show the stats of the most recent streams of the streamer with this login
+    Stats(Box<str>, u8),
+    /// Export the recorded stream history of the streamer with this login
+    Export(Box<str>, ExportFormat),
+    /// Suppress the notifications of the streamer with this login, for a duration like `24h`
+    Mute(Box<str>, Box<str>),
+    /// Poll all tracked streamers immediately and report the results
+    Resync,
+}
+
+impl AppCommand {
+    /// Whether the command is restricted to administrators, or the members configured in `admins`.
+    pub const fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            Self::Track(_) | Self::Untrack(_) | Self::Export(..) | Self::Mute(..) | Self::Resync
+        )
+    }
+}
+
+/// The file format of the `/export` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [Self::Csv, Self::Json];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+}
+
+/// The time range for the top clips of the `/clips` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClipPeriod {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl ClipPeriod {
+    pub const ALL: [ClipPeriod; 3] = [Self::Day, Self::Week, Self::Month];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+
+    pub const fn as_secs(self) -> u64 {
+        match self {
+            Self::Day => 86400,
+            Self::Week => 7 * 86400,
+            Self::Month => 30 * 86400,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|period| period.name() == name)
+    }
+}
+
+/// The message to reply to a command with.
+#[derive(Debug, Clone, Default)]
+pub struct CommandReply {
+    pub content: String,
+    pub embeds: Vec<Embed>,
+    pub files: Vec<MessageFile>,
+}
+
+impl CommandReply {
+    pub fn text(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn embed(embed: Embed) -> Self {
+        Self {
+            embeds: vec![embed],
+            ..Self::default()
+        }
+    }
+
+    pub fn file(content: impl Into<String>, file: MessageFile) -> Self {
+        Self {
+            content: content.into(),
+            files: vec![file],
+            ..Self::default()
+        }
+    }
+}
+
+/// A command forwarded to the application, which responds through the reply channel.
+pub struct CommandRequest {
+    pub command: AppCommand,
+    pub reply: oneshot::Sender<CommandReply>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_period() {
+        assert_eq!(ClipPeriod::parse("day"), Some(ClipPeriod::Day));
+        assert_eq!(ClipPeriod::parse("month"), Some(ClipPeriod::Month));
+        assert_eq!(ClipPeriod::parse("year"), None);
+        assert_eq!(ClipPeriod::Week.as_secs(), 604800);
+    }
+
+    #[test]
+    fn test_export_format() {
+        assert_eq!(ExportFormat::parse("csv"), Some(ExportFormat::Csv));
+        assert_eq!(ExportFormat::parse("json"), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::parse("xml"), None);
+    }
+}
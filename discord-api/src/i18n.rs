@@ -0,0 +1,254 @@
+use commons::time::DateStyle;
+use serde::Deserialize;
+
+/// The language of the notifications and command descriptions.
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum Locale {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "de")]
+    German,
+    #[serde(rename = "fr")]
+    French,
+    #[serde(rename = "es")]
+    Spanish,
+}
+
+/// Message templates, the placeholders like `{user}` are replaced by [`render`].
+pub struct Messages {
+    /// Placeholders: `{user}`
+    pub live: &'static str,
+    /// Placeholders: `{user}`, `{game}`
+    pub live_with: &'static str,
+    /// Placeholders: `{user}`, `{platform}`
+    pub live_on: &'static str,
+    /// Placeholders: `{count}`
+    pub live_digest: &'static str,
+    /// Placeholders: `{user}`, `{game}`
+    pub switched_game: &'static str,
+    /// Placeholders: `{user}`, `{game}`
+    pub category_live: &'static str,
+    /// Placeholders: `{user}`, `{time}`
+    pub starting_soon: &'static str,
+    /// Placeholders: `{user}`, `{level}`
+    pub hype_train_started: &'static str,
+    /// Placeholders: `{user}`, `{level}`
+    pub hype_train_ended: &'static str,
+    /// Placeholders: `{user}`, `{goal}`, `{percent}`
+    pub goal_progress: &'static str,
+    /// Placeholders: `{user}`
+    pub new_video: &'static str,
+    /// Placeholders: `{user}`, `{count}`
+    pub follower_milestone: &'static str,
+    /// Placeholders: `{user}`, `{count}`
+    pub subscriber_milestone: &'static str,
+    /// Placeholders: `{user}`, `{duration}`
+    pub vod_from: &'static str,
+    /// Placeholders: `{timestamp}`
+    pub start_watching: &'static str,
+    /// Placeholders: `{role}`
+    pub notify_hint: &'static str,
+    /// Placeholders: `{role}`
+    pub role_added: &'static str,
+    /// Placeholders: `{role}`
+    pub role_removed: &'static str,
+    /// Placeholders: `{role}`
+    pub role_not_found: &'static str,
+    pub role_error: &'static str,
+    pub notify_description: &'static str,
+    pub notify_role_description: &'static str,
+}
+
+const ENGLISH: Messages = Messages {
+    live: "{user} is live!",
+    live_with: "{user} is live with **{game}**!",
+    live_on: "{user} is live on {platform}!",
+    live_digest: "{count} streamers went live!",
+    switched_game: "{user} switched game to **{game}**!",
+    category_live: "{user} went live in **{game}**!",
+    starting_soon: "{user} is going live {time}!",
+    hype_train_started: "A hype train started for {user} at level {level}!",
+    hype_train_ended: "The hype train of {user} ended at level {level}!",
+    goal_progress: "{user} reached {percent}% of the **{goal}** goal!",
+    new_video: "{user} published a new video!",
+    follower_milestone: "{user} reached **{count}** followers!",
+    subscriber_milestone: "{user} reached **{count}** subscribers!",
+    vod_from: "VOD from {user} [{duration}]",
+    start_watching: "Start watching at {timestamp}",
+    notify_hint: "Subscribe to notifications by typing: /notify role: {role}",
+    role_added: "You will now be notified for **{role}**!",
+    role_removed: "You will no longer be notified for **{role}**.",
+    role_not_found: "The role **{role}** does not exist on this server.",
+    role_error: "Your roles could not be updated, please try again later.",
+    notify_description: "Subscribe or unsubscribe for notifications",
+    notify_role_description: "The event role to subscribe or unsubscribe",
+};
+
+const GERMAN: Messages = Messages {
+    live: "{user} ist live!",
+    live_with: "{user} ist live mit **{game}**!",
+    live_on: "{user} ist live auf {platform}!",
+    live_digest: "{count} Streamer sind live gegangen!",
+    switched_game: "{user} spielt jetzt **{game}**!",
+    category_live: "{user} ist in **{game}** live gegangen!",
+    starting_soon: "{user} geht {time} live!",
+    hype_train_started: "Ein Hype Train für {user} ist auf Level {level} gestartet!",
+    hype_train_ended: "Der Hype Train von {user} endete auf Level {level}!",
+    goal_progress: "{user} hat {percent}% des Ziels **{goal}** erreicht!",
+    new_video: "{user} hat ein neues Video veröffentlicht!",
+    follower_milestone: "{user} hat **{count}** Follower erreicht!",
+    subscriber_milestone: "{user} hat **{count}** Abonnenten erreicht!",
+    vod_from: "VOD von {user} [{duration}]",
+    start_watching: "Ab hier ansehen: {timestamp}",
+    notify_hint: "Benachrichtigungen abonnieren mit: /notify role: {role}",
+    role_added: "Du wirst jetzt für **{role}** benachrichtigt!",
+    role_removed: "Du wirst nicht mehr für **{role}** benachrichtigt.",
+    role_not_found: "Die Rolle **{role}** existiert auf diesem Server nicht.",
+    role_error: "Deine Rollen konnten nicht aktualisiert werden, bitte versuche es später erneut.",
+    notify_description: "Benachrichtigungen abonnieren oder abbestellen",
+    notify_role_description: "Die Rolle, die abonniert oder abbestellt werden soll",
+};
+
+const FRENCH: Messages = Messages {
+    live: "{user} est en live !",
+    live_with: "{user} est en live sur **{game}** !",
+    live_on: "{user} est en live sur {platform} !",
+    live_digest: "{count} streamers sont en live !",
+    switched_game: "{user} est passé à **{game}** !",
+    category_live: "{user} a lancé un live dans **{game}** !",
+    starting_soon: "{user} sera en live {time} !",
+    hype_train_started: "Un Hype Train a démarré pour {user} au niveau {level} !",
+    hype_train_ended: "Le Hype Train de {user} s'est terminé au niveau {level} !",
+    goal_progress: "{user} a atteint {percent} % de l'objectif **{goal}** !",
+    new_video: "{user} a publié une nouvelle vidéo !",
+    follower_milestone: "{user} a atteint **{count}** followers !",
+    subscriber_milestone: "{user} a atteint **{count}** abonnés !",
+    vod_from: "VOD de {user} [{duration}]",
+    start_watching: "Commencer à regarder à {timestamp}",
+    notify_hint: "Abonnez-vous aux notifications avec : /notify role: {role}",
+    role_added: "Vous serez désormais notifié pour **{role}** !",
+    role_removed: "Vous ne serez plus notifié pour **{role}**.",
+    role_not_found: "Le rôle **{role}** n'existe pas sur ce serveur.",
+    role_error: "Vos rôles n'ont pas pu être mis à jour, veuillez réessayer plus tard.",
+    notify_description: "S'abonner ou se désabonner des notifications",
+    notify_role_description: "Le rôle auquel s'abonner ou se désabonner",
+};
+
+const SPANISH: Messages = Messages {
+    live: "¡{user} está en directo!",
+    live_with: "¡{user} está en directo con **{game}**!",
+    live_on: "¡{user} está en directo en {platform}!",
+    live_digest: "¡{count} streamers están en directo!",
+    switched_game: "¡{user} cambió de juego a **{game}**!",
+    category_live: "¡{user} empezó un directo en **{game}**!",
+    starting_soon: "¡{user} estará en directo {time}!",
+    hype_train_started: "¡Un Hype Train empezó para {user} en el nivel {level}!",
+    hype_train_ended: "¡El Hype Train de {user} terminó en el nivel {level}!",
+    goal_progress: "¡{user} alcanzó el {percent} % del objetivo **{goal}**!",
+    new_video: "¡{user} publicó un nuevo video!",
+    follower_milestone: "¡{user} alcanzó **{count}** seguidores!",
+    subscriber_milestone: "¡{user} alcanzó **{count}** suscriptores!",
+    vod_from: "VOD de {user} [{duration}]",
+    start_watching: "Empieza a ver en {timestamp}",
+    notify_hint: "Suscríbete a las notificaciones con: /notify role: {role}",
+    role_added: "¡Ahora recibirás notificaciones de **{role}**!",
+    role_removed: "Ya no recibirás notificaciones de **{role}**.",
+    role_not_found: "El rol **{role}** no existe en este servidor.",
+    role_error: "No se pudieron actualizar tus roles, inténtalo de nuevo más tarde.",
+    notify_description: "Suscribirse o cancelar la suscripción a las notificaciones",
+    notify_role_description: "El rol del evento para suscribirse o cancelar la suscripción",
+};
+
+impl Locale {
+    pub const ALL: [Locale; 4] = [Self::English, Self::German, Self::French, Self::Spanish];
+
+    pub const fn messages(self) -> &'static Messages {
+        match self {
+            Self::English => &ENGLISH,
+            Self::German => &GERMAN,
+            Self::French => &FRENCH,
+            Self::Spanish => &SPANISH,
+        }
+    }
+
+    /// The locale code used by discord for command localizations.
+    pub const fn discord_code(self) -> &'static str {
+        match self {
+            Self::English => "en-US",
+            Self::German => "de",
+            Self::French => "fr",
+            Self::Spanish => "es-ES",
+        }
+    }
+
+    /// How dates and durations are written in this language.
+    pub const fn date_style(self) -> DateStyle {
+        match self {
+            Self::English => DateStyle::English,
+            Self::German => DateStyle::Dotted,
+            Self::French | Self::Spanish => DateStyle::Slashed,
+        }
+    }
+
+    /// The localizations of a message for all supported locales, keyed by the discord locale code.
+    pub fn localizations(message: impl Fn(&Messages) -> &'static str) -> Vec<(&'static str, &'static str)> {
+        Self::ALL
+            .into_iter()
+            .map(|locale| (locale.discord_code(), message(locale.messages())))
+            .collect()
+    }
+}
+
+/// Replaces the `{name}` placeholders of the template with the provided values.
+pub fn render(template: &str, args: &[(&str, &str)]) -> String {
+    args.iter().fold(template.to_owned(), |text, (name, value)| {
+        text.replace(&format!("{{{name}}}"), value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let text = render(ENGLISH.live_with, &[("user", "Elajjaz"), ("game", "Elden Ring")]);
+        assert_eq!(text, "Elajjaz is live with **Elden Ring**!");
+        let text = render(GERMAN.vod_from, &[("user", "Elajjaz"), ("duration", "01h02m03s")]);
+        assert_eq!(text, "VOD von Elajjaz [01h02m03s]");
+    }
+
+    #[test]
+    fn test_placeholders() {
+        for locale in Locale::ALL {
+            let messages = locale.messages();
+            assert!(messages.live.contains("{user}"), "{locale:?}");
+            assert!(messages.live_with.contains("{user}") && messages.live_with.contains("{game}"));
+            assert!(messages.live_on.contains("{user}") && messages.live_on.contains("{platform}"));
+            assert!(messages.switched_game.contains("{user}") && messages.switched_game.contains("{game}"));
+            assert!(messages.category_live.contains("{user}") && messages.category_live.contains("{game}"));
+            assert!(messages.starting_soon.contains("{user}") && messages.starting_soon.contains("{time}"));
+            assert!(messages.vod_from.contains("{user}") && messages.vod_from.contains("{duration}"));
+            for message in [messages.hype_train_started, messages.hype_train_ended] {
+                assert!(message.contains("{user}") && message.contains("{level}"), "{locale:?}");
+            }
+            assert!(messages.goal_progress.contains("{goal}") && messages.goal_progress.contains("{percent}"));
+            assert!(messages.new_video.contains("{user}"), "{locale:?}");
+            for message in [messages.follower_milestone, messages.subscriber_milestone] {
+                assert!(message.contains("{user}") && message.contains("{count}"), "{locale:?}");
+            }
+            assert!(messages.start_watching.contains("{timestamp}"), "{locale:?}");
+            assert!(messages.notify_hint.contains("{role}"), "{locale:?}");
+            assert!(messages.role_added.contains("{role}") && messages.role_removed.contains("{role}"));
+            assert!(messages.role_not_found.contains("{role}"), "{locale:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_locale() {
+        let locale: Locale = serde_json::from_str(r#""de""#).unwrap();
+        assert_eq!(locale, Locale::German);
+        assert_eq!(Locale::localizations(|m| m.role_error).len(), Locale::ALL.len());
+    }
+}
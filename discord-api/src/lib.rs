@@ -1,7 +1,17 @@
 mod commands;
+mod forum;
+mod handler;
+mod queue;
+mod sync;
 mod webhook;
 
 pub mod config;
+pub mod i18n;
+pub mod logging;
+pub mod permissions;
 
 pub use commands::Gateway;
+pub use forum::ForumPost;
+pub use handler::*;
+pub use queue::SendQueue;
 pub use webhook::*;
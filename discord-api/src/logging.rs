@@ -0,0 +1,166 @@
+use std::{
+    fmt::{Debug, Write},
+    time::Duration,
+};
+
+use tokio::{sync::mpsc, time::sleep};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+use crate::{WebhookClient, WebhookMessage};
+
+/// Minimum time between two batches sent to the logging webhook
+const INTERVAL: Duration = Duration::from_secs(5);
+/// Maximum number of messages sent for one batch, the remaining records are dropped
+const MAX_MESSAGES: usize = 3;
+/// Maximum length of the content inside the code block of one message
+const MAX_LENGTH: usize = 1900;
+
+pub struct LogRecord {
+    level: Level,
+    target: String,
+    message: String,
+}
+
+/// Tracing layer which forwards WARN and ERROR records to the logging webhook.
+///
+/// Records are queued until the [`LogForwarder`] is started, and dropped if the queue is full.
+pub struct WebhookLayer {
+    sender: mpsc::Sender<LogRecord>,
+}
+
+/// Receiving end of the [`WebhookLayer`], which sends the records in rate limited batches.
+pub struct LogForwarder {
+    receiver: mpsc::Receiver<LogRecord>,
+}
+
+pub fn webhook_layer() -> (WebhookLayer, LogForwarder) {
+    let (sender, receiver) = mpsc::channel(100);
+    (WebhookLayer { sender }, LogForwarder { receiver })
+}
+
+impl<S: Subscriber> Layer<S> for WebhookLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // Ignore our own failures to avoid feedback loops
+        if *metadata.level() > Level::WARN || metadata.target() == module_path!() {
+            return;
+        }
+
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+
+        drop(self.sender.try_send(LogRecord {
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            message: visitor.0,
+        }));
+    }
+}
+
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            drop(write!(self.0, "{:?}", value));
+        } else {
+            drop(write!(self.0, " {}={:?}", field.name(), value));
+        }
+    }
+}
+
+impl LogForwarder {
+    pub fn start(self, webhook: WebhookClient) {
+        tokio::spawn(self.run(webhook));
+    }
+
+    async fn run(mut self, webhook: WebhookClient) {
+        while let Some(first) = self.receiver.recv().await {
+            // Wait for the rest of the burst, this also rate limits the messages
+            sleep(INTERVAL).await;
+
+            let mut batch = vec![first];
+            while let Ok(record) = self.receiver.try_recv() {
+                batch.push(record);
+            }
+
+            for content in Self::format_batch(&batch) {
+                let message = WebhookMessage {
+                    content,
+                    ..Default::default()
+                };
+
+                if let Err(e) = webhook.execute(&message).await {
+                    tracing::warn!("Failed to send logs to webhook: {e}");
+                }
+            }
+        }
+    }
+
+    fn format_batch(batch: &[LogRecord]) -> Vec<String> {
+        let mut messages = Vec::new();
+        let mut current = String::with_capacity(MAX_LENGTH);
+        let mut skipped = 0;
+
+        for record in batch {
+            let line = format!("[{} {}] {}\n", record.level, record.target, record.message).replace("```", "'''");
+            if messages.len() == MAX_MESSAGES {
+                skipped += 1;
+                continue;
+            }
+
+            if current.len() + line.len() > MAX_LENGTH && !current.is_empty() {
+                messages.push(format!("```\n{current}```"));
+                current.clear();
+                if messages.len() == MAX_MESSAGES {
+                    skipped += 1;
+                    continue;
+                }
+            }
+
+            current.extend(line.chars().take(MAX_LENGTH));
+        }
+
+        if !current.is_empty() {
+            messages.push(format!("```\n{current}```"));
+        }
+
+        if skipped > 0 {
+            messages.push(format!("... and {skipped} more"));
+        }
+
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(message: &str) -> LogRecord {
+        LogRecord {
+            level: Level::ERROR,
+            target: "strumbot".to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_format_batch() {
+        let messages = LogForwarder::format_batch(&[record("first"), record("second ```")]);
+        assert_eq!(
+            messages,
+            vec!["```\n[ERROR strumbot] first\n[ERROR strumbot] second '''\n```"]
+        );
+
+        let long = "x".repeat(1000);
+        let batch: Vec<_> = (0..10).map(|_| record(&long)).collect();
+        let messages = LogForwarder::format_batch(&batch);
+        assert_eq!(messages.len(), MAX_MESSAGES + 1);
+        assert_eq!(messages.last().unwrap(), "... and 7 more");
+    }
+}
@@ -0,0 +1,186 @@
+use commons::resolve;
+use twilight_http::Client;
+use twilight_model::{
+    channel::Channel,
+    guild::{Guild, Permissions, Role},
+    id::{
+        marker::{GuildMarker, RoleMarker, UserMarker},
+        Id,
+    },
+};
+use twilight_util::permission_calculator::PermissionCalculator;
+
+use crate::WebhookParams;
+
+/// Checks whether the bot can assign the notification roles, and describes every problem that prevents it.
+pub async fn role_problems(http: &Client, guild: &Guild, roles: &[Id<RoleMarker>]) -> anyhow::Result<Vec<String>> {
+    let user = resolve! { http.current_user() }?;
+    let member = resolve! { http.guild_member(guild.id, user.id) }?;
+    Ok(check_roles(&guild.roles, guild.id, &member.roles, roles))
+}
+
+/// Finds the problems with the roles of the bot member, which has the `member_roles` in the guild.
+fn check_roles(
+    guild_roles: &[Role],
+    guild_id: Id<GuildMarker>,
+    member_roles: &[Id<RoleMarker>],
+    roles: &[Id<RoleMarker>],
+) -> Vec<String> {
+    // The @everyone role has the id of the guild and applies to every member
+    let own: Vec<&Role> = guild_roles
+        .iter()
+        .filter(|role| role.id.cast::<GuildMarker>() == guild_id || member_roles.contains(&role.id))
+        .collect();
+    let permissions = own
+        .iter()
+        .fold(Permissions::empty(), |permissions, role| permissions | role.permissions);
+    let highest = own.iter().map(|role| role.position).max().unwrap_or(0);
+
+    let mut problems = Vec::new();
+    if !permissions.intersects(Permissions::ADMINISTRATOR | Permissions::MANAGE_ROLES) {
+        problems.push("The bot is missing the Manage Roles permission, so it can't create or assign roles".to_owned());
+    }
+
+    for role in guild_roles.iter().filter(|role| roles.contains(&role.id)) {
+        if role.position >= highest {
+            problems.push(format!(
+                "The role {:?} is not below the highest role of the bot, move the bot role above it to assign it",
+                role.name
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Checks whether the bot can pin messages in the channel of the webhook, and describes every problem that prevents it.
+pub async fn pin_problems(http: &Client, guild: &Guild, webhook: &WebhookParams) -> anyhow::Result<Vec<String>> {
+    let webhook = resolve! { http.webhook(webhook.id).token(&webhook.token) }?;
+    let channel = resolve! { http.channel(webhook.channel_id) }?;
+    let user = resolve! { http.current_user() }?;
+    let member = resolve! { http.guild_member(guild.id, user.id) }?;
+    Ok(check_pins(&guild.roles, guild.id, user.id, &member.roles, &channel))
+}
+
+/// Finds the problems with the permissions of the bot member in the channel, which are needed to pin messages.
+fn check_pins(
+    guild_roles: &[Role],
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    member_roles: &[Id<RoleMarker>],
+    channel: &Channel,
+) -> Vec<String> {
+    let everyone = guild_roles
+        .iter()
+        .find(|role| role.id.cast::<GuildMarker>() == guild_id)
+        .map_or(Permissions::empty(), |role| role.permissions);
+    let roles: Vec<(Id<RoleMarker>, Permissions)> = guild_roles
+        .iter()
+        .filter(|role| member_roles.contains(&role.id))
+        .map(|role| (role.id, role.permissions))
+        .collect();
+    // Bots can't own servers, so the owner doesn't matter here
+    let permissions = PermissionCalculator::new(guild_id, user_id, everyone, &roles).in_channel(
+        channel.kind,
+        channel.permission_overwrites.as_deref().unwrap_or_default(),
+    );
+
+    let name = channel.name.as_deref().unwrap_or("notification");
+    let mut problems = Vec::new();
+    if !permissions.contains(Permissions::VIEW_CHANNEL) {
+        problems.push(format!(
+            "The bot can't view the {name:?} channel, so it can't pin the live notifications"
+        ));
+    } else if !permissions.contains(Permissions::MANAGE_MESSAGES) {
+        problems.push(format!(
+            "The bot is missing the Manage Messages permission in the {name:?} channel, so it can't pin the live notifications"
+        ));
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(id: u64, name: &str, position: i64, permissions: Permissions) -> Role {
+        serde_json::from_value(serde_json::json!({
+            "id": id.to_string(),
+            "name": name,
+            "color": 0,
+            "hoist": false,
+            "managed": false,
+            "mentionable": false,
+            "permissions": permissions.bits().to_string(),
+            "position": position,
+            "flags": 0,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_roles() {
+        let guild_roles = vec![
+            role(1, "@everyone", 0, Permissions::SEND_MESSAGES),
+            role(2, "live", 1, Permissions::empty()),
+            role(3, "strumbot", 2, Permissions::MANAGE_ROLES),
+            role(4, "moderator", 3, Permissions::empty()),
+        ];
+
+        let problems = check_roles(&guild_roles, Id::new(1), &[Id::new(3)], &[Id::new(2)]);
+        assert!(problems.is_empty());
+
+        let problems = check_roles(&guild_roles, Id::new(1), &[Id::new(3)], &[Id::new(2), Id::new(4)]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("moderator"));
+
+        let problems = check_roles(&guild_roles, Id::new(1), &[], &[Id::new(2)]);
+        assert_eq!(problems.len(), 2);
+    }
+
+    fn channel(overwrites: serde_json::Value) -> Channel {
+        serde_json::from_value(serde_json::json!({
+            "id": "10",
+            "type": 0,
+            "name": "streams",
+            "permission_overwrites": overwrites,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_pins() {
+        let everyone = Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES;
+        let guild_roles = vec![
+            role(1, "@everyone", 0, everyone),
+            role(3, "strumbot", 2, Permissions::MANAGE_MESSAGES),
+        ];
+        let user = Id::new(5);
+
+        let open = channel(serde_json::json!([]));
+        assert!(check_pins(&guild_roles, Id::new(1), user, &[Id::new(3)], &open).is_empty());
+
+        let problems = check_pins(&guild_roles, Id::new(1), user, &[], &open);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("Manage Messages"));
+
+        let denied = channel(serde_json::json!([
+            {"id": "3", "type": 0, "allow": "0", "deny": Permissions::MANAGE_MESSAGES.bits().to_string()},
+        ]));
+        assert_eq!(
+            check_pins(&guild_roles, Id::new(1), user, &[Id::new(3)], &denied).len(),
+            1
+        );
+
+        let allowed = channel(serde_json::json!([
+            {"id": "5", "type": 1, "allow": Permissions::MANAGE_MESSAGES.bits().to_string(), "deny": "0"},
+        ]));
+        assert!(check_pins(&guild_roles, Id::new(1), user, &[], &allowed).is_empty());
+
+        let hidden = channel(serde_json::json!([
+            {"id": "1", "type": 0, "allow": "0", "deny": Permissions::VIEW_CHANNEL.bits().to_string()},
+        ]));
+        let problems = check_pins(&guild_roles, Id::new(1), user, &[Id::new(3)], &hidden);
+        assert!(problems[0].contains("can't view"));
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::sync::{Mutex, MutexGuard};
+use twilight_http::error::{Error as HttpError, ErrorType};
+
+/// Central queue which serializes webhook executions in FIFO order.
+///
+/// Rate limit waits happen while holding the queue, so a 429 pauses all senders sharing it.
+#[derive(Default)]
+pub struct SendQueue {
+    // The tokio mutex is fair, so waiting senders are served in order
+    turn: Mutex<()>,
+}
+
+impl SendQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) async fn acquire(&self) -> MutexGuard<'_, ()> {
+        self.turn.lock().await
+    }
+}
+
+#[derive(Deserialize)]
+struct RatelimitBody {
+    retry_after: f64,
+}
+
+/// The time to wait before retrying, if the error is a 429 response.
+pub(crate) fn retry_after(error: &HttpError) -> Option<Duration> {
+    match error.kind() {
+        ErrorType::Response { body, status, .. } if status.get() == 429 => {
+            let body: RatelimitBody = serde_json::from_slice(body).ok()?;
+            Some(Duration::from_secs_f64(body.retry_after.max(0.0)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ratelimit_body() {
+        let body: RatelimitBody =
+            serde_json::from_str(r#"{"message": "You are being rate limited.", "retry_after": 0.3, "global": false}"#)
+                .unwrap();
+        assert_eq!(Duration::from_secs_f64(body.retry_after), Duration::from_millis(300));
+    }
+}
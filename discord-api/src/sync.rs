@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use twilight_http::{client::InteractionClient, Client};
+use twilight_model::{
+    application::command::Command,
+    id::{
+        marker::{ApplicationMarker, CommandMarker, GuildMarker},
+        Id,
+    },
+};
+
+use tracing as log;
+
+/// The changes required to get from the existing to the desired commands.
+#[derive(Debug, Default)]
+struct SyncPlan<'a> {
+    /// Commands which are missing or outdated. Creating a command with an existing name overwrites it.
+    upsert: Vec<&'a Command>,
+    /// Commands which are no longer desired
+    delete: Vec<Id<CommandMarker>>,
+}
+
+fn is_same(existing: &Command, desired: &Command) -> bool {
+    existing.kind == desired.kind
+        && existing.description == desired.description
+        && existing.options == desired.options
+        && existing.default_member_permissions == desired.default_member_permissions
+        && existing.dm_permission.unwrap_or(true) == desired.dm_permission.unwrap_or(true)
+        && same_localizations(
+            existing.name_localizations.as_ref(),
+            desired.name_localizations.as_ref(),
+        )
+        && same_localizations(
+            existing.description_localizations.as_ref(),
+            desired.description_localizations.as_ref(),
+        )
+}
+
+/// Compares the localizations of a command, where discord might list missing localizations as an empty map.
+fn same_localizations(existing: Option<&HashMap<String, String>>, desired: Option<&HashMap<String, String>>) -> bool {
+    let empty = HashMap::new();
+    existing.unwrap_or(&empty) == desired.unwrap_or(&empty)
+}
+
+fn plan<'a>(existing: &[Command], desired: &'a [Command]) -> SyncPlan<'a> {
+    let upsert = desired
+        .iter()
+        .filter(|command| !existing.iter().any(|e| e.name == command.name && is_same(e, command)))
+        .collect();
+
+    let delete = existing
+        .iter()
+        .filter(|e| !desired.iter().any(|command| command.name == e.name))
+        .filter_map(|e| e.id)
+        .collect();
+
+    SyncPlan { upsert, delete }
+}
+
+/// Updates the application commands to match the desired set, only sending requests for commands that changed.
+///
+/// With a guild id, the commands are registered for that guild, which applies immediately,
+/// and the global commands of previous setups are removed.
+pub(crate) async fn sync_commands(
+    http: &Client,
+    application_id: Id<ApplicationMarker>,
+    guild_id: Option<Id<GuildMarker>>,
+    desired: &[Command],
+) -> anyhow::Result<()> {
+    let client = http.interaction(application_id);
+
+    let global: &[Command] = match guild_id {
+        Some(guild_id) => {
+            // Guild commands cannot be used in direct messages
+            let guild_commands: Vec<Command> = desired
+                .iter()
+                .cloned()
+                .map(|mut command| {
+                    command.dm_permission = None;
+                    command
+                })
+                .collect();
+
+            // The listed commands only include their localizations if requested
+            let existing = client
+                .guild_commands(guild_id)
+                .with_localizations(true)
+                .await?
+                .models()
+                .await?;
+            apply(&client, Some(guild_id), plan(&existing, &guild_commands)).await?;
+            &[]
+        }
+        None => desired,
+    };
+
+    let existing = client
+        .global_commands()
+        .with_localizations(true)
+        .await?
+        .models()
+        .await?;
+    apply(&client, None, plan(&existing, global)).await
+}
+
+async fn apply(
+    client: &InteractionClient<'_>,
+    guild_id: Option<Id<GuildMarker>>,
+    plan: SyncPlan<'_>,
+) -> anyhow::Result<()> {
+    if plan.upsert.is_empty() && plan.delete.is_empty() {
+        log::debug!("Commands are up to date");
+        return Ok(());
+    }
+
+    for command in plan.upsert {
+        log::info!("Updating command /{}", command.name);
+        let permissions = command.default_member_permissions;
+        match guild_id {
+            Some(guild_id) => {
+                let mut request = client
+                    .create_guild_command(guild_id)
+                    .chat_input(&command.name, &command.description)?
+                    .command_options(&command.options)?;
+                if let Some(permissions) = permissions {
+                    request = request.default_member_permissions(permissions);
+                }
+                if let Some(ref localizations) = command.description_localizations {
+                    request = request.description_localizations(localizations)?;
+                }
+                request.await?;
+            }
+            None => {
+                let mut request = client
+                    .create_global_command()
+                    .chat_input(&command.name, &command.description)?
+                    .command_options(&command.options)?
+                    .dm_permission(command.dm_permission.unwrap_or(true));
+                if let Some(permissions) = permissions {
+                    request = request.default_member_permissions(permissions);
+                }
+                if let Some(ref localizations) = command.description_localizations {
+                    request = request.description_localizations(localizations)?;
+                }
+                request.await?;
+            }
+        }
+    }
+
+    for id in plan.delete {
+        log::info!("Deleting command {id}");
+        match guild_id {
+            Some(guild_id) => client.delete_guild_command(guild_id, id).await?,
+            None => client.delete_global_command(id).await?,
+        };
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use twilight_model::application::command::CommandType;
+    use twilight_util::builder::command::{CommandBuilder, StringBuilder};
+
+    use super::*;
+
+    fn command(name: &str, description: &str, id: u64) -> Command {
+        let mut command = CommandBuilder::new(name, description, CommandType::ChatInput)
+            .option(StringBuilder::new("login", "The twitch login").required(true))
+            .build();
+        command.id = Some(Id::new(id));
+        command
+    }
+
+    #[test]
+    fn test_plan() {
+        let existing = vec![command("notify", "Subscribe", 1), command("old", "Removed", 2)];
+        let desired = vec![command("notify", "Subscribe", 1), command("track", "Track", 3)];
+
+        let result = plan(&existing, &desired);
+        let upsert: Vec<&str> = result.upsert.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(upsert, vec!["track"]);
+        assert_eq!(result.delete, vec![Id::new(2)]);
+
+        let changed = vec![command("notify", "Subscribe or unsubscribe", 1)];
+        let result = plan(&existing, &changed);
+        assert_eq!(result.upsert.len(), 1);
+        assert_eq!(result.delete, vec![Id::new(2)]);
+
+        let result = plan(&desired, &desired);
+        assert!(result.upsert.is_empty() && result.delete.is_empty());
+
+        let mut localized = command("notify", "Subscribe", 1);
+        localized.description_localizations = Some(HashMap::from([("de".to_owned(), "Abonnieren".to_owned())]));
+        let mut listed = localized.clone();
+        listed.name_localizations = Some(HashMap::new());
+        assert!(plan(&[listed], &[localized.clone()]).upsert.is_empty());
+        assert_eq!(plan(&existing, &[localized]).upsert.len(), 1);
+    }
+}
@@ -1,23 +1,487 @@
+use commons::retry::{retry, RetryPolicy};
 use regex::Regex;
-use serde::Deserialize;
-use std::sync::Arc;
-use twilight_http::{request::channel::webhook::ExecuteWebhook, Client};
-use twilight_model::id::{marker::WebhookMarker, Id};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{fs, sync::mpsc, time::sleep};
+use tracing as log;
+
+use crate::{
+    config::DryRunConfig,
+    forum::{self, ForumPost},
+    queue::{self, SendQueue},
+};
+use twilight_http::{
+    error::{Error as HttpError, ErrorType},
+    request::channel::webhook::ExecuteWebhook,
+    response::DeserializeBodyError,
+    Client,
+};
+use twilight_model::{
+    channel::message::{AllowedMentions, Embed},
+    http::attachment::Attachment,
+    id::{
+        marker::{ChannelMarker, MessageMarker, WebhookMarker},
+        Id,
+    },
+};
+
+/// Creates the discord HTTP client, which sends the requests to `api_base` instead of discord if provided.
+///
+/// The `api_base` is the `host:port` of a server speaking plain HTTP, such as a mock server in tests.
+/// Rate limits are not tracked for that server.
+pub fn http_client(token: String, api_base: Option<&str>) -> Client {
+    match api_base {
+        Some(base) => Client::builder()
+            .token(token)
+            .proxy(base.to_owned(), true)
+            .ratelimiter(None)
+            .build(),
+        None => Client::new(token),
+    }
+}
 
 pub struct WebhookClient {
     client: Arc<Client>,
     params: WebhookParams,
+    failures: Option<mpsc::UnboundedSender<WebhookMessage>>,
+    queue: Arc<SendQueue>,
+    crosspost: bool,
+    /// Let discord parse all mentions of messages without explicit allowed mentions
+    trusted_mentions: bool,
+    /// Forum channel which archives the VOD summaries, posted with the bot token
+    forum: Option<Id<ChannelMarker>>,
+    dry_run: Option<DryRunConfig>,
+    /// Number of messages written by the dry run, to keep the file names unique
+    dry_runs: AtomicUsize,
+}
+
+/// Reference to a message sent by the webhook.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SentMessage {
+    pub channel_id: Id<ChannelMarker>,
+    pub message_id: Id<MessageMarker>,
 }
 
 impl WebhookClient {
+    const RETRY: RetryPolicy = RetryPolicy {
+        max_attempts: 4,
+        min_backoff: Duration::from_secs(1),
+        max_backoff: Duration::from_secs(8),
+        jitter: 0.2,
+    };
+    const MAX_RATELIMIT_RETRIES: u32 = 5;
+
     pub fn new(client: Arc<Client>, params: WebhookParams) -> Self {
-        Self { client, params }
+        Self {
+            client,
+            params,
+            failures: None,
+            queue: Arc::new(SendQueue::new()),
+            crosspost: false,
+            trusted_mentions: false,
+            forum: None,
+            dry_run: None,
+            dry_runs: AtomicUsize::new(0),
+        }
+    }
+
+    /// Logs the messages instead of sending them, if the dry run is enabled.
+    ///
+    /// Messages of a dry run are reported as sent, with placeholder ids.
+    pub fn with_dry_run(mut self, config: &DryRunConfig) -> Self {
+        self.dry_run = config.enabled.then(|| config.clone());
+        self
+    }
+
+    /// Publishes every message to the followers of the channel, if the webhook targets an announcement channel.
+    ///
+    /// This requires the bot to have the Manage Messages permission in that channel.
+    pub fn with_crosspost(mut self, crosspost: bool) -> Self {
+        self.crosspost = crosspost;
+        self
+    }
+
+    /// Lets discord ping every mention in the content of messages which don't set their allowed mentions.
+    ///
+    /// By default, such messages ping nobody, since their content might contain stream titles or game names.
+    pub fn with_trusted_mentions(mut self, trusted: bool) -> Self {
+        self.trusted_mentions = trusted;
+        self
+    }
+
+    /// Posts archived messages as new threads in this forum channel.
+    ///
+    /// This requires the bot to have the Send Messages permission in that channel.
+    pub fn with_forum(mut self, channel: Option<Id<ChannelMarker>>) -> Self {
+        self.forum = channel;
+        self
+    }
+
+    /// Uses a shared send queue, so rate limits of one webhook also pause the others.
+    pub fn with_queue(mut self, queue: Arc<SendQueue>) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    /// Sets the queue which receives messages that could not be delivered due to transient errors.
+    pub fn set_failure_queue(&mut self, sender: mpsc::UnboundedSender<WebhookMessage>) {
+        self.failures = Some(sender);
+    }
+
+    /// Checks that the webhook still exists and the token is valid.
+    pub async fn validate(&self) -> Result<(), SendError> {
+        let params = &self.params;
+        self.client.webhook(params.id).token(&params.token).await?;
+        Ok(())
     }
 
     pub fn send_message(&self) -> ExecuteWebhook {
         let params = &self.params;
         self.client.execute_webhook(params.id, &params.token)
     }
+
+    /// Executes the webhook once with the provided message, waiting for rate limits if necessary.
+    pub async fn execute(&self, message: &WebhookMessage) -> Result<SentMessage, SendError> {
+        let _turn = self.queue.acquire().await;
+        self.execute_queued(message).await
+    }
+
+    /// Must only be called while holding the queue.
+    async fn execute_queued(&self, message: &WebhookMessage) -> Result<SentMessage, SendError> {
+        let mut retries = 0;
+        loop {
+            match self.execute_raw(message).await {
+                Err(SendError::Http(e)) if retries < Self::MAX_RATELIMIT_RETRIES => match queue::retry_after(&e) {
+                    Some(delay) => {
+                        log::warn!("Webhook rate limited, pausing queue for {:?}", delay);
+                        sleep(delay).await;
+                        retries += 1;
+                    }
+                    None => return Err(SendError::Http(e)),
+                },
+                result => return result,
+            }
+        }
+    }
+
+    async fn execute_raw(&self, message: &WebhookMessage) -> Result<SentMessage, SendError> {
+        if let Some(ref dry_run) = self.dry_run {
+            self.log_dry_run(dry_run, message).await;
+            return Ok(SentMessage {
+                channel_id: Id::new(1),
+                message_id: Id::new(1),
+            });
+        }
+
+        let attachments = message.attachments();
+        let no_mentions = AllowedMentions::default();
+        let mut request = self.send_message();
+        if !message.content.is_empty() {
+            request = request.content(&message.content).map_err(SendError::invalid)?;
+        }
+        if !message.embeds.is_empty() {
+            request = request.embeds(&message.embeds).map_err(SendError::invalid)?;
+        }
+        if !attachments.is_empty() {
+            request = request.attachments(&attachments).map_err(SendError::invalid)?;
+        }
+        if let Some(url) = message.avatar_url.as_deref() {
+            request = request.avatar_url(url);
+        }
+        if let Some(name) = message.username.as_deref() {
+            request = request.username(name).map_err(SendError::invalid)?;
+        }
+        match message.allowed_mentions {
+            Some(ref allowed) => request = request.allowed_mentions(Some(allowed)),
+            None if !self.trusted_mentions => request = request.allowed_mentions(Some(&no_mentions)),
+            None => {}
+        }
+
+        let sent = request.wait().await?.model().await?;
+        let sent = SentMessage {
+            channel_id: sent.channel_id,
+            message_id: sent.id,
+        };
+
+        if self.crosspost {
+            // The message was delivered either way, so this only logs failures
+            if let Err(e) = self.client.crosspost_message(sent.channel_id, sent.message_id).await {
+                log::warn!("Failed to crosspost message {}: {}", sent.message_id, e);
+            }
+        }
+
+        Ok(sent)
+    }
+
+    async fn log_dry_run(&self, dry_run: &DryRunConfig, message: &WebhookMessage) {
+        let titles: Vec<&str> = message
+            .embeds
+            .iter()
+            .filter_map(|embed| embed.author.as_ref().map(|author| author.name.as_str()))
+            .collect();
+        log::info!(
+            "[dry run] Webhook message {:?} with {} embeds {:?} and {} files",
+            message.content,
+            message.embeds.len(),
+            titles,
+            message.files.len()
+        );
+
+        let Some(ref output) = dry_run.output else {
+            return;
+        };
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis());
+        let name = format!("{output}/{millis}-{}", self.dry_runs.fetch_add(1, Ordering::Relaxed));
+        let json = match serde_json::to_vec_pretty(&DryRunMessage::from(message)) {
+            Ok(json) => json,
+            Err(e) => {
+                log::error!("[dry run] Failed to serialize message: {e}");
+                return;
+            }
+        };
+
+        let mut files = vec![(format!("{name}.json"), json.as_slice())];
+        files.extend(
+            message
+                .files
+                .iter()
+                .map(|file| (format!("{name}-{}", file.filename), file.data.as_slice())),
+        );
+
+        if let Err(e) = fs::create_dir_all(output.as_ref()).await {
+            log::error!("[dry run] Failed to create output directory {output:?}: {e}");
+            return;
+        }
+        for (path, data) in files {
+            if let Err(e) = fs::write(&path, data).await {
+                log::error!("[dry run] Failed to write {path:?}: {e}");
+            }
+        }
+    }
+
+    /// Replaces the content, embeds, and files of a message which was sent by this webhook.
+    ///
+    /// The content is kept if the new message has none.
+    pub async fn edit(&self, sent: SentMessage, message: &WebhookMessage) -> Result<(), SendError> {
+        if self.dry_run.is_some() {
+            log::info!(
+                "[dry run] Edit of message {} with {} embeds and {} files",
+                sent.message_id,
+                message.embeds.len(),
+                message.files.len()
+            );
+            return Ok(());
+        }
+
+        let _turn = self.queue.acquire().await;
+        let attachments = message.attachments();
+        let params = &self.params;
+        let mut request = self
+            .client
+            .update_webhook_message(params.id, &params.token, sent.message_id)
+            .embeds(Some(&message.embeds))
+            .map_err(SendError::invalid)?;
+        if !message.content.is_empty() {
+            request = request.content(Some(&message.content)).map_err(SendError::invalid)?;
+        }
+        if !attachments.is_empty() {
+            // The new files replace the previous attachments, which are not kept
+            request = request.attachments(&attachments).map_err(SendError::invalid)?;
+        }
+
+        request.await?;
+        Ok(())
+    }
+
+    /// Creates the post as a new thread in the forum channel, the post is dropped without a forum channel.
+    pub async fn archive(&self, post: &ForumPost) -> Result<(), SendError> {
+        let Some(channel_id) = self.forum else {
+            return Ok(());
+        };
+
+        if self.dry_run.is_some() {
+            log::info!(
+                "[dry run] Forum post {:?} with tags {:?} and {} messages",
+                post.name,
+                post.tags,
+                post.messages.len()
+            );
+            return Ok(());
+        }
+
+        let _turn = self.queue.acquire().await;
+        forum::create_post(&self.client, channel_id, post).await
+    }
+
+    /// Pins a message which was sent by this webhook.
+    ///
+    /// This requires the bot to have the Manage Messages permission in the channel of the webhook.
+    pub async fn pin(&self, sent: SentMessage) -> Result<(), SendError> {
+        if self.dry_run.is_some() {
+            log::info!("[dry run] Pin of message {}", sent.message_id);
+            return Ok(());
+        }
+
+        self.client.create_pin(sent.channel_id, sent.message_id).await?;
+        Ok(())
+    }
+
+    /// Unpins a message which was pinned with [`pin`](Self::pin).
+    pub async fn unpin(&self, sent: SentMessage) -> Result<(), SendError> {
+        if self.dry_run.is_some() {
+            log::info!("[dry run] Unpin of message {}", sent.message_id);
+            return Ok(());
+        }
+
+        self.client.delete_pin(sent.channel_id, sent.message_id).await?;
+        Ok(())
+    }
+
+    /// Executes the webhook, retrying with exponential backoff on transient errors.
+    ///
+    /// If the message still could not be delivered, it is handed to the failure queue.
+    pub async fn send(&self, message: WebhookMessage) -> Result<SentMessage, SendError> {
+        // Hold the queue for all attempts to keep the notifications in order
+        let _turn = self.queue.acquire().await;
+        let result = retry(
+            &Self::RETRY,
+            || self.execute_queued(&message),
+            |e: &SendError| {
+                if e.is_transient() {
+                    log::warn!("Failed to execute webhook: {}", e);
+                }
+                e.is_transient()
+            },
+        )
+        .await;
+
+        match result {
+            Err(e) if e.is_transient() => {
+                if let Some(ref queue) = self.failures {
+                    drop(queue.send(message));
+                }
+                Err(e)
+            }
+            result => result,
+        }
+    }
+}
+
+/// An owned webhook message, which can be retried and persisted.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WebhookMessage {
+    pub content: String,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    #[serde(default)]
+    pub files: Vec<MessageFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    /// Replaces the name of the webhook for this message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// The mentions which ping, if this is `None` the webhook decides whether any mentions of the content ping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_mentions: Option<AllowedMentions>,
+    /// The login of the streamer, if the message announces their live stream and is outdated once it ended
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub live_stream: Option<String>,
+}
+
+impl WebhookMessage {
+    pub(crate) fn attachments(&self) -> Vec<Attachment> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(id, file)| Attachment::from_bytes(file.filename.clone(), file.data.clone(), id as u64))
+            .collect()
+    }
+}
+
+/// The written form of a message in a dry run, the files are written next to it.
+#[derive(Serialize)]
+struct DryRunMessage<'a> {
+    content: &'a str,
+    embeds: &'a [Embed],
+    files: Vec<&'a str>,
+}
+
+impl<'a> From<&'a WebhookMessage> for DryRunMessage<'a> {
+    fn from(message: &'a WebhookMessage) -> Self {
+        Self {
+            content: &message.content,
+            embeds: &message.embeds,
+            files: message.files.iter().map(|file| file.filename.as_str()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageFile {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum SendError {
+    /// The message was rejected by validation before sending
+    Invalid(anyhow::Error),
+    Http(HttpError),
+    /// The message was sent, but the response could not be read
+    Response(DeserializeBodyError),
+}
+
+impl SendError {
+    fn invalid<E: std::error::Error + Send + Sync + 'static>(e: E) -> Self {
+        SendError::Invalid(e.into())
+    }
+
+    /// Whether the error might resolve itself when retrying later, such as server errors or rate limits.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SendError::Invalid(_) | SendError::Response(_) => false,
+            SendError::Http(e) => match e.kind() {
+                ErrorType::Response { status, .. } => status.get() == 429 || status.is_server_error(),
+                ErrorType::ServiceUnavailable { .. } | ErrorType::RequestError | ErrorType::RequestTimedOut => true,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::Invalid(e) => write!(f, "Invalid message: {}", e),
+            SendError::Http(e) => write!(f, "HTTP error: {}", e),
+            SendError::Response(e) => write!(f, "Invalid response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+impl From<HttpError> for SendError {
+    fn from(e: HttpError) -> Self {
+        SendError::Http(e)
+    }
+}
+
+impl From<DeserializeBodyError> for SendError {
+    fn from(e: DeserializeBodyError) -> Self {
+        SendError::Response(e)
+    }
 }
 
 #[derive(Clone)]
@@ -26,17 +490,16 @@ pub struct WebhookParams {
     pub token: Box<str>,
 }
 
-impl<'de> Deserialize<'de> for WebhookParams {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
+impl FromStr for WebhookParams {
+    type Err = String;
+
+    /// Parses the webhook url, such as `https://discord.com/api/webhooks/<id>/<token>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         let regex =
             Regex::new(r"^https?://(?:[a-zA-Z]+\.)?discord.com/api/webhooks/([0-9]+)/([a-zA-Z0-9-_]+)$").unwrap();
 
-        let s = String::deserialize(deserializer)?;
         let m = regex
-            .captures(&s)
+            .captures(s)
             .and_then(|c| Option::zip(c.get(1).map(|m| m.as_str()), c.get(2).map(|m| m.as_str())))
             .and_then(|(id, token)| Option::zip(id.parse::<u64>().ok(), Some(token)));
         match m {
@@ -44,15 +507,25 @@ impl<'de> Deserialize<'de> for WebhookParams {
                 id: Id::new(id),
                 token: token.into(),
             }),
-            None => Err(serde::de::Error::custom(format!(
+            None => Err(format!(
                 "Failed to parse string using regex.\nRegex: {}\nProvided: {}",
                 regex.as_str(),
                 s
-            ))),
+            )),
         }
     }
 }
 
+impl<'de> Deserialize<'de> for WebhookParams {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl Default for WebhookParams {
     fn default() -> Self {
         Self {
@@ -0,0 +1,137 @@
+use async_trait::async_trait;
+use commons::source::{LiveStream, SourceVideo, StreamSource};
+use reqwest::{Client as HttpClient, StatusCode};
+use serde::de::DeserializeOwned;
+use tracing as log;
+
+use crate::{error::RequestError, Channel, Video};
+
+const BASE_URL: &str = "https://kick.com/api/v2";
+
+pub struct KickClient {
+    http: HttpClient,
+}
+
+impl KickClient {
+    pub fn new() -> Self {
+        Self::with_http(HttpClient::new())
+    }
+
+    pub fn with_http(http: HttpClient) -> Self {
+        Self { http }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, kind: &'static str, endpoint: String) -> Result<T, RequestError> {
+        let response = self.http.get(format!("{}/{}", BASE_URL, endpoint)).send().await?;
+        match response.status() {
+            status if status.is_success() => Ok(serde_json::from_slice(&response.bytes().await?)?),
+            StatusCode::NOT_FOUND => Err(RequestError::NotFound(kind, endpoint)),
+            status => Err(RequestError::from(status)),
+        }
+    }
+
+    pub async fn get_channel(&self, login: &str) -> Result<Channel, RequestError> {
+        self.get("Channel", format!("channels/{}", login.to_lowercase())).await
+    }
+
+    /// Fetches the channels for all logins which are currently live.
+    pub async fn get_streams_by_login(&self, user_login: &[Box<str>]) -> Result<Vec<Channel>, RequestError> {
+        let mut live = Vec::new();
+        // There is no batch endpoint, so this has to fetch each channel individually
+        for login in user_login {
+            match self.get_channel(login).await {
+                Ok(channel) if channel.livestream.as_ref().map_or(false, |s| s.is_live) => live.push(channel),
+                Ok(_) => {}
+                Err(RequestError::NotFound(_, _)) => {
+                    log::warn!("[{login}] Kick channel does not exist");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(live)
+    }
+
+    pub async fn get_videos(&self, login: &str) -> Result<Vec<Video>, RequestError> {
+        self.get("Video", format!("channels/{}/videos", login.to_lowercase()))
+            .await
+    }
+
+    pub async fn get_thumbnail(&self, url: &str) -> Result<Vec<u8>, RequestError> {
+        let response = self.http.get(url).send().await?;
+        if response.status().is_success() {
+            Ok(response.bytes().await?.to_vec())
+        } else if response.status() == StatusCode::NOT_FOUND {
+            Err(RequestError::NotFound("Thumbnail", url.to_owned()))
+        } else {
+            Err(RequestError::Http(response.status()))
+        }
+    }
+}
+
+impl Default for KickClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl StreamSource for KickClient {
+    fn platform(&self) -> &'static str {
+        "Kick"
+    }
+
+    fn color(&self) -> u32 {
+        0x53FC18
+    }
+
+    fn stream_url(&self, channel: &str, _stream_id: &str) -> String {
+        format!("https://kick.com/{channel}")
+    }
+
+    async fn get_live_streams(&self, channels: &[Box<str>]) -> anyhow::Result<Vec<LiveStream>> {
+        let live = self.get_streams_by_login(channels).await?;
+        Ok(live
+            .into_iter()
+            .filter_map(|channel| {
+                let stream = channel.livestream?;
+                Some(LiveStream {
+                    id: stream.id.to_string().into(),
+                    user_id: channel.id.to_string().into(),
+                    user_login: channel.slug,
+                    user_name: channel.user.username,
+                    title: stream.session_title,
+                    category: stream
+                        .categories
+                        .into_iter()
+                        .next()
+                        .map_or_else(|| "".into(), |c| c.name),
+                    thumbnail_url: stream.thumbnail.map_or_else(|| "".into(), |t| t.url),
+                    // Without a start time, the stream started at the first poll which saw it
+                    started_at: stream.start_time.unwrap_or_else(eos::DateTime::utc_now),
+                    viewer_count: stream.viewer_count,
+                })
+            })
+            .collect())
+    }
+
+    async fn get_video(&self, channel: &str, stream_id: &str) -> anyhow::Result<Option<SourceVideo>> {
+        // The videos are listed with the id of their livestream, and only have a link once they were processed
+        let videos = self.get_videos(channel).await?;
+        Ok(videos
+            .into_iter()
+            .find(|video| video.id.to_string() == stream_id)
+            .and_then(|video| {
+                Some(SourceVideo {
+                    url: video.url()?.into(),
+                    id: video.id.to_string().into(),
+                    title: video.session_title,
+                    thumbnail_url: video.thumbnail.map_or_else(|| "".into(), |t| t.src),
+                    duration: video.duration / 1000,
+                })
+            }))
+    }
+
+    async fn get_thumbnail(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(KickClient::get_thumbnail(self, url).await?)
+    }
+}
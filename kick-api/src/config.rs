@@ -0,0 +1,29 @@
+use serde::Deserialize;
+
+const fn default_poll_interval() -> u64 {
+    60
+}
+
+#[derive(Deserialize, Default)]
+pub struct KickConfig {
+    pub user_login: Vec<Box<str>>,
+    /// Seconds between polls
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_parse() {
+        let file = br#"{
+            "user_login": ["xqc", "amouranth"]
+        }"#;
+        let kick: KickConfig = serde_json::from_slice(file).unwrap();
+
+        assert_eq!(kick.user_login, vec!["xqc".into(), "amouranth".into()]);
+        assert_eq!(kick.poll_interval, 60);
+    }
+}
@@ -0,0 +1,7 @@
+pub use client::KickClient;
+pub use model::*;
+
+pub mod client;
+pub mod config;
+pub mod error;
+pub mod model;
@@ -0,0 +1,145 @@
+use serde::{de::IntoDeserializer, Deserialize, Deserializer};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Channel {
+    pub id: u64,
+    pub slug: Box<str>,
+    pub user: ChannelUser,
+    pub livestream: Option<Livestream>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChannelUser {
+    pub username: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Livestream {
+    pub id: u64,
+    pub session_title: Box<str>,
+    pub is_live: bool,
+    #[serde(default, deserialize_with = "deserialize_time")]
+    pub start_time: Option<eos::DateTime>,
+    #[serde(default)]
+    pub viewer_count: u32,
+    #[serde(default)]
+    pub thumbnail: Option<Thumbnail>,
+    #[serde(default)]
+    pub categories: Vec<Category>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Thumbnail {
+    pub url: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Category {
+    pub name: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Video {
+    pub id: u64,
+    pub session_title: Box<str>,
+    /// Duration in milliseconds
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub thumbnail: Option<VideoThumbnail>,
+    pub video: Option<VideoSource>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VideoThumbnail {
+    pub src: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VideoSource {
+    pub uuid: Box<str>,
+}
+
+impl Video {
+    pub fn url(&self) -> Option<String> {
+        self.video
+            .as_ref()
+            .map(|v| format!("https://kick.com/video/{}", v.uuid))
+    }
+}
+
+/// Kick formats its timestamps like "2024-01-01 12:00:00" in UTC, instead of RFC 3339.
+fn deserialize_time<'de, D>(deserializer: D) -> Result<Option<eos::DateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(time) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    let time = format!("{}Z", time.replacen(' ', "T", 1));
+    eos::DateTime::deserialize(IntoDeserializer::<D::Error>::into_deserializer(time)).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_channel() {
+        let channel: Channel = serde_json::from_str(
+            r#"{
+                "id": 668,
+                "slug": "xqc",
+                "user": { "username": "xQc" },
+                "livestream": {
+                    "id": 12345678,
+                    "session_title": "JUST CHATTING",
+                    "is_live": true,
+                    "start_time": "2024-01-01 12:30:00",
+                    "viewer_count": 52131,
+                    "thumbnail": { "url": "https://images.kick.com/video_thumbnails/xqc/thumb.webp" },
+                    "categories": [{ "name": "Just Chatting" }]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(channel.user.username.as_ref(), "xQc");
+        let stream = channel.livestream.unwrap();
+        assert!(stream.is_live);
+        assert_eq!(stream.start_time.unwrap().timestamp().as_seconds(), 1704112200);
+        assert_eq!(stream.viewer_count, 52131);
+        assert_eq!(stream.categories[0].name.as_ref(), "Just Chatting");
+
+        let offline: Channel =
+            serde_json::from_str(r#"{"id": 1, "slug": "test", "user": {"username": "test"}, "livestream": null}"#)
+                .unwrap();
+        assert!(offline.livestream.is_none());
+    }
+
+    #[test]
+    fn parse_video() {
+        let videos: Vec<Video> = serde_json::from_str(
+            r#"[{
+                "id": 12345678,
+                "session_title": "JUST CHATTING",
+                "duration": 3723000,
+                "thumbnail": { "src": "https://images.kick.com/video_thumbnails/xqc/thumb.webp" },
+                "video": { "uuid": "0b2c6a6e-5b9e-4c1a-9d2f-1f2e3d4c5b6a" }
+            }, {
+                "id": 12345679,
+                "session_title": "LIVE",
+                "video": null
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(videos[0].duration, 3723000);
+        assert_eq!(
+            videos[0].url().as_deref(),
+            Some("https://kick.com/video/0b2c6a6e-5b9e-4c1a-9d2f-1f2e3d4c5b6a")
+        );
+        assert!(videos[1].url().is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use discord_api::WebhookMessage;
+use tokio::{sync::mpsc, time::sleep};
+use tracing as log;
+use twilight_util::builder::embed::EmbedBuilder;
+
+use crate::{config::AlertConfig, errors::ErrorCategory, notifier::Notifier};
+
+/// Time to collect the rest of a burst, so related errors end up in the same alert
+const BURST: Duration = Duration::from_secs(30);
+
+/// The errors within the sliding window, by their category.
+struct ErrorWindow {
+    window: Duration,
+    errors: HashMap<ErrorCategory, VecDeque<Instant>>,
+}
+
+impl ErrorWindow {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            errors: HashMap::new(),
+        }
+    }
+
+    /// Adds the error and returns the number of errors of its category within the window.
+    fn record(&mut self, category: ErrorCategory, now: Instant) -> usize {
+        let times = self.errors.entry(category).or_default();
+        times.push_back(now);
+        while times
+            .front()
+            .map_or(false, |time| now.duration_since(*time) > self.window)
+        {
+            times.pop_front();
+        }
+        times.len()
+    }
+
+    /// Removes the categories with at least `threshold` errors within the window, so they are counted from zero again.
+    fn take_exceeded(&mut self, threshold: usize, now: Instant) -> Vec<(ErrorCategory, usize)> {
+        let window = self.window;
+        let mut exceeded = Vec::new();
+        self.errors.retain(|category, times| {
+            times.retain(|time| now.duration_since(*time) <= window);
+            if times.len() >= threshold {
+                exceeded.push((*category, times.len()));
+                return false;
+            }
+            !times.is_empty()
+        });
+        exceeded.sort_by(|a, b| b.1.cmp(&a.1));
+        exceeded
+    }
+}
+
+/// Starts the task which counts the errors and posts an alert to the webhook when the threshold is exceeded.
+pub fn start(config: AlertConfig, webhook: Arc<dyn Notifier>) -> mpsc::UnboundedSender<ErrorCategory> {
+    let (send, receive) = mpsc::unbounded_channel();
+    tokio::spawn(run(config, webhook, receive));
+    send
+}
+
+async fn run(config: AlertConfig, webhook: Arc<dyn Notifier>, mut receive: mpsc::UnboundedReceiver<ErrorCategory>) {
+    let mut errors = ErrorWindow::new(config.window.0);
+    while let Some(category) = receive.recv().await {
+        if errors.record(category, Instant::now()) < config.threshold {
+            continue;
+        }
+
+        sleep(BURST).await;
+        while let Ok(category) = receive.try_recv() {
+            errors.record(category, Instant::now());
+        }
+
+        let exceeded = errors.take_exceeded(config.threshold, Instant::now());
+        send(webhook.as_ref(), &exceeded, config.window.0).await;
+    }
+}
+
+async fn send(webhook: &dyn Notifier, exceeded: &[(ErrorCategory, usize)], window: Duration) {
+    let description = describe(exceeded, window);
+    log::info!("Sending error rate alert: {}", description.replace('\n', ", "));
+    let message = WebhookMessage {
+        embeds: vec![EmbedBuilder::new()
+            .color(0xE03C31)
+            .title("High error rate")
+            .description(description)
+            .build()],
+        ..WebhookMessage::default()
+    };
+
+    if let Err(e) = webhook.send(message).await {
+        log::error!("Failed to send error rate alert: {e}");
+    }
+}
+
+fn describe(exceeded: &[(ErrorCategory, usize)], window: Duration) -> String {
+    let window = match window.as_secs() {
+        secs if secs % 3600 == 0 && secs > 3600 => format!("{} hours", secs / 3600),
+        3600 => "hour".to_owned(),
+        secs if secs % 60 == 0 && secs > 60 => format!("{} minutes", secs / 60),
+        60 => "minute".to_owned(),
+        secs => format!("{secs} seconds"),
+    };
+    exceeded
+        .iter()
+        .map(|(category, count)| format!("{count} {category} in the last {window}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window() {
+        let start = Instant::now();
+        let mut errors = ErrorWindow::new(Duration::from_secs(600));
+        for i in 0..5 {
+            errors.record(ErrorCategory::TwitchServer, start + Duration::from_secs(i * 60));
+        }
+        errors.record(ErrorCategory::Discord, start);
+
+        // The first two errors are outside of the window by now
+        let now = start + Duration::from_secs(11 * 60 + 30);
+        assert_eq!(errors.record(ErrorCategory::TwitchServer, now), 4);
+        assert!(errors.take_exceeded(5, now).is_empty());
+
+        assert_eq!(errors.take_exceeded(4, now), vec![(ErrorCategory::TwitchServer, 4)]);
+        assert_eq!(errors.record(ErrorCategory::TwitchServer, now), 1);
+    }
+
+    #[test]
+    fn test_describe() {
+        let exceeded = [(ErrorCategory::TwitchServer, 15), (ErrorCategory::Discord, 10)];
+        assert_eq!(
+            describe(&exceeded, Duration::from_secs(600)),
+            "15 Twitch server errors in the last 10 minutes\n10 Discord errors in the last 10 minutes"
+        );
+        assert_eq!(
+            describe(&exceeded[1..], Duration::from_secs(3600)),
+            "10 Discord errors in the last hour"
+        );
+    }
+}
@@ -0,0 +1,623 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use commons::source::StreamSource;
+use database_api::{Database, DatabaseError};
+use discord_api::{config::EventName, AppCommand, CommandReply, CommandRequest};
+use futures::{stream, FutureExt, StreamExt};
+use tokio::{
+    sync::mpsc::{self, error::SendTimeoutError},
+    time::sleep,
+};
+use tracing as log;
+use twitch_api::{config::TwitchConfig, TwitchClient};
+
+use crate::{
+    alerts, category,
+    config::Config,
+    digest, engagement,
+    health::Health,
+    milestones,
+    notifier::Notifier,
+    platform::{self, Source},
+    polling::{Activity, PollScheduler},
+    raids,
+    status::StatusBoard,
+    supervisor::start_watcher,
+    tracking::Overrides,
+    upcoming, videos,
+    watcher::{StreamUpdate, StreamWatcher},
+    Cache,
+};
+
+/// Time between checks for renamed twitch users
+const RENAME_CHECK_INTERVAL: Duration = Duration::from_secs(600);
+/// Maximum number of watchers which receive their update at the same time
+const MAX_DISPATCH_CONCURRENCY: usize = 16;
+
+/// Builds a [`Bot`] from the configuration and the implementations of its dependencies.
+#[derive(Default)]
+pub struct BotBuilder {
+    config: Option<Arc<Config>>,
+    database: Option<Arc<Cache>>,
+    notifier: Option<Arc<dyn Notifier>>,
+    twitch: Option<Arc<TwitchClient>>,
+    health: Option<Arc<Health>>,
+    alerts: Option<Arc<dyn Notifier>>,
+    sources: Vec<Source>,
+}
+
+impl BotBuilder {
+    pub fn config(mut self, config: Arc<Config>) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// The database for the watcher states, stats, and other persisted state.
+    pub fn database(mut self, database: Arc<Cache>) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// Receives the stream notifications, which is the discord webhook for the bot itself.
+    pub fn notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn twitch(mut self, client: Arc<TwitchClient>) -> Self {
+        self.twitch = Some(client);
+        self
+    }
+
+    /// Records the polls, so the health check can report a stuck main loop.
+    pub fn health(mut self, health: Arc<Health>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Receives the error rate alerts, which is the logging webhook for the bot itself.
+    pub fn alerts(mut self, alerts: Arc<dyn Notifier>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Adds a platform besides twitch, whose channels are watched like the tracked twitch streamers.
+    pub fn source(mut self, source: Arc<dyn StreamSource>, channels: Vec<Box<str>>, interval: Duration) -> Self {
+        self.sources.push(Source {
+            source,
+            channels,
+            interval,
+        });
+        self
+    }
+
+    /// Creates the bot and loads its persisted state from the database.
+    pub async fn build(self) -> anyhow::Result<Bot> {
+        let config = self.config.context("Missing configuration")?;
+        let cache = self.database.context("Missing database")?;
+        let notifier = self.notifier.context("Missing notifier")?;
+        let client = self.twitch.context("Missing twitch client")?;
+
+        let mut status = StatusBoard::default();
+        if let Some(ref digest) = config.discord.digest {
+            status = status.with_digest(digest::start(
+                digest.window.0,
+                Arc::clone(&config),
+                Arc::clone(&notifier),
+            ));
+        }
+        if let Some(alerts) = self.alerts.filter(|_| config.logging.alerts.enabled) {
+            status = status.with_alerts(alerts::start(config.logging.alerts.clone(), alerts));
+        }
+        let status = Arc::new(status);
+        status.load_mutes(&cache, config.cache.enabled).await;
+        status.load_announced(&cache).await;
+
+        let (commands, tracking) = mpsc::channel(10);
+        Ok(Bot {
+            config,
+            cache,
+            notifier,
+            client,
+            health: self.health,
+            sources: self.sources,
+            status,
+            commands,
+            tracking,
+        })
+    }
+}
+
+/// The watcher pipeline, which polls the tracked streams and sends their notifications.
+pub struct Bot {
+    config: Arc<Config>,
+    cache: Arc<Cache>,
+    notifier: Arc<dyn Notifier>,
+    client: Arc<TwitchClient>,
+    health: Option<Arc<Health>>,
+    sources: Vec<Source>,
+    status: Arc<StatusBoard>,
+    commands: mpsc::Sender<CommandRequest>,
+    tracking: mpsc::Receiver<CommandRequest>,
+}
+
+impl Bot {
+    pub fn builder() -> BotBuilder {
+        BotBuilder::default()
+    }
+
+    /// The live streams and mutes, which are shared with the command handler.
+    pub fn status(&self) -> Arc<StatusBoard> {
+        Arc::clone(&self.status)
+    }
+
+    /// Accepts the commands which change the tracked streamers or trigger a poll, like `/track` and `/resync`.
+    pub fn commands(&self) -> mpsc::Sender<CommandRequest> {
+        self.commands.clone()
+    }
+
+    /// Polls the tracked streams until an error occurs, the sources besides twitch run in the background.
+    pub async fn run(self) -> anyhow::Result<()> {
+        let Self {
+            config,
+            cache,
+            notifier: webhook,
+            client,
+            health,
+            sources,
+            status,
+            mut tracking,
+            ..
+        } = self;
+
+        for source in sources {
+            tokio::spawn(platform::watch_source(
+                source,
+                Arc::clone(&client),
+                Arc::clone(&webhook),
+                Arc::clone(&config),
+                Arc::clone(&status),
+                Arc::clone(&cache),
+            ));
+        }
+
+        for category in &config.twitch.categories {
+            tokio::spawn(category::watch(
+                category.clone(),
+                Arc::clone(&client),
+                Arc::clone(&webhook),
+                Arc::clone(&config),
+                Arc::clone(&status),
+            ));
+        }
+
+        if config.discord.enabled_events.contains(&EventName::Upcoming) {
+            tokio::spawn(upcoming::remind(
+                Arc::clone(&config),
+                Arc::clone(&client),
+                Arc::clone(&webhook),
+                Arc::clone(&status),
+            ));
+        }
+
+        if config.discord.enabled_events.contains(&EventName::Video) {
+            tokio::spawn(videos::watch(
+                Arc::clone(&config),
+                Arc::clone(&client),
+                Arc::clone(&webhook),
+                Arc::clone(&status),
+                Arc::clone(&cache),
+            ));
+        }
+
+        if config.discord.enabled_events.contains(&EventName::Milestone) {
+            tokio::spawn(milestones::watch(
+                Arc::clone(&config),
+                Arc::clone(&client),
+                Arc::clone(&webhook),
+                Arc::clone(&status),
+                Arc::clone(&cache),
+            ));
+        }
+
+        if config.raids.enabled {
+            tokio::spawn(raids::listen(Arc::clone(&client), Arc::clone(&status)));
+        }
+
+        let events = &config.discord.enabled_events;
+        if events.contains(&EventName::HypeTrain) || events.contains(&EventName::Goal) {
+            tokio::spawn(engagement::watch(
+                Arc::clone(&config),
+                Arc::clone(&client),
+                Arc::clone(&webhook),
+                Arc::clone(&status),
+            ));
+        }
+
+        let mut watchers = HashMap::with_capacity(config.twitch.user_login.len());
+
+        // Map of user id -> current login, streams are tracked by id to handle renames
+        let mut overrides = Overrides::load(&cache, config.cache.enabled).await;
+        let mut tracked = resolve_members(&client, &config.twitch, &overrides).await?;
+        let mut user_ids: Vec<Box<str>> = tracked.keys().cloned().collect();
+        let mut next_rename_check = Instant::now() + RENAME_CHECK_INTERVAL;
+        let membership_interval = Duration::from_secs(config.twitch.membership_interval);
+        let mut next_membership_check = Instant::now() + membership_interval;
+
+        if config.cache.enabled {
+            let logins: Vec<String> = tracked.values().cloned().collect();
+            if let Err(err) = load_cache(&mut watchers, &logins, &config, &client, &webhook, &cache, &status).await {
+                log::error!("Could not load cache: {}", err);
+            }
+        }
+
+        let idle_interval = match config.twitch.idle_poll_interval {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        let activity = Activity::load(&cache, config.cache.enabled).await;
+        let mut scheduler = PollScheduler::new(activity, idle_interval);
+        let send_timeout = Duration::from_secs(config.watchers.send_timeout);
+
+        log::info!("Listening for streams from {:?}", tracked.values());
+
+        // A command which arrived while waiting for the next poll
+        let mut pending = None;
+        loop {
+            log::debug!("Fetching streams {:?}", tracked.values());
+            watchers.retain(|_, watcher| !watcher.is_closed());
+
+            let has_members = config.twitch.team.is_some() || config.twitch.followed_by.is_some();
+            if has_members && next_membership_check.elapsed() > Duration::ZERO {
+                next_membership_check = Instant::now() + membership_interval;
+                match refresh_members(&client, &config.twitch, &overrides, &mut tracked, &mut watchers).await {
+                    Ok(()) => user_ids = tracked.keys().cloned().collect(),
+                    Err(e) => log::error!("Failed to refresh tracked channels: {e}"),
+                }
+            } else if next_rename_check.elapsed() > Duration::ZERO {
+                next_rename_check = Instant::now() + RENAME_CHECK_INTERVAL;
+                if let Err(e) = follow_renames(&client, &mut tracked, &mut watchers).await {
+                    log::error!("Failed to check for renamed users: {e}");
+                }
+            }
+
+            // Apply changes from the /track and /untrack commands
+            let mut resync = None;
+            while let Some(request) = pending.take().or_else(|| tracking.try_recv().ok()) {
+                let CommandRequest { command, reply } = request;
+                if matches!(command, AppCommand::Resync) {
+                    log::info!("Polling all tracked streamers for /resync");
+                    resync = Some(reply);
+                    continue;
+                }
+
+                let result = on_tracking_command(
+                    command,
+                    &client,
+                    &mut tracked,
+                    &mut watchers,
+                    &mut overrides,
+                    &cache,
+                    config.cache.enabled,
+                )
+                .await;
+
+                let message = result.unwrap_or_else(|e| {
+                    log::error!("Failed to update tracked streamers: {e}");
+                    CommandReply::text("Failed to update the tracked streamers, check the logs for details.")
+                });
+                drop(reply.send(message));
+                user_ids = tracked.keys().cloned().collect();
+            }
+
+            status.set_tracked(&user_ids);
+
+            // 1. Fetch streams in batch, skipping streamers which are unlikely to go live right now
+            let polled = if resync.is_some() {
+                user_ids.clone()
+            } else {
+                scheduler.due(&user_ids, |id| {
+                    tracked.get(id).map_or(false, |login| watchers.contains_key(login))
+                })
+            };
+            let streams = if polled.is_empty() {
+                Vec::new()
+            } else {
+                client.get_streams_by_user_id(&polled).await?
+            };
+            if let Some(ref health) = health {
+                health.record_poll();
+            }
+            let live = streams.len();
+
+            // 2. Check which streams are offline/missing
+            let mut offline: HashSet<String> = polled.iter().filter_map(|id| tracked.get(id)).cloned().collect();
+
+            // 3. Collect updates for all currently live streams
+            let mut updates = Vec::with_capacity(polled.len());
+            for stream in streams {
+                let name = match tracked.get(&stream.user_id) {
+                    Some(login) => login.clone(),
+                    None => stream.user_login.to_lowercase(),
+                };
+                offline.remove(&name);
+                let send = match watchers.get(&name) {
+                    Some(send) => send.clone(),
+                    None => {
+                        let started_at = stream.started_at.timestamp().as_seconds();
+                        scheduler
+                            .record(&stream.user_id, started_at, &cache, config.cache.enabled)
+                            .await;
+                        let watcher = StreamWatcher::new(name.to_string(), Arc::clone(&config));
+                        let send = start_watcher(config.cache.enabled, &client, &webhook, &cache, &status, watcher);
+                        watchers.insert(name.clone(), send.clone());
+                        send
+                    }
+                };
+                updates.push((name, send, StreamUpdate::Live(Box::new(stream))));
+            }
+
+            log::debug!("Offline streams are: {:?}", offline);
+
+            // 4. Collect updates for all streams that are offline
+            for name in offline {
+                if let Some(send) = watchers.get(&name) {
+                    updates.push((name, send.clone(), StreamUpdate::Offline));
+                }
+            }
+
+            // Every watcher receives at most one update per poll, so the order of its updates is kept
+            dispatch(updates, send_timeout, health.as_deref()).await;
+
+            if let Some(reply) = resync {
+                let message = format!("Polled {} tracked streamers, {live} of them are live.", polled.len());
+                drop(reply.send(CommandReply::text(message)));
+            }
+
+            // 5. Refresh oauth token if needed and wait 10 seconds for next poll event, or until the next command
+            let wait = async {
+                tokio::select! {
+                    () = sleep(Duration::from_secs(10)) => None,
+                    Some(request) = tracking.recv() => Some(request),
+                }
+            };
+            pending = tokio::try_join!(client.refresh_auth(), wait.map(Result::Ok))?.1;
+        }
+    }
+}
+
+/// Resolves the configured logins to user ids, and reports logins which do not exist.
+async fn resolve_users(client: &TwitchClient, logins: &[Box<str>]) -> anyhow::Result<HashMap<Box<str>, String>> {
+    let users = client.get_users_by_login(logins).await?;
+
+    let tracked: HashMap<Box<str>, String> = users
+        .into_iter()
+        .map(|user| (user.id, user.login.to_lowercase()))
+        .collect();
+
+    for login in logins {
+        let login = login.to_lowercase();
+        if !tracked.values().any(|l| *l == login) {
+            log::error!("Could not find twitch user {login:?}, the account might have been renamed or banned");
+        }
+    }
+
+    Ok(tracked)
+}
+
+/// Resolves all channels to track, from the configured logins, the team, and the followed channels.
+async fn resolve_members(
+    client: &TwitchClient,
+    config: &TwitchConfig,
+    overrides: &Overrides,
+) -> anyhow::Result<HashMap<Box<str>, String>> {
+    let mut tracked = resolve_users(client, &overrides.logins(&config.user_login)).await?;
+
+    if let Some(ref team) = config.team {
+        for member in client.get_team_members(team).await? {
+            tracked.insert(member.user_id, member.user_login.to_lowercase());
+        }
+    }
+
+    if let Some(ref login) = config.followed_by {
+        let Some(user) = client.get_users_by_login(&[login.clone()]).await?.pop() else {
+            anyhow::bail!("Could not find twitch user {login:?} for followed channels");
+        };
+
+        for channel in client.get_followed_channels(&user.id).await? {
+            tracked.insert(channel.broadcaster_id, channel.broadcaster_login.to_lowercase());
+        }
+    }
+
+    tracked.retain(|_, login| !overrides.is_removed(login));
+    Ok(tracked)
+}
+
+/// Refreshes the tracked channels. Channels which are no longer members keep their watcher until the stream ended.
+async fn refresh_members(
+    client: &TwitchClient,
+    config: &TwitchConfig,
+    overrides: &Overrides,
+    tracked: &mut HashMap<Box<str>, String>,
+    watchers: &mut HashMap<String, mpsc::Sender<StreamUpdate>>,
+) -> anyhow::Result<()> {
+    let mut members = resolve_members(client, config, overrides).await?;
+
+    let mut retired = Vec::new();
+    for (id, login) in tracked.iter_mut() {
+        match members.remove(id) {
+            Some(current) if *login != current => rename(watchers, login, current).await,
+            Some(_) => {}
+            None if watchers.contains_key(login.as_str()) => {
+                log::debug!("Keeping watcher for {login:?} until the stream has ended");
+            }
+            None => {
+                log::info!("No longer tracking streams from {login:?}");
+                retired.push(id.clone());
+            }
+        }
+    }
+
+    for id in retired {
+        tracked.remove(&id);
+    }
+
+    for (id, login) in members {
+        log::info!("Now tracking streams from {login:?}");
+        tracked.insert(id, login);
+    }
+
+    Ok(())
+}
+
+/// Starts or stops tracking a streamer, and persists the change for the next start.
+async fn on_tracking_command(
+    command: AppCommand,
+    client: &TwitchClient,
+    tracked: &mut HashMap<Box<str>, String>,
+    watchers: &mut HashMap<String, mpsc::Sender<StreamUpdate>>,
+    overrides: &mut Overrides,
+    cache: &Arc<Cache>,
+    persist: bool,
+) -> anyhow::Result<CommandReply> {
+    match command {
+        AppCommand::Track(login) => {
+            let login = login.to_lowercase();
+            if tracked.values().any(|l| *l == login) {
+                return Ok(CommandReply::text(format!("Already tracking `{login}`.")));
+            }
+
+            let Some(user) = client.get_users_by_login(&[login.as_str().into()]).await?.pop() else {
+                return Ok(CommandReply::text(format!("Could not find twitch user `{login}`.")));
+            };
+
+            log::info!("Now tracking streams from {login:?}");
+            tracked.insert(user.id, login.clone());
+            overrides.update(cache, persist, |o| o.track(&login)).await;
+            Ok(CommandReply::text(format!("Now tracking `{login}`.")))
+        }
+        AppCommand::Untrack(login) => {
+            let login = login.to_lowercase();
+            let before = tracked.len();
+            tracked.retain(|_, l| *l != login);
+            if tracked.len() == before {
+                return Ok(CommandReply::text(format!("Not tracking `{login}`.")));
+            }
+
+            // Dropping the sender ends the watcher and removes its cache entry
+            watchers.remove(&login);
+
+            log::info!("No longer tracking streams from {login:?}");
+            overrides.update(cache, persist, |o| o.untrack(&login)).await;
+            Ok(CommandReply::text(format!("No longer tracking `{login}`.")))
+        }
+        other => anyhow::bail!("Unexpected command for the tracked streamers: {other:?}"),
+    }
+}
+
+/// Checks the current logins of all tracked users, and moves the watchers of renamed users to the new login.
+async fn follow_renames(
+    client: &TwitchClient,
+    tracked: &mut HashMap<Box<str>, String>,
+    watchers: &mut HashMap<String, mpsc::Sender<StreamUpdate>>,
+) -> anyhow::Result<()> {
+    let ids: Vec<Box<str>> = tracked.keys().cloned().collect();
+    for user in client.get_users_by_id(&ids).await? {
+        let login = user.login.to_lowercase();
+        let Some(current) = tracked.get_mut(&user.id) else {
+            continue;
+        };
+
+        if *current != login {
+            rename(watchers, current, login).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the watcher of a renamed user to the new login.
+async fn rename(watchers: &mut HashMap<String, mpsc::Sender<StreamUpdate>>, current: &mut String, login: String) {
+    log::warn!("Twitch user {current:?} was renamed to {login:?}");
+    if let Some(send) = watchers.remove(current.as_str()) {
+        // The rename is never dropped, since the watcher would keep the old cache key
+        drop(send.send(StreamUpdate::Renamed(login.clone().into())).await);
+        watchers.insert(login.clone(), send);
+    }
+    *current = login;
+}
+
+/// Sends the updates to their watchers concurrently, so a busy watcher does not delay the updates of the others.
+pub async fn dispatch(
+    updates: Vec<(String, mpsc::Sender<StreamUpdate>, StreamUpdate)>,
+    timeout: Duration,
+    health: Option<&Health>,
+) {
+    stream::iter(updates)
+        .for_each_concurrent(MAX_DISPATCH_CONCURRENCY, |(name, send, event)| async move {
+            push(&name, &send, event, timeout, health).await;
+        })
+        .await;
+}
+
+/// Sends the update to a watcher, unless its queue stays full until the timeout.
+///
+/// A full queue means the watcher is stuck on a slow update, and the next poll sends a newer update anyway.
+async fn push(
+    name: &str,
+    s: &mpsc::Sender<StreamUpdate>,
+    event: StreamUpdate,
+    timeout: Duration,
+    health: Option<&Health>,
+) {
+    if let Err(SendTimeoutError::Timeout(_)) = s.send_timeout(event, timeout).await {
+        log::warn!("[{name}] Dropped stream update, the watcher is still busy with previous updates");
+        if let Some(health) = health {
+            health.record_dropped_update();
+        }
+    }
+}
+
+async fn load_cache(
+    watchers: &mut HashMap<String, mpsc::Sender<StreamUpdate>>,
+    logins: &[String],
+    config: &Arc<Config>,
+    client: &Arc<TwitchClient>,
+    webhook: &Arc<dyn Notifier>,
+    db: &Arc<Cache>,
+    status: &Arc<StatusBoard>,
+) -> anyhow::Result<()> {
+    let mut count = 0;
+    for name in logins {
+        let name = name.clone();
+        let file = db.read::<StreamWatcher>(&name).await;
+
+        match file {
+            Err(DatabaseError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                log::debug!("Cache file for {} not found", name);
+            }
+            Err(DatabaseError::Io(err)) => {
+                log::error!("Could not load cache for {name}: {}", err);
+            }
+            Err(DatabaseError::Serde(err)) => {
+                log::warn!("Failed to parse watcher state for watcher {name:?} from cache: {}", err);
+            }
+            Ok(mut watcher) => {
+                watcher = watcher.set_config(config.clone());
+                let sender = start_watcher(true, client, webhook, db, status, watcher);
+                watchers.insert(name, sender);
+                count += 1;
+            }
+        }
+    }
+
+    if count > 0 {
+        log::info!("Loaded {count} cached stream watchers");
+    }
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use commons::util::escape_markdown;
+use discord_api::{config::EventName, i18n, WebhookMessage};
+use eos::DateTime;
+use tokio::time::sleep;
+use tracing as log;
+use twilight_model::channel::message::embed::EmbedFooter;
+use twilight_util::builder::embed::ImageSource;
+use twitch_api::{client::resolve_thumbnail_url, config::CategoryConfig, Stream, TwitchClient};
+
+use crate::{
+    config::Config,
+    mentions::Mention,
+    notifications,
+    notifier::Notifier,
+    policy::{self, Delivery},
+    status::StatusBoard,
+};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+/// Limits the number of announcements within the last hour.
+struct RateLimit {
+    max: usize,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimit {
+    fn new(max: u32) -> Self {
+        Self {
+            max: max as usize,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Records an announcement at the provided time, unless the limit is reached.
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        while self
+            .sent
+            .front()
+            .map_or(false, |sent| now.duration_since(*sent) >= HOUR)
+        {
+            self.sent.pop_front();
+        }
+
+        if self.sent.len() >= self.max {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// Polls a category and announces the streams which go live in it.
+///
+/// The streams of the tracked streamers are skipped, since they have their own notifications.
+pub async fn watch(
+    category: CategoryConfig,
+    client: Arc<TwitchClient>,
+    webhook: Arc<dyn Notifier>,
+    config: Arc<Config>,
+    status: Arc<StatusBoard>,
+) {
+    log::info!("Listening for streams in category {}", category.game_id);
+
+    let interval = Duration::from_secs(category.poll_interval);
+    let mut limit = RateLimit::new(category.max_per_hour);
+    // The streams which were already live at startup are not announced
+    let mut known: Option<HashSet<Box<str>>> = None;
+    loop {
+        match client.get_streams_by_game(&category.game_id).await {
+            Ok(streams) => {
+                // Streams below the viewer threshold are remembered too, so they are not announced once they grow
+                let ids: HashSet<Box<str>> = streams.iter().map(|stream| stream.id.clone()).collect();
+                // The tracked streamers change with the /track and /untrack commands
+                let tracked: HashSet<Box<str>> = status.tracked().into_iter().collect();
+                let streams: Vec<Stream> = streams
+                    .into_iter()
+                    .filter(|stream| stream.viewer_count >= category.min_viewers)
+                    .filter(|stream| !tracked.contains(&stream.user_id))
+                    .collect();
+
+                if let Some(ref known) = known {
+                    for stream in streams.iter().filter(|stream| !known.contains(&stream.id)) {
+                        if limit.try_acquire(Instant::now()) {
+                            announce(stream, &client, webhook.as_ref(), &config).await;
+                        } else {
+                            log::debug!(
+                                "[{}] Skipping stream in category {}, the hourly limit is reached",
+                                stream.user_login,
+                                category.game_id
+                            );
+                        }
+                    }
+                }
+
+                known = Some(ids);
+            }
+            Err(e) => log::error!("Failed to fetch streams of category {}: {e}", category.game_id),
+        }
+
+        sleep(interval).await;
+    }
+}
+
+async fn announce(stream: &Stream, client: &TwitchClient, webhook: &dyn Notifier, config: &Config) {
+    if !config.discord.enabled_events.contains(&EventName::Category) {
+        return;
+    }
+
+    let now = DateTime::utc_now().timestamp().as_seconds();
+    let mention = match policy::delivery(&config.discord, EventName::Category, now) {
+        Delivery::Normal => config.mention(EventName::Category),
+        Delivery::Silent => Mention::silent(EventName::Category),
+        Delivery::Skip => {
+            log::info!(
+                "[{}] Skipping category notification during quiet hours",
+                stream.user_login
+            );
+            return;
+        }
+    };
+
+    let game = match stream.get_game(client).await {
+        Ok(game) => game,
+        Err(e) => {
+            log::error!("[{}] Failed to get game of category stream: {e}", stream.user_login);
+            return;
+        }
+    };
+
+    log::info!(
+        "[{}] User started streaming in category {}",
+        stream.user_login,
+        game.name
+    );
+    let escape = config.discord.escape;
+    let started_at = stream.started_at.timestamp().as_seconds();
+    let mut embed = notifications::stream_embed(stream, &game, started_at, &config.discord);
+    if let Ok(image) = ImageSource::url(resolve_thumbnail_url(&stream.thumbnail_url)) {
+        embed = embed.image(image);
+    }
+
+    let messages = config.discord.locale.messages();
+    let role_name = &config.discord.role_name.category;
+    if config.discord.show_notify_hints && !role_name.is_empty() {
+        embed = embed.footer(EmbedFooter {
+            icon_url: None,
+            proxy_icon_url: None,
+            text: i18n::render(messages.notify_hint, &[("role", role_name.as_ref())]),
+        });
+    }
+
+    let text = i18n::render(
+        messages.category_live,
+        &[
+            ("user", escape_markdown(&stream.user_name, escape).as_ref()),
+            ("game", escape_markdown(&game.name, escape).as_ref()),
+        ],
+    );
+
+    let message = WebhookMessage {
+        content: format!("{}{text}", mention.prefix()),
+        embeds: vec![embed.build()],
+        files: Vec::new(),
+        avatar_url: config.discord.avatar_url.as_deref().map(str::to_owned),
+        username: config.discord.username(&stream.user_name),
+        allowed_mentions: mention.allowed_mentions(),
+        live_stream: None,
+    };
+
+    if let Err(e) = webhook.send(message).await {
+        log::error!("[{}] Failed to send category notification: {e}", stream.user_login);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(2);
+        assert!(limit.try_acquire(start));
+        assert!(limit.try_acquire(start + Duration::from_secs(60)));
+        assert!(!limit.try_acquire(start + Duration::from_secs(120)));
+        // The first announcement is older than an hour
+        assert!(limit.try_acquire(start + HOUR));
+        assert!(!limit.try_acquire(start + HOUR + Duration::from_secs(30)));
+    }
+}
@@ -0,0 +1,85 @@
+use image::{codecs::jpeg::JpegEncoder, imageops, imageops::FilterType, RgbImage};
+
+/// The size of each tile, which matches the aspect ratio of the twitch box art
+const TILE_WIDTH: u32 = 285;
+const TILE_HEIGHT: u32 = 380;
+/// The grid is at most 2x2, later games are not shown
+pub const MAX_TILES: usize = 4;
+const COLUMNS: u32 = 2;
+
+/// Combines the images into one grid, like the box art of each game played in a stream.
+///
+/// Images which cannot be decoded are skipped. Returns the encoded jpeg, or `None` if no image could be decoded.
+pub fn render(images: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let tiles: Vec<RgbImage> = images
+        .iter()
+        .filter_map(|data| image::load_from_memory(data).ok())
+        .take(MAX_TILES)
+        .map(|image| {
+            image
+                .resize_to_fill(TILE_WIDTH, TILE_HEIGHT, FilterType::Triangle)
+                .to_rgb8()
+        })
+        .collect();
+
+    if tiles.is_empty() {
+        return None;
+    }
+
+    let count = tiles.len() as u32;
+    let columns = count.min(COLUMNS);
+    let rows = count.div_ceil(COLUMNS);
+    let mut canvas = RgbImage::new(columns * TILE_WIDTH, rows * TILE_HEIGHT);
+    for (index, tile) in (0..).zip(tiles.iter()) {
+        let x = (index % COLUMNS) * TILE_WIDTH;
+        let y = (index / COLUMNS) * TILE_HEIGHT;
+        imageops::overlay(&mut canvas, tile, x.into(), y.into());
+    }
+
+    let mut output = Vec::new();
+    JpegEncoder::new_with_quality(&mut output, 85)
+        .encode_image(&canvas)
+        .ok()?;
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageOutputFormat, Rgb};
+
+    use super::*;
+
+    fn png(color: [u8; 3]) -> Vec<u8> {
+        let mut data = Vec::new();
+        RgbImage::from_pixel(10, 10, Rgb(color))
+            .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_render() {
+        assert!(render(&[]).is_none());
+        assert!(render(&[b"not an image".to_vec()]).is_none());
+
+        let single = image::load_from_memory(&render(&[png([255, 0, 0])]).unwrap()).unwrap();
+        assert_eq!((single.width(), single.height()), (TILE_WIDTH, TILE_HEIGHT));
+
+        // Invalid images are skipped and the grid is limited to 2x2
+        let images = vec![
+            png([255, 0, 0]),
+            b"not an image".to_vec(),
+            png([0, 255, 0]),
+            png([0, 0, 255]),
+            png([255, 255, 0]),
+            png([0, 255, 255]),
+        ];
+        let grid = image::load_from_memory(&render(&images).unwrap()).unwrap();
+        assert_eq!((grid.width(), grid.height()), (2 * TILE_WIDTH, 2 * TILE_HEIGHT));
+
+        let three = image::load_from_memory(&render(&images[..4]).unwrap()).unwrap();
+        assert_eq!((three.width(), three.height()), (2 * TILE_WIDTH, 2 * TILE_HEIGHT));
+    }
+}
@@ -0,0 +1,280 @@
+use std::{sync::Arc, time::Duration};
+
+use commons::util::{parse_duration, Timestamp};
+use discord_api::{i18n, AppCommand, ClipPeriod, CommandReply, CommandRequest, ExportFormat, MessageFile};
+use eos::DateTime;
+use tokio::sync::mpsc;
+use tracing as log;
+use twilight_util::builder::embed::{EmbedAuthorBuilder, EmbedBuilder, EmbedFieldBuilder, ImageSource};
+use twitch_api::{client::resolve_thumbnail_url, error::RequestError, TwitchClient};
+
+use crate::{
+    config::Config, embeds, health::Health, notifications::NotificationBuilder, schedule, stats, status::StatusBoard,
+    watcher::timestamp_link, Cache,
+};
+
+/// Handles the application commands forwarded by the discord gateway.
+pub struct CommandHandler {
+    tracking: mpsc::Sender<CommandRequest>,
+    status: Arc<StatusBoard>,
+    health: Arc<Health>,
+    client: Arc<TwitchClient>,
+    config: Arc<Config>,
+    db: Arc<Cache>,
+}
+
+impl CommandHandler {
+    pub fn new(
+        tracking: mpsc::Sender<CommandRequest>,
+        status: Arc<StatusBoard>,
+        health: Arc<Health>,
+        client: Arc<TwitchClient>,
+        config: Arc<Config>,
+        db: Arc<Cache>,
+    ) -> Self {
+        Self {
+            tracking,
+            status,
+            health,
+            client,
+            config,
+            db,
+        }
+    }
+
+    pub async fn run(self, mut requests: mpsc::Receiver<CommandRequest>) {
+        while let Some(request) = requests.recv().await {
+            let reply = match request.command {
+                AppCommand::Track(_) | AppCommand::Untrack(_) | AppCommand::Resync => {
+                    // Changes to the tracked streamers and polls are applied by the main loop
+                    if self.tracking.send(request).await.is_err() {
+                        log::error!("Cannot update tracked streamers, the main loop is no longer running");
+                    }
+                    continue;
+                }
+                AppCommand::Status => Ok(self.on_status()),
+                AppCommand::Vod(ref login) => self.on_vod(login).await,
+                AppCommand::Clips(ref login, period) => self.on_clips(login, period).await,
+                AppCommand::Schedule => self.on_schedule().await,
+                AppCommand::Stats(ref login, count) => self.on_stats(login, count).await,
+                AppCommand::Export(ref login, format) => self.on_export(login, format).await,
+                AppCommand::Mute(ref login, ref duration) => Ok(self.on_mute(login, duration).await),
+            };
+
+            let reply = reply.unwrap_or_else(|e| {
+                log::error!("Failed to handle command {:?}: {e}", request.command);
+                CommandReply::text("Something went wrong, please try again later.")
+            });
+            drop(request.reply.send(reply));
+        }
+    }
+
+    fn on_status(&self) -> CommandReply {
+        let streams = self.status.streams();
+        let description = if streams.is_empty() {
+            "None of the tracked streamers are live.".to_owned()
+        } else {
+            streams
+                .iter()
+                .map(|stream| {
+                    let state = if stream.offline { " *(offline)*" } else { "" };
+                    format!(
+                        "**{}** playing **{}** since <t:{}:R>{state}",
+                        stream.user_name, stream.game, stream.started_at
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let last_poll = match self.health.last_poll() {
+            0 => "Never".to_owned(),
+            time => format!("<t:{time}:R>"),
+        };
+
+        let embed = EmbedBuilder::new()
+            .color(embeds::TWITCH_COLOR)
+            .title("Live Streams")
+            .description(description)
+            .field(EmbedFieldBuilder::new("Last Poll", last_poll).inline())
+            .build();
+
+        CommandReply::embed(embed)
+    }
+
+    async fn on_vod(&self, login: &str) -> anyhow::Result<CommandReply> {
+        let login = login.to_lowercase();
+        let Some(user) = self.client.get_users_by_login(&[login.as_str().into()]).await?.pop() else {
+            return Ok(CommandReply::text(format!("Could not find twitch user `{login}`.")));
+        };
+
+        let video = match self.client.get_latest_archive(&user.id).await {
+            Ok(video) => video,
+            Err(RequestError::NotFound(..)) => {
+                return Ok(CommandReply::text(format!("There are no VODs from `{login}`.")));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // Markers require a broadcaster token, so this only works for some setups
+        let markers = self.client.get_stream_markers(&video.id).await.unwrap_or_default();
+        let timestamps: Vec<String> = markers
+            .iter()
+            .map(|m| format!("{} {}", timestamp_link(&video.id, m.position_seconds), m.description))
+            .collect();
+
+        let mut notification = NotificationBuilder::new(embeds::vod_embed(Some(&video)));
+        if !timestamps.is_empty() {
+            (notification, _) = notification.lines("Timestamps", &timestamps, 4, "*...*", true);
+        }
+
+        let num = usize::from(self.config.twitch.top_clips).min(embeds::CLIPS_PER_EMBED);
+        if num > 0 {
+            let clips = self
+                .client
+                .get_top_clips(user.id.to_string(), &video.created_at, 100)
+                .await?;
+            let clips = self.config.twitch.select_clips(clips, num);
+            if !clips.is_empty() {
+                notification = notification.field(embeds::top_clips_field(&clips));
+            }
+        }
+
+        if !video.thumbnail_url.is_empty() {
+            notification = notification.image(ImageSource::url(resolve_thumbnail_url(&video.thumbnail_url))?);
+        }
+
+        // A reply is a single message, which is always enough for the limited timestamps and clips
+        let embeds = notification.build().into_iter().next().unwrap_or_default();
+        Ok(CommandReply {
+            content: i18n::render(
+                self.config.discord.locale.messages().vod_from,
+                &[
+                    ("user", user.display_name.as_ref()),
+                    (
+                        "duration",
+                        self.config
+                            .discord
+                            .time_format()
+                            .duration(video.duration.as_secs())
+                            .as_str(),
+                    ),
+                ],
+            ),
+            embeds,
+            ..CommandReply::default()
+        })
+    }
+
+    async fn on_schedule(&self) -> anyhow::Result<CommandReply> {
+        let embed =
+            schedule::build_embed(&self.client, &self.status.tracked(), self.config.discord.time_format()).await?;
+        Ok(CommandReply::embed(embed))
+    }
+
+    async fn on_stats(&self, login: &str, count: u8) -> anyhow::Result<CommandReply> {
+        if !self.config.cache.enabled {
+            return Ok(CommandReply::text(
+                "Stream stats are only recorded while the cache is enabled.",
+            ));
+        }
+
+        let login = login.to_lowercase();
+        let Some(user) = self.client.get_users_by_login(&[login.as_str().into()]).await?.pop() else {
+            return Ok(CommandReply::text(format!("Could not find twitch user `{login}`.")));
+        };
+
+        let sessions = stats::load(&self.db, &user.id).await?;
+        if sessions.is_empty() {
+            return Ok(CommandReply::text(format!(
+                "There are no recorded streams from `{login}`."
+            )));
+        }
+
+        let recent = &sessions[sessions.len().saturating_sub(count as usize)..];
+        Ok(CommandReply::embed(stats::summary_embed(&user.display_name, recent)))
+    }
+
+    async fn on_mute(&self, login: &str, duration: &str) -> CommandReply {
+        let login = login.to_lowercase();
+        let Some(duration) = parse_duration(duration) else {
+            return CommandReply::text(format!(
+                "Invalid duration `{duration}`, use a duration like `2h` or `1d`."
+            ));
+        };
+
+        let reply = if duration.is_zero() {
+            self.status.set_muted(&login, None);
+            format!("Notifications of `{login}` are no longer muted.")
+        } else {
+            let until = Timestamp::now().as_secs() + duration.as_secs();
+            self.status.set_muted(&login, Some(until));
+            format!("Notifications of `{login}` are muted until <t:{until}:f>.")
+        };
+
+        self.status.save_mutes(&self.db, self.config.cache.enabled).await;
+        CommandReply::text(reply)
+    }
+
+    async fn on_export(&self, login: &str, format: ExportFormat) -> anyhow::Result<CommandReply> {
+        if !self.config.cache.enabled {
+            return Ok(CommandReply::text(
+                "Stream stats are only recorded while the cache is enabled.",
+            ));
+        }
+
+        let login = login.to_lowercase();
+        let Some(user) = self.client.get_users_by_login(&[login.as_str().into()]).await?.pop() else {
+            return Ok(CommandReply::text(format!("Could not find twitch user `{login}`.")));
+        };
+
+        let sessions = stats::load(&self.db, &user.id).await?;
+        if sessions.is_empty() {
+            return Ok(CommandReply::text(format!(
+                "There are no recorded streams from `{login}`."
+            )));
+        }
+
+        let data = match format {
+            ExportFormat::Csv => stats::export_csv(&sessions).into_bytes(),
+            ExportFormat::Json => serde_json::to_vec_pretty(&sessions)?,
+        };
+
+        let file = MessageFile {
+            filename: format!("{login}-streams.{}", format.name()),
+            data,
+        };
+        Ok(CommandReply::file(
+            format!("Stream history of `{login}` ({} streams)", sessions.len()),
+            file,
+        ))
+    }
+
+    async fn on_clips(&self, login: &str, period: ClipPeriod) -> anyhow::Result<CommandReply> {
+        let login = login.to_lowercase();
+        let Some(user) = self.client.get_users_by_login(&[login.as_str().into()]).await?.pop() else {
+            return Ok(CommandReply::text(format!("Could not find twitch user `{login}`.")));
+        };
+
+        let started_at = DateTime::utc_now() - Duration::from_secs(period.as_secs());
+        let clips = self.client.get_top_clips(user.id.to_string(), &started_at, 100).await?;
+        let clips = self.config.twitch.select_clips(clips, embeds::CLIPS_PER_EMBED);
+        if clips.is_empty() {
+            return Ok(CommandReply::text(format!(
+                "There are no clips from `{login}` in the last {}.",
+                period.name()
+            )));
+        }
+
+        let url = format!("https://twitch.tv/{login}/clips");
+        let embed = EmbedBuilder::new()
+            .color(embeds::TWITCH_COLOR)
+            .author(EmbedAuthorBuilder::new(user.display_name.to_string()))
+            .title(format!("Top clips of the last {}", period.name()))
+            .url(url);
+
+        Ok(CommandReply::embed(
+            embed.field(embeds::top_clips_field(&clips)).build(),
+        ))
+    }
+}
@@ -0,0 +1,840 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
+
+use commons::util::ConfigDuration;
+
+use discord_api::{
+    config::{DiscordConfig, EventName},
+    permissions,
+};
+use kick_api::config::KickConfig;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing as log;
+use twilight_http::{error::ErrorType, Client};
+use twilight_model::guild::{Guild, Permissions};
+use twilight_model::id::{
+    marker::{GuildMarker, RoleMarker},
+    Id,
+};
+use twitch_api::config::{NetworkConfig, TwitchConfig};
+use youtube_api::config::YoutubeConfig;
+
+use commons::resolve;
+
+use crate::{
+    errors::{BotError, InitError},
+    mentions::Mention,
+    roles::{SavedRole, SavedRoles},
+};
+
+const fn default_true() -> bool {
+    true
+}
+
+fn http_status(error: &twilight_http::Error) -> Option<u16> {
+    match error.kind() {
+        ErrorType::Response { status, .. } => Some(status.get()),
+        _ => None,
+    }
+}
+
+/// Checks the bot token and logs which bot account is used.
+pub async fn verify_token(client: &Client) -> Result<(), BotError> {
+    let user = match client.current_user().await {
+        Ok(user) => user.model().await?,
+        Err(err) if http_status(&err) == Some(401) => return Err(InitError::InvalidToken.into()),
+        Err(err) => return Err(err.into()),
+    };
+
+    log::info!("Logged in as {} (id={})", user.name, user.id);
+    Ok(())
+}
+
+const fn default_write_interval() -> ConfigDuration {
+    ConfigDuration(Duration::from_secs(30))
+}
+
+#[derive(Deserialize)]
+pub struct CacheConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Minimum time between two writes of the same cache entry, later writes are delayed
+    #[serde(default = "default_write_interval")]
+    pub write_interval: ConfigDuration,
+    /// Compress the cache files with gzip
+    #[serde(default)]
+    pub compress: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: true,
+            write_interval: default_write_interval(),
+            compress: false,
+        }
+    }
+}
+
+fn default_health_address() -> String {
+    "0.0.0.0:8080".to_owned()
+}
+
+const fn default_max_poll_age() -> u64 {
+    120
+}
+
+#[derive(Deserialize)]
+pub struct HealthConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_health_address")]
+    pub address: String,
+    /// Number of seconds since the last successful poll before the bot is considered unhealthy
+    #[serde(default = "default_max_poll_age")]
+    pub max_poll_age: u64,
+    /// Also serve the state of the stream watchers at `/api/streams` and `/api/watchers/<login>`
+    #[serde(default)]
+    pub api: bool,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            enabled: false,
+            address: default_health_address(),
+            max_poll_age: default_max_poll_age(),
+            api: false,
+        }
+    }
+}
+
+fn default_log_level() -> Box<str> {
+    "info".into()
+}
+
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LogFormat {
+    #[default]
+    #[serde(rename = "pretty")]
+    Pretty,
+    #[serde(rename = "json")]
+    Json,
+}
+
+const fn default_alert_threshold() -> usize {
+    10
+}
+
+const fn default_alert_window() -> ConfigDuration {
+    ConfigDuration(Duration::from_secs(600))
+}
+
+/// Settings of the error rate alerts, which are sent to the logging webhook.
+#[derive(Deserialize, Clone)]
+pub struct AlertConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Number of errors of one kind within the window which trigger an alert
+    #[serde(default = "default_alert_threshold")]
+    pub threshold: usize,
+    /// How far back the errors are counted
+    #[serde(default = "default_alert_window")]
+    pub window: ConfigDuration,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: default_alert_threshold(),
+            window: default_alert_window(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
+    pub level: Box<str>,
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Map of module -> level, such as `"twilight_gateway": "error"`
+    #[serde(default)]
+    pub filters: HashMap<String, String>,
+    #[serde(default)]
+    pub alerts: AlertConfig,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: default_log_level(),
+            format: LogFormat::default(),
+            filters: HashMap::new(),
+            alerts: AlertConfig::default(),
+        }
+    }
+}
+
+const fn default_watcher_buffer() -> usize {
+    8
+}
+
+const fn default_send_timeout() -> u64 {
+    2
+}
+
+const fn default_stuck_after() -> u64 {
+    60
+}
+
+/// Settings of the queues between the poll loop and the stream watchers.
+#[derive(Deserialize, Clone)]
+pub struct WatcherConfig {
+    /// Number of updates which can wait for a busy watcher
+    #[serde(default = "default_watcher_buffer")]
+    pub buffer: usize,
+    /// Number of seconds to wait for space in the queue of a watcher, before the update is dropped
+    #[serde(default = "default_send_timeout")]
+    pub send_timeout: u64,
+    /// Number of seconds after which an update is reported as stuck
+    #[serde(default = "default_stuck_after")]
+    pub stuck_after: u64,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            buffer: default_watcher_buffer(),
+            send_timeout: default_send_timeout(),
+            stuck_after: default_stuck_after(),
+        }
+    }
+}
+
+const fn default_lease() -> u64 {
+    120
+}
+
+/// Settings for several instances of the bot which share one database, where only one announces each stream.
+#[derive(Deserialize, Clone)]
+pub struct ReplicationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// The unique name of this instance, which is generated on startup if missing
+    #[serde(default)]
+    pub id: Option<Box<str>>,
+    /// Number of seconds until a standby takes over the streams of an instance which stopped updating them
+    #[serde(default = "default_lease")]
+    pub lease: u64,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            id: None,
+            lease: default_lease(),
+        }
+    }
+}
+
+const fn default_update_check_interval() -> u64 {
+    86400
+}
+
+fn default_repository() -> Box<str> {
+    "MinnDevelopment/strumbot-rs".into()
+}
+
+/// Settings of the periodic check for new releases of strumbot.
+#[derive(Deserialize, Clone)]
+pub struct UpdateCheckConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Number of seconds between checks, at least one hour
+    #[serde(default = "default_update_check_interval")]
+    pub interval: u64,
+    /// The github repository with the releases, such as a fork
+    #[serde(default = "default_repository")]
+    pub repository: Box<str>,
+}
+
+impl Default for UpdateCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: default_update_check_interval(),
+            repository: default_repository(),
+        }
+    }
+}
+
+const fn default_schedule_interval() -> u64 {
+    3600
+}
+
+const fn default_reminder() -> u64 {
+    10
+}
+
+const fn default_upcoming_interval() -> u64 {
+    300
+}
+
+const fn default_engagement_interval() -> u64 {
+    60
+}
+
+const fn default_goal_step() -> u64 {
+    25
+}
+
+/// Settings of the hype train and goal events, which are checked while the stream is live.
+#[derive(Deserialize, Clone)]
+pub struct EngagementConfig {
+    /// Number of seconds between checks of each live stream
+    #[serde(default = "default_engagement_interval")]
+    pub interval: u64,
+    /// The goal progress is announced every this many percent
+    #[serde(default = "default_goal_step")]
+    pub goal_step: u64,
+}
+
+impl Default for EngagementConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_engagement_interval(),
+            goal_step: default_goal_step(),
+        }
+    }
+}
+
+const fn default_video_interval() -> u64 {
+    900
+}
+
+/// Settings of the video event, which announces new highlights and uploads.
+#[derive(Deserialize, Clone)]
+pub struct VideoConfig {
+    /// Number of seconds between checks for new videos
+    #[serde(default = "default_video_interval")]
+    pub interval: u64,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_video_interval(),
+        }
+    }
+}
+
+const fn default_milestone_interval() -> u64 {
+    3600
+}
+
+const fn default_follower_step() -> u64 {
+    1000
+}
+
+const fn default_subscriber_step() -> u64 {
+    100
+}
+
+/// Settings of the milestone event, which announces follower and subscriber counts of the tracked streamers.
+#[derive(Deserialize, Clone)]
+pub struct MilestoneConfig {
+    /// Number of seconds between checks of the counts
+    #[serde(default = "default_milestone_interval")]
+    pub interval: u64,
+    /// A follower milestone is announced every this many followers, 0 to disable
+    #[serde(default = "default_follower_step")]
+    pub followers: u64,
+    /// A subscriber milestone is announced every this many subscribers, 0 to disable
+    #[serde(default = "default_subscriber_step")]
+    pub subscribers: u64,
+}
+
+impl Default for MilestoneConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_milestone_interval(),
+            followers: default_follower_step(),
+            subscribers: default_subscriber_step(),
+        }
+    }
+}
+
+/// Settings of the raid detection, which adds the raided channel to the VOD notification.
+#[derive(Deserialize, Clone)]
+pub struct RaidConfig {
+    /// Listen for the raids of the tracked streamers with EventSub, which requires `user_auth`
+    #[serde(default)]
+    pub enabled: bool,
+    /// Link the stream of the raided channel
+    #[serde(default = "default_true")]
+    pub link: bool,
+}
+
+impl Default for RaidConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            link: true,
+        }
+    }
+}
+
+/// Settings of the upcoming event, which reminds of scheduled streams shortly before they start.
+#[derive(Deserialize, Clone)]
+pub struct UpcomingConfig {
+    /// Number of minutes before the scheduled start to send the reminder
+    #[serde(default = "default_reminder")]
+    pub reminder: u64,
+    /// Number of seconds between checks of the schedules
+    #[serde(default = "default_upcoming_interval")]
+    pub interval: u64,
+}
+
+impl Default for UpcomingConfig {
+    fn default() -> Self {
+        Self {
+            reminder: default_reminder(),
+            interval: default_upcoming_interval(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ScheduleConfig {
+    /// The channel which shows the weekly schedule of the tracked streamers
+    pub channel_id: Box<str>,
+    /// Number of seconds between updates of the schedule message
+    #[serde(default = "default_schedule_interval")]
+    pub interval: u64,
+}
+
+#[derive(Deserialize, Default)]
+pub struct Config {
+    pub twitch: TwitchConfig,
+    pub discord: DiscordConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub youtube: Option<YoutubeConfig>,
+    #[serde(default)]
+    pub kick: Option<KickConfig>,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub schedule: Option<ScheduleConfig>,
+    #[serde(default)]
+    pub upcoming: UpcomingConfig,
+    #[serde(default)]
+    pub engagement: EngagementConfig,
+    #[serde(default)]
+    pub videos: VideoConfig,
+    #[serde(default)]
+    pub milestones: MilestoneConfig,
+    #[serde(default)]
+    pub raids: RaidConfig,
+    #[serde(default)]
+    pub watchers: WatcherConfig,
+    #[serde(default)]
+    pub update_check: UpdateCheckConfig,
+    #[serde(default)]
+    pub replication: ReplicationConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    role_map: HashMap<String, String>, // map of event -> id (for mentions)
+}
+
+impl Config {
+    /// Reads and parses the configuration file.
+    pub async fn load(path: &Path) -> Result<Self, BotError> {
+        Self::load_profile(path, None).await
+    }
+
+    /// Reads and parses the configuration file, with the settings of the profile applied over the shared settings.
+    ///
+    /// The profiles are defined in the `profiles` object of the file, such as a test server setup next to the production setup.
+    pub async fn load_profile(path: &Path, profile: Option<&str>) -> Result<Self, BotError> {
+        let file = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| InitError::Read(path.to_owned(), e))?;
+        let parse_error = |e| InitError::Parse(path.to_owned(), e);
+
+        // Without a profile, the profiles are ignored and the errors keep their position in the file
+        let Some(profile) = profile else {
+            return Ok(serde_json::from_str(&file).map_err(parse_error)?);
+        };
+
+        let value = serde_json::from_str(&file).map_err(parse_error)?;
+        let value = apply_profile(value, profile)?;
+        Ok(serde_json::from_value(value).map_err(parse_error)?)
+    }
+
+    /// Problems of the configuration which are not caught by parsing, such as settings without any effect.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let twitch = &self.twitch;
+        if twitch.user_login.is_empty() && twitch.team.is_none() && twitch.followed_by.is_none() {
+            problems.push("twitch: no streamers are tracked, set user_login, team, or followed_by".to_owned());
+        }
+        if self.discord.enabled_events.is_empty() {
+            problems.push("discord: enabled_events is empty, so no notifications are sent".to_owned());
+        }
+        if !twitch.categories.is_empty() && !self.discord.enabled_events.contains(&EventName::Category) {
+            problems.push("twitch: categories are configured, but the category event is not enabled".to_owned());
+        }
+        if self.discord.role_buttons.is_some() && !self.discord.enable_command {
+            problems.push("discord: role_buttons requires enable_command".to_owned());
+        }
+        if self.raids.enabled && !twitch.user_auth {
+            problems
+                .push("raids: enabled requires twitch.user_auth, since EventSub only accepts user tokens".to_owned());
+        }
+        let alerts = &self.logging.alerts;
+        if alerts.enabled && alerts.threshold == 0 {
+            problems.push("logging: alerts.threshold must be at least 1".to_owned());
+        }
+        problems
+    }
+
+    pub fn get_role(&self, event: &str) -> Option<String> {
+        self.role_map.get(event).cloned()
+    }
+
+    /// The mentions of the notifications for the event, from `enabled_events` or its notification role.
+    pub fn mention(&self, event: EventName) -> Mention {
+        let role = self.role_map.get(event.name()).and_then(|id| id.parse().ok());
+        Mention::resolve(event, self.discord.enabled_events.mention(event), role)
+    }
+
+    /// The configured role name of each event.
+    fn role_names(&self) -> [(&'static str, &str); 9] {
+        let role_name = &self.discord.role_name;
+        [
+            ("live", role_name.live.as_ref()),
+            ("update", role_name.update.as_ref()),
+            ("vod", role_name.vod.as_ref()),
+            ("category", role_name.category.as_ref()),
+            ("upcoming", role_name.upcoming.as_ref()),
+            ("hype_train", role_name.hype_train.as_ref()),
+            ("goal", role_name.goal.as_ref()),
+            ("video", role_name.video.as_ref()),
+            ("milestone", role_name.milestone.as_ref()),
+        ]
+    }
+
+    /// The notification roles which were found or created, to find them by id on the next start.
+    pub fn saved_roles(&self) -> SavedRoles {
+        self.role_names()
+            .into_iter()
+            .filter_map(|(event, name)| {
+                let id = self.role_map.get(event)?;
+                let role = SavedRole {
+                    id: id.as_str().into(),
+                    name: name.into(),
+                };
+                Some((event.to_owned(), role))
+            })
+            .collect()
+    }
+
+    /// Finds or creates the notification roles, and returns the problems which prevent assigning them.
+    /// With `pin_live`, this also includes the problems which prevent pinning the live notifications.
+    ///
+    /// Roles which are not found by their name are looked up by their saved id, since they might have been renamed.
+    pub async fn init_roles(&mut self, client: &Client, saved: &SavedRoles) -> Result<Vec<String>, BotError> {
+        let guild = if let Some(ref id) = self.discord.guild_id {
+            let id = id.parse().map_err(|_| InitError::InvalidGuildId(id.clone()))?;
+            Self::get_guild(client, id).await?
+        } else {
+            let guilds = client
+                .current_user_guilds()
+                .limit(2)
+                .expect("2 is a valid guild limit")
+                .await?
+                .models()
+                .await?;
+            match guilds[..] {
+                [ref guild] => Self::get_guild(client, guild.id).await?,
+                [] => return Err(InitError::NoGuilds.into()),
+                _ => return Err(InitError::TooManyGuilds.into()),
+            }
+        };
+
+        Ok(self.init_roles_from_guild(client, guild, saved).await)
+    }
+
+    async fn get_guild(client: &Client, id: Id<GuildMarker>) -> Result<Guild, BotError> {
+        match client.guild(id).await {
+            Ok(guild) => Ok(guild.model().await?),
+            Err(err) if matches!(http_status(&err), Some(403 | 404)) => Err(InitError::MissingGuild(id).into()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn init_roles_from_guild(&mut self, client: &Client, guild: Guild, saved: &SavedRoles) -> Vec<String> {
+        let names: HashMap<String, &str> = self
+            .role_names()
+            .into_iter()
+            .map(|(event, name)| (name.to_lowercase(), event))
+            .collect();
+        let mut not_found: HashSet<&String> = names.keys().collect();
+
+        for role in &guild.roles {
+            let name = &role.name.to_lowercase();
+            if let Some(event) = names.get(name).copied() {
+                let owned = event.to_owned();
+                not_found.remove(name);
+                log::info!(
+                    "Found notification role for {} event: {} (id={})",
+                    event,
+                    role.name,
+                    role.id
+                );
+                self.role_map.insert(owned, role.id.to_string());
+            }
+        }
+
+        not_found.retain(|name| {
+            let event = names[*name];
+            let renamed = saved
+                .get(event)
+                .filter(|saved| !name.is_empty() && saved.name.to_lowercase() == **name)
+                .and_then(|saved| guild.roles.iter().find(|role| role.id.to_string() == saved.id.as_ref()));
+            let Some(role) = renamed else {
+                return true;
+            };
+
+            log::info!(
+                "Found renamed notification role for {} event: {} (id={})",
+                event,
+                role.name,
+                role.id
+            );
+            self.role_map.insert(event.to_owned(), role.id.to_string());
+            false
+        });
+
+        let guild_id = guild.id;
+        for name in not_found {
+            if name.is_empty() {
+                continue;
+            }
+
+            if self.discord.dry_run.enabled {
+                log::info!("[dry run] Missing role with name {name:?} would be created");
+                continue;
+            }
+
+            let response = resolve! {
+                client
+                    .create_role(guild_id)
+                    .name(name.as_str())
+                    .mentionable(false)
+                    .permissions(Permissions::empty())
+            };
+
+            match response {
+                Err(err) => {
+                    log::error!("Could not create roles due to error: {err:?}");
+                    log::warn!("Make sure the bot has permissions to manage roles in your server. Missing: {name:?}");
+                    break;
+                }
+                Ok(role) => {
+                    let event = names.get(name).copied().unwrap().to_owned();
+                    log::info!("Created role with name {name:?} for {event:?} event");
+                    self.role_map.insert(event, role.id.to_string());
+                }
+            }
+        }
+
+        let roles: Vec<Id<RoleMarker>> = self.role_map.values().filter_map(|id| id.parse().ok()).collect();
+        let mut problems = match permissions::role_problems(client, &guild, &roles).await {
+            Ok(problems) => problems,
+            Err(e) => {
+                log::warn!("Could not check the permissions of the bot for the notification roles: {e}");
+                Vec::new()
+            }
+        };
+
+        if self.discord.pin_live {
+            match permissions::pin_problems(client, &guild, &self.discord.stream_notifications).await {
+                Ok(pins) => problems.extend(pins),
+                Err(e) => log::warn!("Could not check the permissions of the bot to pin notifications: {e}"),
+            }
+        }
+        problems
+    }
+}
+
+/// Merges the settings of the profile into the shared settings, and removes the profiles.
+fn apply_profile(mut config: Value, name: &str) -> Result<Value, InitError> {
+    let profile = config
+        .as_object_mut()
+        .and_then(|root| root.remove("profiles"))
+        .and_then(|mut profiles| profiles.get_mut(name).map(Value::take));
+
+    match profile {
+        Some(profile) => {
+            merge(&mut config, profile);
+            Ok(config)
+        }
+        None => Err(InitError::UnknownProfile(name.into())),
+    }
+}
+
+/// Merges objects by their keys, while any other value of the overrides replaces the base value.
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(current) => merge(current, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_profile() {
+        let config = serde_json::json!({
+            "twitch": {"client_id": "id", "user_login": ["Elajjaz", "distortion2"]},
+            "discord": {"stream_notifications": "prod", "role_name": {"live": "live"}},
+            "profiles": {
+                "dev": {
+                    "twitch": {"user_login": ["Elajjaz"]},
+                    "discord": {"stream_notifications": "dev", "role_name": {"vod": ""}}
+                }
+            }
+        });
+
+        let dev = apply_profile(config.clone(), "dev").unwrap();
+        assert_eq!(
+            dev,
+            serde_json::json!({
+                "twitch": {"client_id": "id", "user_login": ["Elajjaz"]},
+                "discord": {"stream_notifications": "dev", "role_name": {"live": "live", "vod": ""}}
+            })
+        );
+
+        assert!(matches!(
+            apply_profile(config, "prod"),
+            Err(InitError::UnknownProfile(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_parse() {
+        let file = std::fs::read("../example-config.json").unwrap();
+        let Config {
+            cache,
+            youtube,
+            kick,
+            health,
+            logging,
+            schedule,
+            upcoming,
+            engagement,
+            videos,
+            milestones,
+            raids,
+            watchers,
+            update_check,
+            replication,
+            network,
+            ..
+        } = serde_json::from_slice(&file).unwrap();
+
+        assert!(!cache.enabled);
+        assert_eq!(cache.write_interval.0.as_secs(), 30);
+        assert!(!cache.compress);
+        assert!(youtube.is_none());
+        assert!(kick.is_none());
+        assert!(!health.enabled);
+        assert_eq!(health.address, "0.0.0.0:8080");
+        assert!(!health.api);
+        assert_eq!(logging.level.as_ref(), "info");
+        assert!(logging.format == LogFormat::Pretty);
+        assert!(logging.alerts.enabled);
+        assert_eq!(logging.alerts.threshold, 10);
+        assert_eq!(logging.alerts.window.0.as_secs(), 600);
+        assert!(schedule.is_none());
+        assert_eq!(upcoming.reminder, 10);
+        assert_eq!(upcoming.interval, 300);
+        assert_eq!(engagement.interval, 60);
+        assert_eq!(engagement.goal_step, 25);
+        assert_eq!(videos.interval, 900);
+        assert_eq!(milestones.followers, 1000);
+        assert_eq!(milestones.subscribers, 100);
+        assert!(!raids.enabled);
+        assert!(raids.link);
+        assert_eq!(watchers.buffer, 8);
+        assert_eq!(watchers.send_timeout, 2);
+        assert!(!update_check.enabled);
+        assert_eq!(update_check.interval, 86400);
+        assert!(!replication.enabled);
+        assert_eq!(replication.lease, 120);
+        assert_eq!(network.request_timeout, 30);
+        assert!(network.proxy.is_none());
+    }
+
+    #[test]
+    fn test_schedule_config_parse() {
+        let schedule: ScheduleConfig = serde_json::from_str(r#"{"channel_id": "123"}"#).unwrap();
+        assert_eq!(schedule.channel_id.as_ref(), "123");
+        assert_eq!(schedule.interval, 3600);
+    }
+
+    #[test]
+    fn test_saved_roles() {
+        let file = std::fs::read("../example-config.json").unwrap();
+        let mut config: Config = serde_json::from_slice(&file).unwrap();
+        config
+            .role_map
+            .insert("live".to_owned(), "81384788765712384".to_owned());
+
+        let saved = config.saved_roles();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved["live"].id.as_ref(), "81384788765712384");
+        assert_eq!(saved["live"].name, config.discord.role_name.live);
+    }
+
+    #[test]
+    fn test_problems() {
+        let file = std::fs::read("../example-config.json").unwrap();
+        let mut config: Config = serde_json::from_slice(&file).unwrap();
+        assert!(config.problems().is_empty());
+
+        config.twitch.user_login.clear();
+        config.discord.enabled_events.clear();
+        assert_eq!(config.problems().len(), 2);
+
+        config.raids.enabled = true;
+        assert_eq!(config.problems().len(), 3);
+
+        config.logging.alerts.threshold = 0;
+        assert_eq!(config.problems().len(), 4);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{sync::Arc, time::Duration};
+
+use commons::util::{escape_markdown, EscapeMode};
+use discord_api::{config::EventName, i18n, WebhookMessage};
+use eos::DateTime;
+use tokio::{sync::mpsc, time::sleep};
+use tracing as log;
+use twilight_util::builder::embed::EmbedBuilder;
+
+use crate::{
+    config::Config,
+    embeds,
+    mentions::Mention,
+    notifications::NotificationBuilder,
+    notifier::Notifier,
+    policy::{self, Delivery},
+};
+
+/// A stream which went live, waiting to be announced in the next digest.
+#[derive(Clone, Debug)]
+pub struct DigestEntry {
+    pub user_name: Box<str>,
+    pub title: Box<str>,
+    /// The name of the game, or empty if none is set
+    pub game: Box<str>,
+    /// Unix timestamp of the stream start
+    pub started_at: i64,
+}
+
+/// Starts the task which collects live events over the window and sends them as one notification.
+pub fn start(window: Duration, config: Arc<Config>, webhook: Arc<dyn Notifier>) -> mpsc::UnboundedSender<DigestEntry> {
+    let (send, receive) = mpsc::unbounded_channel();
+    tokio::spawn(run(window, config, webhook, receive));
+    send
+}
+
+async fn run(
+    window: Duration,
+    config: Arc<Config>,
+    webhook: Arc<dyn Notifier>,
+    mut receive: mpsc::UnboundedReceiver<DigestEntry>,
+) {
+    // The window starts with the first live event, so a lone stream is announced after one window at most
+    while let Some(first) = receive.recv().await {
+        sleep(window).await;
+        let mut entries = vec![first];
+        while let Ok(entry) = receive.try_recv() {
+            entries.push(entry);
+        }
+
+        send(&config, &webhook, &entries).await;
+    }
+}
+
+async fn send(config: &Config, webhook: &dyn Notifier, entries: &[DigestEntry]) {
+    let now = DateTime::utc_now().timestamp().as_seconds();
+    let mention = match policy::delivery(&config.discord, EventName::Live, now) {
+        Delivery::Normal => config.mention(EventName::Live),
+        Delivery::Silent => Mention::silent(EventName::Live),
+        Delivery::Skip => {
+            log::info!("Skipping live digest of {} streams during quiet hours", entries.len());
+            return;
+        }
+    };
+
+    let messages = config.discord.locale.messages();
+    let count = entries.len().to_string();
+    let mut content = format!(
+        "{}{}",
+        mention.prefix(),
+        i18n::render(messages.live_digest, &[("count", &count)])
+    );
+    log::info!("Sending live digest of {} streams", entries.len());
+
+    for embeds in notification(entries, config.discord.escape).build() {
+        let message = WebhookMessage {
+            content: std::mem::take(&mut content),
+            embeds,
+            files: Vec::new(),
+            avatar_url: config.discord.avatar_url.as_deref().map(str::to_owned),
+            username: None,
+            allowed_mentions: mention.allowed_mentions(),
+            live_stream: None,
+        };
+
+        if let Err(e) = webhook.send(message).await {
+            log::error!("Failed to send live digest: {e}");
+            break;
+        }
+    }
+}
+
+/// Lists the streams in order of their start, with a link, the game, and the title.
+fn notification(entries: &[DigestEntry], escape: EscapeMode) -> NotificationBuilder {
+    let mut entries: Vec<&DigestEntry> = entries.iter().collect();
+    entries.sort_by_key(|entry| entry.started_at);
+
+    let lines: Vec<String> = entries
+        .into_iter()
+        .map(|entry| {
+            let user_name = escape_markdown(&entry.user_name, escape);
+            let mut line = format!("[**{user_name}**](https://twitch.tv/{})", entry.user_name);
+            if !entry.game.is_empty() {
+                line.push_str(&format!(" \u{2014} {}", escape_markdown(&entry.game, escape)));
+            }
+            format!("{line}\n{}", escape_markdown(&entry.title, escape))
+        })
+        .collect();
+
+    let (notification, _) = NotificationBuilder::new(EmbedBuilder::new().color(embeds::TWITCH_COLOR)).lines(
+        "Live",
+        &lines,
+        usize::MAX,
+        "",
+        false,
+    );
+    notification
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(user_name: &str, game: &str, started_at: i64) -> DigestEntry {
+        DigestEntry {
+            user_name: user_name.into(),
+            title: "Blind playthrough".into(),
+            game: game.into(),
+            started_at,
+        }
+    }
+
+    #[test]
+    fn test_notification() {
+        let entries = vec![
+            entry("Elajjaz", "Elden Ring", 1_696_874_460),
+            entry("Distortion2", "", 1_696_874_400),
+        ];
+
+        let messages = notification(&entries, EscapeMode::Markdown).build();
+        assert_eq!(messages.len(), 1);
+        let value = &messages[0][0].fields[0].value;
+        assert!(value.starts_with("[**Distortion2**](https://twitch.tv/Distortion2)\n"));
+        assert!(value.contains("[**Elajjaz**](https://twitch.tv/Elajjaz) \u{2014} Elden Ring\n"));
+    }
+}
@@ -0,0 +1,191 @@
+use commons::{
+    source::SourceVideo,
+    util::{escape_markdown, sanitize_link_title, EscapeMode},
+};
+use twilight_model::channel::message::Embed;
+use twilight_util::builder::embed::{EmbedAuthorBuilder, EmbedBuilder, EmbedFieldBuilder};
+use twitch_api::{Clip, Video};
+
+use crate::stats::{format_duration, GameTime};
+
+/// The twitch brand color used for the embeds of twitch streams
+pub const TWITCH_COLOR: u32 = 0x6441A4;
+
+/// Number of clips shown in the vod embed, the remaining clips are moved into another embed
+pub const CLIPS_PER_EMBED: usize = 5;
+
+/// Creates the embed of a VOD summary, linking the video if it still exists.
+pub fn vod_embed(video: Option<&Video>) -> EmbedBuilder {
+    let embed = EmbedBuilder::new().color(TWITCH_COLOR);
+    match video {
+        Some(video) => embed
+            .author(EmbedAuthorBuilder::new(video.title.to_string()))
+            .url(video.url.as_ref())
+            .title(video.url.as_ref()),
+        None => embed.author(EmbedAuthorBuilder::new("<Video Removed>".to_owned())),
+    }
+}
+
+/// Creates the embed of a VOD summary for another platform, which only links the video if the platform kept one.
+pub fn source_vod_embed(video: Option<&SourceVideo>, color: u32) -> EmbedBuilder {
+    let embed = EmbedBuilder::new().color(color);
+    match video {
+        Some(video) => embed
+            .author(EmbedAuthorBuilder::new(video.title.to_string()))
+            .url(video.url.as_ref())
+            .title(video.url.as_ref()),
+        None => embed,
+    }
+}
+
+/// Links the video in a VOD summary which was sent before the video was available.
+pub fn link_video(embed: &mut Embed, video: &Video) {
+    embed.author = Some(EmbedAuthorBuilder::new(video.title.to_string()).build());
+    embed.url = Some(video.url.to_string());
+    embed.title = Some(video.url.to_string());
+}
+
+/// Lists how long each game was played, like "Dark Souls — 2h14m".
+pub fn game_times_field(games: &[GameTime], escape: EscapeMode) -> Option<EmbedFieldBuilder> {
+    if games.is_empty() {
+        return None;
+    }
+
+    let lines: String = games
+        .iter()
+        .map(|game| {
+            let name = escape_markdown(&game.name, escape);
+            format!("{name} \u{2014} {}\n", format_duration(game.seconds))
+        })
+        .collect();
+    Some(EmbedFieldBuilder::new("Games", lines))
+}
+
+/// Lists the top clips, with shortened and sanitized titles.
+pub fn top_clips_field(clips: &[Clip]) -> EmbedFieldBuilder {
+    EmbedFieldBuilder::new("Top Clips", clip_lines(clips, 0).concat())
+}
+
+/// Lists the top clips grouped by the game they were created in, as a highlight list for each game.
+///
+/// The segments are the game names with the unix timestamp of their start, in order.
+/// With only one game, this is the same as [`top_clips_field`].
+pub fn highlight_fields(clips: &[Clip], segments: &[(Box<str>, i64)]) -> Vec<EmbedFieldBuilder> {
+    let mut groups: Vec<(&str, Vec<&Clip>)> = Vec::new();
+    for clip in clips {
+        let created_at = clip.created_at.timestamp().as_seconds();
+        let game = segments
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= created_at)
+            .or_else(|| segments.first())
+            .map_or("", |(name, _)| name.as_ref());
+
+        match groups.iter_mut().find(|(name, _)| *name == game) {
+            Some((_, group)) => group.push(clip),
+            None => groups.push((game, vec![clip])),
+        }
+    }
+
+    match groups.len() {
+        0 => Vec::new(),
+        1 => vec![top_clips_field(clips)],
+        _ => groups
+            .into_iter()
+            .map(|(game, group)| {
+                let s: String = group.iter().enumerate().map(|(i, c)| clip_line(i + 1, c)).collect();
+                EmbedFieldBuilder::new(format!("Highlights \u{2014} {game}"), s)
+            })
+            .collect(),
+    }
+}
+
+/// The clips as numbered lines, counting from `offset + 1`.
+pub fn clip_lines(clips: &[Clip], offset: usize) -> Vec<String> {
+    clips
+        .iter()
+        .enumerate()
+        .map(|(i, clip)| clip_line(offset + i + 1, clip))
+        .collect()
+}
+
+/// A clip as a numbered line with a shortened title and its views.
+fn clip_line(number: usize, clip: &Clip) -> String {
+    let sanitized_title = sanitize_link_title(&clip.title);
+    let mut title = String::with_capacity(30);
+    for (i, c) in sanitized_title.chars().enumerate() {
+        if i == 26 {
+            title.push_str("...");
+            break;
+        }
+
+        title.push(match c {
+            '[' => '(',
+            ']' => ')',
+            c => c,
+        });
+    }
+    format!(
+        "`{}.` [**{} \u{1F855}**]({} '{}') \u{2022} **{}**\u{00A0}views\n",
+        number, title, clip.url, sanitized_title, clip.view_count
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(title: &str, created_at: &str) -> Clip {
+        serde_json::from_value(serde_json::json!({
+            "id": title,
+            "video_id": "",
+            "url": "https://clips.twitch.tv/clip",
+            "title": title,
+            "thumbnail_url": "",
+            "view_count": 10,
+            "created_at": created_at,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_highlight_fields() {
+        // 2023-10-09T18:00:00Z and 20:00:00Z
+        let segments = vec![
+            ("Elden Ring".into(), 1_696_874_400),
+            ("Just Chatting".into(), 1_696_881_600),
+        ];
+        let clips = vec![
+            clip("first", "2023-10-09T18:30:00Z"),
+            clip("second", "2023-10-09T20:30:00Z"),
+            clip("third", "2023-10-09T19:00:00Z"),
+        ];
+
+        let fields: Vec<_> = highlight_fields(&clips, &segments)
+            .into_iter()
+            .map(EmbedFieldBuilder::build)
+            .collect();
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Highlights \u{2014} Elden Ring", "Highlights \u{2014} Just Chatting"]
+        );
+        assert!(fields[0].value.contains("third"));
+
+        let fields = highlight_fields(&clips[..1], &segments);
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields.into_iter().next().unwrap().build().name, "Top Clips");
+        assert!(highlight_fields(&[], &segments).is_empty());
+    }
+
+    #[test]
+    fn test_clip_lines() {
+        let clips = vec![
+            clip("first", "2023-10-09T18:30:00Z"),
+            clip("second", "2023-10-09T20:30:00Z"),
+        ];
+        let lines = clip_lines(&clips, 5);
+        assert!(lines[0].starts_with("`6.`"));
+        assert!(lines[1].starts_with("`7.`"));
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use commons::util::escape_markdown;
+use discord_api::{config::EventName, i18n, WebhookMessage};
+use eos::DateTime;
+use tokio::time::sleep;
+use tracing as log;
+use twilight_model::channel::message::embed::EmbedFooter;
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+use twitch_api::{CreatorGoal, HypeTrain, TwitchClient};
+
+use crate::{
+    config::Config,
+    embeds,
+    mentions::Mention,
+    notifier::Notifier,
+    policy::{self, Delivery},
+    status::{StatusBoard, StreamStatus},
+};
+
+/// Announces the hype trains and the goal progress of the tracked streamers while they are live.
+///
+/// Twitch only provides these to a user token of the broadcaster, so this only works for the channel which
+/// authorized the bot with the `channel:read:hype_train` and `channel:read:goals` scopes.
+pub async fn watch(
+    config: Arc<Config>,
+    client: Arc<TwitchClient>,
+    webhook: Arc<dyn Notifier>,
+    status: Arc<StatusBoard>,
+) {
+    let interval = Duration::from_secs(config.engagement.interval.max(30));
+    let events = &config.discord.enabled_events;
+    let hype_trains = events.contains(&EventName::HypeTrain);
+    let goals = events.contains(&EventName::Goal);
+    // The active hype train of each broadcaster, as of the last poll
+    let mut trains: HashMap<Box<str>, HypeTrain> = HashMap::new();
+    // The last announced step of each goal, by goal id
+    let mut steps: HashMap<Box<str>, u64> = HashMap::new();
+
+    loop {
+        sleep(interval).await;
+
+        for stream in status.streams() {
+            if status.is_muted(&stream.user_name) {
+                continue;
+            }
+            if hype_trains {
+                check_hype_train(&stream, &mut trains, &client, webhook.as_ref(), &config).await;
+            }
+            if goals {
+                check_goals(&stream, &mut steps, &client, webhook.as_ref(), &config).await;
+            }
+        }
+    }
+}
+
+async fn check_hype_train(
+    stream: &StreamStatus,
+    trains: &mut HashMap<Box<str>, HypeTrain>,
+    client: &TwitchClient,
+    webhook: &dyn Notifier,
+    config: &Config,
+) {
+    let latest = match client.get_hype_train(&stream.user_id).await {
+        Ok(event) => event.map(|event| event.event_data),
+        Err(e) => {
+            log::debug!("[{}] Could not fetch hype train: {}", stream.user_name, e);
+            return;
+        }
+    };
+
+    let now = DateTime::utc_now().timestamp().as_seconds();
+    let previous = trains.remove(&stream.user_id);
+    let (ended, started) = match (previous, latest) {
+        (Some(previous), Some(latest)) if previous.id == latest.id => {
+            // The level of the last poll is reported, in case it went up before the train ended
+            if latest.expires_at.timestamp().as_seconds() > now {
+                trains.insert(stream.user_id.clone(), latest);
+                return;
+            }
+            (Some(latest), None)
+        }
+        (previous, Some(latest)) if latest.expires_at.timestamp().as_seconds() > now => (previous, Some(latest)),
+        (previous, _) => (previous, None),
+    };
+
+    let messages = config.discord.locale.messages();
+    let user = escape_markdown(&stream.user_name, config.discord.escape);
+    if let Some(train) = ended {
+        let level = train.level.to_string();
+        let text = i18n::render(
+            messages.hype_train_ended,
+            &[("user", user.as_ref()), ("level", level.as_str())],
+        );
+        announce(stream, EventName::HypeTrain, text, Vec::new(), webhook, config).await;
+    }
+
+    if let Some(train) = started {
+        let level = train.level.to_string();
+        let text = i18n::render(
+            messages.hype_train_started,
+            &[("user", user.as_ref()), ("level", level.as_str())],
+        );
+        let expires_at = train.expires_at.timestamp().as_seconds();
+        let fields = vec![
+            EmbedFieldBuilder::new("Progress", format!("{}/{}", train.total, train.goal)).inline(),
+            EmbedFieldBuilder::new("Ends", format!("<t:{expires_at}:R>")).inline(),
+        ];
+        announce(stream, EventName::HypeTrain, text, fields, webhook, config).await;
+        trains.insert(stream.user_id.clone(), train);
+    }
+}
+
+async fn check_goals(
+    stream: &StreamStatus,
+    steps: &mut HashMap<Box<str>, u64>,
+    client: &TwitchClient,
+    webhook: &dyn Notifier,
+    config: &Config,
+) {
+    let goals = match client.get_creator_goals(&stream.user_id).await {
+        Ok(goals) => goals,
+        Err(e) => {
+            log::debug!("[{}] Could not fetch creator goals: {}", stream.user_name, e);
+            return;
+        }
+    };
+
+    let step = config.engagement.goal_step.clamp(1, 100);
+    for goal in goals {
+        let Some(reached) = next_step(steps.get(&goal.id).copied(), &goal, step) else {
+            continue;
+        };
+        steps.insert(goal.id.clone(), reached);
+        if reached == 0 {
+            continue;
+        }
+
+        let title = if goal.description.is_empty() {
+            goal.kind.as_ref()
+        } else {
+            goal.description.as_ref()
+        };
+        let percent = reached.to_string();
+        let text = i18n::render(
+            config.discord.locale.messages().goal_progress,
+            &[
+                (
+                    "user",
+                    escape_markdown(&stream.user_name, config.discord.escape).as_ref(),
+                ),
+                ("goal", escape_markdown(title, config.discord.escape).as_ref()),
+                ("percent", percent.as_str()),
+            ],
+        );
+        let fields =
+            vec![
+                EmbedFieldBuilder::new("Progress", format!("{}/{}", goal.current_amount, goal.target_amount)).inline(),
+            ];
+        announce(stream, EventName::Goal, text, fields, webhook, config).await;
+    }
+}
+
+/// The step of the goal which was reached since the last announced step, if any.
+///
+/// Goals seen for the first time only record their current step, so a restart does not announce them again.
+/// The returned step is 0 for new goals below the first step.
+fn next_step(last: Option<u64>, goal: &CreatorGoal, step: u64) -> Option<u64> {
+    let reached = goal.percent().min(100) / step * step;
+    match last {
+        None => Some(reached),
+        Some(last) if reached > last => Some(reached),
+        Some(_) => None,
+    }
+}
+
+async fn announce(
+    stream: &StreamStatus,
+    event: EventName,
+    text: String,
+    fields: Vec<EmbedFieldBuilder>,
+    webhook: &dyn Notifier,
+    config: &Config,
+) {
+    let login = stream.user_name.to_lowercase();
+    let (role, role_name) = match event {
+        EventName::Goal => ("goal", &config.discord.role_name.goal),
+        _ => ("hype_train", &config.discord.role_name.hype_train),
+    };
+
+    let now = DateTime::utc_now().timestamp().as_seconds();
+    let mention = match policy::delivery(&config.discord, event, now) {
+        Delivery::Normal => config.mention(event),
+        Delivery::Silent => Mention::silent(event),
+        Delivery::Skip => {
+            log::info!("[{login}] Skipping {role} notification during quiet hours");
+            return;
+        }
+    };
+
+    let url = format!("https://twitch.tv/{login}");
+    let mut embed = EmbedBuilder::new().color(embeds::TWITCH_COLOR).title(&url).url(&url);
+    for field in fields {
+        embed = embed.field(field);
+    }
+    if config.discord.show_notify_hints && !role_name.is_empty() {
+        let messages = config.discord.locale.messages();
+        embed = embed.footer(EmbedFooter {
+            icon_url: None,
+            proxy_icon_url: None,
+            text: i18n::render(messages.notify_hint, &[("role", role_name.as_ref())]),
+        });
+    }
+
+    log::info!("[{login}] Sending {role} notification");
+    let message = WebhookMessage {
+        content: format!("{}{text}", mention.prefix()),
+        embeds: vec![embed.build()],
+        files: Vec::new(),
+        avatar_url: config.discord.avatar_url.as_deref().map(str::to_owned),
+        username: config.discord.username(&stream.user_name),
+        allowed_mentions: mention.allowed_mentions(),
+        live_stream: None,
+    };
+
+    if let Err(e) = webhook.send(message).await {
+        log::error!("[{login}] Failed to send {role} notification: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn goal(current_amount: u64, target_amount: u64) -> CreatorGoal {
+        serde_json::from_value(serde_json::json!({
+            "id": "1woowvbkiNv8BRxEWSqmQz6Zk92",
+            "broadcaster_id": "141981764",
+            "broadcaster_login": "twitchdev",
+            "broadcaster_name": "TwitchDev",
+            "type": "follower",
+            "description": "Follow goal for Helix testing",
+            "current_amount": current_amount,
+            "target_amount": target_amount,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_next_step() {
+        // The first poll only records the current step
+        assert_eq!(next_step(None, &goal(60, 100), 25), Some(50));
+        assert_eq!(next_step(None, &goal(10, 100), 25), Some(0));
+
+        assert_eq!(next_step(Some(50), &goal(70, 100), 25), None);
+        assert_eq!(next_step(Some(50), &goal(80, 100), 25), Some(75));
+        assert_eq!(next_step(Some(75), &goal(250, 100), 25), Some(100));
+        assert_eq!(next_step(Some(100), &goal(300, 100), 25), None);
+        assert_eq!(next_step(Some(0), &goal(5, 0), 25), Some(100));
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+
+use database_api::DatabaseError;
+use discord_api::SendError;
+use thiserror::Error;
+use twilight_http::response::DeserializeBodyError;
+use twilight_model::id::{marker::GuildMarker, Id};
+use twitch_api::error::RequestError;
+
+/// The failures of the bot, by the service which caused them.
+#[derive(Error, Debug)]
+pub enum BotError {
+    #[error("twitch request failed: {0}")]
+    Twitch(#[from] RequestError),
+    #[error("discord request failed: {0}")]
+    Discord(#[from] DiscordError),
+    #[error("database error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    Config(#[from] InitError),
+}
+
+impl BotError {
+    /// Whether the error might resolve itself when retrying later, such as server errors or timeouts.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            BotError::Twitch(e @ (RequestError::Http(_) | RequestError::TwitchApi(_))) => e
+                .status()
+                .map_or(false, |status| status.as_u16() == 429 || status.is_server_error()),
+            BotError::Twitch(RequestError::Timeout | RequestError::Unexpected(_)) => true,
+            BotError::Twitch(RequestError::Deserialize(_) | RequestError::NotFound(..) | RequestError::TooLarge(_)) => {
+                false
+            }
+            BotError::Discord(DiscordError::Webhook(e)) => e.is_transient(),
+            BotError::Discord(DiscordError::Http(_)) | BotError::Database(DatabaseError::Io(_)) => true,
+            BotError::Discord(DiscordError::Response(_))
+            | BotError::Database(DatabaseError::Serde(_))
+            | BotError::Config(_) => false,
+        }
+    }
+
+    /// The kind of the error, or `None` for errors which are not counted, such as an invalid configuration.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match self {
+            BotError::Twitch(e) => Some(ErrorCategory::of_request(e)),
+            BotError::Discord(_) => Some(ErrorCategory::Discord),
+            BotError::Database(_) => Some(ErrorCategory::Database),
+            BotError::Config(_) => None,
+        }
+    }
+}
+
+/// The kind of a failure, by which the errors are counted for the error rate alerts.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum ErrorCategory {
+    /// Twitch responded with a 5xx status
+    TwitchServer,
+    /// Twitch responded with 429 Too Many Requests
+    TwitchRateLimit,
+    TwitchTimeout,
+    Twitch,
+    Discord,
+    Database,
+}
+
+impl ErrorCategory {
+    pub fn of_request(error: &RequestError) -> Self {
+        match error {
+            RequestError::Timeout => Self::TwitchTimeout,
+            e => match e.status() {
+                Some(status) if status.as_u16() == 429 => Self::TwitchRateLimit,
+                Some(status) if status.is_server_error() => Self::TwitchServer,
+                _ => Self::Twitch,
+            },
+        }
+    }
+}
+
+impl Display for ErrorCategory {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ErrorCategory::TwitchServer => write!(f, "Twitch server errors"),
+            ErrorCategory::TwitchRateLimit => write!(f, "Twitch rate limits"),
+            ErrorCategory::TwitchTimeout => write!(f, "Twitch timeouts"),
+            ErrorCategory::Twitch => write!(f, "Twitch request errors"),
+            ErrorCategory::Discord => write!(f, "Discord errors"),
+            ErrorCategory::Database => write!(f, "cache errors"),
+        }
+    }
+}
+
+impl From<twilight_http::Error> for BotError {
+    fn from(e: twilight_http::Error) -> Self {
+        BotError::Discord(e.into())
+    }
+}
+
+impl From<DeserializeBodyError> for BotError {
+    fn from(e: DeserializeBodyError) -> Self {
+        BotError::Discord(e.into())
+    }
+}
+
+impl From<SendError> for BotError {
+    fn from(e: SendError) -> Self {
+        BotError::Discord(e.into())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DiscordError {
+    #[error(transparent)]
+    Http(#[from] twilight_http::Error),
+    #[error("invalid response: {0}")]
+    Response(#[from] DeserializeBodyError),
+    #[error(transparent)]
+    Webhook(#[from] SendError),
+}
+
+#[derive(Error, Debug)]
+pub enum InitError {
+    NoGuilds,
+    TooManyGuilds,
+    InvalidToken,
+    MissingGuild(Id<GuildMarker>),
+    InvalidGuildId(Box<str>),
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, serde_json::Error),
+    UnknownProfile(Box<str>),
+}
+
+impl Display for InitError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            InitError::NoGuilds => write!(f, "No guilds found"),
+            InitError::TooManyGuilds => write!(f, "Too many guilds found"),
+            InitError::InvalidToken => write!(f, "The discord bot token is invalid, check the token in the config"),
+            InitError::MissingGuild(id) => write!(
+                f,
+                "The bot has no access to the server {id}, check the server_id and invite the bot to the server"
+            ),
+            InitError::InvalidGuildId(id) => write!(f, "The server_id {id:?} is not a valid id"),
+            InitError::Read(path, e) => write!(f, "Failed to read {}: {e}", path.display()),
+            InitError::Parse(path, e) => write!(f, "Failed to parse {}: {e}", path.display()),
+            InitError::UnknownProfile(name) => write!(f, "The profile {name:?} is not defined in the config"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twitch_api::error::TwitchApiError;
+
+    #[test]
+    fn test_transient() {
+        assert!(BotError::from(RequestError::Timeout).is_transient());
+        let unauthorized = TwitchApiError {
+            status: 401,
+            error: "Unauthorized".into(),
+            message: "Invalid OAuth token".into(),
+            request_id: None,
+        };
+        assert!(!BotError::from(RequestError::TwitchApi(unauthorized)).is_transient());
+        assert!(!BotError::from(RequestError::NotFound("game", "id=1".to_owned())).is_transient());
+        assert!(!BotError::from(InitError::NoGuilds).is_transient());
+    }
+
+    #[test]
+    fn test_category() {
+        assert_eq!(
+            BotError::from(RequestError::Timeout).category(),
+            Some(ErrorCategory::TwitchTimeout)
+        );
+        let unavailable = TwitchApiError {
+            status: 503,
+            error: "Service Unavailable".into(),
+            message: "Service is temporarily unavailable".into(),
+            request_id: None,
+        };
+        assert_eq!(
+            BotError::from(RequestError::TwitchApi(unavailable)).category(),
+            Some(ErrorCategory::TwitchServer)
+        );
+        assert_eq!(BotError::from(InitError::NoGuilds).category(), None);
+    }
+}
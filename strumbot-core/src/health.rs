@@ -0,0 +1,183 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+use commons::util::Timestamp;
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing as log;
+use twitch_api::TwitchClient;
+
+use crate::status::StatusBoard;
+
+/// Shared health state of the bot, reported by the `/healthz` endpoint.
+pub struct Health {
+    /// Unix timestamp of the last successful twitch poll, 0 if there was none yet
+    last_poll: AtomicU64,
+    /// Number of updates which were dropped, since the queue of their watcher was full
+    dropped_updates: AtomicU64,
+    /// The gateway connection flag, if the gateway is enabled
+    gateway: Option<Arc<AtomicBool>>,
+    twitch: Arc<TwitchClient>,
+    max_poll_age: u64,
+}
+
+impl Health {
+    pub fn new(twitch: Arc<TwitchClient>, gateway: Option<Arc<AtomicBool>>, max_poll_age: u64) -> Self {
+        Self {
+            last_poll: AtomicU64::new(0),
+            dropped_updates: AtomicU64::new(0),
+            gateway,
+            twitch,
+            max_poll_age,
+        }
+    }
+
+    pub fn record_poll(&self) {
+        self.last_poll.store(Timestamp::now().as_secs(), Ordering::Relaxed);
+    }
+
+    pub fn record_dropped_update(&self) {
+        self.dropped_updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn last_poll(&self) -> u64 {
+        self.last_poll.load(Ordering::Relaxed)
+    }
+
+    fn report(&self) -> (bool, serde_json::Value) {
+        let last_poll = self.last_poll();
+        let token_valid = self.twitch.is_authorized();
+        let gateway = self.gateway.as_ref().map(|flag| flag.load(Ordering::Relaxed));
+        let polling = last_poll + self.max_poll_age >= Timestamp::now().as_secs();
+
+        let healthy = token_valid && polling && gateway.unwrap_or(true);
+        let body = json!({
+            "status": if healthy { "ok" } else { "unhealthy" },
+            "twitch_token_valid": token_valid,
+            "last_poll": last_poll,
+            "gateway_connected": gateway,
+            "dropped_updates": self.dropped_updates.load(Ordering::Relaxed),
+        });
+
+        (healthy, body)
+    }
+}
+
+/// Runs a minimal HTTP server which answers `GET /healthz` with the current health report.
+///
+/// With a status board, the read-only `/api` routes return the state of the stream watchers.
+pub async fn serve(health: Arc<Health>, address: String, api: Option<Arc<StatusBoard>>) {
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Failed to bind health endpoint to {address}: {e}");
+            return;
+        }
+    };
+
+    log::info!("Health endpoint listening on http://{address}/healthz");
+
+    loop {
+        match listener.accept().await {
+            Ok((socket, _)) => {
+                tokio::spawn(handle(socket, Arc::clone(&health), api.clone()));
+            }
+            Err(e) => {
+                log::warn!("Failed to accept health check connection: {e}");
+            }
+        }
+    }
+}
+
+async fn handle(mut socket: TcpStream, health: Arc<Health>, api: Option<Arc<StatusBoard>>) {
+    let mut buf = [0u8; 1024];
+    let Ok(n) = socket.read(&mut buf).await else {
+        return;
+    };
+
+    // We only care about the request line, e.g. "GET /healthz HTTP/1.1"
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split('?').next().unwrap_or(path);
+
+    let (status, body) = match (path, api) {
+        ("/healthz", _) => match health.report() {
+            (true, body) => ("200 OK", body.to_string()),
+            (false, body) => ("503 Service Unavailable", body.to_string()),
+        },
+        (path, Some(board)) if path.starts_with("/api/") => api_response(&board, path),
+        _ => ("404 Not Found", "{}".to_owned()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    if let Err(e) = socket.write_all(response.as_bytes()).await {
+        log::debug!("Failed to write health response: {e}");
+    }
+}
+
+/// Answers the `/api` routes with snapshots of the status board, which is also used by the `/status` command.
+fn api_response(board: &StatusBoard, path: &str) -> (&'static str, String) {
+    match path.trim_end_matches('/') {
+        "/api/streams" => ("200 OK", json!(board.streams()).to_string()),
+        path => match path
+            .strip_prefix("/api/watchers/")
+            .and_then(|login| board.stream(login))
+        {
+            Some(stream) => {
+                let login = stream.user_name.to_lowercase();
+                let body = json!({
+                    "stream": stream,
+                    "muted": board.is_muted(&login),
+                    "announced": board.announced(&login),
+                });
+                ("200 OK", body.to_string())
+            }
+            None => ("404 Not Found", "{}".to_owned()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::StreamStatus;
+
+    #[test]
+    fn test_api_response() {
+        let board = StatusBoard::default();
+        board.set(
+            "elajjaz",
+            Some(StreamStatus {
+                user_id: "37085797".into(),
+                user_name: "Elajjaz".into(),
+                game: "Elden Ring".into(),
+                started_at: 1_696_892_400,
+                offline: false,
+            }),
+        );
+
+        let (status, body) = api_response(&board, "/api/streams");
+        assert_eq!(status, "200 OK");
+        let streams: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(streams[0]["game"], "Elden Ring");
+
+        let (status, body) = api_response(&board, "/api/watchers/Elajjaz");
+        assert_eq!(status, "200 OK");
+        let watcher: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(watcher["stream"]["started_at"], 1_696_892_400);
+        assert_eq!(watcher["muted"], false);
+
+        assert_eq!(api_response(&board, "/api/watchers/distortion2").0, "404 Not Found");
+        assert_eq!(api_response(&board, "/api/watchers/").0, "404 Not Found");
+        assert_eq!(api_response(&board, "/api/unknown").0, "404 Not Found");
+    }
+}
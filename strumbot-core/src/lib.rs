@@ -0,0 +1,49 @@
+//! The watcher pipeline of strumbot, which can be embedded in other applications.
+//!
+//! The [`Bot`] polls the tracked twitch streams and sends their notifications with a [`Notifier`].
+//! The discord setup, like the roles and the application commands, is left to the application.
+
+use database_api::DynDatabase;
+
+pub use bot::{Bot, BotBuilder};
+pub use notifier::Notifier;
+
+mod alerts;
+mod bot;
+mod category;
+mod collage;
+mod digest;
+mod engagement;
+mod milestones;
+mod notifier;
+mod platform;
+mod policy;
+mod polling;
+mod raids;
+mod recheck;
+mod replication;
+mod stats;
+mod supervisor;
+mod tracking;
+mod upcoming;
+mod videos;
+
+pub mod commands;
+pub mod config;
+pub mod embeds;
+pub mod errors;
+pub mod health;
+pub mod mentions;
+pub mod notifications;
+pub mod outbox;
+pub mod releases;
+pub mod replay;
+pub mod roles;
+pub mod schedule;
+pub mod simulate;
+pub mod status;
+pub mod token;
+pub mod watcher;
+
+/// The database for all persisted state, which can be backed by any [`database_api::Database`].
+pub type Cache = DynDatabase;
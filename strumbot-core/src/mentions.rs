@@ -0,0 +1,127 @@
+use std::fmt::{Display, Formatter};
+
+use discord_api::config::{EventName, MentionConfig};
+use twilight_model::{
+    channel::message::{AllowedMentions, MentionType},
+    id::{marker::RoleMarker, Id},
+};
+
+/// The mentions of a notification, with the allowed mentions which make discord ping exactly those.
+#[derive(Clone, Debug)]
+pub struct Mention {
+    pub event: EventName,
+    text: String,
+    allowed: AllowedMentions,
+}
+
+impl Mention {
+    /// Resolves the configured mention targets of the event, which default to its notification role.
+    pub fn resolve(event: EventName, targets: Option<&MentionConfig>, role: Option<Id<RoleMarker>>) -> Self {
+        let default = MentionConfig {
+            role_name: true,
+            ..MentionConfig::default()
+        };
+        let targets = targets.unwrap_or(&default);
+
+        let mut roles: Vec<Id<RoleMarker>> = role.filter(|_| targets.role_name).into_iter().collect();
+        for role in &targets.roles {
+            if !roles.contains(role) {
+                roles.push(*role);
+            }
+        }
+
+        let mut mentions: Vec<String> = roles.iter().map(|id| format!("<@&{id}>")).collect();
+        mentions.extend(targets.users.iter().map(|id| format!("<@{id}>")));
+        if targets.here {
+            mentions.push("@here".to_owned());
+        }
+        if targets.everyone {
+            mentions.push("@everyone".to_owned());
+        }
+
+        // Both @here and @everyone are allowed by the everyone type
+        let parse = if targets.here || targets.everyone {
+            vec![MentionType::Everyone]
+        } else {
+            Vec::new()
+        };
+        Self {
+            event,
+            text: mentions.join(" "),
+            allowed: AllowedMentions {
+                parse,
+                replied_user: false,
+                roles,
+                users: targets.users.clone(),
+            },
+        }
+    }
+
+    /// No mentions, for notifications during the quiet hours.
+    pub fn silent(event: EventName) -> Self {
+        Self {
+            event,
+            text: String::new(),
+            allowed: AllowedMentions::default(),
+        }
+    }
+
+    /// The mentions followed by a space, to put in front of the notification text.
+    pub fn prefix(&self) -> String {
+        if self.text.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", self.text)
+        }
+    }
+
+    /// The allowed mentions of the webhook message, so user-controlled text in the content can't ping anyone.
+    pub fn allowed_mentions(&self) -> Option<AllowedMentions> {
+        Some(self.allowed.clone())
+    }
+}
+
+impl Display for Mention {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use twilight_model::id::Id;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let role = Some(Id::new(81384788765712384));
+        let mention = Mention::resolve(EventName::Live, None, role);
+        assert_eq!(mention.to_string(), "<@&81384788765712384>");
+        assert_eq!(mention.prefix(), "<@&81384788765712384> ");
+        assert_eq!(mention.allowed.roles, vec![Id::new(81384788765712384)]);
+        assert!(mention.allowed.parse.is_empty());
+
+        assert_eq!(Mention::resolve(EventName::Live, None, None).prefix(), "");
+
+        let targets = MentionConfig {
+            role_name: true,
+            roles: vec![Id::new(81384788765712384), Id::new(2)],
+            users: vec![Id::new(86699011792191488)],
+            here: true,
+            everyone: false,
+        };
+        let mention = Mention::resolve(EventName::Update, Some(&targets), role);
+        assert_eq!(
+            mention.to_string(),
+            "<@&81384788765712384> <@&2> <@86699011792191488> @here"
+        );
+        assert_eq!(mention.allowed.roles, vec![Id::new(81384788765712384), Id::new(2)]);
+        assert_eq!(mention.allowed.users, vec![Id::new(86699011792191488)]);
+        assert_eq!(mention.allowed.parse, vec![MentionType::Everyone]);
+
+        let mention = Mention::resolve(EventName::Vod, Some(&MentionConfig::default()), role);
+        assert_eq!(mention.to_string(), "");
+        assert_eq!(mention.allowed, AllowedMentions::default());
+    }
+}
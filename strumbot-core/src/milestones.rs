@@ -0,0 +1,206 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use commons::util::escape_markdown;
+use database_api::{Database, DatabaseError};
+use discord_api::{config::EventName, i18n, WebhookMessage};
+use eos::DateTime;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing as log;
+use twilight_model::channel::message::embed::EmbedFooter;
+use twilight_util::builder::embed::EmbedBuilder;
+use twitch_api::{TwitchClient, User};
+
+use crate::{
+    config::Config,
+    embeds,
+    mentions::Mention,
+    notifier::Notifier,
+    policy::{self, Delivery},
+    status::StatusBoard,
+    Cache,
+};
+
+/// The last milestones of each streamer, by user id
+const MILESTONES_KEY: &str = "_milestones";
+
+/// The last reached milestones of a streamer, which are persisted to avoid duplicate announcements.
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+struct Reached {
+    #[serde(default)]
+    followers: Option<u64>,
+    #[serde(default)]
+    subscribers: Option<u64>,
+}
+
+/// Announces when the tracked streamers reach another follower or subscriber milestone.
+///
+/// The follower count requires a user token, while the subscriber count is only available to a user token of the
+/// broadcaster with the `channel:read:subscriptions` scope.
+pub async fn watch(
+    config: Arc<Config>,
+    client: Arc<TwitchClient>,
+    webhook: Arc<dyn Notifier>,
+    status: Arc<StatusBoard>,
+    db: Arc<Cache>,
+) {
+    let settings = &config.milestones;
+    let interval = Duration::from_secs(settings.interval.max(300));
+    let mut reached: HashMap<Box<str>, Reached> = match db.read(MILESTONES_KEY).await {
+        Ok(reached) => reached,
+        Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+        Err(e) => {
+            log::error!("Failed to load the reached milestones: {e}");
+            HashMap::new()
+        }
+    };
+
+    // The tracked streamers are only known after the first poll
+    sleep(Duration::from_secs(30)).await;
+
+    loop {
+        let tracked = status.tracked();
+        let users = match client.get_users_by_id(&tracked).await {
+            Ok(users) => users,
+            Err(e) => {
+                log::error!("Failed to fetch the tracked users for milestones: {e}");
+                Vec::new()
+            }
+        };
+
+        let mut changed = false;
+        for user in users {
+            let last = reached.get(&user.id).copied().unwrap_or_default();
+            let mut current = last;
+
+            if settings.followers > 0 {
+                match client.get_follower_count(&user.id).await {
+                    Ok(count) => current.followers = Some(milestone(count, settings.followers)).max(last.followers),
+                    Err(e) => log::debug!("[{}] Could not fetch follower count: {}", user.login, e),
+                }
+            }
+            if settings.subscribers > 0 {
+                match client.get_subscriber_count(&user.id).await {
+                    Ok(count) => {
+                        current.subscribers = Some(milestone(count, settings.subscribers)).max(last.subscribers)
+                    }
+                    Err(e) => log::debug!("[{}] Could not fetch subscriber count: {}", user.login, e),
+                }
+            }
+
+            // Muted streamers still record their milestones, so they are not announced after the mute
+            if !status.is_muted(&user.login) {
+                if let Some(count) = is_new(last.followers, current.followers) {
+                    announce(&user, count, false, webhook.as_ref(), &config).await;
+                }
+                if let Some(count) = is_new(last.subscribers, current.subscribers) {
+                    announce(&user, count, true, webhook.as_ref(), &config).await;
+                }
+            }
+
+            // The milestones never go down, so a streamer who loses followers is not announced again
+            if current.followers != last.followers || current.subscribers != last.subscribers {
+                reached.insert(user.id.clone(), current);
+                changed = true;
+            }
+        }
+
+        if changed {
+            if let Err(e) = db.save(MILESTONES_KEY, &reached).await {
+                log::error!("Failed to save the reached milestones: {e}");
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
+
+/// The highest milestone below the count, such as 3000 for 3456 followers with a step of 1000.
+const fn milestone(count: u64, step: u64) -> u64 {
+    count / step * step
+}
+
+/// The milestone to announce, if it is higher than the last one.
+///
+/// The first milestone of a streamer is only recorded, so the current count is not announced on startup.
+fn is_new(last: Option<u64>, current: Option<u64>) -> Option<u64> {
+    match (last, current) {
+        (Some(last), Some(current)) if current > last => Some(current),
+        _ => None,
+    }
+}
+
+async fn announce(user: &User, count: u64, subscribers: bool, webhook: &dyn Notifier, config: &Config) {
+    let login = &user.login;
+    let now = DateTime::utc_now().timestamp().as_seconds();
+    let mention = match policy::delivery(&config.discord, EventName::Milestone, now) {
+        Delivery::Normal => config.mention(EventName::Milestone),
+        Delivery::Silent => Mention::silent(EventName::Milestone),
+        Delivery::Skip => {
+            log::info!("[{login}] Skipping milestone notification during quiet hours");
+            return;
+        }
+    };
+
+    log::info!("[{login}] Sending milestone notification for {count} (subscribers: {subscribers})");
+    let url = format!("https://twitch.tv/{login}");
+    let mut embed = EmbedBuilder::new().color(embeds::TWITCH_COLOR).title(&url).url(&url);
+
+    let messages = config.discord.locale.messages();
+    let role_name = &config.discord.role_name.milestone;
+    if config.discord.show_notify_hints && !role_name.is_empty() {
+        embed = embed.footer(EmbedFooter {
+            icon_url: None,
+            proxy_icon_url: None,
+            text: i18n::render(messages.notify_hint, &[("role", role_name.as_ref())]),
+        });
+    }
+
+    let template = if subscribers {
+        messages.subscriber_milestone
+    } else {
+        messages.follower_milestone
+    };
+    let count = count.to_string();
+    let text = i18n::render(
+        template,
+        &[
+            (
+                "user",
+                escape_markdown(&user.display_name, config.discord.escape).as_ref(),
+            ),
+            ("count", count.as_str()),
+        ],
+    );
+
+    let message = WebhookMessage {
+        content: format!("{}{text}", mention.prefix()),
+        embeds: vec![embed.build()],
+        files: Vec::new(),
+        avatar_url: config.discord.avatar_url.as_deref().map(str::to_owned),
+        username: config.discord.username(&user.display_name),
+        allowed_mentions: mention.allowed_mentions(),
+        live_stream: None,
+    };
+
+    if let Err(e) = webhook.send(message).await {
+        log::error!("[{login}] Failed to send milestone notification: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_milestones() {
+        assert_eq!(milestone(3456, 1000), 3000);
+        assert_eq!(milestone(999, 1000), 0);
+
+        assert_eq!(is_new(None, Some(3000)), None);
+        assert_eq!(is_new(Some(3000), Some(3000)), None);
+        assert_eq!(is_new(Some(3000), Some(4000)), Some(4000));
+        assert_eq!(is_new(Some(4000), Some(3000)), None);
+        assert_eq!(is_new(Some(3000), None), None);
+    }
+}
@@ -0,0 +1,360 @@
+use commons::util::escape_markdown;
+use discord_api::{config::DiscordConfig, MessageFile};
+use twilight_model::channel::message::{
+    embed::{EmbedAuthor, EmbedImage},
+    Embed,
+};
+use twilight_util::builder::embed::{EmbedAuthorBuilder, EmbedBuilder, EmbedFieldBuilder, ImageSource};
+use twitch_api::{Game, Stream};
+
+use crate::embeds;
+
+/// Discord allows at most 25 fields per embed
+pub const MAX_FIELDS: usize = 25;
+/// Discord allows at most 256 characters in a field name
+pub const MAX_FIELD_NAME_LENGTH: usize = 256;
+/// Discord allows at most 1024 characters in a field value
+pub const MAX_FIELD_LENGTH: usize = 1024;
+/// Discord limits the total number of characters across all embeds of a message
+pub const MAX_MESSAGE_LENGTH: usize = 6000;
+/// Discord allows at most 10 embeds per message
+pub const MAX_EMBEDS: usize = 10;
+/// The name of the uploaded preview image
+const THUMBNAIL_FILENAME: &str = "thumbnail.jpg";
+
+/// Creates the embed for the live and update events, with the unix timestamp of the stream start.
+pub fn stream_embed(stream: &Stream, game: &Game, started_at: i64, config: &DiscordConfig) -> EmbedBuilder {
+    let url = format!("https://twitch.tv/{}", stream.user_name);
+    let mut embed = EmbedBuilder::new()
+        .author(EmbedAuthorBuilder::new(stream.title.to_string()).build())
+        .color(embeds::TWITCH_COLOR)
+        .title(&url)
+        .url(&url);
+
+    if !game.id.is_empty() {
+        embed = embed.field(EmbedFieldBuilder::new("Playing", escape_markdown(&game.name, config.escape)).inline());
+    }
+
+    embed.field(EmbedFieldBuilder::new("Started", config.time_format().datetime(started_at)).inline())
+}
+
+/// Replaces the image of a sent embed with a newer preview, the uploaded image is added to the files.
+pub fn replace_thumbnail(embed: &mut Embed, thumbnail: Thumbnail, files: &mut Vec<MessageFile>) {
+    let url = match thumbnail {
+        Thumbnail::Attachment(data) => {
+            files.push(MessageFile {
+                filename: THUMBNAIL_FILENAME.to_owned(),
+                data,
+            });
+            format!("attachment://{THUMBNAIL_FILENAME}")
+        }
+        Thumbnail::Link(url) => url,
+    };
+    embed.image = Some(EmbedImage {
+        height: None,
+        proxy_url: None,
+        url,
+        width: None,
+    });
+}
+
+/// The number of characters of the embed which count towards the message limit.
+pub fn length(embed: &Embed) -> usize {
+    let count = |s: Option<&String>| s.map_or(0, |s| s.chars().count());
+    count(embed.title.as_ref())
+        + count(embed.description.as_ref())
+        + count(embed.author.as_ref().map(|a| &a.name))
+        + count(embed.footer.as_ref().map(|f| &f.text))
+        + embed
+            .fields
+            .iter()
+            .map(|f| f.name.chars().count() + f.value.chars().count())
+            .sum::<usize>()
+}
+
+/// Shortens the text to at most `max` characters, with an ellipsis at the end.
+fn truncate(text: &mut String, max: usize) {
+    if text.chars().count() > max {
+        let end = text.char_indices().nth(max - 1).map_or(text.len(), |(i, _)| i);
+        text.truncate(end);
+        text.push('\u{2026}');
+    }
+}
+
+/// The preview image of a notification.
+pub enum Thumbnail {
+    /// The downloaded image, which is uploaded with the message and stays the same
+    Attachment(Vec<u8>),
+    /// The url of the image, which discord loads itself and might show a newer preview later
+    Link(String),
+}
+
+/// Builds the embeds of a notification while staying within the discord limits.
+///
+/// Fields which don't fit into the current embed continue in a new embed with the same color,
+/// and embeds which don't fit into the current message continue in another message.
+pub struct NotificationBuilder {
+    messages: Vec<Vec<Embed>>,
+    color: Option<u32>,
+}
+
+impl NotificationBuilder {
+    pub fn new(embed: EmbedBuilder) -> Self {
+        let embed = embed.build();
+        Self {
+            color: embed.color,
+            messages: vec![vec![embed]],
+        }
+    }
+
+    /// Starts a new embed, all following fields are added to this embed.
+    pub fn embed(mut self, embed: EmbedBuilder) -> Self {
+        self.push(embed.build());
+        self
+    }
+
+    /// Adds the field to the current embed, shortening the name and value to the field limits.
+    pub fn field(mut self, field: EmbedFieldBuilder) -> Self {
+        let mut field = field.build();
+        truncate(&mut field.name, MAX_FIELD_NAME_LENGTH);
+        truncate(&mut field.value, MAX_FIELD_LENGTH);
+
+        let size = field.name.chars().count() + field.value.chars().count();
+        if self.message_length() + size > MAX_MESSAGE_LENGTH {
+            let embed = self.continuation();
+            self.messages.push(vec![embed]);
+        } else if self.current().fields.len() >= MAX_FIELDS {
+            let embed = self.continuation();
+            self.push(embed);
+        }
+
+        self.current().fields.push(field);
+        self
+    }
+
+    /// Adds the lines in fields of up to 1000 characters, using at most `max_fields` fields.
+    ///
+    /// If the lines don't fit, the remaining lines are replaced by the `overflow` note and this returns true.
+    pub fn lines(
+        mut self,
+        name: &str,
+        lines: &[String],
+        max_fields: usize,
+        overflow: &str,
+        inline: bool,
+    ) -> (Self, bool) {
+        let mut truncated = false;
+        let mut index = vec![];
+        let mut current = String::with_capacity(1000);
+        for line in lines {
+            if current.len() + line.len() > 1000 {
+                if index.len() + 1 >= max_fields {
+                    current.push_str(overflow);
+                    truncated = true;
+                    break; // pushed after loop
+                }
+
+                index.push(current);
+                current = String::with_capacity(1000);
+            }
+
+            current.push_str(line);
+            current.push('\n');
+        }
+        index.push(current);
+
+        for part in index {
+            let field = EmbedFieldBuilder::new(name, part);
+            self = self.field(if inline { field.inline() } else { field });
+        }
+
+        (self, truncated)
+    }
+
+    /// Sets the image of the first embed.
+    pub fn image(mut self, source: ImageSource) -> Self {
+        self.messages[0][0].image = EmbedBuilder::new().image(source).build().image;
+        self
+    }
+
+    /// Shows the image next to the author of the first embed, which is added with this name if it has no author yet.
+    pub fn author_icon(mut self, name: &str, url: &str) -> Self {
+        let embed = &mut self.messages[0][0];
+        match embed.author {
+            Some(ref mut author) => author.icon_url = Some(url.to_owned()),
+            None => {
+                embed.author = Some(EmbedAuthor {
+                    icon_url: Some(url.to_owned()),
+                    name: name.to_owned(),
+                    proxy_icon_url: None,
+                    url: None,
+                });
+            }
+        }
+        self
+    }
+
+    /// Sets the thumbnail as the image of the first embed, an attachment is added to the files of the message.
+    pub fn thumbnail(self, thumbnail: Thumbnail, files: &mut Vec<MessageFile>) -> Self {
+        match thumbnail {
+            Thumbnail::Attachment(data) => {
+                files.push(MessageFile {
+                    filename: THUMBNAIL_FILENAME.to_owned(),
+                    data,
+                });
+                self.image(ImageSource::attachment(THUMBNAIL_FILENAME).expect("Filename for thumbnail is invalid"))
+            }
+            Thumbnail::Link(url) => match ImageSource::url(url) {
+                Ok(source) => self.image(source),
+                Err(_) => self,
+            },
+        }
+    }
+
+    /// The embeds of each message, in the order they should be sent.
+    pub fn build(self) -> Vec<Vec<Embed>> {
+        self.messages
+    }
+
+    fn push(&mut self, embed: Embed) {
+        let fits = {
+            let message = self.messages.last().expect("Notification without any messages");
+            message.len() < MAX_EMBEDS && self.message_length() + length(&embed) <= MAX_MESSAGE_LENGTH
+        };
+
+        if fits {
+            self.messages
+                .last_mut()
+                .expect("Notification without any messages")
+                .push(embed);
+        } else {
+            self.messages.push(vec![embed]);
+        }
+    }
+
+    /// An empty embed to continue the fields of the previous embed.
+    fn continuation(&self) -> Embed {
+        let mut embed = EmbedBuilder::new();
+        if let Some(color) = self.color {
+            embed = embed.color(color);
+        }
+        embed.build()
+    }
+
+    fn current(&mut self) -> &mut Embed {
+        self.messages
+            .last_mut()
+            .and_then(|message| message.last_mut())
+            .expect("Notification without any embeds")
+    }
+
+    fn message_length(&self) -> usize {
+        self.messages
+            .last()
+            .map_or(0, |message| message.iter().map(length).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("{i:03} {}", "x".repeat(96))).collect()
+    }
+
+    #[test]
+    fn test_lines() {
+        let notification = NotificationBuilder::new(EmbedBuilder::new().color(embeds::TWITCH_COLOR));
+        let (notification, truncated) = notification.lines("Timestamps", &lines(20), 4, "*...*", true);
+        assert!(!truncated);
+
+        let messages = notification.build();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0][0].fields.len(), 3);
+        assert!(messages[0][0].fields.iter().all(|f| f.inline && f.value.len() <= 1000));
+
+        let notification = NotificationBuilder::new(EmbedBuilder::new());
+        let (notification, truncated) = notification.lines("Timestamps", &lines(100), 4, "*...*", true);
+        assert!(truncated);
+        let messages = notification.build();
+        assert_eq!(messages[0][0].fields.len(), 4);
+        assert!(messages[0][0].fields[3].value.ends_with("*...*"));
+    }
+
+    #[test]
+    fn test_split_embeds() {
+        let mut notification = NotificationBuilder::new(EmbedBuilder::new().color(embeds::TWITCH_COLOR));
+        for i in 0..30 {
+            notification = notification.field(EmbedFieldBuilder::new(format!("Field {i}"), "value"));
+        }
+
+        let messages = notification.build();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].len(), 2);
+        assert_eq!(messages[0][0].fields.len(), MAX_FIELDS);
+        assert_eq!(messages[0][1].color, Some(embeds::TWITCH_COLOR));
+
+        let (notification, _) =
+            NotificationBuilder::new(EmbedBuilder::new()).lines("Clips", &lines(100), usize::MAX, "", false);
+        let messages = notification.build();
+        assert_eq!(messages.len(), 2);
+        assert!(messages
+            .iter()
+            .all(|message| message.iter().map(length).sum::<usize>() <= MAX_MESSAGE_LENGTH));
+        assert_eq!(
+            messages
+                .iter()
+                .flatten()
+                .flat_map(|e| &e.fields)
+                .map(|f| f.value.lines().count())
+                .sum::<usize>(),
+            100
+        );
+    }
+
+    #[test]
+    fn test_truncate_field() {
+        let notification = NotificationBuilder::new(EmbedBuilder::new())
+            .field(EmbedFieldBuilder::new("Games", "\u{00E4}".repeat(2000)));
+        let messages = notification.build();
+        let field = &messages[0][0].fields[0];
+        assert_eq!(field.value.chars().count(), MAX_FIELD_LENGTH);
+        assert!(field.value.ends_with('\u{2026}'));
+    }
+
+    #[test]
+    fn test_author_icon() {
+        let url = "https://static-cdn.jtvnw.net/jtv_user_pictures/elajjaz-profile_image.png";
+        let embed = EmbedBuilder::new().author(EmbedAuthorBuilder::new("Blind playthrough").build());
+        let messages = NotificationBuilder::new(embed).author_icon("Elajjaz", url).build();
+        let author = messages[0][0].author.as_ref().unwrap();
+        assert_eq!(author.name, "Blind playthrough");
+        assert_eq!(author.icon_url.as_deref(), Some(url));
+
+        let messages = NotificationBuilder::new(EmbedBuilder::new())
+            .author_icon("Elajjaz", url)
+            .build();
+        let author = messages[0][0].author.as_ref().unwrap();
+        assert_eq!(author.name, "Elajjaz");
+        assert_eq!(author.icon_url.as_deref(), Some(url));
+    }
+
+    #[test]
+    fn test_replace_thumbnail() {
+        let mut files = Vec::new();
+        let messages = NotificationBuilder::new(EmbedBuilder::new())
+            .thumbnail(Thumbnail::Attachment(vec![1]), &mut files)
+            .build();
+        let mut embed = messages[0][0].clone();
+
+        let mut files = Vec::new();
+        replace_thumbnail(&mut embed, Thumbnail::Attachment(vec![2]), &mut files);
+        assert_eq!(embed.image, messages[0][0].image);
+        assert_eq!(files[0].data, vec![2]);
+
+        let url = "https://static-cdn.jtvnw.net/previews-ttv/live_user_elajjaz-1920x1080.jpg?t=1";
+        replace_thumbnail(&mut embed, Thumbnail::Link(url.to_owned()), &mut Vec::new());
+        assert_eq!(embed.image.map(|image| image.url).as_deref(), Some(url));
+    }
+}
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use discord_api::{ForumPost, SendError, SentMessage, WebhookClient, WebhookMessage};
+
+/// Delivers the stream notifications, which is the discord webhook for the bot itself.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Sends the message, and retries transient failures if supported by the implementation.
+    async fn send(&self, message: WebhookMessage) -> Result<SentMessage, SendError>;
+
+    /// Replaces a sent message, such as a VOD summary once its video is available.
+    ///
+    /// Implementations which cannot edit their messages keep the original message.
+    async fn edit(&self, _sent: SentMessage, _message: WebhookMessage) -> Result<(), SendError> {
+        Ok(())
+    }
+
+    /// Pins a sent message, such as the live notification while the stream is live.
+    ///
+    /// Implementations which cannot pin their messages keep them unpinned.
+    async fn pin(&self, _sent: SentMessage) -> Result<(), SendError> {
+        Ok(())
+    }
+
+    /// Unpins a message which was pinned before.
+    async fn unpin(&self, _sent: SentMessage) -> Result<(), SendError> {
+        Ok(())
+    }
+
+    /// Archives the VOD summary of a stream, such as a thread in a forum channel.
+    ///
+    /// Implementations without an archive drop the post.
+    async fn archive(&self, _post: ForumPost) -> Result<(), SendError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookClient {
+    async fn send(&self, message: WebhookMessage) -> Result<SentMessage, SendError> {
+        WebhookClient::send(self, message).await
+    }
+
+    async fn edit(&self, sent: SentMessage, message: WebhookMessage) -> Result<(), SendError> {
+        WebhookClient::edit(self, sent, &message).await
+    }
+
+    async fn pin(&self, sent: SentMessage) -> Result<(), SendError> {
+        WebhookClient::pin(self, sent).await
+    }
+
+    async fn unpin(&self, sent: SentMessage) -> Result<(), SendError> {
+        WebhookClient::unpin(self, sent).await
+    }
+
+    async fn archive(&self, post: ForumPost) -> Result<(), SendError> {
+        WebhookClient::archive(self, &post).await
+    }
+}
@@ -0,0 +1,250 @@
+use std::{sync::Arc, time::Duration};
+
+use database_api::{Database, DatabaseError};
+use discord_api::{WebhookClient, WebhookMessage};
+use eos::DateTime;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc,
+    time::{sleep_until, Instant},
+};
+use tracing as log;
+
+use crate::{status::StatusBoard, Cache};
+
+/// Twitch logins cannot start with an underscore, so this does not collide with watcher keys
+const KEY: &str = "_outbox";
+/// Time between attempts to deliver the pending messages
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Maximum number of pending messages, older messages are dropped first
+const MAX_PENDING: usize = 50;
+/// Time after which a pending message is dropped, since the notification is outdated by then
+const MAX_AGE: Duration = Duration::from_secs(6 * 3600);
+
+/// A message in the outbox, with the unix timestamp after which it is dropped.
+#[derive(Serialize, Deserialize)]
+struct PendingMessage {
+    message: WebhookMessage,
+    expires_at: i64,
+}
+
+impl PendingMessage {
+    fn new(message: WebhookMessage, now: i64) -> Self {
+        Self {
+            message,
+            expires_at: now + MAX_AGE.as_secs() as i64,
+        }
+    }
+
+    /// Whether the message should still be delivered, live events are outdated once the stream ended.
+    fn is_current(&self, now: i64, status: &StatusBoard) -> bool {
+        now < self.expires_at
+            && self
+                .message
+                .live_stream
+                .as_deref()
+                .map_or(true, |login| status.is_live(login))
+    }
+
+    /// The message without its attachments, which are too large for the cache.
+    ///
+    /// Embed images of the attachments are removed as well, since they would show up broken.
+    fn without_files(&self) -> Self {
+        let message = &self.message;
+        let mut embeds = message.embeds.clone();
+        for embed in &mut embeds {
+            if embed
+                .image
+                .as_ref()
+                .map_or(false, |image| image.url.starts_with("attachment://"))
+            {
+                embed.image = None;
+            }
+        }
+
+        Self {
+            message: WebhookMessage {
+                content: message.content.clone(),
+                embeds,
+                files: Vec::new(),
+                avatar_url: message.avatar_url.clone(),
+                username: message.username.clone(),
+                allowed_mentions: message.allowed_mentions.clone(),
+                live_stream: message.live_stream.clone(),
+            },
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// Persistent queue of notifications which could not be delivered due to transient errors.
+pub struct Outbox {
+    webhook: Arc<WebhookClient>,
+    db: Arc<Cache>,
+    persist: bool,
+    status: Arc<StatusBoard>,
+    pending: Vec<PendingMessage>,
+}
+
+impl Outbox {
+    /// Loads the pending messages from the cache, if enabled.
+    pub async fn load(webhook: Arc<WebhookClient>, db: Arc<Cache>, persist: bool, status: Arc<StatusBoard>) -> Self {
+        let pending = if persist {
+            db.read::<Vec<PendingMessage>>(KEY).await.unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        if !pending.is_empty() {
+            log::info!("Loaded {} pending notifications from outbox", pending.len());
+        }
+
+        Self {
+            webhook,
+            db,
+            persist,
+            status,
+            pending,
+        }
+    }
+
+    pub async fn run(mut self, mut failures: mpsc::UnboundedReceiver<WebhookMessage>) {
+        // The deadline only moves after a retry, so frequent failures don't delay the delivery of older messages
+        let retry = sleep_until(Instant::now() + RETRY_INTERVAL);
+        tokio::pin!(retry);
+        loop {
+            tokio::select! {
+                Some(message) = failures.recv() => {
+                    log::warn!("Queued notification for later delivery");
+                    if self.pending.is_empty() {
+                        retry.as_mut().reset(Instant::now() + RETRY_INTERVAL);
+                    }
+                    self.pending.push(PendingMessage::new(message, now()));
+                    if self.pending.len() > MAX_PENDING {
+                        self.pending.remove(0);
+                    }
+                    self.save().await;
+                }
+                () = &mut retry, if !self.pending.is_empty() => {
+                    self.flush().await;
+                    retry.as_mut().reset(Instant::now() + RETRY_INTERVAL);
+                }
+                else => break,
+            }
+        }
+    }
+
+    async fn flush(&mut self) {
+        let total = self.pending.len();
+        let mut remaining = Vec::new();
+        for pending in std::mem::take(&mut self.pending) {
+            if !pending.is_current(now(), &self.status) {
+                log::info!("Dropping outdated notification from outbox");
+                continue;
+            }
+
+            match self.webhook.execute(&pending.message).await {
+                Ok(_) => {}
+                Err(e) if e.is_transient() => {
+                    log::debug!("Notification from outbox still failing: {e}");
+                    remaining.push(pending);
+                }
+                Err(e) => {
+                    log::error!("Dropping notification from outbox: {e}");
+                }
+            }
+        }
+
+        self.pending = remaining;
+        if self.pending.len() < total {
+            log::info!("Delivered notifications from outbox, {} remaining", self.pending.len());
+        }
+        self.save().await;
+    }
+
+    async fn save(&self) {
+        if !self.persist {
+            return;
+        }
+
+        let result = if self.pending.is_empty() {
+            self.db.delete(KEY).await
+        } else {
+            let pending: Vec<PendingMessage> = self.pending.iter().map(PendingMessage::without_files).collect();
+            self.db.save(KEY, &pending).await
+        };
+
+        match result {
+            Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::error!("Failed to persist outbox: {e}"),
+            Ok(()) => {}
+        }
+    }
+}
+
+fn now() -> i64 {
+    DateTime::utc_now().timestamp().as_seconds()
+}
+
+#[cfg(test)]
+mod tests {
+    use discord_api::MessageFile;
+    use twilight_util::builder::embed::{EmbedBuilder, ImageSource};
+
+    use super::*;
+    use crate::status::StreamStatus;
+
+    #[test]
+    fn test_is_current() {
+        let status = StatusBoard::default();
+        let message = PendingMessage::new(WebhookMessage::default(), 1000);
+        assert!(message.is_current(1000, &status));
+        assert!(!message.is_current(message.expires_at, &status));
+
+        let live = WebhookMessage {
+            live_stream: Some("Elajjaz".to_owned()),
+            ..WebhookMessage::default()
+        };
+        let live = PendingMessage::new(live, 1000);
+        assert!(!live.is_current(1000, &status));
+
+        status.set(
+            "elajjaz",
+            Some(StreamStatus {
+                user_id: "1".into(),
+                user_name: "Elajjaz".into(),
+                game: "Elden Ring".into(),
+                started_at: 900,
+                offline: false,
+            }),
+        );
+        assert!(live.is_current(1000, &status));
+    }
+
+    #[test]
+    fn test_without_files() {
+        let message = WebhookMessage {
+            content: "Elajjaz is live".to_owned(),
+            embeds: vec![
+                EmbedBuilder::new()
+                    .image(ImageSource::attachment("thumbnail.jpg").unwrap())
+                    .build(),
+                EmbedBuilder::new()
+                    .image(ImageSource::url("https://example.com/box.jpg").unwrap())
+                    .build(),
+            ],
+            files: vec![MessageFile {
+                filename: "thumbnail.jpg".to_owned(),
+                data: vec![1, 2, 3],
+            }],
+            ..WebhookMessage::default()
+        };
+
+        let stored = PendingMessage::new(message, 1000).without_files();
+        assert!(stored.message.files.is_empty());
+        assert_eq!(stored.message.content, "Elajjaz is live");
+        assert!(stored.message.embeds[0].image.is_none());
+        assert!(stored.message.embeds[1].image.is_some());
+        assert_eq!(stored.expires_at, 1000 + MAX_AGE.as_secs() as i64);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use commons::source::{LiveStream, StreamSource};
+use database_api::{Database, DatabaseError};
+use tokio::{sync::mpsc, time::sleep};
+use tracing as log;
+use twitch_api::{Stream, StreamType, TwitchClient};
+
+use crate::{
+    bot::dispatch,
+    config::Config,
+    notifier::Notifier,
+    status::StatusBoard,
+    supervisor::start_watcher,
+    watcher::{watcher_key, StreamUpdate, StreamWatcher},
+    Cache,
+};
+
+/// An additional platform, which is polled for live streams of its channels.
+pub struct Source {
+    pub source: Arc<dyn StreamSource>,
+    pub channels: Vec<Box<str>>,
+    pub interval: Duration,
+}
+
+/// Polls an additional stream source, such as YouTube, and feeds its streams into stream watchers.
+///
+/// The watchers are the same as for twitch streams, so these channels also get update and VOD notifications,
+/// and their streams are recorded in the stats. Failed polls are skipped, so they don't end the streams.
+pub async fn watch_source(
+    Source {
+        source,
+        channels,
+        interval,
+    }: Source,
+    client: Arc<TwitchClient>,
+    webhook: Arc<dyn Notifier>,
+    config: Arc<Config>,
+    status: Arc<StatusBoard>,
+    cache: Arc<Cache>,
+) {
+    let platform = source.platform();
+    let send_timeout = Duration::from_secs(config.watchers.send_timeout);
+    let mut watchers: HashMap<String, mpsc::Sender<StreamUpdate>> = HashMap::with_capacity(channels.len());
+
+    let start = |watcher: StreamWatcher| {
+        let watcher = watcher.set_source(Some(Arc::clone(&source)));
+        start_watcher(config.cache.enabled, &client, &webhook, &cache, &status, watcher)
+    };
+
+    if config.cache.enabled {
+        for channel in &channels {
+            let key = watcher_key(Some(platform), channel);
+            match cache.read::<StreamWatcher>(&key).await {
+                Ok(watcher) => {
+                    let watcher = watcher.set_config(Arc::clone(&config));
+                    watchers.insert(key, start(watcher));
+                }
+                Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => log::error!("Could not load cache for {key}: {e}"),
+            }
+        }
+    }
+
+    log::info!("Listening for {platform} streams from {channels:?}");
+
+    loop {
+        watchers.retain(|_, watcher| !watcher.is_closed());
+
+        let streams = match source.get_live_streams(&channels).await {
+            Ok(streams) => streams,
+            Err(e) => {
+                log::error!("Failed to fetch streams from {platform}: {e}");
+                sleep(interval).await;
+                continue;
+            }
+        };
+
+        let mut offline: HashSet<String> = watchers.keys().cloned().collect();
+        let mut updates = Vec::with_capacity(channels.len());
+        for stream in streams {
+            let key = watcher_key(Some(platform), &stream.user_login);
+            offline.remove(&key);
+
+            let send = match watchers.get(&key) {
+                Some(send) => send.clone(),
+                None => {
+                    let send = start(StreamWatcher::new(stream.user_login.to_string(), Arc::clone(&config)));
+                    watchers.insert(key.clone(), send.clone());
+                    send
+                }
+            };
+            let stream = to_stream(platform, stream);
+            updates.push((key, send, StreamUpdate::Live(Box::new(stream))));
+        }
+
+        for key in offline {
+            if let Some(send) = watchers.get(&key) {
+                updates.push((key, send.clone(), StreamUpdate::Offline));
+            }
+        }
+
+        dispatch(updates, send_timeout, None).await;
+        sleep(interval).await;
+    }
+}
+
+/// Converts the stream into the twitch format of the watchers.
+///
+/// The category is used as the game id, since other platforms only provide its name.
+/// The user id is prefixed with the platform, so the stats don't collide with twitch users.
+fn to_stream(platform: &str, stream: LiveStream) -> Stream {
+    Stream {
+        id: stream.id,
+        game_id: stream.category,
+        title: stream.title,
+        kind: StreamType::Live,
+        language: "".into(),
+        thumbnail_url: stream.thumbnail_url,
+        user_id: format!("{}-{}", platform.to_lowercase(), stream.user_id).into(),
+        user_login: stream.user_login,
+        user_name: stream.user_name,
+        started_at: stream.started_at,
+        viewer_count: stream.viewer_count,
+    }
+}
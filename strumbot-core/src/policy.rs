@@ -0,0 +1,46 @@
+use discord_api::config::{DiscordConfig, EventName, QuietMode};
+
+/// How a notification is delivered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// Send the notification with the role mention
+    Normal,
+    /// Send the notification without the role mention
+    Silent,
+    /// Don't send the notification
+    Skip,
+}
+
+/// Decides how the notification of an event at this unix timestamp is delivered, according to the quiet hours.
+///
+/// The VOD event is always delivered normally, since it only summarizes the stream.
+pub fn delivery(config: &DiscordConfig, event: EventName, timestamp: i64) -> Delivery {
+    match config.quiet_hours {
+        Some(ref quiet) if event != EventName::Vod && quiet.is_quiet(timestamp) => match quiet.mode {
+            QuietMode::Silent => Delivery::Silent,
+            QuietMode::Skip => Delivery::Skip,
+        },
+        _ => Delivery::Normal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delivery() {
+        let mut config = DiscordConfig {
+            quiet_hours: serde_json::from_str(r#"{"ranges": ["22:00-08:00"]}"#).unwrap(),
+            ..DiscordConfig::default()
+        };
+
+        // 2023-10-09T23:00:00Z and 12:00:00Z
+        assert_eq!(delivery(&config, EventName::Live, 1_696_892_400), Delivery::Silent);
+        assert_eq!(delivery(&config, EventName::Vod, 1_696_892_400), Delivery::Normal);
+        assert_eq!(delivery(&config, EventName::Update, 1_696_852_800), Delivery::Normal);
+
+        config.quiet_hours = None;
+        assert_eq!(delivery(&config, EventName::Live, 1_696_892_400), Delivery::Normal);
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use database_api::{Database, DatabaseError};
+use serde::{Deserialize, Serialize};
+use tracing as log;
+
+use crate::Cache;
+
+/// Twitch logins cannot start with an underscore, so this does not collide with watcher keys
+const KEY: &str = "_activity";
+/// Number of go-live timestamps to remember per streamer
+const MAX_STARTS: usize = 50;
+/// Streamers with fewer known streams are always polled at the regular interval
+const MIN_STARTS: usize = 5;
+const HOURS_PER_WEEK: i64 = 7 * 24;
+
+const fn hour_of_week(timestamp: i64) -> i64 {
+    (timestamp / 3600).rem_euclid(HOURS_PER_WEEK)
+}
+
+/// The go-live history of the tracked streamers, used to learn their usual start times.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Activity {
+    /// Map of user id -> unix timestamps of the most recent stream starts
+    #[serde(default)]
+    starts: HashMap<String, Vec<i64>>,
+}
+
+impl Activity {
+    pub async fn load(db: &Cache, persist: bool) -> Self {
+        if !persist {
+            return Self::default();
+        }
+
+        match db.read(KEY).await {
+            Ok(activity) => activity,
+            Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                log::error!("Failed to load stream activity: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Adds a stream start, returns false if it was already known.
+    pub fn record(&mut self, user_id: &str, started_at: i64) -> bool {
+        let starts = self.starts.entry(user_id.to_owned()).or_default();
+        if starts.contains(&started_at) {
+            return false;
+        }
+
+        starts.push(started_at);
+        if starts.len() > MAX_STARTS {
+            starts.remove(0);
+        }
+        true
+    }
+
+    /// Whether the streamer usually goes live within an hour of this time of the week.
+    ///
+    /// Without enough history, this is always true.
+    pub fn is_likely_live(&self, user_id: &str, now: i64) -> bool {
+        let Some(starts) = self.starts.get(user_id) else {
+            return true;
+        };
+
+        if starts.len() < MIN_STARTS {
+            return true;
+        }
+
+        let hour = hour_of_week(now);
+        starts.iter().any(|&start| {
+            let diff = (hour_of_week(start) - hour).rem_euclid(HOURS_PER_WEEK);
+            diff <= 1 || diff == HOURS_PER_WEEK - 1
+        })
+    }
+}
+
+/// Decides which streamers are polled, based on their usual start times.
+pub struct PollScheduler {
+    activity: Activity,
+    /// The poll interval outside of the usual start times, or `None` to always poll everyone
+    idle_interval: Option<Duration>,
+    next_poll: HashMap<Box<str>, Instant>,
+}
+
+impl PollScheduler {
+    pub fn new(activity: Activity, idle_interval: Option<Duration>) -> Self {
+        Self {
+            activity,
+            idle_interval,
+            next_poll: HashMap::new(),
+        }
+    }
+
+    /// The user ids to poll now. Streamers which are live are always included.
+    pub fn due(&mut self, user_ids: &[Box<str>], is_live: impl Fn(&str) -> bool) -> Vec<Box<str>> {
+        let Some(idle_interval) = self.idle_interval else {
+            return user_ids.to_vec();
+        };
+
+        let now = Instant::now();
+        let timestamp = eos::DateTime::utc_now().timestamp().as_seconds();
+        self.next_poll.retain(|id, _| user_ids.contains(id));
+
+        let mut due = Vec::with_capacity(user_ids.len());
+        for id in user_ids {
+            if is_live(id) {
+                due.push(id.clone());
+                continue;
+            }
+
+            if self.next_poll.get(id).map_or(true, |next| *next <= now) {
+                let delay = if self.activity.is_likely_live(id, timestamp) {
+                    Duration::ZERO
+                } else {
+                    idle_interval
+                };
+                self.next_poll.insert(id.clone(), now + delay);
+                due.push(id.clone());
+            }
+        }
+
+        due
+    }
+
+    /// Remembers the start of a stream to improve the schedule.
+    pub async fn record(&mut self, user_id: &str, started_at: i64, db: &Arc<Cache>, persist: bool) {
+        if !self.activity.record(user_id, started_at) || !persist {
+            return;
+        }
+
+        if let Err(e) = db.save(KEY, &self.activity).await {
+            log::error!("Failed to save stream activity: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_likely_live() {
+        let mut activity = Activity::default();
+        // Monday 18:00 UTC for five weeks in a row
+        let start = 1_696_874_400;
+        for week in 0..5 {
+            assert!(activity.is_likely_live("1", start));
+            assert!(activity.record("1", start + week * 7 * 86400));
+        }
+        assert!(!activity.record("1", start));
+
+        assert!(activity.is_likely_live("1", start + 30 * 60));
+        assert!(activity.is_likely_live("1", start - 30 * 60));
+        assert!(!activity.is_likely_live("1", start + 6 * 3600));
+        assert!(!activity.is_likely_live("1", start + 86400));
+        // Unknown streamers are always polled
+        assert!(activity.is_likely_live("2", start + 86400));
+    }
+
+    #[test]
+    fn test_max_starts() {
+        let mut activity = Activity::default();
+        for i in 0..60 {
+            activity.record("1", i * 3600);
+        }
+        assert_eq!(activity.starts["1"].len(), MAX_STARTS);
+        assert_eq!(activity.starts["1"][0], 10 * 3600);
+    }
+}
@@ -0,0 +1,72 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use eos::DateTime;
+use tokio::time::sleep;
+use tracing as log;
+use twitch_api::{
+    error::EventSubError,
+    eventsub::{EventSub, Notification, EVENTSUB_URL},
+    RaidEvent, TwitchClient,
+};
+
+use crate::status::{Raid, StatusBoard};
+
+/// Time to wait before starting a new session, after the websocket failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Listens for the raids of the tracked streamers, which are added to the VOD notification by their watchers.
+///
+/// Twitch only accepts a few subscriptions for channels which did not authorize the bot, so the raids of large
+/// teams are not detected for every streamer.
+pub async fn listen(client: Arc<TwitchClient>, status: Arc<StatusBoard>) {
+    // The tracked streamers are only known after the first poll
+    sleep(Duration::from_secs(30)).await;
+
+    loop {
+        if let Err(e) = session(&client, &status).await {
+            log::error!("EventSub session for raids failed: {e}");
+        }
+        sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn session(client: &TwitchClient, status: &StatusBoard) -> Result<(), EventSubError> {
+    let mut eventsub = EventSub::connect(EVENTSUB_URL).await?;
+    log::info!("Connected to EventSub, listening for raids");
+
+    let mut subscribed = HashSet::new();
+    loop {
+        // Streamers which are tracked later are subscribed after the next keepalive
+        for user_id in status.tracked() {
+            if subscribed.contains(&user_id) {
+                continue;
+            }
+            if let Err(e) = client.subscribe_raids(eventsub.session_id(), &user_id).await {
+                log::warn!("Failed to subscribe to the raids of {user_id}: {e}");
+            }
+            subscribed.insert(user_id);
+        }
+
+        match eventsub.next().await? {
+            Notification::Raid(event) => on_raid(status, event),
+            Notification::Keepalive => {}
+        }
+    }
+}
+
+fn on_raid(status: &StatusBoard, event: RaidEvent) {
+    log::info!(
+        "[{}] Raided {} with {} viewers",
+        event.from_broadcaster_user_login,
+        event.to_broadcaster_user_login,
+        event.viewers
+    );
+
+    let raid = Raid {
+        login: event.to_broadcaster_user_login,
+        name: event.to_broadcaster_user_name,
+        viewers: event.viewers,
+        at: DateTime::utc_now().timestamp().as_seconds(),
+    };
+    status.set_raid(&event.from_broadcaster_user_login, raid);
+}
@@ -0,0 +1,64 @@
+use std::{sync::Arc, time::Duration};
+
+use discord_api::{SentMessage, WebhookMessage};
+use tokio::time::sleep;
+use tracing as log;
+use twitch_api::{TwitchClient, Video, VideoType};
+
+use crate::{embeds, notifier::Notifier};
+
+/// A VOD notification which was sent without its video, since twitch did not list the archive yet.
+pub struct VodRecheck {
+    pub user_name: Box<str>,
+    pub user_id: Box<str>,
+    /// Unix timestamp of the stream start, the archive is created after it
+    pub started_at: i64,
+    pub sent: SentMessage,
+    /// The first message of the notification, which contains the VOD embed
+    pub message: WebhookMessage,
+    /// The delays after the stream ended, in ascending order
+    pub delays: Vec<Duration>,
+}
+
+impl VodRecheck {
+    /// Looks for the archive after each delay, and edits the notification once it is found.
+    pub async fn run(mut self, client: Arc<TwitchClient>, webhook: Arc<dyn Notifier>) {
+        let name = &self.user_name;
+        let mut waited = Duration::ZERO;
+        for delay in &self.delays {
+            sleep(delay.saturating_sub(waited)).await;
+            waited = waited.max(*delay);
+
+            let Some(video) = self.find_archive(&client).await else {
+                log::debug!("[{name}] VOD is still missing after {} seconds", waited.as_secs());
+                continue;
+            };
+
+            log::info!("[{name}] Found VOD {} after {} seconds", video.id, waited.as_secs());
+            if let Some(embed) = self.message.embeds.first_mut() {
+                embeds::link_video(embed, &video);
+            }
+            if let Err(e) = webhook.edit(self.sent, self.message).await {
+                log::error!("[{name}] Failed to edit VOD notification: {e}");
+            }
+            return;
+        }
+
+        log::info!(
+            "[{name}] No VOD found within {} seconds after the stream",
+            waited.as_secs()
+        );
+    }
+
+    async fn find_archive(&self, client: &TwitchClient) -> Option<Video> {
+        match client.get_user_videos(&self.user_id, VideoType::Archive).await {
+            Ok(videos) => videos
+                .into_iter()
+                .find(|video| video.created_at.timestamp().as_seconds() >= self.started_at),
+            Err(e) => {
+                log::error!("[{}] Failed to fetch videos: {}", self.user_name, e);
+                None
+            }
+        }
+    }
+}
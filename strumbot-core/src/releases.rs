@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use discord_api::{WebhookClient, WebhookMessage};
+use reqwest::Client as HttpClient;
+use serde::Deserialize;
+use tokio::time::sleep;
+use tracing as log;
+use twilight_util::builder::embed::EmbedBuilder;
+
+use crate::config::UpdateCheckConfig;
+
+/// The version of this build, which is shared by all crates of the workspace
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The latest release of the repository, as returned by the github releases api.
+#[derive(Deserialize)]
+struct Release {
+    tag_name: Box<str>,
+    html_url: Box<str>,
+    #[serde(default)]
+    name: Option<Box<str>>,
+}
+
+/// Parses a version like `1.2.5` or `v1.2.5`, ignoring the pre-release and build suffix.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(str::parse);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), patch) => Some((major, minor, patch.and_then(Result::ok).unwrap_or(0))),
+        _ => None,
+    }
+}
+
+/// Whether the released version is newer than the current one.
+fn is_newer(release: &str, current: &str) -> bool {
+    match (parse_version(release), parse_version(current)) {
+        (Some(release), Some(current)) => release > current,
+        _ => false,
+    }
+}
+
+async fn fetch_latest(http: &HttpClient, repository: &str) -> anyhow::Result<Release> {
+    let url = format!("https://api.github.com/repos/{repository}/releases/latest");
+    let response = http
+        .get(url)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.json().await?)
+}
+
+/// Checks the github releases periodically, and posts a notice to the logging webhook once for each newer version.
+///
+/// Without a logging webhook, the notice is only logged.
+pub async fn check_updates(config: UpdateCheckConfig, http: HttpClient, alerts: Option<WebhookClient>) {
+    let interval = Duration::from_secs(config.interval.max(3600));
+    let mut notified: Option<Box<str>> = None;
+
+    loop {
+        match fetch_latest(&http, &config.repository).await {
+            Ok(release) if is_newer(&release.tag_name, VERSION) && notified.as_ref() != Some(&release.tag_name) => {
+                log::info!(
+                    "A new version of strumbot is available: {} (current: {VERSION}) {}",
+                    release.tag_name,
+                    release.html_url
+                );
+                if let Some(ref webhook) = alerts {
+                    notify(webhook, &release).await;
+                }
+                notified = Some(release.tag_name);
+            }
+            Ok(_) => log::debug!("Strumbot {VERSION} is up to date"),
+            Err(e) => log::warn!("Failed to check for a new strumbot version: {e}"),
+        }
+
+        sleep(interval).await;
+    }
+}
+
+async fn notify(webhook: &WebhookClient, release: &Release) {
+    let title = release.name.as_deref().unwrap_or(&release.tag_name);
+    let message = WebhookMessage {
+        embeds: vec![EmbedBuilder::new()
+            .color(0x2EA043)
+            .title(format!("Strumbot {title} is available"))
+            .url(release.html_url.as_ref())
+            .description(format!(
+                "This bot is running version {VERSION}. The changelog is linked above."
+            ))
+            .build()],
+        ..WebhookMessage::default()
+    };
+
+    if let Err(e) = webhook.execute(&message).await {
+        log::error!("Failed to post the update notice: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert_eq!(parse_version("v1.2.5"), Some((1, 2, 5)));
+        assert_eq!(parse_version("1.3.0-beta.1"), Some((1, 3, 0)));
+        assert_eq!(parse_version("latest"), None);
+
+        assert!(is_newer("v1.3.0", "1.2.5"));
+        assert!(is_newer("v1.2.10", "1.2.9"));
+        assert!(!is_newer("v1.2.5", "1.2.5"));
+        assert!(!is_newer("v1.1.0", "1.2.5"));
+        assert!(!is_newer("nightly", "1.2.5"));
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use eos::DateTime;
+use serde::Deserialize;
+use tokio::time::sleep;
+use tracing as log;
+use twitch_api::{Game, Stream, TwitchClient};
+
+use crate::{
+    config::Config,
+    errors::{BotError, InitError},
+    notifier::Notifier,
+    watcher::{StreamUpdate, StreamWatcher, WatcherState},
+};
+
+/// A recording of the twitch streams, which is played back through the stream watchers instead of polling twitch.
+///
+/// This is used to check the notifications and the segment logic against real streams.
+#[derive(Deserialize)]
+pub struct Replay {
+    /// Games of the recorded streams, so they are resolved without a request
+    #[serde(default)]
+    games: Vec<Game>,
+    snapshots: Vec<Snapshot>,
+}
+
+/// The live streams at one point of the recording, in the format of the helix streams endpoint.
+#[derive(Deserialize)]
+struct Snapshot {
+    at: DateTime,
+    #[serde(alias = "data")]
+    streams: Vec<Stream>,
+}
+
+impl Replay {
+    pub async fn load(path: &Path) -> Result<Self, BotError> {
+        let file = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| InitError::Read(path.to_owned(), e))?;
+        Ok(serde_json::from_str(&file).map_err(|e| InitError::Parse(path.to_owned(), e))?)
+    }
+
+    /// Plays the snapshots in order, with the time between them divided by `speed`.
+    ///
+    /// A stream is offline once it is missing from a snapshot, and the offline grace period is applied in real time.
+    pub async fn run(self, client: &TwitchClient, webhook: &dyn Notifier, config: &Arc<Config>, speed: u32) {
+        let Self { games, mut snapshots } = self;
+        for game in games {
+            client.insert_game(game);
+        }
+
+        snapshots.sort_by_key(|snapshot| snapshot.at.timestamp().as_seconds());
+        log::info!("Replaying {} snapshots at {speed}x speed", snapshots.len());
+
+        let mut watchers: HashMap<Box<str>, StreamWatcher> = HashMap::new();
+        let mut previous = None;
+        for Snapshot { at, streams } in snapshots {
+            let at = at.timestamp().as_seconds();
+            if let Some(previous) = previous {
+                let gap = u64::try_from(at - previous).unwrap_or_default();
+                sleep(Duration::from_secs(gap) / speed.max(1)).await;
+            }
+            previous = Some(at);
+
+            let mut offline: HashSet<Box<str>> = watchers.keys().cloned().collect();
+            for stream in streams {
+                let login: Box<str> = stream.user_login.to_lowercase().into();
+                offline.remove(&login);
+                let watcher = watchers
+                    .entry(login.clone())
+                    .or_insert_with(|| StreamWatcher::new(login.to_string(), Arc::clone(config)));
+                if let Err(e) = watcher
+                    .update(client, webhook, StreamUpdate::Live(Box::new(stream)))
+                    .await
+                {
+                    log::error!("[{login}] Failed to replay snapshot: {e}");
+                }
+            }
+
+            for login in offline {
+                let Some(watcher) = watchers.get_mut(&login) else {
+                    continue;
+                };
+
+                match watcher.update(client, webhook, StreamUpdate::Offline).await {
+                    Ok(WatcherState::Ended) => {
+                        watchers.remove(&login);
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::error!("[{login}] Failed to replay snapshot: {e}"),
+                }
+            }
+        }
+
+        log::info!("Replay finished, {} streams are still live", watchers.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_replay() {
+        let replay: Replay = serde_json::from_str(
+            r#"{
+                "games": [{"id": "1", "name": "Celeste"}],
+                "snapshots": [
+                    {"at": "2023-01-01T00:10:00Z", "data": []},
+                    {"at": "2023-01-01T00:00:00Z", "streams": [{
+                        "id": "40",
+                        "game_id": "1",
+                        "title": "Testing",
+                        "type": "live",
+                        "language": "en",
+                        "thumbnail_url": "",
+                        "user_id": "50",
+                        "user_login": "elajjaz",
+                        "user_name": "Elajjaz",
+                        "started_at": "2023-01-01T00:00:00Z"
+                    }]}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(replay.games.len(), 1);
+        assert_eq!(replay.snapshots.len(), 2);
+        assert!(replay.snapshots[0].streams.is_empty());
+        assert_eq!(replay.snapshots[1].streams[0].user_login.as_ref(), "elajjaz");
+    }
+}
@@ -0,0 +1,59 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use database_api::Database;
+use discord_api::{SendError, SentMessage, WebhookMessage};
+use tracing as log;
+use twilight_model::id::Id;
+
+use crate::{config::ReplicationConfig, notifier::Notifier, Cache};
+
+static REPLICA_ID: OnceLock<Box<str>> = OnceLock::new();
+
+/// The name of this instance in the claims, which is generated once if it is not configured.
+fn replica_id(config: &ReplicationConfig) -> &str {
+    match config.id {
+        Some(ref id) => id,
+        None => REPLICA_ID.get_or_init(|| {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.subsec_nanos());
+            format!("strumbot-{}-{nanos:08x}", std::process::id()).into()
+        }),
+    }
+}
+
+/// Whether this instance announces the stream, when several instances share the database.
+///
+/// The claim is renewed with every update of the watcher, so a standby only takes over once the lease of the
+/// announcing instance expired. If the claim fails, the stream is announced anyway, a duplicate is better than nothing.
+pub async fn is_announcer(db: &Cache, config: &ReplicationConfig, key: &str) -> bool {
+    if !config.enabled {
+        return true;
+    }
+
+    let lease = Duration::from_secs(config.lease.max(1));
+    match db.claim(&format!("claim-{key}"), replica_id(config), lease).await {
+        Ok(claimed) => claimed,
+        Err(e) => {
+            log::warn!("[{key}] Failed to claim the stream, announcing it anyway: {e}");
+            true
+        }
+    }
+}
+
+/// Discards the notifications of a standby, which still follows the streams to take over at any time.
+pub struct Standby;
+
+#[async_trait]
+impl Notifier for Standby {
+    async fn send(&self, _message: WebhookMessage) -> Result<SentMessage, SendError> {
+        Ok(SentMessage {
+            channel_id: Id::new(1),
+            message_id: Id::new(1),
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use database_api::{Database, DatabaseError};
+use serde::{Deserialize, Serialize};
+use tracing as log;
+
+use crate::Cache;
+
+/// Twitch logins cannot start with an underscore, so this does not collide with watcher keys
+const KEY: &str = "_roles";
+
+/// A notification role from the last start, which is found by its id even after it was renamed in discord.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedRole {
+    pub id: Box<str>,
+    /// The configured role name, a different name in the config means the role was replaced
+    pub name: Box<str>,
+}
+
+/// The notification roles by event, such as `live`.
+pub type SavedRoles = HashMap<String, SavedRole>;
+
+pub async fn load(db: &Cache) -> SavedRoles {
+    match db.read(KEY).await {
+        Ok(roles) => roles,
+        Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+        Err(e) => {
+            log::error!("Failed to load notification roles: {e}");
+            HashMap::new()
+        }
+    }
+}
+
+pub async fn save(db: &Cache, roles: &SavedRoles) {
+    if let Err(e) = db.save(KEY, roles).await {
+        log::error!("Failed to save notification roles: {e}");
+    }
+}
@@ -0,0 +1,166 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use commons::time::TimeFormat;
+use database_api::{Database, DatabaseError};
+use eos::DateTime;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing as log;
+use twilight_http::Client;
+use twilight_model::{
+    channel::message::Embed,
+    id::{
+        marker::{ChannelMarker, MessageMarker},
+        Id,
+    },
+};
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+use twitch_api::TwitchClient;
+
+use crate::{config::ScheduleConfig, embeds, status::StatusBoard, Cache};
+
+/// Twitch logins cannot start with an underscore, so this does not collide with watcher keys
+const KEY: &str = "_schedule";
+const WEEK: i64 = 7 * 86400;
+/// Discord allows at most 25 fields per embed
+const MAX_FIELDS: usize = 25;
+/// Keep some room below the 1024 characters limit of a field value
+const MAX_FIELD_LENGTH: usize = 1000;
+
+/// The message which shows the schedule in the configured channel.
+#[derive(Serialize, Deserialize, Debug)]
+struct ScheduleMessage {
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+}
+
+/// Builds an embed with the scheduled streams of the next 7 days.
+pub async fn build_embed(client: &TwitchClient, user_ids: &[Box<str>], format: TimeFormat) -> anyhow::Result<Embed> {
+    let now = DateTime::utc_now().timestamp().as_seconds();
+    let mut fields = Vec::new();
+
+    for user_id in user_ids {
+        let Some(schedule) = client.get_schedule(user_id).await? else {
+            continue;
+        };
+
+        let mut lines = String::new();
+        for segment in schedule.segments.unwrap_or_default() {
+            let start = segment.start_time.timestamp().as_seconds();
+            if segment.canceled_until.is_some() || start < now || start > now + WEEK {
+                continue;
+            }
+
+            let mut line = format!("{} **{}**", format.datetime(start), segment.title);
+            if let Some(category) = segment.category {
+                line.push_str(&format!(" ({})", category.name));
+            }
+
+            if lines.len() + line.len() + 1 > MAX_FIELD_LENGTH {
+                break;
+            }
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+
+        if !lines.is_empty() {
+            fields.push(EmbedFieldBuilder::new(schedule.broadcaster_name.to_string(), lines));
+        }
+    }
+
+    let mut embed = EmbedBuilder::new().color(embeds::TWITCH_COLOR).title("Stream Schedule");
+    if fields.is_empty() {
+        embed = embed.description("There are no scheduled streams in the next 7 days.");
+    }
+    for field in fields.into_iter().take(MAX_FIELDS) {
+        embed = embed.field(field);
+    }
+
+    Ok(embed.build())
+}
+
+/// Keeps the schedule message in the configured channel up to date.
+///
+/// The message is edited in place, or sent again if it was deleted.
+pub async fn maintain(
+    config: ScheduleConfig,
+    http: Arc<Client>,
+    client: Arc<TwitchClient>,
+    status: Arc<StatusBoard>,
+    db: Arc<Cache>,
+    persist: bool,
+    format: TimeFormat,
+) {
+    let channel_id = match Id::<ChannelMarker>::from_str(&config.channel_id) {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("Invalid schedule channel id {:?}: {e}", config.channel_id);
+            return;
+        }
+    };
+
+    let mut message = if persist { load(&db).await } else { None };
+    // The tracked streamers are only known after the first poll
+    sleep(Duration::from_secs(30)).await;
+
+    loop {
+        let embed = build_embed(&client, &status.tracked(), format).await;
+        match embed {
+            Ok(embed) => match update(&http, channel_id, message.as_ref(), embed).await {
+                Ok(sent) => {
+                    if persist && message.as_ref().map(|m| m.message_id) != Some(sent.message_id) {
+                        if let Err(e) = db.save(KEY, &sent).await {
+                            log::error!("Failed to save schedule message: {e}");
+                        }
+                    }
+                    message = Some(sent);
+                }
+                Err(e) => log::error!("Failed to update schedule message: {e}"),
+            },
+            Err(e) => log::error!("Failed to fetch stream schedules: {e}"),
+        }
+
+        sleep(Duration::from_secs(config.interval)).await;
+    }
+}
+
+async fn load(db: &Cache) -> Option<ScheduleMessage> {
+    match db.read(KEY).await {
+        Ok(message) => Some(message),
+        Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            log::error!("Failed to load schedule message: {e}");
+            None
+        }
+    }
+}
+
+async fn update(
+    http: &Client,
+    channel_id: Id<ChannelMarker>,
+    existing: Option<&ScheduleMessage>,
+    embed: Embed,
+) -> anyhow::Result<ScheduleMessage> {
+    let embeds = [embed];
+    if let Some(existing) = existing.filter(|m| m.channel_id == channel_id) {
+        match http
+            .update_message(channel_id, existing.message_id)
+            .embeds(Some(&embeds))?
+            .await
+        {
+            Ok(_) => {
+                return Ok(ScheduleMessage {
+                    channel_id,
+                    message_id: existing.message_id,
+                })
+            }
+            Err(e) => log::warn!("Failed to edit schedule message, sending a new one: {e}"),
+        }
+    }
+
+    let message = http.create_message(channel_id).embeds(&embeds)?.await?.model().await?;
+    Ok(ScheduleMessage {
+        channel_id,
+        message_id: message.id,
+    })
+}
@@ -0,0 +1,96 @@
+use std::{sync::Arc, time::Duration};
+
+use discord_api::config::EventName;
+use eos::DateTime;
+use twitch_api::{Game, Stream, StreamType, TwitchClient};
+
+use crate::{
+    config::Config,
+    errors::BotError,
+    notifier::Notifier,
+    watcher::{StreamUpdate, StreamWatcher},
+};
+
+/// The games of the fabricated stream, the second one is used for the update event.
+const GAMES: [(&str, &str); 2] = [
+    ("509658", "Just Chatting"),
+    ("1469308723", "Software and Game Development"),
+];
+
+/// Sends the notification of one event for a fabricated stream, to preview the templates and embeds.
+///
+/// The stream goes through a stream watcher, so the notification is rendered like for a real stream.
+/// Only the simulated event is enabled, the events leading up to it are not sent.
+pub async fn simulate(
+    client: &TwitchClient,
+    webhook: &dyn Notifier,
+    mut config: Config,
+    login: &str,
+    event: EventName,
+) -> Result<(), BotError> {
+    config.discord.enabled_events = vec![event].into();
+    config.discord.digest = None;
+    config.discord.quiet_hours = None;
+    config.twitch.offline_grace_period.0 = Duration::ZERO;
+    config.twitch.offline_grace_periods.clear();
+    config.twitch.min_update_interval.0 = Duration::ZERO;
+
+    for (id, name) in GAMES {
+        client.insert_game(Game {
+            id: id.into(),
+            name: name.into(),
+            box_art_url: format!("https://static-cdn.jtvnw.net/ttv-boxart/{id}-{{width}}x{{height}}.jpg").into(),
+        });
+    }
+
+    // The real user is used if it exists, so the links and the display name are correct
+    let users = client.get_users_by_login(&[login.into()]).await?;
+    let (user_id, user_name) = match users.into_iter().next() {
+        Some(user) => (user.id, user.display_name),
+        None => ("0".into(), login.into()),
+    };
+
+    let stream = |game_id: &str| {
+        Box::new(Stream {
+            id: "0".into(),
+            game_id: game_id.into(),
+            title: "Simulated stream".into(),
+            kind: StreamType::Live,
+            language: "en".into(),
+            thumbnail_url: format!(
+                "https://static-cdn.jtvnw.net/previews-ttv/live_user_{login}-{{width}}x{{height}}.jpg"
+            )
+            .into(),
+            user_id: user_id.clone(),
+            user_login: login.into(),
+            user_name: user_name.clone(),
+            started_at: DateTime::utc_now(),
+            viewer_count: 0,
+        })
+    };
+
+    let mut watcher = StreamWatcher::new(login.to_lowercase(), Arc::new(config));
+    watcher
+        .update(client, webhook, StreamUpdate::Live(stream(GAMES[0].0)))
+        .await?;
+    match event {
+        EventName::Live
+        | EventName::Category
+        | EventName::Upcoming
+        | EventName::HypeTrain
+        | EventName::Goal
+        | EventName::Video
+        | EventName::Milestone => {}
+        EventName::Update => {
+            watcher
+                .update(client, webhook, StreamUpdate::Live(stream(GAMES[1].0)))
+                .await?;
+        }
+        EventName::Vod => {
+            // The first offline update only starts the grace period
+            watcher.update(client, webhook, StreamUpdate::Offline).await?;
+            watcher.update(client, webhook, StreamUpdate::Offline).await?;
+        }
+    }
+    Ok(())
+}
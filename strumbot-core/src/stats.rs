@@ -0,0 +1,313 @@
+use std::borrow::Cow;
+
+use database_api::{Database, DatabaseError};
+use serde::{Deserialize, Serialize};
+use tracing as log;
+use twilight_model::channel::message::Embed;
+use twilight_util::builder::embed::{EmbedAuthorBuilder, EmbedBuilder, EmbedFieldBuilder};
+
+use crate::{embeds, Cache};
+
+/// Number of sessions to keep per streamer
+const MAX_SESSIONS: usize = 100;
+
+/// Twitch logins cannot start with an underscore, so this does not collide with watcher keys
+fn key(user_id: &str) -> String {
+    format!("_stats-{user_id}")
+}
+
+/// Formats seconds as a short duration, like `2h14m`.
+pub fn format_duration(secs: u32) -> String {
+    let hours = secs / 3600;
+    let mins = (secs / 60) % 60;
+    if hours > 0 {
+        format!("{hours}h{mins:02}m")
+    } else {
+        format!("{mins}m")
+    }
+}
+
+/// How long a game was played during a stream.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GameTime {
+    pub name: Box<str>,
+    pub seconds: u32,
+}
+
+impl GameTime {
+    /// Sums up the play time of each game, from the start timestamps of the segments until the end of the stream.
+    ///
+    /// Games which were played in multiple segments are merged, in order of their first appearance.
+    pub fn from_segments(segments: &[(Box<str>, i64)], ended_at: i64) -> Vec<GameTime> {
+        let mut games: Vec<GameTime> = Vec::new();
+        for (i, (name, start)) in segments.iter().enumerate() {
+            let end = segments.get(i + 1).map_or(ended_at, |(_, next)| *next);
+            let seconds = (end - start).max(0) as u32;
+            match games.iter_mut().find(|game| &game.name == name) {
+                Some(game) => game.seconds += seconds,
+                None => games.push(GameTime {
+                    name: name.clone(),
+                    seconds,
+                }),
+            }
+        }
+        games
+    }
+}
+
+/// Viewer counts sampled during a stream.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct ViewerStats {
+    peak: u32,
+    total: u64,
+    samples: u32,
+}
+
+impl ViewerStats {
+    pub fn record(&mut self, viewers: u32) {
+        self.peak = self.peak.max(viewers);
+        self.total += u64::from(viewers);
+        self.samples += 1;
+    }
+
+    pub const fn peak(&self) -> u32 {
+        self.peak
+    }
+
+    pub fn average(&self) -> u32 {
+        if self.samples == 0 {
+            0
+        } else {
+            (self.total / u64::from(self.samples)) as u32
+        }
+    }
+}
+
+/// Summary of a finished stream.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StreamSession {
+    pub stream_id: Box<str>,
+    pub user_name: Box<str>,
+    /// Unix timestamp of the stream start
+    pub started_at: i64,
+    /// Unix timestamp of the last time the stream was seen live
+    pub ended_at: i64,
+    pub peak_viewers: u32,
+    pub average_viewers: u32,
+    pub games: Vec<GameTime>,
+    /// Number of clips created during the stream
+    pub clips: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vod_url: Option<Box<str>>,
+}
+
+impl StreamSession {
+    pub fn duration(&self) -> u32 {
+        (self.ended_at - self.started_at).max(0) as u32
+    }
+}
+
+/// Loads the recorded sessions of a streamer, oldest first.
+pub async fn load(db: &Cache, user_id: &str) -> Result<Vec<StreamSession>, DatabaseError> {
+    match db.read(&key(user_id)).await {
+        Ok(sessions) => Ok(sessions),
+        Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Appends the session to the history of the streamer, dropping the oldest sessions above the limit.
+pub async fn record(db: &Cache, user_id: &str, session: StreamSession) {
+    let user_name = session.user_name.clone();
+    let result = db
+        .update(&key(user_id), |sessions: Option<Vec<StreamSession>>| {
+            let mut sessions = sessions.unwrap_or_default();
+            sessions.push(session);
+            if sessions.len() > MAX_SESSIONS {
+                sessions.drain(..sessions.len() - MAX_SESSIONS);
+            }
+            sessions
+        })
+        .await;
+
+    if let Err(e) = result {
+        log::error!("[{user_name}] Failed to save stream stats: {e}");
+    }
+}
+
+/// Quotes a CSV value if necessary, according to RFC 4180.
+fn csv_escape(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Renders the sessions as CSV, with one row per stream and the games joined into a single column.
+pub fn export_csv(sessions: &[StreamSession]) -> String {
+    let mut csv = String::from(
+        "stream_id,user_name,started_at,ended_at,duration,peak_viewers,average_viewers,clips,games,vod_url\n",
+    );
+    for s in sessions {
+        let games = s
+            .games
+            .iter()
+            .map(|g| format!("{} ({})", g.name, format_duration(g.seconds)))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let row = [
+            csv_escape(&s.stream_id),
+            csv_escape(&s.user_name),
+            Cow::Owned(s.started_at.to_string()),
+            Cow::Owned(s.ended_at.to_string()),
+            Cow::Owned(s.duration().to_string()),
+            Cow::Owned(s.peak_viewers.to_string()),
+            Cow::Owned(s.average_viewers.to_string()),
+            Cow::Owned(s.clips.to_string()),
+            csv_escape(&games),
+            csv_escape(s.vod_url.as_deref().unwrap_or_default()),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Renders the summary of the most recent sessions.
+pub fn summary_embed(user_name: &str, sessions: &[StreamSession]) -> Embed {
+    let count = sessions.len() as u32;
+    let total_duration: u32 = sessions.iter().map(StreamSession::duration).sum();
+    let peak = sessions.iter().map(|s| s.peak_viewers).max().unwrap_or(0);
+    let average = sessions.iter().map(|s| s.average_viewers).sum::<u32>() / count.max(1);
+
+    let mut games: Vec<GameTime> = Vec::new();
+    for game in sessions.iter().flat_map(|s| &s.games) {
+        match games.iter_mut().find(|g| g.name == game.name) {
+            Some(g) => g.seconds += game.seconds,
+            None => games.push(game.clone()),
+        }
+    }
+    games.sort_by(|a, b| b.seconds.cmp(&a.seconds));
+
+    let top_games: String = games
+        .iter()
+        .take(5)
+        .map(|g| format!("{} \u{2014} {}\n", g.name, format_duration(g.seconds)))
+        .collect();
+
+    let mut recent = String::new();
+    for s in sessions.iter().rev() {
+        let line = format!(
+            "<t:{}:d> {} \u{2022} peak {} \u{2022} avg {} \u{2022} {} clips\n",
+            s.started_at,
+            format_duration(s.duration()),
+            s.peak_viewers,
+            s.average_viewers,
+            s.clips
+        );
+        // Stay below the limit of 1024 characters per field
+        if recent.len() + line.len() > 1000 {
+            break;
+        }
+        recent.push_str(&line);
+    }
+
+    let mut embed = EmbedBuilder::new()
+        .color(embeds::TWITCH_COLOR)
+        .author(EmbedAuthorBuilder::new(user_name.to_owned()))
+        .title(format!("Stats of the last {count} streams"))
+        .field(EmbedFieldBuilder::new("Streamed", format_duration(total_duration)).inline())
+        .field(EmbedFieldBuilder::new("Average Duration", format_duration(total_duration / count.max(1))).inline())
+        .field(EmbedFieldBuilder::new("Peak Viewers", peak.to_string()).inline())
+        .field(EmbedFieldBuilder::new("Average Viewers", average.to_string()).inline());
+
+    if !top_games.is_empty() {
+        embed = embed.field(EmbedFieldBuilder::new("Top Games", top_games));
+    }
+
+    if !recent.is_empty() {
+        embed = embed.field(EmbedFieldBuilder::new("Recent Streams", recent));
+    }
+
+    embed.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_times() {
+        let segments = vec![
+            ("Elden Ring".into(), 1000),
+            ("Just Chatting".into(), 4600),
+            ("Elden Ring".into(), 5200),
+        ];
+        let games = GameTime::from_segments(&segments, 6000);
+        assert_eq!(
+            games,
+            vec![
+                GameTime {
+                    name: "Elden Ring".into(),
+                    seconds: 4400
+                },
+                GameTime {
+                    name: "Just Chatting".into(),
+                    seconds: 600
+                },
+            ]
+        );
+        assert!(GameTime::from_segments(&[], 6000).is_empty());
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let session = StreamSession {
+            stream_id: "40457151671".into(),
+            user_name: "Elajjaz".into(),
+            started_at: 1000,
+            ended_at: 9040,
+            peak_viewers: 500,
+            average_viewers: 300,
+            games: vec![
+                GameTime {
+                    name: "Warhammer 40,000: Darktide".into(),
+                    seconds: 8040,
+                },
+                GameTime {
+                    name: "Just \"Chatting\"".into(),
+                    seconds: 60,
+                },
+            ],
+            clips: 3,
+            vod_url: None,
+        };
+
+        let csv = export_csv(&[session]);
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().starts_with("stream_id,user_name,"));
+        assert_eq!(
+            lines.next().unwrap(),
+            r#"40457151671,Elajjaz,1000,9040,8040,500,300,3,"Warhammer 40,000: Darktide (2h14m); Just ""Chatting"" (1m)","#
+        );
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn test_viewer_stats() {
+        let mut viewers = ViewerStats::default();
+        assert_eq!(viewers.average(), 0);
+        viewers.record(100);
+        viewers.record(300);
+        assert_eq!(viewers.peak(), 300);
+        assert_eq!(viewers.average(), 200);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(8040), "2h14m");
+        assert_eq!(format_duration(3600), "1h00m");
+        assert_eq!(format_duration(59), "0m");
+    }
+}
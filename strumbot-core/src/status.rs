@@ -0,0 +1,261 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use commons::util::Timestamp;
+use database_api::{Database, DatabaseError};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing as log;
+
+use crate::{
+    digest::DigestEntry,
+    errors::{BotError, ErrorCategory},
+    Cache,
+};
+
+const MUTES_KEY: &str = "_mutes";
+const ANNOUNCED_KEY: &str = "_announced";
+
+/// Snapshot of a stream which is currently tracked by a watcher.
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamStatus {
+    pub user_id: Box<str>,
+    pub user_name: Box<str>,
+    /// The name of the current game
+    pub game: Box<str>,
+    /// Unix timestamp of the stream start
+    pub started_at: i64,
+    /// Whether the stream appears offline and is in its grace period
+    pub offline: bool,
+}
+
+/// The raid which ended a stream, shown in its VOD notification.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Raid {
+    /// The login of the raided channel
+    pub login: Box<str>,
+    pub name: Box<str>,
+    pub viewers: u64,
+    /// Unix timestamp of the raid
+    pub at: i64,
+}
+
+/// Shared view of all active stream watchers, which is updated by the supervisor after every update.
+#[derive(Default)]
+pub struct StatusBoard {
+    streams: RwLock<HashMap<String, StreamStatus>>,
+    /// The user ids of all tracked streamers, live or not
+    tracked: RwLock<Vec<Box<str>>>,
+    /// Unix timestamps until which the notifications of a streamer are muted, by login
+    mutes: RwLock<HashMap<String, u64>>,
+    /// The id of the last stream which was announced, by login
+    announced: RwLock<HashMap<String, Box<str>>>,
+    /// The raids which were not picked up by a watcher yet, by login
+    raids: RwLock<HashMap<String, Raid>>,
+    /// The queue of the digest task, if live events are combined
+    digest: Option<mpsc::UnboundedSender<DigestEntry>>,
+    /// The queue of the error rate alerts, if they are sent
+    alerts: Option<mpsc::UnboundedSender<ErrorCategory>>,
+}
+
+impl StatusBoard {
+    pub fn with_digest(mut self, digest: mpsc::UnboundedSender<DigestEntry>) -> Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    pub fn with_alerts(mut self, alerts: mpsc::UnboundedSender<ErrorCategory>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Counts the error towards the error rate alerts.
+    pub fn record_error(&self, category: ErrorCategory) {
+        if let Some(ref alerts) = self.alerts {
+            drop(alerts.send(category));
+        }
+    }
+
+    /// Counts the error towards the error rate alerts, unless it is not of a counted kind.
+    pub fn record_bot_error(&self, error: &BotError) {
+        if let Some(category) = error.category() {
+            self.record_error(category);
+        }
+    }
+
+    /// Adds the live event to the next digest.
+    pub fn push_digest(&self, entry: DigestEntry) {
+        match self.digest {
+            Some(ref digest) => drop(digest.send(entry)),
+            None => log::warn!("[{}] Dropped live event without digest task", entry.user_name),
+        }
+    }
+
+    pub fn set(&self, key: &str, status: Option<StreamStatus>) {
+        let mut streams = self.streams.write().expect("Status board poisoned");
+        match status {
+            Some(status) => streams.insert(key.to_owned(), status),
+            None => streams.remove(key),
+        };
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.set(key, None);
+    }
+
+    /// All current streams, sorted by the stream start.
+    pub fn streams(&self) -> Vec<StreamStatus> {
+        let streams = self.streams.read().expect("Status board poisoned");
+        let mut list: Vec<StreamStatus> = streams.values().cloned().collect();
+        list.sort_by_key(|s| s.started_at);
+        list
+    }
+
+    /// The status of the stream, if the streamer has an active watcher.
+    pub fn stream(&self, login: &str) -> Option<StreamStatus> {
+        let streams = self.streams.read().expect("Status board poisoned");
+        streams.get(&login.to_lowercase()).cloned()
+    }
+
+    /// Whether the streamer has an active watcher, which includes streams in their grace period.
+    pub fn is_live(&self, login: &str) -> bool {
+        let streams = self.streams.read().expect("Status board poisoned");
+        streams.contains_key(&login.to_lowercase())
+    }
+
+    pub fn set_tracked(&self, user_ids: &[Box<str>]) {
+        let mut tracked = self.tracked.write().expect("Status board poisoned");
+        user_ids.clone_into(&mut tracked);
+    }
+
+    pub fn tracked(&self) -> Vec<Box<str>> {
+        self.tracked.read().expect("Status board poisoned").clone()
+    }
+
+    /// Mutes the notifications of the streamer until the unix timestamp, or unmutes them for `None`.
+    pub fn set_muted(&self, login: &str, until: Option<u64>) {
+        let mut mutes = self.mutes.write().expect("Status board poisoned");
+        let now = Timestamp::now().as_secs();
+        mutes.retain(|_, until| *until > now);
+        match until {
+            Some(until) => mutes.insert(login.to_lowercase(), until),
+            None => mutes.remove(&login.to_lowercase()),
+        };
+    }
+
+    pub fn is_muted(&self, login: &str) -> bool {
+        let mutes = self.mutes.read().expect("Status board poisoned");
+        mutes
+            .get(&login.to_lowercase())
+            .map_or(false, |until| *until > Timestamp::now().as_secs())
+    }
+
+    /// Remembers the raid of the streamer, until their watcher receives the next update.
+    pub fn set_raid(&self, login: &str, raid: Raid) {
+        let mut raids = self.raids.write().expect("Status board poisoned");
+        raids.insert(login.to_lowercase(), raid);
+    }
+
+    pub fn take_raid(&self, login: &str) -> Option<Raid> {
+        let mut raids = self.raids.write().expect("Status board poisoned");
+        raids.remove(&login.to_lowercase())
+    }
+
+    /// The id of the last stream of the streamer which was announced.
+    pub fn announced(&self, login: &str) -> Option<Box<str>> {
+        let announced = self.announced.read().expect("Status board poisoned");
+        announced.get(&login.to_lowercase()).cloned()
+    }
+
+    /// Remembers the announced stream, which is saved even if the cache is disabled.
+    pub async fn set_announced(&self, db: &Cache, login: &str, stream_id: &str) {
+        let announced = {
+            let mut announced = self.announced.write().expect("Status board poisoned");
+            let login = login.to_lowercase();
+            if announced.get(&login).map_or(false, |id| id.as_ref() == stream_id) {
+                return;
+            }
+            announced.insert(login, stream_id.into());
+            announced.clone()
+        };
+
+        if let Err(e) = db.save(ANNOUNCED_KEY, &announced).await {
+            log::error!("Failed to save announced streams: {e}");
+        }
+    }
+
+    /// Moves the announced stream of a renamed streamer to the new login.
+    pub async fn rename_announced(&self, db: &Cache, old: &str, new: &str) {
+        let announced = {
+            let mut announced = self.announced.write().expect("Status board poisoned");
+            let Some(stream_id) = announced.remove(&old.to_lowercase()) else {
+                return;
+            };
+            announced.insert(new.to_lowercase(), stream_id);
+            announced.clone()
+        };
+
+        if let Err(e) = db.save(ANNOUNCED_KEY, &announced).await {
+            log::error!("Failed to save announced streams: {e}");
+        }
+    }
+
+    pub async fn load_announced(&self, db: &Cache) {
+        let announced = match db.read(ANNOUNCED_KEY).await {
+            Ok(announced) => announced,
+            Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                log::error!("Failed to load announced streams: {e}");
+                return;
+            }
+        };
+        *self.announced.write().expect("Status board poisoned") = announced;
+    }
+
+    /// Loads the mutes from the cache, if enabled.
+    pub async fn load_mutes(&self, db: &Cache, persist: bool) {
+        if !persist {
+            return;
+        }
+
+        let mutes = match db.read(MUTES_KEY).await {
+            Ok(mutes) => mutes,
+            Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                log::error!("Failed to load muted streamers: {e}");
+                return;
+            }
+        };
+        *self.mutes.write().expect("Status board poisoned") = mutes;
+    }
+
+    pub async fn save_mutes(&self, db: &Cache, persist: bool) {
+        if !persist {
+            log::warn!("Muted streamers are unmuted on restart, because the cache is disabled");
+            return;
+        }
+
+        let mutes = self.mutes.read().expect("Status board poisoned").clone();
+        if let Err(e) = db.save(MUTES_KEY, &mutes).await {
+            log::error!("Failed to save muted streamers: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mutes() {
+        let status = StatusBoard::default();
+        let now = Timestamp::now().as_secs();
+
+        status.set_muted("Elajjaz", Some(now + 3600));
+        status.set_muted("distortion2", Some(now - 1));
+        assert!(status.is_muted("elajjaz"));
+        assert!(!status.is_muted("distortion2"));
+
+        status.set_muted("elajjaz", None);
+        assert!(!status.is_muted("Elajjaz"));
+    }
+}
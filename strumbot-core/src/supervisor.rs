@@ -0,0 +1,240 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use commons::source::StreamSource;
+use database_api::{Database, DatabaseError};
+use tokio::{sync::mpsc, time::timeout};
+use tracing as log;
+use twitch_api::TwitchClient;
+
+use crate::{
+    config::Config,
+    notifier::Notifier,
+    replication::{self, Standby},
+    stats,
+    status::StatusBoard,
+    watcher::{StreamUpdate, StreamWatcher, WatcherState},
+    Cache,
+};
+
+/// Starts the task for a stream watcher and returns the channel to send updates to.
+///
+/// Every update is handled in its own child task, so a panic only loses the current update.
+/// The supervisor then restores the watcher from the cache and continues with the next update.
+/// Updates which take longer than the configured `stuck_after` are logged, while later updates wait in the queue.
+/// With replication, a watcher whose stream is claimed by another instance follows it without sending anything.
+pub fn start_watcher(
+    cache_enabled: bool,
+    client: &Arc<TwitchClient>,
+    webhook: &Arc<dyn Notifier>,
+    db: &Arc<Cache>,
+    status: &Arc<StatusBoard>,
+    watcher: StreamWatcher,
+) -> mpsc::Sender<StreamUpdate> {
+    let settings = watcher.config().watchers.clone();
+    let (send, mut receive) = mpsc::channel(settings.buffer.max(1));
+    let stuck_after = Duration::from_secs(settings.stuck_after.max(1));
+    let twitch = Arc::clone(client);
+    let webhook = Arc::clone(webhook);
+    let db = Arc::clone(db);
+    let status = Arc::clone(status);
+
+    tokio::spawn(async move {
+        let mut key = watcher.key();
+        let mut name = watcher.user_name.clone();
+        let source = watcher.source();
+        let config = watcher.config();
+        let mut watcher = Some(watcher);
+        let mut standby = false;
+
+        while let Some(event) = receive.recv().await {
+            if let StreamUpdate::Renamed(renamed) = event {
+                let old = std::mem::replace(&mut key, renamed.to_lowercase());
+                name.clone_from(&renamed);
+                status.remove(&old);
+                status.rename_announced(&db, &old, &key).await;
+                if let Some(ref mut current) = watcher {
+                    current.rename(renamed.clone());
+                    status.set(&key, current.status());
+                }
+                if cache_enabled {
+                    move_cache(&db, &old, renamed, watcher.as_ref()).await;
+                }
+                continue;
+            }
+
+            let mut current = match watcher.take() {
+                Some(w) => w,
+                None => restore(&db, cache_enabled, &key, &name, source.clone(), &config).await,
+            };
+
+            // Mutes apply to the streamer on every platform
+            current.set_muted(status.is_muted(&name));
+            current.set_announced(status.announced(&key));
+            if let Some(raid) = status.take_raid(&key) {
+                current.set_raid(raid);
+            }
+
+            let announcer = replication::is_announcer(&db, &config.replication, &key).await;
+            if standby == announcer {
+                standby = !announcer;
+                if config.replication.enabled {
+                    let role = if announcer { "announcing" } else { "on standby for" };
+                    log::info!("[{key}] This instance is {role} the stream");
+                }
+            }
+
+            let client = Arc::clone(&twitch);
+            let notifier = if announcer {
+                Arc::clone(&webhook)
+            } else {
+                Arc::new(Standby) as Arc<dyn Notifier>
+            };
+            let mut task = tokio::spawn(async move {
+                let result = current.update(&client, &notifier, event).await;
+                (current, result)
+            });
+
+            let started = Instant::now();
+            let outcome = loop {
+                match timeout(stuck_after, &mut task).await {
+                    Ok(outcome) => break outcome,
+                    Err(_) => log::warn!(
+                        "[{key}] Stream watcher is stuck on an update for {} seconds",
+                        started.elapsed().as_secs()
+                    ),
+                }
+            };
+
+            let (mut current, result) = match outcome {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    // The watcher state is lost with the panicked task, restore it on the next update
+                    log::error!("[{key}] Stream watcher task failed, restarting from cache: {e}");
+                    continue;
+                }
+            };
+
+            status.set(&key, current.status());
+            if let Some(entry) = current.take_digest() {
+                status.push_digest(entry);
+            }
+            if let Some(stream_id) = current.announced() {
+                status.set_announced(&db, &key, stream_id).await;
+            }
+
+            match result {
+                Ok(WatcherState::Ended) => {
+                    // The announcing instance records the stats, so they are not counted twice
+                    if let Some(session) = current.take_session().filter(|_| cache_enabled && announcer) {
+                        stats::record(&db, current.user_id(), session).await;
+                    }
+                    // The edit outlives the watcher, since the next stream may start before the VOD shows up
+                    if let Some(recheck) = current.take_recheck().filter(|_| announcer) {
+                        tokio::spawn(recheck.run(Arc::clone(&twitch), Arc::clone(&webhook)));
+                    }
+                    break;
+                }
+                Err(e) if e.is_transient() => {
+                    status.record_bot_error(&e);
+                    // The same update is repeated with the next poll
+                    log::warn!("[{key}] Failed to update stream watcher, retrying with the next poll: {e}");
+                }
+                Err(e) => {
+                    status.record_bot_error(&e);
+                    // The partial update is discarded, the watcher continues from the last saved state
+                    log::error!("[{key}] Error when updating stream watcher: {e:?}");
+                    if cache_enabled {
+                        continue;
+                    }
+                }
+                Ok(WatcherState::Updated) => {
+                    // The saved state belongs to the announcing instance, a standby keeps its own in memory
+                    if cache_enabled && announcer {
+                        // Save the current watcher state to cache file
+                        match db.save(&key, &current).await {
+                            Err(DatabaseError::Io(e)) => {
+                                log::error!("[{key}] Failed to save cache: {e:?}");
+                            }
+                            Err(DatabaseError::Serde(e)) => {
+                                log::error!("[{key}] Could not serialize watcher: {e:?}");
+                            }
+                            Ok(_) => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            watcher = Some(current);
+        }
+
+        status.remove(&key);
+        if let Err(err) = db.delete(&key).await {
+            log::error!("[{key}] Failed to delete database entry: {err:?}");
+        }
+        receive.close();
+    });
+
+    send
+}
+
+/// Moves the cache entry of a renamed watcher to the new key.
+///
+/// Without a watcher, the restore is still pending, so the saved state is renamed and moved instead.
+async fn move_cache(db: &Cache, old: &str, name: Box<str>, watcher: Option<&StreamWatcher>) {
+    let new = name.to_lowercase();
+    let saved = match watcher {
+        Some(watcher) => db.save(&new, watcher).await,
+        None => match db.read::<StreamWatcher>(old).await {
+            Ok(mut watcher) => {
+                watcher.rename(name);
+                db.save(&new, &watcher).await
+            }
+            Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => Err(e),
+        },
+    };
+
+    // The old entry is kept if the new one is missing, so the state is not lost
+    if let Err(e) = saved {
+        log::error!("[{new}] Failed to save cache after rename: {e}");
+        return;
+    }
+    // Only the capitalization changed, so the entry was replaced
+    if new == old {
+        return;
+    }
+
+    match db.delete(old).await {
+        Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log::error!("[{new}] Failed to delete cache entry of previous name {old:?}: {e}"),
+        Ok(()) => {}
+    }
+}
+
+/// Restores the last saved watcher state, or starts from scratch if there is none.
+async fn restore(
+    db: &Cache,
+    cache_enabled: bool,
+    key: &str,
+    name: &str,
+    source: Option<Arc<dyn StreamSource>>,
+    config: &Arc<Config>,
+) -> StreamWatcher {
+    if cache_enabled {
+        match db.read::<StreamWatcher>(key).await {
+            Ok(watcher) => {
+                log::info!("[{key}] Restored stream watcher from cache");
+                return watcher.set_config(Arc::clone(config)).set_source(source);
+            }
+            Err(e) => {
+                log::warn!("[{key}] Could not restore stream watcher from cache: {e}");
+            }
+        }
+    }
+
+    StreamWatcher::new(name.to_owned(), Arc::clone(config)).set_source(source)
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use database_api::{Database, DatabaseError};
+use tracing as log;
+use twitch_api::oauth::{StoredToken, TokenStore};
+
+use crate::Cache;
+
+/// Twitch logins cannot start with an underscore, so these do not collide with watcher keys
+pub const APP_TOKEN: &str = "_oauth";
+pub const USER_TOKEN: &str = "_user_token";
+
+/// Persists a twitch access token in the cache.
+pub struct CachedToken {
+    db: Arc<Cache>,
+    key: &'static str,
+}
+
+impl CachedToken {
+    pub fn new(db: Arc<Cache>, key: &'static str) -> Self {
+        Self { db, key }
+    }
+}
+
+#[async_trait]
+impl TokenStore for CachedToken {
+    async fn load(&self) -> Option<StoredToken> {
+        match self.db.read(self.key).await {
+            Ok(token) => Some(token),
+            Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                log::warn!("Failed to load stored oauth token: {e}");
+                None
+            }
+        }
+    }
+
+    async fn save(&self, token: &StoredToken) {
+        if let Err(e) = self.db.save(self.key, token).await {
+            log::warn!("Failed to save oauth token: {e}");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use database_api::{Database, DatabaseError};
+use serde::{Deserialize, Serialize};
+use tracing as log;
+
+use crate::Cache;
+
+/// Twitch logins cannot start with an underscore, so this does not collide with watcher keys
+const KEY: &str = "_tracking";
+
+/// Changes to the tracked streamers made at runtime with the `/track` and `/untrack` commands.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Overrides {
+    /// Logins tracked in addition to the configured ones
+    #[serde(default)]
+    added: Vec<Box<str>>,
+    /// Logins which are not tracked, even if they are configured or members of the team
+    #[serde(default)]
+    removed: Vec<Box<str>>,
+}
+
+impl Overrides {
+    /// Loads the overrides from the cache, if enabled.
+    pub async fn load(db: &Cache, persist: bool) -> Self {
+        if !persist {
+            return Self::default();
+        }
+
+        match db.read(KEY).await {
+            Ok(overrides) => overrides,
+            Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                log::error!("Failed to load tracked streamers: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Applies the change to these overrides and to the stored overrides, keeping changes saved in the meantime.
+    pub async fn update<F>(&mut self, db: &Arc<Cache>, persist: bool, change: F)
+    where
+        F: Fn(&mut Self) + Send + Sync,
+    {
+        change(self);
+        if !persist {
+            log::warn!("Changes to the tracked streamers are lost on restart, because the cache is disabled");
+            return;
+        }
+
+        let result = db
+            .update(KEY, |stored: Option<Self>| {
+                let mut stored = stored.unwrap_or_default();
+                change(&mut stored);
+                stored
+            })
+            .await;
+
+        match result {
+            Ok(stored) => *self = stored,
+            Err(e) => log::error!("Failed to save tracked streamers: {e}"),
+        }
+    }
+
+    /// The configured logins with the runtime changes applied.
+    pub fn logins(&self, configured: &[Box<str>]) -> Vec<Box<str>> {
+        let mut logins: Vec<Box<str>> = configured
+            .iter()
+            .map(|login| login.to_lowercase().into_boxed_str())
+            .filter(|login| !self.is_removed(login))
+            .collect();
+
+        for login in &self.added {
+            if !logins.contains(login) {
+                logins.push(login.clone());
+            }
+        }
+
+        logins
+    }
+
+    pub fn is_removed(&self, login: &str) -> bool {
+        self.removed.iter().any(|l| l.as_ref() == login)
+    }
+
+    pub fn track(&mut self, login: &str) {
+        self.removed.retain(|l| l.as_ref() != login);
+        if !self.added.iter().any(|l| l.as_ref() == login) {
+            self.added.push(login.into());
+        }
+    }
+
+    pub fn untrack(&mut self, login: &str) {
+        self.added.retain(|l| l.as_ref() != login);
+        if !self.is_removed(login) {
+            self.removed.push(login.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logins(values: &[&str]) -> Vec<Box<str>> {
+        values.iter().map(|&login| login.into()).collect()
+    }
+
+    #[test]
+    fn test_overrides() {
+        let configured = logins(&["Elajjaz", "distortion2"]);
+        let mut overrides = Overrides::default();
+
+        overrides.track("moonmoon");
+        overrides.untrack("elajjaz");
+        assert_eq!(overrides.logins(&configured), logins(&["distortion2", "moonmoon"]));
+
+        overrides.track("elajjaz");
+        overrides.untrack("moonmoon");
+        assert_eq!(overrides.logins(&configured), logins(&["elajjaz", "distortion2"]));
+        assert!(overrides.is_removed("moonmoon"));
+    }
+}
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use commons::util::escape_markdown;
+use discord_api::{config::EventName, i18n, WebhookMessage};
+use eos::DateTime;
+use tokio::time::sleep;
+use tracing as log;
+use twilight_model::channel::message::embed::EmbedFooter;
+use twilight_util::builder::embed::{EmbedAuthorBuilder, EmbedBuilder, EmbedFieldBuilder};
+use twitch_api::{Schedule, ScheduleSegment, TwitchClient};
+
+use crate::{
+    config::Config,
+    embeds,
+    errors::ErrorCategory,
+    mentions::Mention,
+    notifier::Notifier,
+    policy::{self, Delivery},
+    status::StatusBoard,
+};
+
+/// Sends a reminder for the scheduled streams of the tracked streamers, shortly before they start.
+///
+/// Streamers who are already live or muted are skipped.
+pub async fn remind(
+    config: Arc<Config>,
+    client: Arc<TwitchClient>,
+    webhook: Arc<dyn Notifier>,
+    status: Arc<StatusBoard>,
+) {
+    let reminder = config.upcoming.reminder as i64 * 60;
+    let interval = Duration::from_secs(config.upcoming.interval);
+    // The start time of each segment which was already announced, by segment id
+    let mut reminded: HashMap<Box<str>, i64> = HashMap::new();
+
+    // The tracked streamers are only known after the first poll
+    sleep(Duration::from_secs(30)).await;
+
+    loop {
+        let now = DateTime::utc_now().timestamp().as_seconds();
+        reminded.retain(|_, start| *start > now);
+
+        for user_id in status.tracked() {
+            let schedule = match client.get_schedule(&user_id).await {
+                Ok(Some(schedule)) => schedule,
+                Ok(None) => continue,
+                Err(e) => {
+                    status.record_error(ErrorCategory::of_request(&e));
+                    log::error!("Failed to fetch the schedule of {user_id}: {e}");
+                    continue;
+                }
+            };
+
+            let login = &schedule.broadcaster_login;
+            if status.is_live(login) || status.is_muted(login) {
+                continue;
+            }
+
+            let segments = schedule.segments.as_deref().unwrap_or_default();
+            for segment in due(segments, now, reminder) {
+                if reminded.contains_key(&segment.id) {
+                    continue;
+                }
+
+                let start = segment.start_time.timestamp().as_seconds();
+                reminded.insert(segment.id.clone(), start);
+                announce(&schedule, segment, webhook.as_ref(), &config).await;
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
+
+/// The segments which start within the next `reminder` seconds, and were not canceled.
+fn due(segments: &[ScheduleSegment], now: i64, reminder: i64) -> impl Iterator<Item = &ScheduleSegment> {
+    segments.iter().filter(move |segment| {
+        let start = segment.start_time.timestamp().as_seconds();
+        segment.canceled_until.is_none() && start > now && start <= now + reminder
+    })
+}
+
+async fn announce(schedule: &Schedule, segment: &ScheduleSegment, webhook: &dyn Notifier, config: &Config) {
+    if !config.discord.enabled_events.contains(&EventName::Upcoming) {
+        return;
+    }
+
+    let login = &schedule.broadcaster_login;
+    let now = DateTime::utc_now().timestamp().as_seconds();
+    let mention = match policy::delivery(&config.discord, EventName::Upcoming, now) {
+        Delivery::Normal => config.mention(EventName::Upcoming),
+        Delivery::Silent => Mention::silent(EventName::Upcoming),
+        Delivery::Skip => {
+            log::info!("[{login}] Skipping upcoming notification during quiet hours");
+            return;
+        }
+    };
+
+    log::info!("[{login}] Sending reminder for scheduled stream {:?}", segment.title);
+    let escape = config.discord.escape;
+    let start = segment.start_time.timestamp().as_seconds();
+    let url = format!("https://twitch.tv/{login}");
+    let mut embed = EmbedBuilder::new().color(embeds::TWITCH_COLOR).title(&url).url(&url);
+    if !segment.title.is_empty() {
+        embed = embed.author(EmbedAuthorBuilder::new(segment.title.to_string()).build());
+    }
+    if let Some(ref category) = segment.category {
+        embed = embed.field(EmbedFieldBuilder::new("Playing", escape_markdown(&category.name, escape)).inline());
+    }
+    embed = embed.field(EmbedFieldBuilder::new("Starts", config.discord.time_format().datetime(start)).inline());
+
+    let messages = config.discord.locale.messages();
+    let role_name = &config.discord.role_name.upcoming;
+    if config.discord.show_notify_hints && !role_name.is_empty() {
+        embed = embed.footer(EmbedFooter {
+            icon_url: None,
+            proxy_icon_url: None,
+            text: i18n::render(messages.notify_hint, &[("role", role_name.as_ref())]),
+        });
+    }
+
+    let text = i18n::render(
+        messages.starting_soon,
+        &[
+            ("user", escape_markdown(&schedule.broadcaster_name, escape).as_ref()),
+            ("time", format!("<t:{start}:R>").as_str()),
+        ],
+    );
+
+    let message = WebhookMessage {
+        content: format!("{}{text}", mention.prefix()),
+        embeds: vec![embed.build()],
+        files: Vec::new(),
+        avatar_url: config.discord.avatar_url.as_deref().map(str::to_owned),
+        username: config.discord.username(&schedule.broadcaster_name),
+        allowed_mentions: mention.allowed_mentions(),
+        live_stream: None,
+    };
+
+    if let Err(e) = webhook.send(message).await {
+        log::error!("[{login}] Failed to send upcoming notification: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(id: &str, start_time: &str, canceled: bool) -> ScheduleSegment {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "start_time": start_time,
+            "canceled_until": canceled.then_some("2023-01-02T00:00:00Z"),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_due() {
+        // 2023-01-01T12:00:00Z
+        let now = 1_672_574_400;
+        let segments = [
+            segment("started", "2023-01-01T11:55:00Z", false),
+            segment("soon", "2023-01-01T12:05:00Z", false),
+            segment("canceled", "2023-01-01T12:05:00Z", true),
+            segment("later", "2023-01-01T12:30:00Z", false),
+        ];
+
+        let ids: Vec<&str> = due(&segments, now, 600).map(|segment| segment.id.as_ref()).collect();
+        assert_eq!(ids, vec!["soon"]);
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use commons::util::escape_markdown;
+use database_api::{Database, DatabaseError};
+use discord_api::{config::EventName, i18n, WebhookMessage};
+use eos::DateTime;
+use tokio::time::sleep;
+use tracing as log;
+use twilight_model::channel::message::embed::EmbedFooter;
+use twilight_util::builder::embed::{EmbedFieldBuilder, ImageSource};
+use twitch_api::{client::resolve_thumbnail_url, TwitchClient, Video, VideoType};
+
+use crate::{
+    config::Config,
+    embeds,
+    errors::ErrorCategory,
+    mentions::Mention,
+    notifier::Notifier,
+    policy::{self, Delivery},
+    status::StatusBoard,
+    Cache,
+};
+
+/// The creation time of the newest announced video, by user id
+const VIDEOS_KEY: &str = "_videos";
+
+/// Announces new highlights and uploads of the tracked streamers.
+///
+/// The newest video of each streamer is persisted, so videos are not announced again after a restart.
+pub async fn watch(
+    config: Arc<Config>,
+    client: Arc<TwitchClient>,
+    webhook: Arc<dyn Notifier>,
+    status: Arc<StatusBoard>,
+    db: Arc<Cache>,
+) {
+    let interval = Duration::from_secs(config.videos.interval.max(60));
+    let mut latest: HashMap<Box<str>, i64> = match db.read(VIDEOS_KEY).await {
+        Ok(latest) => latest,
+        Err(DatabaseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+        Err(e) => {
+            log::error!("Failed to load the announced videos: {e}");
+            HashMap::new()
+        }
+    };
+
+    // The tracked streamers are only known after the first poll
+    sleep(Duration::from_secs(30)).await;
+
+    loop {
+        let mut changed = false;
+        for user_id in status.tracked() {
+            let mut videos = Vec::new();
+            for kind in [VideoType::Highlight, VideoType::Upload] {
+                match client.get_user_videos(&user_id, kind).await {
+                    Ok(list) => videos.extend(list),
+                    Err(e) => {
+                        status.record_error(ErrorCategory::of_request(&e));
+                        log::error!("Failed to fetch the videos of {user_id}: {e}");
+                    }
+                }
+            }
+
+            let previous = latest.get(&user_id).copied();
+            let (new, newest) = unseen(videos, previous);
+            if newest != previous {
+                if let Some(newest) = newest {
+                    latest.insert(user_id.clone(), newest);
+                    changed = true;
+                }
+            }
+
+            for video in new {
+                if !status.is_muted(&video.user_login) {
+                    announce(&video, webhook.as_ref(), &config).await;
+                }
+            }
+        }
+
+        if changed {
+            if let Err(e) = db.save(VIDEOS_KEY, &latest).await {
+                log::error!("Failed to save the announced videos: {e}");
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
+
+/// The videos which are newer than the last announced one, oldest first, and the creation time of the newest video.
+///
+/// Without a previous video, the newest video is only recorded, so the existing videos are not announced.
+fn unseen(mut videos: Vec<Video>, previous: Option<i64>) -> (Vec<Video>, Option<i64>) {
+    videos.sort_by_key(|video| video.created_at.timestamp().as_seconds());
+    let newest = videos.last().map(|video| video.created_at.timestamp().as_seconds());
+    let Some(previous) = previous else {
+        return (Vec::new(), newest);
+    };
+
+    videos.retain(|video| video.created_at.timestamp().as_seconds() > previous);
+    (videos, newest.max(Some(previous)))
+}
+
+async fn announce(video: &Video, webhook: &dyn Notifier, config: &Config) {
+    let login = &video.user_login;
+    let now = DateTime::utc_now().timestamp().as_seconds();
+    let mention = match policy::delivery(&config.discord, EventName::Video, now) {
+        Delivery::Normal => config.mention(EventName::Video),
+        Delivery::Silent => Mention::silent(EventName::Video),
+        Delivery::Skip => {
+            log::info!("[{login}] Skipping video notification during quiet hours");
+            return;
+        }
+    };
+
+    log::info!("[{login}] Sending notification for new video {:?}", video.title);
+    let mut embed = embeds::vod_embed(Some(video)).field(
+        EmbedFieldBuilder::new(
+            "Duration",
+            config.discord.time_format().duration(video.duration.as_secs()),
+        )
+        .inline(),
+    );
+    if config.discord.thumbnails && !video.thumbnail_url.is_empty() {
+        if let Ok(image) = ImageSource::url(resolve_thumbnail_url(&video.thumbnail_url)) {
+            embed = embed.image(image);
+        }
+    }
+
+    let messages = config.discord.locale.messages();
+    let role_name = &config.discord.role_name.video;
+    if config.discord.show_notify_hints && !role_name.is_empty() {
+        embed = embed.footer(EmbedFooter {
+            icon_url: None,
+            proxy_icon_url: None,
+            text: i18n::render(messages.notify_hint, &[("role", role_name.as_ref())]),
+        });
+    }
+
+    let text = i18n::render(
+        messages.new_video,
+        &[(
+            "user",
+            escape_markdown(&video.user_name, config.discord.escape).as_ref(),
+        )],
+    );
+
+    let message = WebhookMessage {
+        content: format!("{}{text}", mention.prefix()),
+        embeds: vec![embed.build()],
+        files: Vec::new(),
+        avatar_url: config.discord.avatar_url.as_deref().map(str::to_owned),
+        username: config.discord.username(&video.user_name),
+        allowed_mentions: mention.allowed_mentions(),
+        live_stream: None,
+    };
+
+    if let Err(e) = webhook.send(message).await {
+        log::error!("[{login}] Failed to send video notification: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str, created_at: &str) -> Video {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "user_login": "elajjaz",
+            "user_name": "Elajjaz",
+            "url": format!("https://www.twitch.tv/videos/{id}"),
+            "title": "Highlight",
+            "thumbnail_url": "",
+            "view_count": 0,
+            "type": "highlight",
+            "created_at": created_at,
+            "duration": "1h2m3s",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_unseen() {
+        let videos = || {
+            vec![
+                video("3", "2023-01-03T12:00:00Z"),
+                video("1", "2023-01-01T12:00:00Z"),
+                video("2", "2023-01-02T12:00:00Z"),
+            ]
+        };
+
+        // The existing videos are only recorded on the first check
+        let (new, newest) = unseen(videos(), None);
+        assert!(new.is_empty());
+        assert_eq!(newest, Some(1_672_747_200));
+
+        // 2023-01-01T12:00:00Z
+        let (new, newest) = unseen(videos(), Some(1_672_574_400));
+        let ids: Vec<&str> = new.iter().map(|video| video.id.as_ref()).collect();
+        assert_eq!(ids, vec!["2", "3"]);
+        assert_eq!(newest, Some(1_672_747_200));
+
+        let (new, newest) = unseen(Vec::new(), Some(1_672_574_400));
+        assert!(new.is_empty());
+        assert_eq!(newest, Some(1_672_574_400));
+    }
+}
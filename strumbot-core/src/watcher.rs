@@ -0,0 +1,1420 @@
+use std::{
+    borrow::Cow,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use commons::{
+    source::{SourceVideo, StreamSource},
+    util::{escape_markdown, Timestamp},
+};
+use discord_api::{
+    config::{ChapterFormat, EventName, ProfileImage},
+    i18n::{self, Messages},
+    ForumPost, MessageFile, SendError, SentMessage, WebhookMessage,
+};
+use eos::DateTime;
+use serde::{Deserialize, Serialize};
+use tracing as log;
+use twilight_model::channel::message::embed::EmbedFooter;
+use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder, ImageSource};
+use twitch_api::VideoDuration;
+use twitch_api::{
+    client::resolve_thumbnail_url,
+    config::{StreamTypeMode, MAX_TOP_CLIPS},
+    error::RequestError,
+    Game, Stream, StreamMarker, StreamType, TwitchClient,
+};
+
+use crate::{
+    collage,
+    config::Config,
+    digest::DigestEntry,
+    embeds,
+    errors::BotError,
+    mentions::Mention,
+    notifications::{self, NotificationBuilder, Thumbnail},
+    notifier::Notifier,
+    policy::{self, Delivery},
+    recheck::VodRecheck,
+    stats::{GameTime, StreamSession, ViewerStats},
+    status::{Raid, StreamStatus},
+};
+
+/// Number of seconds after the stream start, after which a stream without any VOD is assumed to have VODs disabled
+const VOD_MISSING_AFTER: u64 = 600;
+
+const fn split_duration(secs: u32) -> (u8, u8, u8) {
+    let hour = (secs / 3600) % 60;
+    let mins = (secs / 60) % 60;
+    let secs = secs % 60;
+    (hour as u8, mins as u8, secs as u8)
+}
+
+#[inline]
+fn empty_str() -> Box<str> {
+    "".into()
+}
+
+fn video_url(video_id: &str) -> String {
+    format!("https://www.twitch.tv/videos/{}", video_id)
+}
+
+/// The key of a watcher in the cache and the status board, for the streams of the platform or twitch.
+///
+/// Other platforms are prefixed with their name, which can't collide with twitch logins since they have no dashes.
+pub fn watcher_key(platform: Option<&str>, user_name: &str) -> String {
+    match platform {
+        Some(platform) => format!("{}-{}", platform.to_lowercase(), user_name.to_lowercase()),
+        None => user_name.to_lowercase(),
+    }
+}
+
+/// The game of a stream from another platform, whose category is only known by its name.
+fn category(name: &str) -> Arc<Game> {
+    if name.is_empty() {
+        Game::empty()
+    } else {
+        Arc::new(Game {
+            id: name.into(),
+            name: name.into(),
+            box_art_url: empty_str(),
+        })
+    }
+}
+
+pub fn timestamp_link(video_id: &str, position: u32) -> String {
+    let (hour, min, sec) = split_duration(position);
+    let display = format!("`{hour:02}:{min:02}:{sec:02}`");
+    if video_id.is_empty() {
+        // Don't link a VOD if there is no video ID (deleted vod or streamer forgot to enable it or twitch being twitch)
+        display
+    } else {
+        // Otherwise, hyperlink the VOD in the timestamp
+        let query = format!("{hour:02}h{min:02}m{sec:02}s");
+        let url = format!("{}?t={}", video_url(video_id), query);
+        format!("[{display}]({url})")
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct StreamSegment {
+    /// The game the stream was playing in this segment
+    game: Arc<Game>,
+    /// Seconds since the stream started
+    position: u32,
+    /// The id for the VOD link
+    video_id: Box<str>,
+    /// The associated stream id
+    #[serde(skip_serializing_if = "str::is_empty", default)] // Backwards compatibility, TODO: Remove in 2.0
+    stream_id: Box<str>,
+    /// Unix timestamp of the segment start, 0 for segments cached by older versions
+    #[serde(default)]
+    started_at: i64,
+}
+
+impl StreamSegment {
+    async fn from(client: &TwitchClient, stream: &Stream, game: Arc<Game>, lookup: bool) -> Self {
+        let position = DateTime::utc_now().duration_since(&stream.started_at).as_secs() as u32;
+        // Channels without VODs would never find a video
+        let video_id = if lookup {
+            match stream.get_video(client).await {
+                Ok(v) => v.id,
+                Err(e) => {
+                    log::error!(
+                        "[{}] Failed to get video for stream: {}",
+                        stream.user_name.to_lowercase(),
+                        e
+                    );
+                    empty_str()
+                }
+            }
+        } else {
+            empty_str()
+        };
+
+        Self {
+            game,
+            position,
+            video_id,
+            stream_id: stream.id.clone(),
+            started_at: DateTime::utc_now().timestamp().as_seconds(),
+        }
+    }
+
+    fn vod_link(&self) -> String {
+        timestamp_link(&self.video_id, self.position)
+    }
+}
+
+/// An entry in the chapter list of a VOD, either from a game segment or a stream marker.
+#[derive(Serialize)]
+struct Chapter {
+    timestamp: String,
+    position: u32,
+    title: Box<str>,
+    /// The number of the VOD part, if the stream was split into several videos
+    #[serde(skip_serializing_if = "Option::is_none")]
+    part: Option<usize>,
+    #[serde(skip)]
+    video_id: Box<str>,
+}
+
+impl Chapter {
+    fn new(position: u32, title: Box<str>, video_id: Box<str>) -> Self {
+        let (hour, min, sec) = split_duration(position);
+        Self {
+            timestamp: format!("{hour:02}:{min:02}:{sec:02}"),
+            position,
+            title,
+            part: None,
+            video_id,
+        }
+    }
+
+    fn from_segment(segment: &StreamSegment) -> Self {
+        Self::new(segment.position, segment.game.name.clone(), segment.video_id.clone())
+    }
+
+    fn from_marker(marker: &StreamMarker, video_id: &str) -> Self {
+        let title = if marker.description.is_empty() {
+            "Marker".into()
+        } else {
+            marker.description.clone()
+        };
+
+        Self::new(marker.position_seconds, title, video_id.into())
+    }
+
+    fn vod_link(&self) -> String {
+        timestamp_link(&self.video_id, self.position)
+    }
+
+    /// Renders the full chapter list into an attachment, used when the list does not fit into the embed.
+    fn export(chapters: &[Chapter], format: ChapterFormat) -> MessageFile {
+        match format {
+            ChapterFormat::Text => {
+                let text: String = chapters
+                    .iter()
+                    .map(|c| match c.part {
+                        Some(part) => format!("Part {part} {} {}\n", c.timestamp, c.title),
+                        None => format!("{} {}\n", c.timestamp, c.title),
+                    })
+                    .collect();
+                MessageFile {
+                    filename: "chapters.txt".to_owned(),
+                    data: text.into_bytes(),
+                }
+            }
+            ChapterFormat::Json => {
+                let json = serde_json::to_vec_pretty(chapters).unwrap_or_default();
+                MessageFile {
+                    filename: "chapters.json".to_owned(),
+                    data: json,
+                }
+            }
+        }
+    }
+}
+
+/// The live notification, which is edited with a new stream preview while the stream is live.
+struct LivePreview {
+    sent: SentMessage,
+    /// The first message of the notification, without its files
+    message: WebhookMessage,
+    refreshed: Instant,
+}
+
+pub enum StreamUpdate {
+    Live(Box<Stream>),
+    Offline,
+    /// The streamer changed their login
+    Renamed(Box<str>),
+}
+
+pub enum WatcherState {
+    Unchanged,
+    Ended,
+    Updated,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct StreamWatcher {
+    pub user_name: Box<str>,
+    user_id: Box<str>,
+    stream_id: Box<str>,
+    segments: Vec<StreamSegment>,
+    start_timestamp: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offline_timestamp: Option<Timestamp>,
+    /// Whether notifications are suppressed for this stream, e.g. for skipped reruns
+    #[serde(default)]
+    suppressed: bool,
+    /// Whether the streamer is muted with the `/mute` command
+    #[serde(default, skip)]
+    muted: bool,
+    /// The id of the last stream which was announced, kept outside of the watcher cache
+    #[serde(default, skip)]
+    announced: Option<Box<str>>,
+    #[serde(default)]
+    viewers: ViewerStats,
+    /// Whether the VOD lookups stopped, since the stream had no VOD for a while
+    #[serde(default)]
+    vods_missing: bool,
+    /// The raid which ended the stream, if it was received from EventSub
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raid: Option<Raid>,
+    /// Unix timestamp of the last update while the stream was live
+    #[serde(default)]
+    last_seen: i64,
+    /// The profile image of the streamer, if the notifications show it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile_image: Option<Box<str>>,
+    /// The display name of the streamer, as of the stream start
+    #[serde(default)]
+    display_name: Box<str>,
+    /// The live notification, while it is pinned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pinned: Option<SentMessage>,
+    /// The summary of the stream, once it ended
+    #[serde(default, skip)]
+    session: Option<StreamSession>,
+    /// The live event, if it is announced in the next digest instead of its own notification
+    #[serde(default, skip)]
+    digest: Option<DigestEntry>,
+    /// Live updates are ignored until this time, after the last change of the stream
+    #[serde(default, skip)]
+    next_update: Option<Instant>,
+    /// The VOD notification which is edited once its video is available
+    #[serde(default, skip)]
+    recheck: Option<VodRecheck>,
+    /// The live notification, while its preview is refreshed
+    #[serde(default, skip)]
+    live_preview: Option<LivePreview>,
+    /// The platform of the stream, or `None` for twitch streams
+    #[serde(default, skip)]
+    source: Option<Arc<dyn StreamSource>>,
+    #[serde(default, skip)]
+    config: Arc<Config>,
+}
+
+impl StreamWatcher {
+    pub fn new(user_name: String, config: Arc<Config>) -> Self {
+        Self {
+            user_name: user_name.into(),
+            user_id: empty_str(),   // initialized in go_live
+            stream_id: empty_str(), // initialized in go_live
+            config,
+            segments: Vec::new(),
+            start_timestamp: DateTime::utc_now(),
+            offline_timestamp: None,
+            suppressed: false,
+            muted: false,
+            announced: None,
+            viewers: ViewerStats::default(),
+            vods_missing: false,
+            raid: None,
+            last_seen: 0,
+            profile_image: None,
+            display_name: empty_str(),
+            pinned: None,
+            session: None,
+            digest: None,
+            next_update: None,
+            recheck: None,
+            live_preview: None,
+            source: None,
+        }
+    }
+
+    pub fn set_config(mut self, config: Arc<Config>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the platform of the streams, which are fetched from twitch without a source.
+    pub fn set_source(mut self, source: Option<Arc<dyn StreamSource>>) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn source(&self) -> Option<Arc<dyn StreamSource>> {
+        self.source.clone()
+    }
+
+    /// The key of the watcher in the cache and the status board.
+    pub fn key(&self) -> String {
+        watcher_key(self.source.as_ref().map(|source| source.platform()), &self.user_name)
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config)
+    }
+
+    /// The user id of the streamer, empty until the stream started.
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    /// Takes the VOD notification which should be edited once its video is available.
+    pub fn take_recheck(&mut self) -> Option<VodRecheck> {
+        self.recheck.take()
+    }
+
+    /// Takes the summary of the stream, once it ended.
+    pub fn take_session(&mut self) -> Option<StreamSession> {
+        self.session.take()
+    }
+
+    /// Takes the live event which should be added to the digest.
+    pub fn take_digest(&mut self) -> Option<DigestEntry> {
+        self.digest.take()
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn set_announced(&mut self, stream_id: Option<Box<str>>) {
+        self.announced = stream_id;
+    }
+
+    /// The id of the last stream which was announced, or handled without a notification.
+    pub fn announced(&self) -> Option<&str> {
+        self.announced.as_deref()
+    }
+
+    /// Keeps the raid for the VOD notification, unless it happened before the current stream.
+    pub fn set_raid(&mut self, raid: Raid) {
+        if !self.segments.is_empty() && raid.at >= self.start_timestamp.timestamp().as_seconds() {
+            self.raid = Some(raid);
+        }
+    }
+
+    pub fn rename(&mut self, user_name: Box<str>) {
+        self.user_name = user_name;
+    }
+
+    /// The current status of the stream, or `None` if the stream has not started yet.
+    pub fn status(&self) -> Option<StreamStatus> {
+        let segment = self.segments.last()?;
+        Some(StreamStatus {
+            user_id: self.user_id.clone(),
+            user_name: self.user_name.clone(),
+            game: segment.game.name.clone(),
+            started_at: self.start_timestamp.timestamp().as_seconds(),
+            offline: self.offline_timestamp.is_some(),
+        })
+    }
+
+    pub async fn update(
+        &mut self,
+        client: &TwitchClient,
+        webhook: &dyn Notifier,
+        stream: StreamUpdate,
+    ) -> Result<WatcherState, BotError> {
+        if let StreamUpdate::Live(ref stream) = stream {
+            self.last_seen = DateTime::utc_now().timestamp().as_seconds();
+            if !self.segments.is_empty() {
+                self.viewers.record(stream.viewer_count);
+                self.refresh_preview(client, webhook, stream).await;
+                // Offline updates are never throttled, so the grace period starts on time
+                if self.next_update.map_or(false, |next| Instant::now() < next) {
+                    return Ok(WatcherState::Unchanged);
+                }
+            }
+        }
+
+        let state = self.apply(client, webhook, stream).await?;
+        if let WatcherState::Updated = state {
+            self.next_update = Some(Instant::now() + self.config.twitch.min_update_interval.0);
+        }
+        Ok(state)
+    }
+
+    async fn apply(
+        &mut self,
+        client: &TwitchClient,
+        webhook: &dyn Notifier,
+        stream: StreamUpdate,
+    ) -> Result<WatcherState, BotError> {
+        match stream {
+            StreamUpdate::Live(stream) if self.segments.is_empty() => {
+                self.on_go_live(client, webhook, *stream).await?;
+                Ok(WatcherState::Updated)
+            }
+            StreamUpdate::Live(stream) => {
+                if self.on_update(client, webhook, *stream).await? {
+                    Ok(WatcherState::Updated)
+                } else {
+                    Ok(WatcherState::Unchanged)
+                }
+            }
+            StreamUpdate::Offline if !self.segments.is_empty() => {
+                if self.on_offline(client, webhook).await? {
+                    Ok(WatcherState::Ended)
+                } else {
+                    Ok(WatcherState::Updated)
+                }
+            }
+            _ => Ok(WatcherState::Unchanged),
+        }
+    }
+
+    async fn on_go_live(
+        &mut self,
+        client: &TwitchClient,
+        webhook: &dyn Notifier,
+        stream: Stream,
+    ) -> Result<(), BotError> {
+        self.offline_timestamp = None;
+        self.start_timestamp = stream.started_at;
+        self.user_id = stream.user_id.clone();
+        self.display_name = stream.user_name.clone();
+        self.stream_id = stream.id.clone();
+        self.viewers = ViewerStats::default();
+        self.vods_missing = false;
+        self.raid = None;
+        self.live_preview = None;
+        self.viewers.record(stream.viewer_count);
+        self.profile_image = match self.source {
+            Some(_) => None,
+            None => self.fetch_profile_image(client).await,
+        };
+
+        let mode = match stream.kind {
+            StreamType::Rerun => self.config.twitch.reruns,
+            StreamType::Premiere => self.config.twitch.premieres,
+            _ => StreamTypeMode::Announce,
+        };
+        self.suppressed = mode == StreamTypeMode::Skip;
+
+        let segment = self.add_segment(client, &stream).await?;
+        segment.position = 0;
+        segment.started_at = stream.started_at.timestamp().as_seconds();
+        let game = segment.game.clone();
+
+        let user_name = &stream.user_name;
+        log::info!("[{}] User started streaming {}", self.user_name, game.name);
+
+        if self.suppressed {
+            log::info!(
+                "[{}] Skipping notifications for {:?} stream",
+                self.user_name,
+                stream.kind
+            );
+        }
+
+        if self.announced.as_deref() == Some(stream.id.as_ref()) {
+            log::info!("[{}] Stream {} was already announced", self.user_name, stream.id);
+            return Ok(());
+        }
+        self.announced = Some(stream.id.clone());
+
+        if self.is_skipped(EventName::Live) {
+            return Ok(());
+        }
+
+        if self.config.discord.digest.is_some() {
+            self.digest = Some(DigestEntry {
+                user_name: stream.user_name.clone(),
+                title: stream.title.clone(),
+                game: game.name.clone(),
+                started_at: stream.started_at.timestamp().as_seconds(),
+            });
+            return Ok(());
+        }
+
+        let Some(mention) = self.notify_mention(EventName::Live) else {
+            return Ok(());
+        };
+
+        let started_at = stream.started_at.timestamp().as_seconds();
+        let mut embed = self.stream_embed(&stream, &game, started_at);
+        embed = self.set_footer(embed, &self.config.discord.role_name.live);
+
+        let label = match stream.kind.label() {
+            Some(label) if mode == StreamTypeMode::Label => format!("**[{label}]** "),
+            _ => String::new(),
+        };
+
+        let messages = self.messages();
+        let user_name = self.escape(user_name);
+        let text = if let Some(ref source) = self.source {
+            i18n::render(
+                messages.live_on,
+                &[("user", user_name.as_ref()), ("platform", source.platform())],
+            )
+        } else if game.is_empty() {
+            i18n::render(messages.live, &[("user", user_name.as_ref())])
+        } else {
+            i18n::render(
+                messages.live_with,
+                &[("user", user_name.as_ref()), ("game", self.escape(&game.name).as_ref())],
+            )
+        };
+        let content = format!("{mention} {label}{text}");
+
+        let thumbnail = self.thumbnail(client, &stream.thumbnail_url).await;
+        let notification = NotificationBuilder::new(embed);
+        // The notification of an earlier stream might still be pinned, if the bot missed its end
+        self.unpin(webhook).await;
+        let sent = self
+            .send(webhook, content, &mention, notification, thumbnail, Vec::new())
+            .await;
+
+        let Some((sent, mut message)) = sent else {
+            return Ok(());
+        };
+
+        if self.config.discord.pin_live {
+            match webhook.pin(sent).await {
+                Ok(()) => self.pinned = Some(sent),
+                Err(e) => log::warn!("[{}] Failed to pin the live notification: {}", self.user_name, e),
+            }
+        }
+
+        if self.config.discord.preview_refresh().is_some() {
+            message.files.clear();
+            self.live_preview = Some(LivePreview {
+                sent,
+                message,
+                refreshed: Instant::now(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Edits the live notification with a new stream preview, once the refresh interval passed.
+    async fn refresh_preview(&mut self, client: &TwitchClient, webhook: &dyn Notifier, stream: &Stream) {
+        let Some(interval) = self.config.discord.preview_refresh() else {
+            return;
+        };
+        let due = self
+            .live_preview
+            .as_ref()
+            .map_or(false, |preview| preview.refreshed.elapsed() >= interval);
+        if !due {
+            return;
+        }
+
+        let thumbnail = self.thumbnail(client, &stream.thumbnail_url).await;
+        let Some(preview) = self.live_preview.as_mut() else {
+            return;
+        };
+        // Failed refreshes are tried again after the next interval, instead of every poll
+        preview.refreshed = Instant::now();
+        let mut message = preview.message.clone();
+        let (Some(thumbnail), Some(embed)) = (thumbnail, message.embeds.first_mut()) else {
+            return;
+        };
+        notifications::replace_thumbnail(embed, thumbnail, &mut message.files);
+
+        log::debug!("[{}] Refreshing the preview of the live notification", self.user_name);
+        if let Err(e) = webhook.edit(preview.sent, message).await {
+            log::warn!("[{}] Failed to refresh the live preview: {}", self.user_name, e);
+        }
+    }
+
+    /// Unpins the live notification, once the stream ended.
+    async fn unpin(&mut self, webhook: &dyn Notifier) {
+        let Some(sent) = self.pinned.take() else {
+            return;
+        };
+
+        if let Err(e) = webhook.unpin(sent).await {
+            log::warn!("[{}] Failed to unpin the live notification: {}", self.user_name, e);
+        }
+    }
+
+    async fn on_update(
+        &mut self,
+        client: &TwitchClient,
+        webhook: &dyn Notifier,
+        stream: Stream,
+    ) -> Result<bool, BotError> {
+        self.offline_timestamp = None;
+        let old_game = match self.segments.last() {
+            Some(seg) => seg.game.clone(), // have to clone so the borrow isn't an issue later
+            None => {
+                panic!("Impossible situation encountered. Stream game update without being live?")
+            }
+        };
+
+        let vod_change = stream.id != self.stream_id;
+        let game_change = stream.game_id != old_game.id;
+        let segment = if vod_change || game_change {
+            // Stream has changed, so we need to update the segments
+            self.add_segment(client, &stream).await?
+        } else {
+            // Nothing has changed, continue as usual.
+            // Attempt to insert vod link if necessary
+            return Ok(self.relink(&stream, client).await);
+        };
+
+        // Clone to avoid propagating mutable borrow
+        let game = segment.game.clone();
+
+        // Start from beginning of new vod
+        if vod_change {
+            segment.position = 0;
+            self.merge_stream(&stream);
+        }
+
+        // If the game didn't change, we don't need to send any announcement
+        if !game_change {
+            return Ok(true);
+        }
+
+        log::info!(
+            "[{}] Stream changed game: {} -> {}",
+            self.user_name,
+            old_game.name,
+            game.name
+        );
+
+        if self.is_skipped(EventName::Update) {
+            return Ok(true);
+        }
+
+        let Some(mention) = self.notify_mention(EventName::Update) else {
+            return Ok(true);
+        };
+
+        // After a reconnect, the session still started with the first stream
+        let started_at = self.start_timestamp.timestamp().as_seconds();
+        let mut embed = self.stream_embed(&stream, &game, started_at);
+        embed = self.set_footer(embed, &self.config.discord.role_name.update);
+        if let Some(url) = game.box_art() {
+            match ImageSource::url(url) {
+                Ok(source) => embed = embed.thumbnail(source),
+                Err(e) => log::warn!("[{}] Invalid box art url for {}: {e}", self.user_name, game.name),
+            }
+        }
+        embed = match self.segments.last() {
+            Some(segs) if !segs.video_id.is_empty() => embed.description(i18n::render(
+                self.messages().start_watching,
+                &[("timestamp", segs.vod_link().as_str())],
+            )),
+            _ => embed,
+        };
+
+        let text = i18n::render(
+            self.messages().switched_game,
+            &[
+                ("user", self.escape(&stream.user_name).as_ref()),
+                ("game", self.escape(&game.name).as_ref()),
+            ],
+        );
+        let content = format!("{mention} {text}");
+
+        let thumbnail = self.thumbnail(client, &stream.thumbnail_url).await;
+        let notification = NotificationBuilder::new(embed);
+        self.send(webhook, content, &mention, notification, thumbnail, Vec::new())
+            .await;
+
+        Ok(true)
+    }
+
+    async fn on_offline(&mut self, client: &TwitchClient, webhook: &dyn Notifier) -> Result<bool, BotError> {
+        // Check if the offline grace period is over (usually 2 minutes)
+        match self.offline_timestamp {
+            None => {
+                let offset = self.config.twitch.grace_period(&self.user_name).as_secs();
+                self.offline_timestamp = Some(Timestamp::now() + offset);
+                return Ok(false);
+            }
+            Some(instant) => {
+                if instant > Timestamp::now() {
+                    return Ok(false);
+                }
+            }
+        }
+
+        log::info!("[{}] stream went offline", self.user_name);
+        self.live_preview = None;
+
+        // The clips are needed for the stats and the top clips field, other platforms have no clips
+        let num = self.config.twitch.top_clips.min(MAX_TOP_CLIPS);
+        let clips = if (num > 0 || self.config.cache.enabled) && self.source.is_none() {
+            match client
+                .get_top_clips(self.user_id.to_string(), &self.start_timestamp, 100)
+                .await
+            {
+                Ok(clips) => clips,
+                Err(e) => {
+                    log::error!("[{}] Failed to fetch clips: {}", self.user_name, e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        self.session = Some(self.create_session(clips.len() as u32));
+        let clips = self.config.twitch.select_clips(clips, num as usize);
+
+        // Other platforms keep the whole stream as one video, instead of the twitch VODs
+        let recording = self.recording().await;
+        if let (Some(session), Some(video)) = (self.session.as_mut(), recording.as_ref()) {
+            session.vod_url = Some(video.url.clone());
+        }
+
+        if self.is_skipped(EventName::Vod) {
+            self.unpin(webhook).await;
+            self.segments.clear();
+            self.offline_timestamp = None;
+            self.raid = None;
+            return Ok(true);
+        }
+
+        let start_segment = self.segments.first().expect("Offline without any segments");
+
+        // Channels without VODs get a summary with only the duration and the games
+        let has_vods = self.has_vods();
+        let vid = start_segment.video_id.as_ref();
+        let vod = if vid.is_empty() || !has_vods {
+            None
+        } else {
+            match client.get_video_by_id(vid).await {
+                Ok(video) => Some(video),
+                Err(e) => {
+                    log::error!("[{}] Failed to get VOD for offline stream: {}", self.user_name, e);
+                    None
+                }
+            }
+        };
+
+        // Reconnects start a new VOD, so each part has its own markers and duration
+        let parts = self.video_ids();
+
+        // Markers require a broadcaster token, so this only works for some setups
+        let mut markers = Vec::with_capacity(parts.len());
+        for id in &parts {
+            match client.get_stream_markers(id).await {
+                Ok(list) => markers.push((id.clone(), list)),
+                Err(e) => {
+                    log::debug!("[{}] Could not fetch stream markers: {}", self.user_name, e);
+                }
+            }
+        }
+
+        let mention = self.get_mention(EventName::Vod);
+        let mut embed = match self.source {
+            Some(ref source) => embeds::source_vod_embed(recording.as_ref(), source.color()),
+            None if has_vods => embeds::vod_embed(vod.as_ref()),
+            None => EmbedBuilder::new().color(embeds::TWITCH_COLOR),
+        };
+        embed = self.set_footer(embed, &self.config.discord.role_name.vod);
+
+        let vods = if parts.is_empty() {
+            Vec::new()
+        } else {
+            client
+                .get_videos(parts.iter().map(ToString::to_string).collect())
+                .await
+                .unwrap_or_default()
+        };
+        let duration: VideoDuration = if let Some(video) = recording.as_ref().filter(|video| video.duration > 0) {
+            VideoDuration::from_secs(u32::try_from(video.duration).unwrap_or(u32::MAX))
+        } else if vods.is_empty() {
+            // Without a video, the duration is measured by the polls
+            VideoDuration::from_secs(self.session.as_ref().map_or(0, StreamSession::duration))
+        } else {
+            vods.iter().map(|v| v.duration).sum()
+        };
+
+        let format = self.config.discord.time_format();
+        // The channels of other platforms are configured by their id, which is no name to show
+        let user_name = match self.source {
+            Some(_) => self.display_name(),
+            None => self.user_name.as_ref(),
+        };
+        let text = i18n::render(
+            self.messages().vod_from,
+            &[
+                ("user", self.escape(user_name).as_ref()),
+                ("duration", format.duration(duration.as_secs()).as_str()),
+            ],
+        );
+        let content = format!("{mention} {text}");
+
+        let thumbnail_url = match (&vod, &recording) {
+            (Some(video), _) => Some(video.thumbnail_url.as_ref()),
+            (None, Some(video)) => Some(video.thumbnail_url.as_ref()),
+            (None, None) => None,
+        };
+        let thumbnail = match thumbnail_url {
+            Some(url) => self.thumbnail(client, url).await,
+            None => None,
+        };
+
+        // Build the timestamp index for each segment of the stream
+        let chapters = self.chapters(&markers);
+        let timestamps: Vec<String> = chapters
+            .iter()
+            .map(|c| match c.part {
+                // The timestamps start again in every part
+                Some(part) => format!("Part {part} {} {}", c.vod_link(), self.escape(&c.title)),
+                None => format!("{} {}", c.vod_link(), self.escape(&c.title)),
+            })
+            .collect();
+
+        let mut files = Vec::new();
+        let mut notification = NotificationBuilder::new(embed);
+        if has_vods {
+            let truncated;
+            (notification, truncated) = notification.lines("Timestamps", &timestamps, 4, "*Full list attached*", true);
+            if truncated {
+                // Attach the full list instead of dropping the remaining chapters
+                files.push(Chapter::export(&chapters, self.config.discord.chapter_format));
+            }
+        }
+
+        if self.config.discord.vod_collage {
+            files.extend(self.collage(client).await);
+        }
+
+        let segments = self.segment_starts();
+        let raid = self.raid.take();
+        self.segments.clear();
+        self.offline_timestamp = None;
+
+        // Link every part of the session with its duration
+        if parts.len() > 1 {
+            let lines: String = parts
+                .iter()
+                .enumerate()
+                .map(|(i, id)| {
+                    let duration = vods
+                        .iter()
+                        .find(|video| video.id == *id)
+                        .map_or_else(String::new, |video| {
+                            format!(" ({})", format.duration(video.duration.as_secs()))
+                        });
+                    format!("Part {}: {}{duration}\n", i + 1, video_url(id))
+                })
+                .collect();
+            notification = notification.field(EmbedFieldBuilder::new("VODs", lines));
+        }
+
+        if let Some(raid) = raid {
+            let name = self.escape(&raid.name);
+            let target = if self.config.raids.link {
+                format!("[{name}](https://twitch.tv/{})", raid.login)
+            } else {
+                name.into_owned()
+            };
+            let value = format!("{target} with {} viewers", raid.viewers);
+            notification = notification.field(EmbedFieldBuilder::new("Raided", value).inline());
+        }
+
+        let games = self.session.as_ref().map(|session| session.games.as_slice());
+        if let Some(field) = embeds::game_times_field(games.unwrap_or_default(), self.config.discord.escape) {
+            notification = notification.field(field);
+        }
+
+        // The first few clips are shown in the vod embed and the rest in another embed
+        let (top, more) = clips.split_at(clips.len().min(embeds::CLIPS_PER_EMBED));
+        for field in embeds::highlight_fields(top, &segments) {
+            notification = notification.field(field);
+        }
+
+        if !more.is_empty() {
+            (notification, _) = notification
+                .embed(EmbedBuilder::new().color(embeds::TWITCH_COLOR))
+                .lines(
+                    "More Clips",
+                    &embeds::clip_lines(more, top.len()),
+                    usize::MAX,
+                    "",
+                    false,
+                );
+        }
+
+        let messages = self.messages_of(content, &mention, notification, thumbnail, files);
+        // The VOD archive gets a thread for this stream, tagged with the games played
+        let post = self.config.discord.vod_forum.is_some().then(|| {
+            let mut messages = messages.clone();
+            // The archive is not a notification, so it starts with the text instead of the mentions
+            if let Some(first) = messages.first_mut() {
+                first.content.clone_from(&text);
+            }
+            let title = match (&vod, &recording) {
+                (Some(video), _) => Some(&video.title),
+                (None, Some(video)) => Some(&video.title),
+                (None, None) => None,
+            };
+            ForumPost {
+                name: match title {
+                    Some(title) => format!("{}: {title}", self.display_name()),
+                    None => self.display_name().to_owned(),
+                },
+                tags: games
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|game| game.name.to_string())
+                    .collect(),
+                messages,
+            }
+        });
+        let sent = self.deliver(webhook, EventName::Vod, messages).await;
+        self.unpin(webhook).await;
+        if let Some(post) = post {
+            if let Err(e) = webhook.archive(post).await {
+                log::error!(
+                    "[{}] Failed to post the VOD summary to the forum: {}",
+                    self.user_name,
+                    e
+                );
+            }
+        }
+
+        let delays = &self.config.twitch.vod_recheck;
+        if let (None, Some((sent, message))) = (vod, sent) {
+            if has_vods && !delays.is_empty() {
+                let mut delays: Vec<Duration> = delays.iter().map(|delay| delay.0).collect();
+                delays.sort();
+                self.recheck = Some(VodRecheck {
+                    user_name: self.user_name.clone(),
+                    user_id: self.user_id.clone(),
+                    started_at: self.start_timestamp.timestamp().as_seconds(),
+                    sent,
+                    message,
+                    delays,
+                });
+            }
+        }
+        Ok(true)
+    }
+
+    async fn send(
+        &self,
+        webhook: &dyn Notifier,
+        content: String,
+        mention: &Mention,
+        notification: NotificationBuilder,
+        thumbnail: Option<Thumbnail>,
+        files: Vec<MessageFile>,
+    ) -> Option<(SentMessage, WebhookMessage)> {
+        let messages = self.messages_of(content, mention, notification, thumbnail, files);
+        self.deliver(webhook, mention.event, messages).await
+    }
+
+    /// Builds the webhook messages of the notification, which has more messages if its embeds don't fit into one.
+    fn messages_of(
+        &self,
+        mut content: String,
+        mention: &Mention,
+        mut notification: NotificationBuilder,
+        thumbnail: Option<Thumbnail>,
+        mut files: Vec<MessageFile>,
+    ) -> Vec<WebhookMessage> {
+        if let Some(thumbnail) = thumbnail {
+            notification = notification.thumbnail(thumbnail, &mut files);
+        }
+
+        let discord = &self.config.discord;
+        let mut avatar_url = discord.avatar_url.as_deref().map(str::to_owned);
+        match (discord.profile_image, self.profile_image.as_deref()) {
+            (ProfileImage::Avatar, Some(url)) => avatar_url = Some(url.to_owned()),
+            (ProfileImage::Author, Some(url)) => notification = notification.author_icon(self.display_name(), url),
+            _ => {}
+        }
+
+        notification
+            .build()
+            .into_iter()
+            .map(|embeds| WebhookMessage {
+                // The content and files are only part of the first message
+                content: std::mem::take(&mut content),
+                embeds,
+                files: std::mem::take(&mut files),
+                avatar_url: avatar_url.clone(),
+                username: discord.username(self.display_name()),
+                allowed_mentions: mention.allowed_mentions(),
+                live_stream: matches!(mention.event, EventName::Live | EventName::Update).then(|| self.key()),
+            })
+            .collect()
+    }
+
+    /// Sends the messages of a notification in order, and returns the first message once it was sent.
+    async fn deliver(
+        &self,
+        webhook: &dyn Notifier,
+        event: EventName,
+        messages: Vec<WebhookMessage>,
+    ) -> Option<(SentMessage, WebhookMessage)> {
+        let context = event.name();
+        let mut first = None;
+        for message in messages {
+            let copy = first.is_none().then(|| message.clone());
+
+            match webhook.send(message).await {
+                Ok(sent) => {
+                    if let Some(copy) = copy {
+                        first = Some((sent, copy));
+                    }
+                    continue;
+                }
+                Err(SendError::Invalid(err)) => log::error!(
+                    "[{}] Tried to send invalid message for {} event: {:?}",
+                    self.user_name,
+                    context,
+                    err
+                ),
+                Err(err) => log::error!(
+                    "[{}] Failed to send validated embed for {} event: {}",
+                    self.user_name,
+                    context,
+                    err
+                ),
+            }
+
+            // Don't send the remaining embeds without the start of the notification
+            break;
+        }
+        first
+    }
+
+    #[inline]
+    async fn add_segment<'a>(
+        &'a mut self,
+        client: &TwitchClient,
+        stream: &Stream,
+    ) -> Result<&'a mut StreamSegment, RequestError> {
+        // Other platforms only provide the name of the category
+        let game = if self.source.is_some() {
+            category(&stream.game_id)
+        } else {
+            match stream.get_game(client).await {
+                Ok(g) => g,
+                Err(RequestError::Deserialize(e)) => {
+                    log::error!("[{}] Failed to deserialize game: {}", self.user_name, e);
+                    Game::empty()
+                }
+                Err(RequestError::NotFound(_, _)) => Game::empty(),
+                Err(e) => return Err(e),
+            }
+        };
+
+        let segment = StreamSegment::from(client, stream, game, self.has_vods()).await;
+        self.segments.push(segment);
+        Ok(self.segments.last_mut().unwrap())
+    }
+
+    /// The game of each segment with the unix timestamp of its start.
+    fn segment_starts(&self) -> Vec<(Box<str>, i64)> {
+        let started_at = self.start_timestamp.timestamp().as_seconds();
+        self.segments
+            .iter()
+            .map(|s| {
+                // Older segments only know their position in the VOD
+                let start = if s.started_at > 0 {
+                    s.started_at
+                } else {
+                    started_at + i64::from(s.position)
+                };
+                (s.game.name.clone(), start)
+            })
+            .collect()
+    }
+
+    fn create_session(&self, clips: u32) -> StreamSession {
+        let started_at = self.start_timestamp.timestamp().as_seconds();
+        let ended_at = if self.last_seen > 0 {
+            self.last_seen
+        } else {
+            DateTime::utc_now().timestamp().as_seconds()
+        };
+
+        let segments = self.segment_starts();
+
+        let vod_url = self
+            .segments
+            .first()
+            .filter(|s| !s.video_id.is_empty())
+            .map(|s| video_url(&s.video_id).into_boxed_str());
+
+        StreamSession {
+            stream_id: self.stream_id.clone(),
+            user_name: self.user_name.clone(),
+            started_at,
+            ended_at,
+            peak_viewers: self.viewers.peak(),
+            average_viewers: self.viewers.average(),
+            games: GameTime::from_segments(&segments, ended_at),
+            clips,
+            vod_url,
+        }
+    }
+
+    /// The chapters of each VOD part, where the markers of a video are sorted into the segments of that video.
+    fn chapters(&self, markers: &[(Box<str>, Vec<StreamMarker>)]) -> Vec<Chapter> {
+        let parts = self.video_ids();
+        let mut chapters = Vec::with_capacity(self.segments.len());
+        let mut start = 0;
+        while start < self.segments.len() {
+            // The positions of the segments and markers are relative to the start of their video
+            let video_id = &self.segments[start].video_id;
+            let end = self.segments[start..]
+                .iter()
+                .position(|s| s.video_id != *video_id)
+                .map_or(self.segments.len(), |len| start + len);
+
+            let video_markers = markers
+                .iter()
+                .filter(|(id, _)| id == video_id)
+                .flat_map(|(_, list)| list.iter());
+            let mut part: Vec<Chapter> = self.segments[start..end]
+                .iter()
+                .map(Chapter::from_segment)
+                .chain(video_markers.map(|m| Chapter::from_marker(m, video_id)))
+                .collect();
+            part.sort_by_key(|c| c.position);
+
+            let number = parts
+                .iter()
+                .position(|id| id == video_id)
+                .filter(|_| parts.len() > 1)
+                .map(|index| index + 1);
+            for chapter in &mut part {
+                chapter.part = number;
+            }
+
+            chapters.append(&mut part);
+            start = end;
+        }
+        chapters
+    }
+
+    /// The configured mentions of the event, which default to its notification role.
+    #[inline]
+    fn get_mention(&self, event: EventName) -> Mention {
+        self.config.mention(event)
+    }
+
+    /// The mentions for the event, or `None` if the notification is skipped during quiet hours.
+    fn notify_mention(&self, event: EventName) -> Option<Mention> {
+        let now = DateTime::utc_now().timestamp().as_seconds();
+        match policy::delivery(&self.config.discord, event, now) {
+            Delivery::Normal => Some(self.get_mention(event)),
+            Delivery::Silent => Some(Mention::silent(event)),
+            Delivery::Skip => {
+                log::info!(
+                    "[{}] Skipping {} notification during quiet hours",
+                    self.user_name,
+                    event.name()
+                );
+                None
+            }
+        }
+    }
+
+    #[inline]
+    fn is_skipped(&self, event: EventName) -> bool {
+        self.suppressed || self.muted || !self.config.discord.enabled_events.contains(&event)
+    }
+
+    /// Escapes user-controlled text, such as game names, according to the configured mode.
+    #[inline]
+    fn escape<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        escape_markdown(text, self.config.discord.escape)
+    }
+
+    #[inline]
+    fn messages(&self) -> &'static Messages {
+        self.config.discord.locale.messages()
+    }
+
+    #[inline]
+    fn set_footer(&self, embed: EmbedBuilder, name: &str) -> EmbedBuilder {
+        if !self.config.discord.show_notify_hints || name.is_empty() {
+            return embed;
+        }
+
+        embed.footer(EmbedFooter {
+            icon_url: None,
+            proxy_icon_url: None,
+            text: i18n::render(self.messages().notify_hint, &[("role", name)]),
+        })
+    }
+
+    /// Continues the current session with the stream after a reconnect.
+    ///
+    /// Twitch starts a new stream with a new VOD when the connection drops, even if it comes back within the grace period.
+    /// This is still the same stream for the viewers, so it only adds segments instead of announcing a new stream.
+    fn merge_stream(&mut self, stream: &Stream) {
+        log::info!(
+            "[{}] Stream reconnected with new stream id {}, continuing the current session",
+            self.user_name,
+            stream.id
+        );
+        self.stream_id = stream.id.clone();
+    }
+
+    /// The distinct VODs of the session, in order.
+    fn video_ids(&self) -> Vec<Box<str>> {
+        let mut ids: Vec<Box<str>> = Vec::new();
+        for segment in &self.segments {
+            if !segment.video_id.is_empty() && !ids.contains(&segment.video_id) {
+                ids.push(segment.video_id.clone());
+            }
+        }
+        ids
+    }
+
+    /// The preview image for the notification, which is linked instead of uploaded with `link_thumbnails`.
+    async fn thumbnail(&self, client: &TwitchClient, url: &str) -> Option<Thumbnail> {
+        if !self.config.discord.thumbnails || url.is_empty() {
+            None
+        } else if self.config.discord.link_thumbnails {
+            // The timestamp avoids outdated previews from the CDN cache, like for the downloads
+            let now = DateTime::utc_now().timestamp().as_seconds();
+            Some(Thumbnail::Link(format!("{}?t={now}", resolve_thumbnail_url(url))))
+        } else if let Some(ref source) = self.source {
+            match source.get_thumbnail(url).await {
+                Ok(image) => Some(Thumbnail::Attachment(image)),
+                Err(e) => {
+                    // The link still shows the preview, just without keeping the image of this moment
+                    log::debug!(
+                        "[{}] Could not download thumbnail, linking it instead: {e}",
+                        self.user_name
+                    );
+                    Some(Thumbnail::Link(url.to_owned()))
+                }
+            }
+        } else {
+            client.get_thumbnail(url).await.ok().map(Thumbnail::Attachment)
+        }
+    }
+
+    /// The embed of the live stream, which links the stream on its platform.
+    fn stream_embed(&self, stream: &Stream, game: &Game, started_at: i64) -> EmbedBuilder {
+        let embed = notifications::stream_embed(stream, game, started_at, &self.config.discord);
+        match self.source {
+            Some(ref source) => {
+                let url = source.stream_url(&stream.user_login, &stream.id);
+                embed.color(source.color()).title(&url).url(url)
+            }
+            None => embed,
+        }
+    }
+
+    /// The video of the ended stream on another platform, if the platform kept one.
+    async fn recording(&self) -> Option<SourceVideo> {
+        let source = self.source.as_ref()?;
+        // A reconnect starts a new stream, so the first one has the video of the session start
+        let stream_id = &self.segments.first()?.stream_id;
+        match source.get_video(&self.user_name, stream_id).await {
+            Ok(video) => video,
+            Err(e) => {
+                log::error!("[{}] Failed to get the video of the stream: {e}", self.user_name);
+                None
+            }
+        }
+    }
+
+    /// The display name of the streamer, which is only known once the stream started.
+    fn display_name(&self) -> &str {
+        if self.display_name.is_empty() {
+            &self.user_name
+        } else {
+            &self.display_name
+        }
+    }
+
+    /// The profile image of the streamer, if the notifications show it.
+    async fn fetch_profile_image(&self, client: &TwitchClient) -> Option<Box<str>> {
+        if self.config.discord.profile_image == ProfileImage::None {
+            return None;
+        }
+
+        match client.get_users_by_id(&[self.user_id.clone()]).await {
+            Ok(users) => users
+                .into_iter()
+                .next()
+                .map(|user| user.profile_image_url)
+                .filter(|url| !url.is_empty()),
+            Err(e) => {
+                log::warn!("[{}] Could not fetch profile image: {e}", self.user_name);
+                None
+            }
+        }
+    }
+
+    /// Renders the box art of the games played into a grid, for an overview of the stream.
+    async fn collage(&self, client: &TwitchClient) -> Option<MessageFile> {
+        let mut urls: Vec<String> = Vec::new();
+        for url in self.segments.iter().filter_map(|s| s.game.box_art()) {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+
+        let mut images = Vec::new();
+        for url in urls.iter().take(collage::MAX_TILES) {
+            match client.get_thumbnail(url).await {
+                Ok(image) => images.push(image),
+                Err(e) => log::warn!("[{}] Failed to fetch box art for the collage: {}", self.user_name, e),
+            }
+        }
+
+        // A single game is already shown in the embed
+        if images.len() < 2 {
+            return None;
+        }
+
+        // Decoding and encoding the images takes a moment, which should not block the other watchers
+        match tokio::task::spawn_blocking(move || collage::render(&images)).await {
+            Ok(Some(data)) => Some(MessageFile {
+                filename: "games.jpg".to_owned(),
+                data,
+            }),
+            Ok(None) => None,
+            Err(e) => {
+                log::error!("[{}] Failed to render the collage: {}", self.user_name, e);
+                None
+            }
+        }
+    }
+
+    /// Attempts to fetch VOD links for segments which don't have any yet.
+    async fn relink(&mut self, stream: &Stream, client: &TwitchClient) -> bool {
+        if !self.has_vods() {
+            return false;
+        }
+
+        let mut changed = false;
+        for segment in &mut self.segments {
+            // We will not attempt to link a vod if its too old,
+            // otherwise we end up with linearly increasing numbers of fetch requests to do.
+            if segment.video_id.is_empty() && segment.stream_id == stream.id && segment.position < 120 {
+                if let Ok(video) = client.get_video_by_stream(stream).await {
+                    segment.video_id = video.id;
+                    changed = true;
+                }
+            }
+        }
+
+        // Twitch lists the archive shortly after the stream starts, unless the channel has VODs disabled
+        let live_for = DateTime::utc_now().duration_since(&self.start_timestamp).as_secs();
+        if live_for > VOD_MISSING_AFTER && self.segments.iter().all(|s| s.video_id.is_empty()) {
+            log::info!(
+                "[{}] No VOD after {} minutes, assuming VODs are disabled",
+                self.user_name,
+                live_for / 60
+            );
+            self.vods_missing = true;
+        }
+
+        changed
+    }
+
+    /// Whether the videos of this stream are looked up, which stops for channels with disabled VODs.
+    ///
+    /// Streams of other platforms have no twitch VODs, their recording is fetched once the stream ended.
+    fn has_vods(&self) -> bool {
+        self.source.is_none() && !self.vods_missing && self.config.twitch.has_vods(&self.user_name)
+    }
+}
@@ -0,0 +1,200 @@
+//! Runs the stream watcher against mock Twitch and Discord servers, from going live until the VOD notification.
+
+use std::{sync::Arc, time::Duration};
+
+use discord_api::{http_client, WebhookClient, WebhookParams};
+use serde_json::{json, Value};
+use strumbot_core::{
+    config::Config,
+    watcher::{StreamUpdate, StreamWatcher, WatcherState},
+};
+use twilight_model::id::Id;
+use twitch_api::{
+    oauth::{ClientParams, OauthClient},
+    Stream, TwitchClient,
+};
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const WEBHOOK_PATH: &str = "/api/v10/webhooks/1/token";
+
+async fn mock_server() -> MockServer {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access",
+            "expires_in": 3600,
+            "token_type": "bearer"
+        })))
+        .mount(&server)
+        .await;
+
+    for (id, name) in [("1", "Celeste"), ("2", "Hollow Knight")] {
+        Mock::given(method("GET"))
+            .and(path("/helix/games"))
+            .and(query_param("id", id))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [{"id": id, "name": name, "box_art_url": ""}]
+            })))
+            .mount(&server)
+            .await;
+    }
+
+    Mock::given(method("POST"))
+        .and(path(WEBHOOK_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "2",
+            "channel_id": "3",
+            "author": {"id": "1", "username": "Strumbot", "discriminator": "0000", "avatar": null, "bot": true},
+            "content": "",
+            "timestamp": "2023-01-01T00:00:00.000000+00:00",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0
+        })))
+        .mount(&server)
+        .await;
+
+    // Videos, clips, and markers are optional for the notifications
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": []})))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn config() -> Config {
+    serde_json::from_value(json!({
+        "discord": {
+            "server_id": "81384788765712384",
+            "token": "token",
+            "stream_notifications": "https://discord.com/api/webhooks/1/token",
+            "enabled_events": ["live", "update", "vod"],
+            "thumbnails": false
+        },
+        "twitch": {
+            "client_id": "id",
+            "client_secret": "secret",
+            "user_login": ["Elajjaz"],
+            "top_clips": 0,
+            "offline_grace_period": "0s",
+            "min_update_interval": "0s"
+        }
+    }))
+    .unwrap()
+}
+
+fn stream(game_id: &str) -> Box<Stream> {
+    serde_json::from_value(json!({
+        "id": "40",
+        "game_id": game_id,
+        "title": "Testing",
+        "type": "live",
+        "language": "en",
+        "thumbnail_url": "",
+        "user_id": "50",
+        "user_login": "elajjaz",
+        "user_name": "Elajjaz",
+        "started_at": "2023-01-01T00:00:00Z",
+        "viewer_count": 10
+    }))
+    .unwrap()
+}
+
+async fn sent_messages(server: &MockServer) -> Vec<Value> {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|request| request.url.path() == WEBHOOK_PATH)
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+async fn clients(server: &MockServer) -> (TwitchClient, WebhookClient) {
+    let base = server.uri();
+
+    let oauth = OauthClient::new(ClientParams {
+        client_id: "id".into(),
+        client_secret: "secret".into(),
+    })
+    .with_base_urls(&format!("{base}/helix"), &format!("{base}/oauth2"));
+    let twitch = TwitchClient::new(oauth).await.unwrap();
+
+    let discord = Arc::new(http_client("token".to_owned(), Some(&server.address().to_string())));
+    let webhook = WebhookClient::new(
+        discord,
+        WebhookParams {
+            id: Id::new(1),
+            token: "token".into(),
+        },
+    );
+    (twitch, webhook)
+}
+
+#[tokio::test]
+async fn test_stream_lifecycle() {
+    let server = mock_server().await;
+    let (twitch, webhook) = clients(&server).await;
+
+    let mut watcher = StreamWatcher::new("elajjaz".to_owned(), Arc::new(config()));
+
+    let state = watcher.update(&twitch, &webhook, StreamUpdate::Live(stream("1"))).await;
+    assert!(matches!(state, Ok(WatcherState::Updated)));
+
+    let state = watcher.update(&twitch, &webhook, StreamUpdate::Live(stream("2"))).await;
+    assert!(matches!(state, Ok(WatcherState::Updated)));
+
+    // The first offline update only starts the grace period
+    let state = watcher.update(&twitch, &webhook, StreamUpdate::Offline).await;
+    assert!(matches!(state, Ok(WatcherState::Updated)));
+    let state = watcher.update(&twitch, &webhook, StreamUpdate::Offline).await;
+    assert!(matches!(state, Ok(WatcherState::Ended)));
+
+    let messages = sent_messages(&server).await;
+    let contents: Vec<&str> = messages
+        .iter()
+        .map(|message| message["content"].as_str().unwrap_or_default())
+        .collect();
+    assert_eq!(contents.len(), 3, "unexpected messages: {contents:?}");
+    assert!(contents[0].contains("Elajjaz is live with **Celeste**!"));
+    assert!(contents[1].contains("Elajjaz switched game to **Hollow Knight**!"));
+    assert!(contents[2].contains("Elajjaz"));
+}
+
+#[tokio::test]
+async fn test_update_interval() {
+    let server = mock_server().await;
+    let (twitch, webhook) = clients(&server).await;
+
+    let mut config = config();
+    config.twitch.min_update_interval.0 = Duration::from_secs(60);
+    let mut watcher = StreamWatcher::new("elajjaz".to_owned(), Arc::new(config));
+
+    let state = watcher.update(&twitch, &webhook, StreamUpdate::Live(stream("1"))).await;
+    assert!(matches!(state, Ok(WatcherState::Updated)));
+
+    // The game change is only picked up after the interval
+    let state = watcher.update(&twitch, &webhook, StreamUpdate::Live(stream("2"))).await;
+    assert!(matches!(state, Ok(WatcherState::Unchanged)));
+
+    // Offline updates are never throttled
+    let state = watcher.update(&twitch, &webhook, StreamUpdate::Offline).await;
+    assert!(matches!(state, Ok(WatcherState::Updated)));
+    let state = watcher.update(&twitch, &webhook, StreamUpdate::Offline).await;
+    assert!(matches!(state, Ok(WatcherState::Ended)));
+
+    assert_eq!(sent_messages(&server).await.len(), 2);
+}
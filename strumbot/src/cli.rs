@@ -0,0 +1,282 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::bail;
+use clap::{Parser, Subcommand, ValueEnum};
+use discord_api::{config::EventName, WebhookClient, WebhookMessage, WebhookParams};
+use strumbot_core::{config::Config, embeds, releases, replay::Replay, roles::SavedRoles, simulate, token};
+use tracing as log;
+use twilight_http::Client;
+use twilight_util::builder::embed::EmbedBuilder;
+use twitch_api::{
+    oauth::{ClientParams, OauthClient},
+    TwitchClient,
+};
+
+use crate::init_logging;
+
+#[derive(Parser)]
+#[command(version, about = "Discord notifications for twitch streams")]
+pub struct Cli {
+    /// The configuration file
+    #[arg(long, global = true, env = "STRUMBOT_CONFIG", default_value = "config.json")]
+    pub config: PathBuf,
+    /// The directory of the cache
+    #[arg(long, global = true, env = "STRUMBOT_CACHE_DIR", default_value = ".cache")]
+    pub cache_dir: String,
+    /// Log the notifications instead of sending them to discord
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// The profile of the configuration file, which is applied over the shared settings
+    #[arg(long, global = true, env = "STRUMBOT_PROFILE")]
+    pub profile: Option<String>,
+    /// Append the logs to this file instead of writing them to the terminal
+    #[arg(long, global = true, env = "STRUMBOT_LOG_FILE")]
+    pub log_file: Option<PathBuf>,
+    /// Detach from the terminal and keep running in the background
+    #[cfg(unix)]
+    #[arg(long)]
+    pub daemon: bool,
+    /// Write the process id of the daemon to this file
+    #[cfg(unix)]
+    #[arg(long, requires = "daemon")]
+    pub pid_file: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Default, Clone)]
+pub enum Command {
+    /// Start the bot, which is the default without a command
+    #[default]
+    Run,
+    /// Check the configuration file for errors
+    ValidateConfig,
+    /// Send a test notification to the configured webhooks
+    TestWebhook,
+    /// Find or create the notification roles and show their ids
+    ResolveRoles,
+    /// Delete the cache, which resets the stream watchers, stats, and mutes
+    ClearCache {
+        /// Also delete the stored twitch access tokens
+        #[arg(long)]
+        tokens: bool,
+    },
+    /// Play a recording of twitch streams through the stream watchers, instead of polling twitch
+    Replay {
+        /// The recording, with snapshots of the helix streams endpoint
+        file: PathBuf,
+        /// How many times faster than the recording the snapshots are played
+        #[arg(long, default_value_t = 60)]
+        speed: u32,
+    },
+    /// Send the notification of an event for a fabricated stream, to preview how it looks
+    Simulate {
+        /// The streamer of the fabricated stream
+        #[arg(long)]
+        login: String,
+        /// The event of the notification
+        #[arg(long, value_enum)]
+        event: SimulatedEvent,
+        /// The webhook url for the notification, instead of the stream_notifications webhook
+        #[arg(long)]
+        webhook: Option<WebhookParams>,
+    },
+    /// Manage the windows service, which runs the bot in the background
+    #[cfg(windows)]
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+}
+
+#[cfg(windows)]
+#[derive(Subcommand, Clone, Copy)]
+pub enum ServiceAction {
+    /// Register the service with the current config, cache directory, and log file, which requires an administrator
+    Install,
+    /// Remove the service
+    Uninstall,
+    /// Run as the service, which is only used by the windows service manager
+    Run,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum SimulatedEvent {
+    Live,
+    Update,
+    Vod,
+}
+
+impl From<SimulatedEvent> for EventName {
+    fn from(event: SimulatedEvent) -> Self {
+        match event {
+            SimulatedEvent::Live => EventName::Live,
+            SimulatedEvent::Update => EventName::Update,
+            SimulatedEvent::Vod => EventName::Vod,
+        }
+    }
+}
+
+/// Loads the configuration for a command, with logging set up according to it.
+async fn load(cli: &Cli) -> anyhow::Result<Config> {
+    let mut config = Config::load_profile(&cli.config, cli.profile.as_deref()).await?;
+    config.discord.dry_run.enabled |= cli.dry_run;
+    init_logging(&config.logging, cli.log_file.as_deref());
+    Ok(config)
+}
+
+pub async fn validate_config(cli: &Cli) -> anyhow::Result<()> {
+    let config = load(cli).await?;
+    let problems = config.problems();
+    for problem in &problems {
+        log::error!("{problem}");
+    }
+
+    if !problems.is_empty() {
+        bail!("Found {} problems in {}", problems.len(), cli.config.display());
+    }
+    log::info!("{} is valid", cli.config.display());
+    Ok(())
+}
+
+pub async fn test_webhook(cli: &Cli) -> anyhow::Result<()> {
+    let config = load(cli).await?;
+    let client = Arc::new(Client::new(config.discord.token.to_string()));
+
+    let mut targets = vec![("stream_notifications", config.discord.stream_notifications.clone())];
+    if let Some(ref logging) = config.discord.logging {
+        targets.push(("logging", logging.clone()));
+    }
+
+    let mut failed = 0;
+    for (name, params) in targets {
+        let webhook = WebhookClient::new(Arc::clone(&client), params)
+            .with_trusted_mentions(config.discord.trusted_mentions)
+            .with_dry_run(&config.discord.dry_run);
+        let message = WebhookMessage {
+            content: String::new(),
+            embeds: vec![EmbedBuilder::new()
+                .color(embeds::TWITCH_COLOR)
+                .description(format!("Test notification for the `{name}` webhook"))
+                .build()],
+            files: Vec::new(),
+            avatar_url: config.discord.avatar_url.as_deref().map(str::to_owned),
+            username: None,
+            allowed_mentions: None,
+            live_stream: None,
+        };
+
+        match webhook.execute(&message).await {
+            Ok(_) => log::info!("Sent test notification to the {name} webhook"),
+            Err(e) => {
+                log::error!("Failed to send test notification to the {name} webhook: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} webhooks failed");
+    }
+    Ok(())
+}
+
+pub async fn resolve_roles(cli: &Cli) -> anyhow::Result<()> {
+    let mut config = load(cli).await?;
+    let client = Client::new(config.discord.token.to_string());
+    // The saved roles are only updated by the bot itself
+    for problem in config.init_roles(&client, &SavedRoles::new()).await? {
+        log::warn!("{problem}");
+    }
+
+    for event in ["live", "update", "vod"] {
+        match config.get_role(event) {
+            Some(id) => log::info!("Role for {event} event: {id}"),
+            None => log::warn!("No role for {event} event"),
+        }
+    }
+    Ok(())
+}
+
+pub async fn clear_cache(cli: &Cli, tokens: bool) -> anyhow::Result<()> {
+    // Only loaded for the logging configuration
+    load(cli).await?;
+    let mut entries = match tokio::fs::read_dir(&cli.cache_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::info!("Cache is already empty");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut count = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let key = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        if !tokens && (key == token::APP_TOKEN || key == token::USER_TOKEN) {
+            continue;
+        }
+
+        tokio::fs::remove_file(&path).await?;
+        count += 1;
+    }
+
+    log::info!("Deleted {count} cache entries");
+    Ok(())
+}
+
+pub async fn replay(cli: &Cli, file: &Path, speed: u32) -> anyhow::Result<()> {
+    let mut config = load(cli).await?;
+    // The grace period and the update interval pass in real time, so they would outlast the accelerated recording
+    config.twitch.offline_grace_period.0 = Duration::ZERO;
+    config.twitch.offline_grace_periods.clear();
+    config.twitch.min_update_interval.0 = Duration::ZERO;
+
+    let replay = Replay::load(file).await?;
+    let client = Arc::new(Client::new(config.discord.token.to_string()));
+    let webhook = WebhookClient::new(client, config.discord.stream_notifications.clone())
+        .with_trusted_mentions(config.discord.trusted_mentions)
+        .with_dry_run(&config.discord.dry_run);
+    let oauth = OauthClient::with_http(
+        ClientParams {
+            client_id: config.twitch.client_id.clone(),
+            client_secret: config.twitch.client_secret.clone(),
+        },
+        config.network.build_client(releases::VERSION)?,
+    );
+    let twitch = TwitchClient::new(oauth).await?;
+
+    replay.run(&twitch, &webhook, &Arc::new(config), speed).await;
+    Ok(())
+}
+
+pub async fn simulate(
+    cli: &Cli,
+    login: &str,
+    event: SimulatedEvent,
+    webhook: Option<WebhookParams>,
+) -> anyhow::Result<()> {
+    let config = load(cli).await?;
+    let client = Arc::new(Client::new(config.discord.token.to_string()));
+    let params = webhook.unwrap_or_else(|| config.discord.stream_notifications.clone());
+    let webhook = WebhookClient::new(client, params)
+        .with_trusted_mentions(config.discord.trusted_mentions)
+        .with_dry_run(&config.discord.dry_run);
+    let oauth = OauthClient::with_http(
+        ClientParams {
+            client_id: config.twitch.client_id.clone(),
+            client_secret: config.twitch.client_secret.clone(),
+        },
+        config.network.build_client(releases::VERSION)?,
+    );
+    let twitch = TwitchClient::new(oauth).await?;
+
+    simulate::simulate(&twitch, &webhook, config, login, event.into()).await?;
+    log::info!("Sent the simulated notification for {login}");
+    Ok(())
+}
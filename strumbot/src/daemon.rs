@@ -0,0 +1,30 @@
+use std::{fs::OpenOptions, path::Path};
+
+use anyhow::Context;
+use daemonize::Daemonize;
+
+/// Detaches the process from the terminal, so it keeps running in the background after the shell exits.
+///
+/// This has to happen before the tokio runtime is started, since forking only keeps the current thread.
+/// The output which is not written by the logger, like panics, goes to the log file if there is one.
+pub fn detach(log_file: Option<&Path>, pid_file: Option<&Path>) -> anyhow::Result<()> {
+    // The relative paths of the config and cache stay valid
+    let mut daemon = Daemonize::new().working_directory(std::env::current_dir()?);
+    if let Some(path) = pid_file {
+        daemon = daemon.pid_file(path);
+    }
+
+    if let Some(path) = log_file {
+        let open = || {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))
+        };
+        daemon = daemon.stdout(open()?).stderr(open()?);
+    }
+
+    daemon.start().context("Failed to start the daemon")?;
+    Ok(())
+}
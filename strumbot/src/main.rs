@@ -1,232 +1,451 @@
-use config::Config;
-use database_api::{Database, DatabaseError, FileDatabase};
-use discord_api::{Gateway, WebhookClient};
-use futures::FutureExt;
+use clap::Parser;
+use cli::{Cli, Command};
+use database_api::{BatchedDatabase, EncryptedDatabase, FileDatabase};
+use discord_api::{
+    logging::{self, LogForwarder},
+    Gateway, SendQueue, WebhookClient, WebhookMessage,
+};
+use kick_api::KickClient;
 use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
-    time::{Duration, Instant},
+    fs::OpenOptions,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use strumbot_core::{
+    commands::CommandHandler,
+    config::{self, Config, LogFormat, LoggingConfig},
+    health::{self, Health},
+    outbox::Outbox,
+    releases, roles, schedule,
+    token::{self, CachedToken},
+    Bot, Cache, Notifier,
 };
-use tokio::{fs, sync::mpsc, time::sleep};
+use tokio::sync::mpsc;
 use tracing as log;
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, prelude::*, EnvFilter};
 use twilight_http::Client;
+use twilight_util::builder::embed::EmbedBuilder;
 use twitch_api::{
-    oauth::{ClientParams, OauthClient},
+    oauth::{ClientParams, OauthClient, TokenStore},
     TwitchClient,
 };
-use watcher::{StreamUpdate, StreamWatcher, WatcherState};
+use youtube_api::YoutubeClient;
+
+mod cli;
+#[cfg(unix)]
+mod daemon;
+mod panics;
+#[cfg(windows)]
+mod service;
+
+/// The cache files, which are encrypted and written at most once per interval
+type Store = BatchedDatabase<EncryptedDatabase<FileDatabase>>;
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    #[cfg(windows)]
+    if let Some(Command::Service { action }) = cli.command {
+        // The service itself logs to the file of its launch arguments
+        if !matches!(action, cli::ServiceAction::Run) {
+            init_logging(&LoggingConfig::default(), None);
+        }
+        return service::handle(&cli, action);
+    }
+
+    #[cfg(unix)]
+    if cli.daemon {
+        daemon::detach(cli.log_file.as_deref(), cli.pid_file.as_deref())?;
+    }
 
-mod config;
-mod errors;
-mod watcher;
+    block_on(cli)
+}
 
-type Cache = FileDatabase;
+/// Runs the command on a new runtime, which is only started after the process was detached.
+fn block_on(cli: Cli) -> anyhow::Result<()> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(start(cli))
+}
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
+async fn start(cli: Cli) -> anyhow::Result<()> {
+    match cli.command.clone().unwrap_or_default() {
+        Command::Run => run(&cli).await,
+        Command::ValidateConfig => cli::validate_config(&cli).await,
+        Command::TestWebhook => cli::test_webhook(&cli).await,
+        Command::ResolveRoles => cli::resolve_roles(&cli).await,
+        Command::ClearCache { tokens } => cli::clear_cache(&cli, tokens).await,
+        Command::Replay { file, speed } => cli::replay(&cli, &file, speed).await,
+        Command::Simulate { login, event, webhook } => cli::simulate(&cli, &login, event, webhook).await,
+        #[cfg(windows)]
+        Command::Service { .. } => run(&cli).await,
+    }
+}
 
-    let config: String = match tokio::fs::read_to_string("config.json").await {
-        Ok(conf) => conf,
+async fn run(cli: &Cli) -> anyhow::Result<()> {
+    let mut config = match Config::load_profile(&cli.config, cli.profile.as_deref()).await {
+        Ok(config) => config,
         Err(e) => {
-            log::error!("Failed to read config.json: {}", e);
+            init_logging(&LoggingConfig::default(), cli.log_file.as_deref());
+            log::error!("{e:#}");
             return Ok(());
         }
     };
+    config.discord.dry_run.enabled |= cli.dry_run;
+    let dry_run = config.discord.dry_run.enabled;
 
-    let Ok(mut config) = serde_json::from_str::<Config>(&config) else {
-        panic!("Failed to parse config.json");
-    };
+    let log_forwarder = init_logging(&config.logging, cli.log_file.as_deref());
 
-    let cache = Arc::new(Cache::new(".cache".into()));
-    if config.cache.enabled {
-        cache.setup().await?;
+    // The announced streams are always stored, to avoid duplicate notifications after a restart
+    let files = FileDatabase::new(cli.cache_dir.clone()).with_compression(config.cache.compress);
+    let files = match EncryptedDatabase::from_env(files) {
+        Ok(files) => files,
+        Err(e) => {
+            log::error!("Invalid cache encryption key: {e}");
+            return Ok(());
+        }
+    };
+    if files.is_encrypted() {
+        log::info!(
+            "Encrypting the cache with the key from {}",
+            EncryptedDatabase::<FileDatabase>::KEY_VARIABLE
+        );
+    }
+    let store = Arc::new(Store::new(files, config.cache.write_interval.0));
+    let flush = Arc::clone(&store);
+    tokio::spawn(async move { flush.run().await });
+    tokio::spawn(shutdown(Arc::clone(&store)));
+    if let Err(e) = store.inner().inner().setup().await {
+        log::error!("Failed to create cache directory: {e}");
+        if config.cache.enabled {
+            return Err(e.into());
+        }
     }
 
+    let cache = Arc::new(Cache::new(Arc::clone(&store)));
+
     // Discord setup
 
     log::info!("Connecting to Discord...");
 
     let discord_client = Arc::new(Client::new(config.discord.token.to_string()));
-    if let Err(e) = config.init_roles(&discord_client).await {
+    if let Err(e) = config::verify_token(&discord_client).await {
         log::error!("Failed to setup discord: {}", e);
         return Ok(());
     }
+    // Shared by all webhooks, since rate limits apply to the whole bot
+    let send_queue = Arc::new(SendQueue::new());
+    // Sends warnings about the setup as embeds, which stand out more than the forwarded logs
+    let mut alerts = None;
+    if dry_run {
+        log::info!("Running in dry run mode, notifications are only logged and commands are disabled");
+    } else if let Some(ref params) = config.discord.logging {
+        let webhook = WebhookClient::new(Arc::clone(&discord_client), params.clone())
+            .with_trusted_mentions(config.discord.trusted_mentions);
+        if !check_webhook("logging", &webhook).await {
+            return Ok(());
+        }
+        log_forwarder.start(webhook.with_queue(Arc::clone(&send_queue)));
+        panics::install(config.discord.token.clone(), params.clone());
+        alerts =
+            Some(WebhookClient::new(Arc::clone(&discord_client), params.clone()).with_queue(Arc::clone(&send_queue)));
+    }
 
-    let config = Arc::new(config);
+    let webhook_params = config.discord.stream_notifications.clone();
+    let (failure_sender, failure_receiver) = mpsc::unbounded_channel();
+    let mut webhook = WebhookClient::new(Arc::clone(&discord_client), webhook_params)
+        .with_queue(Arc::clone(&send_queue))
+        .with_crosspost(config.discord.crosspost)
+        .with_trusted_mentions(config.discord.trusted_mentions)
+        .with_forum(config.discord.vod_forum)
+        .with_dry_run(&config.discord.dry_run);
+    if !check_webhook("stream_notifications", &webhook).await {
+        return Ok(());
+    }
+    webhook.set_failure_queue(failure_sender);
+    let webhook = Arc::new(webhook);
 
-    if config.discord.enable_command {
-        let gateway = Gateway::new(Arc::clone(&discord_client), Arc::new(config.discord.clone()));
-        tokio::spawn(gateway.run());
+    let saved_roles = roles::load(&cache).await;
+    match config.init_roles(&discord_client, &saved_roles).await {
+        Ok(problems) if !problems.is_empty() => report_setup_problems(alerts.as_ref(), &problems).await,
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Failed to setup discord: {}", e);
+            return Ok(());
+        }
+    }
+    if !dry_run {
+        roles::save(&cache, &config.saved_roles()).await;
     }
 
-    let webhook_params = config.discord.stream_notifications.clone();
-    let webhook = Arc::new(WebhookClient::new(discord_client, webhook_params));
+    let config = Arc::new(config);
 
-    let mut watchers = HashMap::with_capacity(config.twitch.user_login.len());
+    let mut gateway_status = None;
+    let (command_sender, command_receiver) = mpsc::channel(10);
+    if config.discord.enable_command && !dry_run {
+        let gateway =
+            Gateway::new(Arc::clone(&discord_client), Arc::new(config.discord.clone())).with_handler(command_sender);
+        gateway_status = Some(gateway.connection_status());
+        tokio::spawn(async move {
+            if let Err(e) = gateway.run().await {
+                log::error!("Discord gateway stopped, commands are unavailable until the bot is restarted: {e}");
+            }
+        });
+    }
 
     // Twitch setup
 
     log::info!("Connecting to Twitch...");
 
-    let oauth = OauthClient::new(ClientParams {
-        client_id: config.twitch.client_id.clone(),
-        client_secret: config.twitch.client_secret.clone(),
-    });
+    let http = match config.network.build_client(releases::VERSION) {
+        Ok(http) => http,
+        Err(e) => {
+            log::error!("Invalid network configuration: {e}");
+            return Ok(());
+        }
+    };
 
-    let client = Arc::new(TwitchClient::new(oauth).await?);
+    if config.update_check.enabled {
+        tokio::spawn(releases::check_updates(
+            config.update_check.clone(),
+            http.clone(),
+            alerts,
+        ));
+    }
 
+    let oauth = OauthClient::with_http(
+        ClientParams {
+            client_id: config.twitch.client_id.clone(),
+            client_secret: config.twitch.client_secret.clone(),
+        },
+        http.clone(),
+    )
+    .with_credentials(config.twitch.additional_credentials.clone());
+
+    // The token is stored in the cache directory, so it is only persisted if the cache is enabled
+    let token_store: Option<Box<dyn TokenStore>> = if config.cache.enabled {
+        Some(Box::new(CachedToken::new(Arc::clone(&cache), token::APP_TOKEN)))
+    } else {
+        None
+    };
+    let mut client = TwitchClient::with_token_store(oauth, token_store)
+        .await?
+        .with_cache_config(&config.twitch.cache);
     if config.cache.enabled {
-        if let Err(err) = load_cache(&mut watchers, &config, &client, &webhook, &cache).await {
-            log::error!("Could not load cache: {}", err);
-        }
+        client = client.with_user_token_store(Box::new(CachedToken::new(Arc::clone(&cache), token::USER_TOKEN)));
+    }
+    let client = Arc::new(client);
+
+    let scopes = &config.twitch.user_scopes;
+    if config.twitch.user_auth && !client.restore_user_auth(scopes).await {
+        let client = Arc::clone(&client);
+        let scopes = scopes.clone();
+        // Runs in the background, since the user might take a while to enter the code
+        tokio::spawn(async move {
+            if let Err(e) = client.authorize_user(&scopes).await {
+                log::error!("Failed to authorize twitch user: {e}");
+            }
+        });
     }
 
-    log::info!("Listening for streams from {:?}", config.twitch.user_login);
+    let health = Arc::new(Health::new(
+        Arc::clone(&client),
+        gateway_status,
+        config.health.max_poll_age,
+    ));
+    let mut builder = Bot::builder()
+        .config(Arc::clone(&config))
+        .database(Arc::clone(&cache))
+        .notifier(Arc::clone(&webhook) as Arc<dyn Notifier>)
+        .twitch(Arc::clone(&client))
+        .health(Arc::clone(&health));
+
+    if let Some(params) = config.discord.logging.as_ref().filter(|_| !dry_run) {
+        let alerts =
+            WebhookClient::new(Arc::clone(&discord_client), params.clone()).with_queue(Arc::clone(&send_queue));
+        builder = builder.alerts(Arc::new(alerts));
+    }
 
-    loop {
-        log::debug!("Fetching streams {:?}", config.twitch.user_login);
-        watchers.retain(|_, watcher| !watcher.is_closed());
+    if let Some(ref youtube) = config.youtube {
+        builder = builder.source(
+            Arc::new(YoutubeClient::with_http(youtube.api_key.clone(), http.clone())),
+            youtube.channel_ids.clone(),
+            Duration::from_secs(youtube.poll_interval),
+        );
+    }
 
-        // 1. Fetch streams in batch
-        let streams = client.get_streams_by_login(&config.twitch.user_login).await?;
+    if let Some(ref kick) = config.kick {
+        builder = builder.source(
+            Arc::new(KickClient::with_http(http.clone())),
+            kick.user_login.clone(),
+            Duration::from_secs(kick.poll_interval),
+        );
+    }
 
-        // 2. Check which streams are offline/missing
-        let mut offline: HashSet<String> = config.twitch.user_login.iter().map(|s| s.to_lowercase()).collect();
+    let bot = builder.build().await?;
+    let outbox = Outbox::load(
+        Arc::clone(&webhook),
+        Arc::clone(&cache),
+        config.cache.enabled,
+        bot.status(),
+    )
+    .await;
+    tokio::spawn(outbox.run(failure_receiver));
+
+    if config.health.enabled {
+        let api = config.health.api.then(|| bot.status());
+        tokio::spawn(health::serve(Arc::clone(&health), config.health.address.clone(), api));
+    }
 
-        // 3. Send updates for all currently live streams
-        for stream in streams {
-            let name = stream.user_login.to_lowercase();
-            offline.remove(&name);
-            if let Some(send) = watchers.get_mut(&name) {
-                push(send, StreamUpdate::Live(Box::new(stream))).await;
-            } else {
-                let watcher = StreamWatcher::new(name.to_string(), Arc::clone(&config));
-                let send = start_watcher(config.cache.enabled, &client, &webhook, &cache, watcher);
-                push(&send, StreamUpdate::Live(Box::new(stream))).await;
-                watchers.insert(name, send);
-            }
-        }
+    let handler = CommandHandler::new(
+        bot.commands(),
+        bot.status(),
+        Arc::clone(&health),
+        Arc::clone(&client),
+        Arc::clone(&config),
+        Arc::clone(&cache),
+    );
+    tokio::spawn(handler.run(command_receiver));
+
+    if let Some(schedule) = config.schedule.as_ref().filter(|_| !dry_run) {
+        tokio::spawn(schedule::maintain(
+            schedule.clone(),
+            Arc::clone(&discord_client),
+            Arc::clone(&client),
+            bot.status(),
+            Arc::clone(&cache),
+            config.cache.enabled,
+            config.discord.time_format(),
+        ));
+    }
 
-        log::debug!("Offline streams are: {:?}", offline);
+    bot.run().await
+}
 
-        // 4. Send updates for all streams that are offline
-        for name in offline {
-            if let Some(send) = watchers.get_mut(&name) {
-                push(send, StreamUpdate::Offline).await;
-            }
+/// Installs the global tracing subscriber. The `RUST_LOG` environment variable takes precedence over the config.
+///
+/// The logs are appended to the log file if there is one, and written to stdout otherwise.
+fn init_logging(config: &LoggingConfig, log_file: Option<&Path>) -> LogForwarder {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => config
+            .filters
+            .iter()
+            .map(|(module, level)| format!("{module}={level}"))
+            .fold(
+                EnvFilter::new(config.level.as_ref()),
+                |filter, directive| match directive.parse() {
+                    Ok(directive) => filter.add_directive(directive),
+                    Err(_) => filter,
+                },
+            ),
+    };
+
+    let mut file_error = None;
+    let writer = match log_file.map(|path| (path, OpenOptions::new().create(true).append(true).open(path))) {
+        Some((_, Ok(file))) => BoxMakeWriter::new(Mutex::new(file)),
+        Some((path, Err(e))) => {
+            file_error = Some(format!("Failed to open log file {}: {e}", path.display()));
+            BoxMakeWriter::new(std::io::stdout)
         }
+        None => BoxMakeWriter::new(std::io::stdout),
+    };
 
-        // 5. Refresh oauth token if needed and wait 10 seconds for next poll event
-        tokio::try_join!(client.refresh_auth(), sleep(Duration::from_secs(10)).map(Result::Ok))?;
+    let (webhook_layer, log_forwarder) = logging::webhook_layer();
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(log_file.is_none());
+    let (json, pretty) = match config.format {
+        LogFormat::Json => (Some(fmt.json()), None),
+        LogFormat::Pretty => (None, Some(fmt)),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(json)
+        .with(pretty)
+        .with(webhook_layer)
+        .init();
+
+    if let Some(e) = file_error {
+        log::error!("{e}");
     }
+    log_forwarder
 }
 
-fn start_watcher(
-    cache_enabled: bool,
-    client: &Arc<TwitchClient>,
-    webhook: &Arc<WebhookClient>,
-    db: &Arc<Cache>,
-    mut watcher: StreamWatcher,
-) -> mpsc::Sender<StreamUpdate> {
-    let (send, mut receive) = mpsc::channel(2);
-    let twitch = Arc::clone(client);
-    let webhook = Arc::clone(webhook);
-    let db = Arc::clone(db);
-
-    tokio::spawn(async move {
-        let key = watcher.user_name.to_lowercase();
-
-        let mut next_update = Instant::now();
-
-        while let Some(event) = receive.recv().await {
-            if next_update.elapsed().is_zero() {
-                continue;
-            }
-
-            let result = watcher.update(&twitch, &webhook, event).await;
-            match result {
-                Ok(WatcherState::Ended) => {
-                    break;
-                }
-                Err(e) => {
-                    log::error!("[{key}] Error when updating stream watcher: {e:?}");
-                }
-                Ok(WatcherState::Updated) => {
-                    if cache_enabled {
-                        // Save the current watcher state to cache file
-                        match db.save(&key, &watcher).await {
-                            Err(DatabaseError::Io(e)) => {
-                                log::error!("[{key}] Failed to save cache: {e:?}");
-                            }
-                            Err(DatabaseError::Serde(e)) => {
-                                log::error!("[{key}] Could not serialize watcher: {e:?}");
-                            }
-                            Ok(_) => {}
-                        }
-                    }
-
-                    // Wait a minute before updating again to avoid weird twitch api issues
-                    next_update = Instant::now() + Duration::from_secs(60);
-                }
-                _ => {}
-            }
+/// Checks that the webhook exists, since every notification would fail otherwise.
+///
+/// Returns false if the webhook was deleted or its token is invalid.
+async fn check_webhook(name: &str, webhook: &WebhookClient) -> bool {
+    match webhook.validate().await {
+        Ok(()) => true,
+        Err(e) if e.is_transient() => {
+            log::warn!("Could not check the {name} webhook, continuing anyway: {e}");
+            true
+        }
+        Err(e) => {
+            log::error!("The {name} webhook is invalid, it might have been deleted or the URL is wrong: {e}");
+            false
         }
+    }
+}
 
-        if let Err(err) = db.delete(&key).await {
-            log::error!("[{key}] Failed to delete database entry: {err:?}");
+/// Reports the problems with the permissions of the bot as an embed to the logging webhook, or as warnings otherwise.
+async fn report_setup_problems(alerts: Option<&WebhookClient>, problems: &[String]) {
+    let Some(webhook) = alerts else {
+        for problem in problems {
+            log::warn!("{problem}");
         }
-        receive.close();
-    });
+        return;
+    };
 
-    send
-}
+    let description: String = problems.iter().map(|problem| format!("\u{2022} {problem}\n")).collect();
+    let message = WebhookMessage {
+        embeds: vec![EmbedBuilder::new()
+            .color(0xF0A020)
+            .title("The bot is missing permissions")
+            .description(description)
+            .build()],
+        ..WebhookMessage::default()
+    };
 
-#[inline]
-async fn push(s: &mpsc::Sender<StreamUpdate>, event: StreamUpdate) {
-    drop(s.send(event).await);
+    log::info!("Found {} problems with the permissions of the bot", problems.len());
+    if let Err(e) = webhook.execute(&message).await {
+        log::error!("Failed to report problems with the permissions of the bot: {e}");
+    }
 }
 
-async fn load_cache(
-    watchers: &mut HashMap<String, mpsc::Sender<StreamUpdate>>,
-    config: &Arc<Config>,
-    client: &Arc<TwitchClient>,
-    webhook: &Arc<WebhookClient>,
-    db: &Arc<Cache>,
-) -> anyhow::Result<()> {
-    if let Ok(data) = fs::metadata(".config").await {
-        if !data.is_dir() {
-            log::error!("Cannot load cache: .config is not a directory");
-            return Ok(());
-        }
+/// Exits the process on ctrl+c or SIGTERM, after writing all delayed cache entries.
+async fn shutdown(cache: Arc<Store>) {
+    shutdown_signal().await;
+    log::info!("Shutting down");
+    if let Err(e) = cache.flush(true).await {
+        log::error!("Failed to write delayed cache entries: {e}");
     }
+    #[cfg(windows)]
+    service::report_stopped();
+    std::process::exit(0);
+}
 
-    let mut count = 0;
-    for name in &config.twitch.user_login {
-        let name = name.to_lowercase();
-        let file = db.read::<StreamWatcher>(&name).await;
-
-        match file {
-            Err(DatabaseError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
-                log::debug!("Cache file for {} not found", name);
-            }
-            Err(DatabaseError::Io(err)) => {
-                log::error!("Could not load cache for {name}: {}", err);
-            }
-            Err(DatabaseError::Serde(err)) => {
-                log::warn!("Failed to parse watcher state for watcher {name:?} from cache: {}", err);
-            }
-            Ok(mut watcher) => {
-                watcher = watcher.set_config(config.clone());
-                let sender = start_watcher(true, client, webhook, db, watcher);
-                watchers.insert(name, sender);
-                count += 1;
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
             }
+            Err(_) => drop(tokio::signal::ctrl_c().await),
         }
     }
 
-    if count > 0 {
-        log::info!("Loaded {count} cached stream watchers");
+    #[cfg(windows)]
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        () = service::stopped() => {}
     }
-    Ok(())
 }
@@ -0,0 +1,75 @@
+use std::{
+    backtrace::Backtrace,
+    panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use discord_api::{WebhookClient, WebhookMessage, WebhookParams};
+use tokio::time::timeout;
+use twilight_http::Client;
+
+/// Maximum number of panics which are reported, in case a task keeps panicking after its restart
+const MAX_REPORTS: usize = 5;
+/// Time to wait for the report, before the panic continues
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum length of the report inside the code block, below the limit of 2000 characters per message
+const MAX_LENGTH: usize = 1900;
+
+/// Installs a panic hook which posts the panic with its backtrace to the logging webhook.
+///
+/// The report is sent before the panic unwinds, so it arrives even if the process exits right after.
+pub fn install(token: Box<str>, params: WebhookParams) {
+    let reports = AtomicUsize::new(0);
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default(info);
+        if reports.fetch_add(1, Ordering::Relaxed) >= MAX_REPORTS {
+            return;
+        }
+
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+        let location = info.location().map_or_else(String::new, |l| format!(" at {l}"));
+        let thread = thread::current().name().unwrap_or("<unnamed>").to_owned();
+        let report = format!(
+            "thread '{thread}' panicked{location}:\n{message}\n\n{}",
+            Backtrace::force_capture()
+        );
+
+        let (token, params) = (token.clone(), params.clone());
+        // The hook might run on a thread of the runtime, which cannot block on the request itself
+        let sender = thread::spawn(move || send(token, params, &report));
+        drop(sender.join());
+    }));
+}
+
+fn send(token: Box<str>, params: WebhookParams, report: &str) {
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread().enable_all().build() else {
+        return;
+    };
+
+    let report: String = report.replace("```", "'''").chars().take(MAX_LENGTH).collect();
+    let message = WebhookMessage {
+        content: format!("```\n{report}```"),
+        ..WebhookMessage::default()
+    };
+
+    // A new client, since the connections of the shared client belong to the runtime of the bot
+    let webhook = WebhookClient::new(Arc::new(Client::new(token.into())), params);
+    runtime.block_on(async {
+        match timeout(SEND_TIMEOUT, webhook.execute(&message)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => eprintln!("Failed to report panic to the logging webhook: {e}"),
+            Err(_) => eprintln!("Timed out reporting panic to the logging webhook"),
+        }
+    });
+}
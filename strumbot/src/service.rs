@@ -0,0 +1,164 @@
+use std::{
+    ffi::OsString,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use clap::Parser;
+use tokio::sync::Notify;
+use tracing as log;
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceInfo,
+        ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+use crate::cli::{Cli, ServiceAction};
+
+const SERVICE_NAME: &str = "strumbot";
+const DISPLAY_NAME: &str = "Strumbot";
+
+/// The status handle of the running service, to report the stop before the process exits
+static STATUS: Mutex<Option<ServiceStatusHandle>> = Mutex::new(None);
+/// Notified when the service manager stops the service, since there is no signal for it
+static STOP: OnceLock<Notify> = OnceLock::new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+pub fn handle(cli: &Cli, action: ServiceAction) -> anyhow::Result<()> {
+    match action {
+        ServiceAction::Install => install(cli),
+        ServiceAction::Uninstall => uninstall(),
+        // Blocks until the service is stopped, the service manager calls service_main on another thread
+        ServiceAction::Run => service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+            .context("Failed to connect to the service manager, the service run command is only used by windows"),
+    }
+}
+
+/// Registers the service, which runs with the same config, cache directory, and log file as this command.
+fn install(cli: &Cli) -> anyhow::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CREATE_SERVICE)?;
+
+    // The service starts in the system directory, so the paths have to be absolute
+    let current_dir = std::env::current_dir()?;
+    let absolute = |path: &Path| -> OsString { current_dir.join(path).into_os_string() };
+    let log_file = match cli.log_file {
+        Some(ref path) => path.clone(),
+        None => "strumbot.log".into(),
+    };
+    let mut launch_arguments = vec![
+        "--config".into(),
+        absolute(&cli.config),
+        "--cache-dir".into(),
+        absolute(Path::new(&cli.cache_dir)),
+        "--log-file".into(),
+        absolute(&log_file),
+    ];
+    if let Some(ref profile) = cli.profile {
+        launch_arguments.extend(["--profile".into(), profile.into()]);
+    }
+    if cli.dry_run {
+        launch_arguments.push("--dry-run".into());
+    }
+    launch_arguments.extend(["service".into(), "run".into()]);
+
+    let info = ServiceInfo {
+        name: SERVICE_NAME.into(),
+        display_name: DISPLAY_NAME.into(),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments,
+        dependencies: Vec::new(),
+        account_name: None,
+        account_password: None,
+    };
+
+    let service = manager
+        .create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .context("Failed to install the service, this requires an administrator")?;
+    service.set_description("Discord notifications for twitch streams")?;
+    log::info!("Installed the {SERVICE_NAME} service, start it with: sc start {SERVICE_NAME}");
+    Ok(())
+}
+
+fn uninstall() -> anyhow::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::QUERY_STATUS | ServiceAccess::DELETE)?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        log::warn!("The {SERVICE_NAME} service is still running, it is removed once it stops");
+    }
+    service.delete()?;
+    log::info!("Uninstalled the {SERVICE_NAME} service");
+    Ok(())
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    // The launch arguments of the service are passed to the process, not to the service main
+    let cli = Cli::parse();
+    let handler = |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            set_state(ServiceState::StopPending);
+            STOP.get_or_init(Notify::new).notify_one();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+
+    match service_control_handler::register(SERVICE_NAME, handler) {
+        Ok(handle) => *STATUS.lock().unwrap() = Some(handle),
+        Err(e) => {
+            log::error!("Failed to register the service control handler: {e}");
+            return;
+        }
+    }
+
+    set_state(ServiceState::Running);
+    if let Err(e) = crate::block_on(cli) {
+        log::error!("{e:#}");
+    }
+    report_stopped();
+}
+
+/// Completes once the service manager requested the service to stop.
+pub async fn stopped() {
+    STOP.get_or_init(Notify::new).notified().await;
+}
+
+/// Reports that the service stopped, before the process exits.
+pub fn report_stopped() {
+    set_state(ServiceState::Stopped);
+}
+
+/// Reports the state of the service to the service manager, if this process runs as a service.
+fn set_state(state: ServiceState) {
+    let Some(handle) = *STATUS.lock().unwrap() else {
+        return;
+    };
+
+    let controls_accepted = match state {
+        ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        _ => ServiceControlAccept::empty(),
+    };
+    let status = ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::from_secs(10),
+        process_id: None,
+    };
+    if let Err(e) = handle.set_service_status(status) {
+        log::error!("Failed to report the service state: {e}");
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    borrow::Borrow,
+    hash::Hash,
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+
+/// A least recently used cache, where entries optionally expire after a fixed time.
+///
+/// A cache with a capacity of 0 is disabled, and never returns an entry.
+pub(crate) struct TtlCache<K: Hash + Eq, V> {
+    entries: Option<Mutex<LruCache<K, (Instant, V)>>>,
+    ttl: Option<Duration>,
+}
+
+impl<K: Hash + Eq, V: Clone> TtlCache<K, V> {
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity))),
+            ttl,
+        }
+    }
+
+    /// The cached value, unless it is expired.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut entries = self.entries.as_ref()?.lock().unwrap();
+        let (inserted_at, value) = entries.get(key)?;
+        if self.ttl.map_or(false, |ttl| inserted_at.elapsed() >= ttl) {
+            entries.pop(key);
+            return None;
+        }
+        Some(value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if let Some(ref entries) = self.entries {
+            entries.lock().unwrap().put(key, (Instant::now(), value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_cache() {
+        let cache: TtlCache<String, u32> = TtlCache::new(2, None);
+        cache.insert("a".to_owned(), 1);
+        cache.insert("b".to_owned(), 2);
+        assert_eq!(cache.get("a"), Some(1));
+        // The least recently used entry is evicted
+        cache.insert("c".to_owned(), 3);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+
+        let expired: TtlCache<String, u32> = TtlCache::new(2, Some(Duration::ZERO));
+        expired.insert("a".to_owned(), 1);
+        assert_eq!(expired.get("a"), None);
+
+        let disabled: TtlCache<String, u32> = TtlCache::new(0, None);
+        disabled.insert("a".to_owned(), 1);
+        assert_eq!(disabled.get("a"), None);
+    }
+}
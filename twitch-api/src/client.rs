@@ -1,68 +1,291 @@
 use eos::fmt::{format_spec, FormatSpec};
-use lru::LruCache;
+use futures::{stream, StreamExt, TryStreamExt};
 use once_cell::sync::Lazy;
 use regex::Regex;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use std::{
     borrow::Cow,
-    num::NonZeroUsize,
     sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 use tracing as log;
 
 use super::{
-    oauth::{Identity, OauthClient, QueryParams},
-    Clip, Game, Stream, TwitchData, Video, VideoType,
+    cache::TtlCache,
+    config::CacheConfig,
+    oauth::{Identity, OauthClient, QueryParams, TokenStore, UserIdentity},
+    Clip, CreatorGoal, FollowedChannel, Game, HypeTrainEvent, Schedule, Stream, StreamMarker, Team, TeamMember,
+    TwitchData, TwitchObject, TwitchTotal, User, UserMarkers, Video, VideoType,
 };
-use crate::error::RequestError;
+use crate::{error::RequestError, thumbnail};
 
 type DateTime = eos::DateTime<eos::Utc>;
 
 const RFC3339: [FormatSpec<'static>; 12] = format_spec!("%Y-%m-%dT%H:%M:%SZ");
 
+/// Replaces the `{width}` and `{height}` placeholders of a twitch image url.
+pub fn resolve_image_url(url: &str, width: u32, height: u32) -> String {
+    static W: Lazy<Regex> = Lazy::new(|| Regex::new(r"%?\{width\}").unwrap());
+    static H: Lazy<Regex> = Lazy::new(|| Regex::new(r"%?\{height\}").unwrap());
+
+    let url = W.replace(url, width.to_string());
+    H.replace(&url, height.to_string()).to_string()
+}
+
+/// The key of a user in the cache, logins are case-insensitive.
+fn user_key(key: &str, value: &str) -> String {
+    format!("{key}:{}", value.to_lowercase())
+}
+
+/// Resolves a stream or video thumbnail url in full resolution.
+pub fn resolve_thumbnail_url(url: &str) -> String {
+    resolve_image_url(url, 1920, 1080)
+}
+
 pub struct TwitchClient {
     oauth: OauthClient,
     identity: Mutex<Arc<Identity>>,
-    games_cache: Mutex<LruCache<String, Arc<Game>>>,
+    games_cache: TtlCache<String, Arc<Game>>,
+    videos_cache: TtlCache<String, Video>,
+    /// Users by `id:<id>` and `login:<login>`
+    users_cache: TtlCache<String, User>,
+    token_store: Option<Box<dyn TokenStore>>,
+    /// Optional user access token, for endpoints which require the authorization of a user
+    user_identity: Mutex<Option<Arc<UserIdentity>>>,
+    user_token_store: Option<Box<dyn TokenStore>>,
+    /// Map of resolved url -> (fetched at, image), shared by notifications sent at the same time
+    thumbnail_cache: TtlCache<String, Arc<[u8]>>,
 }
 
 impl TwitchClient {
+    /// Upper limit of pages to fetch for a single paginated request
+    const MAX_PAGES: usize = 10;
+    /// Maximum number of ids or logins per request
+    const MAX_BATCH_SIZE: usize = 100;
+    /// Maximum number of batch requests running at the same time
+    const MAX_CONCURRENCY: usize = 4;
+    /// How long a downloaded thumbnail is reused, since twitch updates the previews every few minutes
+    const THUMBNAIL_TTL: Duration = Duration::from_secs(60);
+    /// Tokens are refreshed when they expire within this time
+    const MIN_TOKEN_LIFETIME: Duration = Duration::from_secs(600);
+
     #[inline]
     fn identity(&self) -> Arc<Identity> {
         self.identity.lock().unwrap().clone()
     }
 
     pub async fn new(oauth: OauthClient) -> Result<TwitchClient, RequestError> {
-        let identity = oauth.authorize().await?;
+        Self::with_token_store(oauth, None).await
+    }
+
+    /// Reuses the persisted access token if it is still valid, and persists new tokens after every refresh.
+    pub async fn with_token_store(
+        oauth: OauthClient,
+        token_store: Option<Box<dyn TokenStore>>,
+    ) -> Result<TwitchClient, RequestError> {
+        let restored = match token_store {
+            Some(ref store) => Self::restore_identity(&oauth, store.as_ref()).await,
+            None => None,
+        };
+
+        let identity = match restored {
+            Some(identity) => identity,
+            None => {
+                let identity = oauth.authorize().await?;
+                if let Some(ref store) = token_store {
+                    store.save(&identity.to_stored()).await;
+                }
+                identity
+            }
+        };
+
+        let cache = CacheConfig::default();
         Ok(Self {
             oauth,
             identity: Mutex::new(Arc::new(identity)),
-            games_cache: unsafe { Mutex::new(LruCache::new(NonZeroUsize::new_unchecked(100))) },
+            games_cache: TtlCache::new(cache.games, None),
+            videos_cache: TtlCache::new(cache.videos, Some(Duration::from_secs(cache.video_ttl))),
+            users_cache: TtlCache::new(cache.users, Some(Duration::from_secs(cache.user_ttl))),
+            token_store,
+            user_identity: Mutex::new(None),
+            user_token_store: None,
+            thumbnail_cache: TtlCache::new(16, Some(Self::THUMBNAIL_TTL)),
         })
     }
 
+    /// Replaces the caches for games, videos, and users with the configured sizes and lifetimes.
+    pub fn with_cache_config(mut self, cache: &CacheConfig) -> Self {
+        self.games_cache = TtlCache::new(cache.games, None);
+        self.videos_cache = TtlCache::new(cache.videos, Some(Duration::from_secs(cache.video_ttl)));
+        self.users_cache = TtlCache::new(cache.users, Some(Duration::from_secs(cache.user_ttl)));
+        self
+    }
+
+    async fn restore_identity(oauth: &OauthClient, store: &dyn TokenStore) -> Option<Identity> {
+        let token = store.load().await?;
+        if token.expires_in() < Self::MIN_TOKEN_LIFETIME {
+            return None;
+        }
+
+        match oauth.validate(&token.access_token).await {
+            Ok(Some(expires_in)) => {
+                log::info!("Reusing stored oauth token");
+                Some(Identity::from_stored(token, expires_in))
+            }
+            Ok(None) => {
+                log::info!("Stored oauth token is no longer valid");
+                None
+            }
+            Err(e) => {
+                // The token is most likely fine, this allows starting while id.twitch.tv is unavailable
+                log::warn!("Could not validate stored oauth token, assuming it is valid: {e}");
+                let expires_in = token.expires_in();
+                Some(Identity::from_stored(token, expires_in))
+            }
+        }
+    }
+
+    /// Persists the user access token, once a user authorized the application.
+    pub fn with_user_token_store(mut self, store: Box<dyn TokenStore>) -> Self {
+        self.user_token_store = Some(store);
+        self
+    }
+
+    fn user_identity(&self) -> Option<Arc<UserIdentity>> {
+        self.user_identity.lock().unwrap().clone()
+    }
+
+    /// The user access token if available, otherwise the app access token.
+    fn user_or_app_identity(&self) -> Arc<Identity> {
+        match self.user_identity() {
+            Some(user) => Arc::new(user.identity.clone()),
+            None => self.identity(),
+        }
+    }
+
+    async fn set_user_identity(&self, user: UserIdentity) {
+        if let Some(ref store) = self.user_token_store {
+            store.save(&user.to_stored()).await;
+        }
+        *self.user_identity.lock().unwrap() = Some(Arc::new(user));
+    }
+
+    /// Restores the stored user access token, if it has all of these scopes.
+    ///
+    /// Returns false if the user has to authorize the application again.
+    pub async fn restore_user_auth(&self, scopes: &[Box<str>]) -> bool {
+        let Some(ref store) = self.user_token_store else {
+            return false;
+        };
+
+        let Some(token) = store.load().await else {
+            return false;
+        };
+
+        let expires_in = match self.oauth.validate(&token.access_token).await {
+            Ok(Some(expires_in)) => expires_in,
+            Ok(None) => Duration::ZERO,
+            Err(e) => {
+                log::warn!("Could not validate stored user token, assuming it is valid: {e}");
+                token.expires_in()
+            }
+        };
+
+        let Some(user) = UserIdentity::from_stored(token, expires_in) else {
+            return false;
+        };
+
+        if !user.has_scopes(scopes) {
+            log::info!("Stored user token is missing some of the configured scopes");
+            return false;
+        }
+
+        *self.user_identity.lock().unwrap() = Some(Arc::new(user));
+        // Expired tokens are renewed with the refresh token
+        self.refresh_user_auth().await.is_ok()
+    }
+
+    /// Authorizes the application for a twitch user with the device code flow.
+    ///
+    /// This logs the link and code for the user, and waits until they authorized the application.
+    pub async fn authorize_user(&self, scopes: &[Box<str>]) -> Result<(), RequestError> {
+        let code = self.oauth.request_device_code(scopes).await?;
+        log::warn!(
+            "To authorize strumbot for your twitch account, open {} and enter the code {}",
+            code.verification_uri,
+            code.user_code
+        );
+
+        let user = self.oauth.poll_device_token(&code, scopes).await?;
+        log::info!("Successfully authorized with a user token");
+        self.set_user_identity(user).await;
+        Ok(())
+    }
+
+    async fn refresh_user_auth(&self) -> Result<(), RequestError> {
+        let Some(user) = self.user_identity() else {
+            return Ok(());
+        };
+
+        if user.identity.expires_at > Instant::now() + Self::MIN_TOKEN_LIFETIME {
+            return Ok(());
+        }
+
+        log::info!("Refreshing user token...");
+        match self.oauth.refresh_user(&user.refresh_token).await {
+            Ok(refreshed) => {
+                self.set_user_identity(refreshed).await;
+                Ok(())
+            }
+            Err(e) => {
+                // The refresh token was revoked, such as after the user changed their password
+                log::error!("Failed to refresh user token, falling back to the app token: {e}");
+                *self.user_identity.lock().unwrap() = None;
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether the current oauth token is still valid.
+    pub fn is_authorized(&self) -> bool {
+        self.identity().expires_at > Instant::now()
+    }
+
     pub async fn refresh_auth(&self) -> Result<(), RequestError> {
         let identity = self.identity();
-        if identity.expires_at < Instant::now() + Duration::from_secs(600) {
+        let rejected = self.oauth.take_unauthorized();
+        if rejected {
+            log::warn!("Oauth token was rejected by twitch, authorizing again...");
+        }
+
+        if rejected || identity.expires_at < Instant::now() + Self::MIN_TOKEN_LIFETIME {
             log::info!("Refreshing oauth token...");
             let id = self.oauth.authorize().await?;
+            if let Some(ref store) = self.token_store {
+                store.save(&id.to_stored()).await;
+            }
             let mut guard = self.identity.lock().unwrap();
             *guard = Arc::new(id);
         }
+
+        // The app token still works, so this does not fail the poll
+        drop(self.refresh_user_auth().await);
         Ok(())
     }
 
+    /// Adds a known game to the cache, so looking it up does not require a request.
+    pub fn insert_game(&self, game: Game) {
+        let key = game.id.to_string();
+        self.games_cache.insert(key, Arc::new(game));
+    }
+
     pub async fn get_game_by_id(&self, id: String) -> Result<Arc<Game>, RequestError> {
         if id.is_empty() {
             return Ok(Game::empty());
         }
 
-        let cached_game = {
-            let mut cache = self.games_cache.lock().unwrap();
-            cache.get(&id).cloned()
-        };
-
-        if let Some(game) = cached_game {
+        if let Some(game) = self.games_cache.get(&id) {
             return Ok(game);
         }
 
@@ -80,27 +303,157 @@ impl TwitchClient {
             .await?;
 
         let game = Arc::new(game);
-        self.games_cache.lock().unwrap().push(key, game.clone());
+        self.games_cache.insert(key, game.clone());
         Ok(game)
     }
 
+    /// Fetches the live streams for all provided logins, in batches of at most 100 logins per request.
     pub async fn get_streams_by_login(&self, user_login: &[Box<str>]) -> Result<Vec<Stream>, RequestError> {
-        let params: Box<_> = user_login
-            .iter()
-            .map(|login| ("user_login", login.as_ref().into()))
-            .collect();
+        let batches: Vec<Vec<Stream>> = stream::iter(user_login.chunks(Self::MAX_BATCH_SIZE))
+            .map(|batch| self.get_streams_batch("user_login", batch))
+            .buffer_unordered(Self::MAX_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(batches.into_iter().flatten().collect())
+    }
+
+    /// Fetches the live streams for all provided user ids, in batches of at most 100 ids per request.
+    pub async fn get_streams_by_user_id(&self, user_ids: &[Box<str>]) -> Result<Vec<Stream>, RequestError> {
+        let batches: Vec<Vec<Stream>> = stream::iter(user_ids.chunks(Self::MAX_BATCH_SIZE))
+            .map(|batch| self.get_streams_batch("user_id", batch))
+            .buffer_unordered(Self::MAX_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(batches.into_iter().flatten().collect())
+    }
+
+    async fn get_streams_batch(&self, key: &'static str, values: &[Box<str>]) -> Result<Vec<Stream>, RequestError> {
+        let mut params: Vec<_> = values.iter().map(|value| (key, Cow::from(value.as_ref()))).collect();
+        // The default page size is 20, which would drop streams from larger batches
+        params.push(("first", Cow::from("100")));
+
+        self.get_paged("streams", params).try_collect().await
+    }
+
+    /// Fetches the live streams of a category, at most the 100 streams with the most viewers.
+    pub async fn get_streams_by_game(&self, game_id: &str) -> Result<Vec<Stream>, RequestError> {
+        let query = build_query!(
+            "game_id" => game_id,
+            "first" => "100"
+        );
 
         self.oauth
-            .get(&self.identity(), "streams", params.into(), |b| {
+            .get(&self.identity(), "streams", query, move |b| {
                 let body: TwitchData<Stream> = serde_json::from_slice(&b)?;
                 Ok(body.data)
             })
             .await
     }
 
+    /// Fetches the users for the provided logins. Unknown logins are not included in the result.
+    pub async fn get_users_by_login(&self, user_login: &[Box<str>]) -> Result<Vec<User>, RequestError> {
+        self.get_users("login", user_login).await
+    }
+
+    /// Fetches the users for the provided ids. Unknown ids are not included in the result.
+    pub async fn get_users_by_id(&self, user_ids: &[Box<str>]) -> Result<Vec<User>, RequestError> {
+        self.get_users("id", user_ids).await
+    }
+
+    async fn get_users(&self, key: &'static str, values: &[Box<str>]) -> Result<Vec<User>, RequestError> {
+        let mut users = Vec::with_capacity(values.len());
+        let mut missing = Vec::with_capacity(values.len());
+        for value in values {
+            match self.users_cache.get(&user_key(key, value)) {
+                Some(user) => users.push(user),
+                None => missing.push(value),
+            }
+        }
+
+        for chunk in missing.chunks(Self::MAX_BATCH_SIZE) {
+            let params = chunk.iter().map(|value| (key, Cow::from(value.as_ref()))).collect();
+            let mut page: Vec<User> = self.get_paged("users", params).try_collect().await?;
+            for user in &page {
+                self.users_cache.insert(user_key("id", &user.id), user.clone());
+                self.users_cache.insert(user_key("login", &user.login), user.clone());
+            }
+            users.append(&mut page);
+        }
+
+        Ok(users)
+    }
+
+    /// Fetches the most recent archived stream of a user.
+    pub async fn get_latest_archive(&self, user_id: &str) -> Result<Video, RequestError> {
+        let query = build_query!(
+            "type" => "archive",
+            "first" => "1",
+            "user_id" => user_id
+        );
+
+        self.oauth
+            .get(&self.identity(), "videos", query, move |b| {
+                let mut body: TwitchData<Video> = serde_json::from_slice(&b)?;
+                match body.data.pop() {
+                    Some(video) => Ok(video),
+                    None => Err(RequestError::NotFound("Video", user_id.to_owned())),
+                }
+            })
+            .await
+    }
+
+    /// Fetches the upcoming stream schedule of a user, or `None` if the user has no schedule.
+    pub async fn get_schedule(&self, user_id: &str) -> Result<Option<Schedule>, RequestError> {
+        let query = build_query!(
+            "broadcaster_id" => user_id,
+            "first" => "25"
+        );
+
+        let result = self
+            .oauth
+            .get(&self.identity(), "schedule", query, |b| {
+                let body: TwitchObject<Schedule> = serde_json::from_slice(&b)?;
+                Ok(body.data)
+            })
+            .await;
+
+        match result {
+            Ok(schedule) => Ok(Some(schedule)),
+            Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fetches the members of a twitch team by the team name.
+    pub async fn get_team_members(&self, team_name: &str) -> Result<Vec<TeamMember>, RequestError> {
+        let query = build_query!("name" => team_name);
+        self.oauth
+            .get(&self.identity(), "teams", query, move |b| {
+                let mut body: TwitchData<Team> = serde_json::from_slice(&b)?;
+                match body.data.pop() {
+                    Some(team) => Ok(team.users),
+                    None => Err(RequestError::NotFound("Team", team_name.to_owned())),
+                }
+            })
+            .await
+    }
+
+    /// Fetches the channels followed by a user. This requires a token authorized by that user.
+    pub async fn get_followed_channels(&self, user_id: &str) -> Result<Vec<FollowedChannel>, RequestError> {
+        let params = vec![("user_id", Cow::from(user_id)), ("first", Cow::from("100"))];
+        self.get_paged("channels/followed", params).try_collect().await
+    }
+
     pub async fn get_video_by_id(&self, id: &str) -> Result<Video, RequestError> {
+        if let Some(video) = self.videos_cache.get(id) {
+            return Ok(video);
+        }
+
         let query = build_query!("id" => id);
-        self.oauth
+        let video: Video = self
+            .oauth
             .get(&self.identity(), "videos", query, move |b| {
                 let mut body: TwitchData<Video> = serde_json::from_slice(&b)?;
                 match body.data.pop() {
@@ -108,7 +461,10 @@ impl TwitchClient {
                     None => Err(RequestError::NotFound("Video", id.to_owned())),
                 }
             })
-            .await
+            .await?;
+
+        self.videos_cache.insert(video.id.to_string(), video.clone());
+        Ok(video)
     }
 
     pub async fn get_video_by_stream(&self, stream: &Stream) -> Result<Video, RequestError> {
@@ -119,7 +475,8 @@ impl TwitchClient {
             "user_id" => stream.user_id.as_ref()
         );
 
-        self.oauth
+        let video: Video = self
+            .oauth
             .get(&self.identity(), "videos", query, move |b| {
                 let body: TwitchData<Video> = serde_json::from_slice(&b)?;
                 let video = body
@@ -132,55 +489,242 @@ impl TwitchClient {
                     None => Err(RequestError::NotFound("Video", user_id)),
                 }
             })
-            .await
+            .await?;
+
+        // The video is looked up by id for the following segments of the stream
+        self.videos_cache.insert(video.id.to_string(), video.clone());
+        Ok(video)
     }
 
-    pub async fn get_videos(&self, mut ids: Vec<String>) -> Result<Vec<Video>, RequestError> {
-        ids.dedup();
-        let params: Box<_> = ids.into_iter().map(|id| ("id", id.into())).collect();
+    /// Fetches the most recent videos of this type from the user, newest first.
+    pub async fn get_user_videos(&self, user_id: &str, kind: VideoType) -> Result<Vec<Video>, RequestError> {
+        let kind = match kind {
+            VideoType::Archive => "archive",
+            VideoType::Upload => "upload",
+            VideoType::Highlight => "highlight",
+        };
+        let query = build_query!(
+            "type" => kind,
+            "first" => "5",
+            "user_id" => user_id
+        );
 
         self.oauth
-            .get(&self.identity(), "videos", params.into(), |b| {
+            .get(&self.identity(), "videos", query, |b| {
                 let body: TwitchData<Video> = serde_json::from_slice(&b)?;
                 Ok(body.data)
             })
             .await
     }
 
+    /// Fetches all items of a paginated endpoint, following the `pagination.cursor` of each page.
+    ///
+    /// The stream ends after the last page, or after [`MAX_PAGES`](Self::MAX_PAGES) pages.
+    pub fn get_paged<'a, T>(
+        &'a self,
+        endpoint: &'a str,
+        params: Vec<(&'a str, Cow<'a, str>)>,
+    ) -> impl futures::Stream<Item = Result<T, RequestError>> + 'a
+    where
+        T: DeserializeOwned + Send + Sync + 'a,
+    {
+        // The state is the cursor for the next page, None when there are no more pages
+        let start: Option<Option<Box<str>>> = Some(None);
+        stream::try_unfold((start, 0), move |(state, page)| {
+            let mut query = params.clone();
+            async move {
+                let Some(cursor) = state else {
+                    return Ok(None);
+                };
+
+                if page == Self::MAX_PAGES {
+                    log::warn!("Stopped pagination of {endpoint} after {page} pages");
+                    return Ok(None);
+                }
+
+                if let Some(cursor) = cursor {
+                    query.push(("after", Cow::Owned(cursor.into())));
+                }
+
+                let body: TwitchData<T> = self
+                    .oauth
+                    .get(&self.identity(), endpoint, query.into_boxed_slice().into(), |b| {
+                        Ok(serde_json::from_slice(&b)?)
+                    })
+                    .await?;
+
+                let next = body.pagination.cursor.filter(|c| !c.is_empty()).map(Some);
+                Ok(Some((body.data, (next, page + 1))))
+            }
+        })
+        .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    pub async fn get_videos(&self, mut ids: Vec<String>) -> Result<Vec<Video>, RequestError> {
+        ids.dedup();
+
+        let mut videos = Vec::with_capacity(ids.len());
+        ids.retain(|id| match self.videos_cache.get(id.as_str()) {
+            Some(video) => {
+                videos.push(video);
+                false
+            }
+            None => true,
+        });
+
+        // The endpoint accepts at most 100 ids per request
+        for chunk in ids.chunks(Self::MAX_BATCH_SIZE) {
+            let params = chunk.iter().map(|id| ("id", Cow::from(id.as_str()))).collect();
+            let mut page: Vec<Video> = self.get_paged("videos", params).try_collect().await?;
+            for video in &page {
+                self.videos_cache.insert(video.id.to_string(), video.clone());
+            }
+            videos.append(&mut page);
+        }
+
+        Ok(videos)
+    }
+
     pub async fn get_top_clips(
         &self,
         user_id: String,
         started_at: &DateTime,
         num: u8,
     ) -> Result<Vec<Clip>, RequestError> {
+        let params = vec![
+            ("first", Cow::from("100")), // twitch filters *after* limiting the number. we need to just get max and then filter
+            ("broadcaster_id", Cow::from(user_id)),
+            ("started_at", Cow::from(started_at.format(RFC3339).to_string())),
+        ];
+
+        self.get_paged("clips", params).take(num as usize).try_collect().await
+    }
+
+    /// Fetches the stream markers of a video.
+    ///
+    /// This requires a token authorized by the broadcaster, so it uses the user token if available.
+    pub async fn get_stream_markers(&self, video_id: &str) -> Result<Vec<StreamMarker>, RequestError> {
         let query = build_query!(
-            "first" => "100", // twitch filters *after* limiting the number. we need to just get max and then filter
-            "broadcaster_id" => user_id,
-            "started_at" => started_at.format(RFC3339).to_string()
+            "video_id" => video_id,
+            "first" => "100"
         );
 
         self.oauth
-            .get(&self.identity(), "clips", query, move |b| {
-                let body: TwitchData<Clip> = serde_json::from_slice(&b)?;
-                let mut clips = body.data;
-                clips.truncate(num as usize);
-                Ok(clips)
+            .get_with_token(&self.user_or_app_identity(), "streams/markers", query, |b| {
+                let body: TwitchData<UserMarkers> = serde_json::from_slice(&b)?;
+                Ok(body
+                    .data
+                    .into_iter()
+                    .flat_map(|user| user.videos)
+                    .flat_map(|video| video.markers)
+                    .collect())
             })
             .await
     }
 
-    pub async fn get_thumbnail(&self, url: &str) -> Result<Vec<u8>, RequestError> {
-        static W: Lazy<Regex> = Lazy::new(|| Regex::new(r"%?\{width\}").unwrap());
-        static H: Lazy<Regex> = Lazy::new(|| Regex::new(r"%?\{height\}").unwrap());
+    /// Fetches the latest event of the most recent hype train of the broadcaster, if there was any.
+    ///
+    /// This requires a user token of the broadcaster with the `channel:read:hype_train` scope.
+    pub async fn get_hype_train(&self, broadcaster_id: &str) -> Result<Option<HypeTrainEvent>, RequestError> {
+        let query = build_query!(
+            "broadcaster_id" => broadcaster_id,
+            "first" => "1"
+        );
+
+        self.oauth
+            .get_with_token(&self.user_or_app_identity(), "hype_train/events", query, |b| {
+                let mut body: TwitchData<HypeTrainEvent> = serde_json::from_slice(&b)?;
+                Ok(body.data.pop())
+            })
+            .await
+    }
+
+    /// Fetches the active creator goals of the broadcaster.
+    ///
+    /// This requires a user token of the broadcaster with the `channel:read:goals` scope.
+    pub async fn get_creator_goals(&self, broadcaster_id: &str) -> Result<Vec<CreatorGoal>, RequestError> {
+        let query = build_query!("broadcaster_id" => broadcaster_id);
+
+        self.oauth
+            .get_with_token(&self.user_or_app_identity(), "goals", query, |b| {
+                let body: TwitchData<CreatorGoal> = serde_json::from_slice(&b)?;
+                Ok(body.data)
+            })
+            .await
+    }
+
+    /// Fetches the number of followers of the broadcaster.
+    ///
+    /// This requires a user token, but the total is also reported for channels the user does not moderate.
+    pub async fn get_follower_count(&self, broadcaster_id: &str) -> Result<u64, RequestError> {
+        let query = build_query!(
+            "broadcaster_id" => broadcaster_id,
+            "first" => "1"
+        );
+
+        self.oauth
+            .get_with_token(&self.user_or_app_identity(), "channels/followers", query, |b| {
+                let body: TwitchTotal = serde_json::from_slice(&b)?;
+                Ok(body.total)
+            })
+            .await
+    }
+
+    /// Fetches the number of subscribers of the broadcaster.
+    ///
+    /// This requires a user token of the broadcaster with the `channel:read:subscriptions` scope.
+    pub async fn get_subscriber_count(&self, broadcaster_id: &str) -> Result<u64, RequestError> {
+        let query = build_query!(
+            "broadcaster_id" => broadcaster_id,
+            "first" => "1"
+        );
 
-        let full_url = H.replace(&W.replace(url, "1920"), "1080").to_string()
-            + format!("?t={}", DateTime::utc_now().timestamp().as_seconds()).as_str();
+        self.oauth
+            .get_with_token(&self.user_or_app_identity(), "subscriptions", query, |b| {
+                let body: TwitchTotal = serde_json::from_slice(&b)?;
+                Ok(body.total)
+            })
+            .await
+    }
+
+    /// Subscribes the EventSub websocket session to the raids of the broadcaster.
+    ///
+    /// Websocket subscriptions require a user token, but the broadcaster does not have to authorize the application.
+    pub async fn subscribe_raids(&self, session_id: &str, broadcaster_id: &str) -> Result<(), RequestError> {
+        let body = serde_json::json!({
+            "type": "channel.raid",
+            "version": "1",
+            "condition": { "from_broadcaster_user_id": broadcaster_id },
+            "transport": { "method": "websocket", "session_id": session_id },
+        });
+
+        self.oauth
+            .post_with_token(
+                &self.user_or_app_identity(),
+                "eventsub/subscriptions",
+                &body,
+                |_| Ok(()),
+            )
+            .await
+    }
+
+    pub async fn get_thumbnail(&self, url: &str) -> Result<Vec<u8>, RequestError> {
+        let resolved = resolve_thumbnail_url(url);
+        if let Some(image) = self.thumbnail_cache.get(&resolved) {
+            return Ok(image.to_vec());
+        }
 
+        // The timestamp avoids outdated previews from the CDN cache
+        let full_url = format!("{resolved}?t={}", DateTime::utc_now().timestamp().as_seconds());
         let request = self.oauth.http.get(full_url).build()?;
         let response = self.oauth.http.execute(request).await?;
 
         if response.status().is_success() {
-            Ok(response.bytes().await?.as_ref().to_vec())
+            let image = thumbnail::read_limited(response, thumbnail::MAX_BYTES).await?;
+            let image = thumbnail::shrink(image).await?;
+            self.thumbnail_cache.insert(resolved, Arc::from(image.as_slice()));
+            Ok(image)
         } else if response.status().as_u16() == 404 {
             Err(RequestError::NotFound("Thumbnail", url.to_owned()))
         } else {
@@ -1,22 +1,296 @@
+use std::{cmp::Reverse, collections::HashMap, time::Duration};
+
+use commons::util::ConfigDuration;
+
+use reqwest::{Certificate, Client as HttpClient, Proxy};
 use serde::Deserialize;
 
+use crate::{oauth::ClientParams, Clip};
+
+/// The maximum number of top clips to show in the vod event
+pub const MAX_TOP_CLIPS: u8 = 25;
+
 const fn default_top_clips() -> u8 {
     0
 }
 
-const fn default_grace_period() -> u8 {
-    2
+const fn default_grace_period() -> ConfigDuration {
+    ConfigDuration(Duration::from_secs(120))
+}
+
+fn default_vod_recheck() -> Vec<ConfigDuration> {
+    vec![
+        ConfigDuration(Duration::from_secs(300)),
+        ConfigDuration(Duration::from_secs(900)),
+    ]
+}
+
+const fn default_update_interval() -> ConfigDuration {
+    ConfigDuration(Duration::from_secs(60))
+}
+
+const fn default_membership_interval() -> u64 {
+    3600
+}
+
+const fn default_category_limit() -> u32 {
+    5
+}
+
+const fn default_category_interval() -> u64 {
+    120
+}
+
+const fn default_games_capacity() -> usize {
+    100
+}
+
+const fn default_videos_capacity() -> usize {
+    50
+}
+
+const fn default_video_ttl() -> u64 {
+    300
+}
+
+const fn default_users_capacity() -> usize {
+    200
+}
+
+const fn default_user_ttl() -> u64 {
+    3600
+}
+
+const fn default_connect_timeout() -> u64 {
+    10
+}
+
+const fn default_request_timeout() -> u64 {
+    30
+}
+
+/// Settings for the HTTP client shared by all outbound requests to twitch and other platforms.
+#[derive(Deserialize, Clone, Debug)]
+pub struct NetworkConfig {
+    /// Number of seconds to wait for a connection to be established
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    /// Number of seconds until a request is aborted, including reading the response body
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    /// Proxy url for all requests, such as `http://127.0.0.1:3128`
+    #[serde(default)]
+    pub proxy: Option<Box<str>>,
+    /// The User-Agent header of all requests, where `{version}` is replaced by the version of the bot
+    #[serde(default)]
+    pub user_agent: Option<Box<str>>,
+    /// Path to a PEM file with the root certificates to trust instead of the built-in ones
+    #[serde(default)]
+    pub ca_file: Option<Box<str>>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: default_connect_timeout(),
+            request_timeout: default_request_timeout(),
+            proxy: None,
+            user_agent: None,
+            ca_file: None,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// The configured user agent with the version of the bot, or `strumbot/<version>` if none is configured.
+    pub fn user_agent(&self, version: &str) -> String {
+        match self.user_agent {
+            Some(ref user_agent) => user_agent.replace("{version}", version),
+            None => format!("strumbot/{version}"),
+        }
+    }
+
+    /// Builds the HTTP client with these settings, which identifies itself with the version of the bot.
+    pub fn build_client(&self, version: &str) -> anyhow::Result<HttpClient> {
+        let mut builder = HttpClient::builder()
+            .connect_timeout(Duration::from_secs(self.connect_timeout))
+            .timeout(Duration::from_secs(self.request_timeout))
+            .user_agent(self.user_agent(version));
+
+        if let Some(ref proxy) = self.proxy {
+            builder = builder.proxy(Proxy::all(proxy.as_ref())?);
+        }
+
+        if let Some(ref path) = self.ca_file {
+            // With rustls, this adds every certificate of the bundle
+            let pem = std::fs::read(path.as_ref())?;
+            builder = builder
+                .tls_built_in_root_certs(false)
+                .add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// Sizes and lifetimes of the caches for twitch resources, a capacity of 0 disables the cache.
+#[derive(Deserialize, Clone, Debug)]
+pub struct CacheConfig {
+    /// Number of games to keep, games never expire
+    #[serde(default = "default_games_capacity")]
+    pub games: usize,
+    #[serde(default = "default_videos_capacity")]
+    pub videos: usize,
+    /// Number of seconds until a cached video is fetched again, since the duration grows while the stream is live
+    #[serde(default = "default_video_ttl")]
+    pub video_ttl: u64,
+    #[serde(default = "default_users_capacity")]
+    pub users: usize,
+    /// Number of seconds until a cached user is fetched again
+    #[serde(default = "default_user_ttl")]
+    pub user_ttl: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            games: default_games_capacity(),
+            videos: default_videos_capacity(),
+            video_ttl: default_video_ttl(),
+            users: default_users_capacity(),
+            user_ttl: default_user_ttl(),
+        }
+    }
+}
+
+/// How to handle streams which are not regular live broadcasts, such as reruns and premieres.
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum StreamTypeMode {
+    /// Announce like any other live stream
+    #[serde(rename = "announce")]
+    Announce,
+    /// Announce with a label, such as "[Rerun]"
+    #[default]
+    #[serde(rename = "label")]
+    Label,
+    /// Don't send any notifications for this stream
+    #[serde(rename = "skip")]
+    Skip,
+}
+
+/// The order of the top clips.
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum ClipSort {
+    /// Most viewed clips first
+    #[default]
+    #[serde(rename = "views")]
+    Views,
+    /// Most recent clips first
+    #[serde(rename = "recency")]
+    Recency,
 }
 
 #[derive(Deserialize, Default)]
 pub struct TwitchConfig {
     pub client_id: Box<str>,
     pub client_secret: Box<str>,
+    /// Credentials of other twitch applications, which are used while the rate limit of the primary ones is exhausted
+    #[serde(default)]
+    pub additional_credentials: Vec<ClientParams>,
+    #[serde(default)]
     pub user_login: Vec<Box<str>>,
+    /// Track all members of this twitch team
+    #[serde(default)]
+    pub team: Option<Box<str>>,
+    /// Track all channels followed by this user
+    #[serde(default)]
+    pub followed_by: Option<Box<str>>,
+    /// Number of seconds between refreshes of the team and followed channels
+    #[serde(default = "default_membership_interval")]
+    pub membership_interval: u64,
     #[serde(default = "default_top_clips")]
     pub top_clips: u8,
+    #[serde(default)]
+    pub clip_sort: ClipSort,
+    /// Clips with fewer views are not shown
+    #[serde(default)]
+    pub clip_min_views: u32,
+    /// How long to wait before firing a VOD event after the stream appears offline
     #[serde(default = "default_grace_period")]
-    pub offline_grace_period: u8,
+    pub offline_grace_period: ConfigDuration,
+    /// Grace periods for individual streamers, by login
+    #[serde(default)]
+    pub offline_grace_periods: HashMap<Box<str>, ConfigDuration>,
+    /// When to look for a missing VOD again after the stream ended, which edits the VOD notification once it is found
+    #[serde(default = "default_vod_recheck")]
+    pub vod_recheck: Vec<ConfigDuration>,
+    /// Streamers with disabled VODs, whose videos are never looked up
+    #[serde(default)]
+    pub vods_disabled: Vec<Box<str>>,
+    /// How long to ignore the live updates of a stream after a change, since twitch may still report the previous state
+    #[serde(default = "default_update_interval")]
+    pub min_update_interval: ConfigDuration,
+    #[serde(default)]
+    pub reruns: StreamTypeMode,
+    #[serde(default)]
+    pub premieres: StreamTypeMode,
+    /// Number of seconds between polls of streamers outside of their usual start times, 0 to always poll
+    #[serde(default)]
+    pub idle_poll_interval: u64,
+    /// Authorize a twitch user with the device code flow, for endpoints which require a user token
+    #[serde(default)]
+    pub user_auth: bool,
+    /// The scopes to request for the user token
+    #[serde(default)]
+    pub user_scopes: Vec<Box<str>>,
+    /// Categories where any stream is announced, besides the streams of the tracked streamers
+    #[serde(default)]
+    pub categories: Vec<CategoryConfig>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+}
+
+/// A twitch category to watch for new streams of any streamer.
+#[derive(Deserialize, Clone, Debug)]
+pub struct CategoryConfig {
+    /// The id of the game or category, as used by the helix api
+    pub game_id: Box<str>,
+    /// Streams with fewer viewers are not announced
+    #[serde(default)]
+    pub min_viewers: u32,
+    /// The maximum number of announced streams per hour, to avoid spam in popular categories
+    #[serde(default = "default_category_limit")]
+    pub max_per_hour: u32,
+    /// Number of seconds between polls of the category
+    #[serde(default = "default_category_interval")]
+    pub poll_interval: u64,
+}
+
+impl TwitchConfig {
+    /// The offline grace period for this streamer, using the override for the login if there is one.
+    pub fn grace_period(&self, login: &str) -> Duration {
+        self.offline_grace_periods
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(login))
+            .map_or(self.offline_grace_period, |(_, period)| *period)
+            .0
+    }
+
+    /// Whether the VODs of this streamer are looked up, which is disabled with `vods_disabled`.
+    pub fn has_vods(&self, login: &str) -> bool {
+        !self.vods_disabled.iter().any(|name| name.eq_ignore_ascii_case(login))
+    }
+
+    /// Filters and sorts the clips according to this config, keeping at most `limit` clips.
+    pub fn select_clips(&self, mut clips: Vec<Clip>, limit: usize) -> Vec<Clip> {
+        clips.retain(|clip| i64::from(clip.view_count) >= i64::from(self.clip_min_views));
+        match self.clip_sort {
+            ClipSort::Views => clips.sort_by_key(|clip| Reverse(clip.view_count)),
+            ClipSort::Recency => clips.sort_by_key(|clip| Reverse(clip.created_at.timestamp().as_seconds())),
+        }
+        clips.truncate(limit);
+        clips
+    }
 }
 
 #[cfg(test)]
@@ -37,6 +311,121 @@ mod tests {
         assert_eq!(twitch.client_secret.as_ref(), "BJW8uMosDo02LcdU25u8dC95YTVBVZmy");
         assert_eq!(twitch.user_login, vec!["Elajjaz".into(), "distortion2".into()]);
         assert_eq!(twitch.top_clips, 5);
-        assert_eq!(twitch.offline_grace_period, 2);
+        assert_eq!(twitch.clip_sort, ClipSort::Views);
+        assert_eq!(twitch.clip_min_views, 0);
+        assert_eq!(twitch.grace_period("Elajjaz"), Duration::from_secs(120));
+        assert_eq!(twitch.min_update_interval.0, Duration::from_secs(60));
+        assert_eq!(twitch.vod_recheck.len(), 2);
+        assert_eq!(twitch.vod_recheck[1].0, Duration::from_secs(900));
+        assert!(twitch.has_vods("Elajjaz"));
+        assert_eq!(twitch.reruns, StreamTypeMode::Label);
+        assert_eq!(twitch.premieres, StreamTypeMode::Label);
+        assert_eq!(twitch.team, None);
+        assert_eq!(twitch.followed_by, None);
+        assert_eq!(twitch.membership_interval, 3600);
+        assert_eq!(twitch.idle_poll_interval, 0);
+        assert!(!twitch.user_auth);
+        assert!(twitch.user_scopes.is_empty());
+        assert!(twitch.categories.is_empty());
+        assert!(twitch.additional_credentials.is_empty());
+        assert_eq!(twitch.cache.games, 100);
+        assert_eq!(twitch.cache.video_ttl, 300);
+        assert_eq!(twitch.cache.user_ttl, 3600);
+    }
+
+    #[test]
+    fn test_category_parse() {
+        let category: CategoryConfig = serde_json::from_str(r#"{"game_id": "509658", "min_viewers": 50}"#).unwrap();
+        assert_eq!(category.game_id.as_ref(), "509658");
+        assert_eq!(category.min_viewers, 50);
+        assert_eq!(category.max_per_hour, 5);
+        assert_eq!(category.poll_interval, 120);
+    }
+
+    fn clip(id: &str, views: i32, created_at: &str) -> Clip {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "video_id": "",
+            "url": "https://clips.twitch.tv/clip",
+            "title": id,
+            "thumbnail_url": "",
+            "view_count": views,
+            "created_at": created_at,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_select_clips() {
+        let clips = vec![
+            clip("old", 50, "2023-10-09T18:00:00Z"),
+            clip("popular", 100, "2023-10-09T19:00:00Z"),
+            clip("new", 20, "2023-10-09T20:00:00Z"),
+            clip("unpopular", 1, "2023-10-09T21:00:00Z"),
+        ];
+        let mut twitch: TwitchConfig = serde_json::from_str(
+            r#"{"client_id": "", "client_secret": "", "clip_sort": "recency", "clip_min_views": 10}"#,
+        )
+        .unwrap();
+
+        let ids = |clips: Vec<Clip>| clips.into_iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(twitch.select_clips(clips.clone(), 2)),
+            vec!["new".into(), "popular".into()]
+        );
+
+        twitch.clip_sort = ClipSort::Views;
+        assert_eq!(
+            ids(twitch.select_clips(clips, 5)),
+            vec!["popular".into(), "old".into(), "new".into()]
+        );
+    }
+
+    #[test]
+    fn test_network_config_parse() {
+        let network: NetworkConfig = serde_json::from_str(r#"{"proxy": "http://127.0.0.1:3128"}"#).unwrap();
+        assert_eq!(network.connect_timeout, 10);
+        assert_eq!(network.request_timeout, 30);
+        assert_eq!(network.proxy.as_deref(), Some("http://127.0.0.1:3128"));
+        assert!(network.build_client("1.2.5").is_ok());
+        assert_eq!(network.user_agent("1.2.5"), "strumbot/1.2.5");
+
+        let network = NetworkConfig {
+            proxy: Some("not a url".into()),
+            user_agent: Some("mybot/{version} (+https://example.com)".into()),
+            ..NetworkConfig::default()
+        };
+        assert!(network.build_client("1.2.5").is_err());
+        assert_eq!(network.user_agent("1.2.5"), "mybot/1.2.5 (+https://example.com)");
+    }
+
+    #[test]
+    fn test_grace_period() {
+        let file = br#"{
+            "client_id": "tRSXhpTsLQtWiI7Az7HNjmFna10XTdmi",
+            "client_secret": "BJW8uMosDo02LcdU25u8dC95YTVBVZmy",
+            "user_login": ["Elajjaz", "distortion2"],
+            "offline_grace_period": "90s",
+            "offline_grace_periods": {"Distortion2": "5m"}
+        }"#;
+        let twitch: TwitchConfig = serde_json::from_slice(file).unwrap();
+
+        assert_eq!(twitch.grace_period("elajjaz"), Duration::from_secs(90));
+        assert_eq!(twitch.grace_period("distortion2"), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_config_parse_team() {
+        let file = br#"{
+            "client_id": "tRSXhpTsLQtWiI7Az7HNjmFna10XTdmi",
+            "client_secret": "BJW8uMosDo02LcdU25u8dC95YTVBVZmy",
+            "team": "staff",
+            "followed_by": "Elajjaz"
+        }"#;
+        let twitch: TwitchConfig = serde_json::from_slice(file).unwrap();
+
+        assert!(twitch.user_login.is_empty());
+        assert_eq!(twitch.team.as_deref(), Some("staff"));
+        assert_eq!(twitch.followed_by.as_deref(), Some("Elajjaz"));
     }
 }
@@ -1,6 +1,54 @@
-use reqwest::{header::ToStrError, StatusCode};
+use std::fmt;
+
+use reqwest::{
+    header::{HeaderMap, ToStrError},
+    Response, StatusCode,
+};
+use serde::Deserialize;
 use thiserror::Error;
 
+/// The JSON body of an error response from twitch, such as `{"error": "Unauthorized", "status": 401, "message": "Invalid OAuth token"}`.
+#[derive(Deserialize, Debug)]
+pub struct TwitchApiError {
+    pub status: u16,
+    /// The reason phrase of the status, which is missing for some auth endpoints
+    #[serde(default)]
+    pub error: Box<str>,
+    pub message: Box<str>,
+    /// The id of the request from the response headers, which twitch support needs to look into a failure
+    #[serde(skip)]
+    pub request_id: Option<Box<str>>,
+}
+
+impl fmt::Display for TwitchApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.error.is_empty() {
+            write!(f, "twitch responded with {}: {}", self.status, self.message)?;
+        } else {
+            write!(
+                f,
+                "twitch responded with {} {}: {}",
+                self.status, self.error, self.message
+            )?;
+        }
+        match self.request_id {
+            Some(ref id) => write!(f, " (request id {id})"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The headers which identify a request, in the order they are looked up.
+const REQUEST_ID_HEADERS: [&str; 2] = ["twitch-trace-id", "x-request-id"];
+
+/// The id of the request, if the response headers contain one.
+pub(crate) fn request_id(headers: &HeaderMap) -> Option<Box<str>> {
+    REQUEST_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(Box::from)
+}
+
 #[derive(Error, Debug)]
 pub enum RequestError {
     #[error("http request failed with code {0}")]
@@ -13,6 +61,38 @@ pub enum RequestError {
     Deserialize(#[from] serde_json::Error),
     #[error("{0} not found for query {1}")]
     NotFound(&'static str, String),
+    #[error("{0}")]
+    TwitchApi(TwitchApiError),
+    #[error("response of {0} bytes exceeds the size limit")]
+    TooLarge(usize),
+}
+
+impl RequestError {
+    /// Reads the error body of a failed response, falling back to the status code if it is not a twitch error.
+    pub(crate) async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let request_id = request_id(response.headers());
+        match response.bytes().await {
+            Ok(body) => Self::from_body(status, &body, request_id),
+            Err(_) => Self::Http(status),
+        }
+    }
+
+    fn from_body(status: StatusCode, body: &[u8], request_id: Option<Box<str>>) -> Self {
+        match serde_json::from_slice(body) {
+            Ok(error) => Self::TwitchApi(TwitchApiError { request_id, ..error }),
+            Err(_) => Self::Http(status),
+        }
+    }
+
+    /// The status code of the response, if the request failed with an error response.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Http(status) => Some(*status),
+            Self::TwitchApi(error) => StatusCode::from_u16(error.status).ok(),
+            _ => None,
+        }
+    }
 }
 
 impl From<reqwest::Error> for RequestError {
@@ -32,3 +112,56 @@ impl From<StatusCode> for RequestError {
         RequestError::Http(code)
     }
 }
+
+/// Errors of the EventSub websocket, after which the session has to be started again.
+#[derive(Error, Debug)]
+pub enum EventSubError {
+    #[error("websocket failed: {0}")]
+    Websocket(Box<tokio_tungstenite::tungstenite::Error>),
+    #[error("failed to deserialize {0:?}")]
+    Deserialize(#[from] serde_json::Error),
+    #[error("no message within the keepalive timeout")]
+    Timeout,
+    #[error("websocket closed by twitch")]
+    Closed,
+    #[error("expected a welcome message, got {0:?}")]
+    MissingWelcome(Box<str>),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for EventSubError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        EventSubError::Websocket(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_body() {
+        let error = RequestError::from_body(
+            StatusCode::UNAUTHORIZED,
+            br#"{"error": "Unauthorized", "status": 401, "message": "Invalid OAuth token"}"#,
+            None,
+        );
+        assert_eq!(error.status(), Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(
+            error.to_string(),
+            "twitch responded with 401 Unauthorized: Invalid OAuth token"
+        );
+
+        let error = RequestError::from_body(StatusCode::BAD_GATEWAY, b"<html>Bad Gateway</html>", None);
+        assert!(matches!(error, RequestError::Http(StatusCode::BAD_GATEWAY)));
+
+        let error = RequestError::from_body(
+            StatusCode::BAD_REQUEST,
+            br#"{"error": "Bad Request", "status": 400, "message": "Malformed query params."}"#,
+            Some("a1b2c3".into()),
+        );
+        assert_eq!(
+            error.to_string(),
+            "twitch responded with 400 Bad Request: Malformed query params. (request id a1b2c3)"
+        );
+    }
+}
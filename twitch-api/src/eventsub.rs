@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::{net::TcpStream, time::timeout};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tracing as log;
+
+use crate::{error::EventSubError, RaidEvent};
+
+/// The websocket of EventSub, see https://dev.twitch.tv/docs/eventsub/handling-websocket-events
+pub const EVENTSUB_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+
+/// Extra time on top of the keepalive timeout, before the connection is considered dead
+const KEEPALIVE_GRACE: Duration = Duration::from_secs(5);
+/// The keepalive timeout of twitch, if the welcome message does not include it
+const DEFAULT_KEEPALIVE: u64 = 10;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Deserialize)]
+struct Envelope {
+    metadata: Metadata,
+    #[serde(default)]
+    payload: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    message_type: Box<str>,
+    /// The type of the subscription, only for notifications and revocations
+    #[serde(default)]
+    subscription_type: Option<Box<str>>,
+}
+
+#[derive(Deserialize)]
+struct SessionPayload {
+    session: Session,
+}
+
+#[derive(Deserialize)]
+struct Session {
+    id: Box<str>,
+    #[serde(default)]
+    keepalive_timeout_seconds: Option<u64>,
+    #[serde(default)]
+    reconnect_url: Option<Box<str>>,
+}
+
+#[derive(Deserialize)]
+struct NotificationPayload<T> {
+    event: T,
+}
+
+/// The messages of the websocket which are handled by the client.
+enum Frame {
+    Welcome(Session),
+    Reconnect(Box<str>),
+    Raid(RaidEvent),
+    Revoked(Box<str>),
+    Keepalive,
+}
+
+fn parse(text: &str) -> Result<Frame, serde_json::Error> {
+    let Envelope { metadata, payload } = serde_json::from_str(text)?;
+    let frame = match metadata.message_type.as_ref() {
+        "session_welcome" => Frame::Welcome(serde_json::from_value::<SessionPayload>(payload)?.session),
+        "session_reconnect" => {
+            let session = serde_json::from_value::<SessionPayload>(payload)?.session;
+            Frame::Reconnect(session.reconnect_url.unwrap_or_default())
+        }
+        "notification" if metadata.subscription_type.as_deref() == Some("channel.raid") => {
+            Frame::Raid(serde_json::from_value::<NotificationPayload<RaidEvent>>(payload)?.event)
+        }
+        "revocation" => Frame::Revoked(metadata.subscription_type.unwrap_or_default()),
+        // Keepalives and notifications of other subscriptions
+        _ => Frame::Keepalive,
+    };
+    Ok(frame)
+}
+
+/// The events which are received by an EventSub session.
+pub enum Notification {
+    Raid(RaidEvent),
+    /// Any message without an event, such as the keepalive messages which twitch sends every few seconds
+    Keepalive,
+}
+
+/// A websocket session of EventSub, which receives the events of its subscriptions.
+///
+/// Twitch closes the session if it has no subscription within a few seconds after connecting.
+pub struct EventSub {
+    socket: Socket,
+    session_id: Box<str>,
+    keepalive: Duration,
+}
+
+impl EventSub {
+    /// Connects to the websocket and waits for the welcome message, which starts the session.
+    pub async fn connect(url: &str) -> Result<Self, EventSubError> {
+        let (mut socket, _) = connect_async(url).await?;
+        let welcome_timeout = Duration::from_secs(DEFAULT_KEEPALIVE) + KEEPALIVE_GRACE;
+        let text = Self::read(&mut socket, welcome_timeout).await?;
+        match parse(&text)? {
+            Frame::Welcome(session) => {
+                let keepalive = session.keepalive_timeout_seconds.unwrap_or(DEFAULT_KEEPALIVE);
+                Ok(Self {
+                    socket,
+                    session_id: session.id,
+                    keepalive: Duration::from_secs(keepalive) + KEEPALIVE_GRACE,
+                })
+            }
+            _ => Err(EventSubError::MissingWelcome(text.into())),
+        }
+    }
+
+    /// The id of the session, which is used to create the subscriptions.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Waits for the next message, and follows the reconnects which are requested by twitch.
+    pub async fn next(&mut self) -> Result<Notification, EventSubError> {
+        loop {
+            let text = Self::read(&mut self.socket, self.keepalive).await?;
+            match parse(&text)? {
+                Frame::Raid(event) => return Ok(Notification::Raid(event)),
+                Frame::Welcome(_) | Frame::Keepalive => return Ok(Notification::Keepalive),
+                Frame::Revoked(kind) => log::warn!("Twitch revoked an EventSub subscription for {kind}"),
+                Frame::Reconnect(url) => {
+                    // The new connection keeps the session and its subscriptions
+                    log::info!("Moving the EventSub session to another connection");
+                    let next = Self::connect(&url).await?;
+                    let mut previous = std::mem::replace(self, next);
+                    drop(previous.socket.close(None).await);
+                    return Ok(Notification::Keepalive);
+                }
+            }
+        }
+    }
+
+    async fn read(socket: &mut Socket, wait: Duration) -> Result<String, EventSubError> {
+        loop {
+            let message = match timeout(wait, socket.next()).await {
+                Err(_) => return Err(EventSubError::Timeout),
+                Ok(None) => return Err(EventSubError::Closed),
+                Ok(Some(message)) => message?,
+            };
+
+            match message {
+                Message::Text(text) => return Ok(text),
+                Message::Close(_) => return Err(EventSubError::Closed),
+                // Pings are answered by the websocket itself
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let welcome = parse(
+            r#"{
+                "metadata": {
+                    "message_id": "96a3f3b5-5dec-4eed-908e-e11ee657416c",
+                    "message_type": "session_welcome",
+                    "message_timestamp": "2023-07-19T14:56:51.634234626Z"
+                },
+                "payload": {
+                    "session": {
+                        "id": "AQoQILE98gtqShGmLD7AM6yJThAB",
+                        "status": "connected",
+                        "connected_at": "2023-07-19T14:56:51.616329898Z",
+                        "keepalive_timeout_seconds": 10,
+                        "reconnect_url": null
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        match welcome {
+            Frame::Welcome(session) => {
+                assert_eq!(session.id.as_ref(), "AQoQILE98gtqShGmLD7AM6yJThAB");
+                assert_eq!(session.keepalive_timeout_seconds, Some(10));
+            }
+            _ => panic!("Expected a welcome"),
+        }
+
+        let raid = parse(
+            r#"{
+                "metadata": {
+                    "message_id": "befa7b53-d79d-478f-86b9-120f112b044e",
+                    "message_type": "notification",
+                    "message_timestamp": "2022-11-16T10:11:12.464757833Z",
+                    "subscription_type": "channel.raid",
+                    "subscription_version": "1"
+                },
+                "payload": {
+                    "subscription": {
+                        "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+                        "status": "enabled",
+                        "type": "channel.raid",
+                        "version": "1",
+                        "cost": 0,
+                        "condition": {"to_broadcaster_user_id": "", "from_broadcaster_user_id": "1337"},
+                        "transport": {"method": "websocket", "session_id": "AQoQILE98gtqShGmLD7AM6yJThAB"},
+                        "created_at": "2022-11-16T10:11:12.464757833Z"
+                    },
+                    "event": {
+                        "from_broadcaster_user_id": "1234",
+                        "from_broadcaster_user_login": "cool_user",
+                        "from_broadcaster_user_name": "Cool_User",
+                        "to_broadcaster_user_id": "1337",
+                        "to_broadcaster_user_login": "cooler_user",
+                        "to_broadcaster_user_name": "Cooler_User",
+                        "viewers": 9001
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        match raid {
+            Frame::Raid(event) => {
+                assert_eq!(event.to_broadcaster_user_login.as_ref(), "cooler_user");
+                assert_eq!(event.viewers, 9001);
+            }
+            _ => panic!("Expected a raid"),
+        }
+
+        let keepalive = parse(
+            r#"{
+                "metadata": {
+                    "message_id": "84c1e79a-2a4b-4c13-ba0b-4312293e9308",
+                    "message_type": "session_keepalive",
+                    "message_timestamp": "2023-07-19T10:11:12.634234626Z"
+                },
+                "payload": {}
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(keepalive, Frame::Keepalive));
+    }
+}
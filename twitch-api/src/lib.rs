@@ -4,9 +4,13 @@ pub use model::*;
 pub mod model;
 #[macro_use]
 pub mod oauth;
+mod cache;
 pub mod client;
 pub mod config;
 pub mod error;
+pub mod eventsub;
+mod source;
+mod thumbnail;
 
 // Serde deserialization into Instant
 pub(crate) mod expires_at {
@@ -4,13 +4,14 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use super::TwitchClient;
+use super::{client::resolve_image_url, TwitchClient};
 use crate::error::RequestError;
 
 static EMPTY: Lazy<Arc<Game>> = Lazy::new(|| {
     Arc::new(Game {
         id: String::new().into_boxed_str(),
         name: "No Category".to_owned().into_boxed_str(),
+        box_art_url: String::new().into_boxed_str(),
     })
 });
 
@@ -18,6 +19,9 @@ static EMPTY: Lazy<Arc<Game>> = Lazy::new(|| {
 pub struct Game {
     pub id: Box<str>,
     pub name: Box<str>,
+    /// The box art url template, with `{width}` and `{height}` placeholders
+    #[serde(default)]
+    pub box_art_url: Box<str>,
 }
 
 impl Game {
@@ -30,6 +34,15 @@ impl Game {
     pub const fn is_empty(&self) -> bool {
         self.id.is_empty()
     }
+
+    /// The url of the box art in the size used by twitch for its directory, if the game has any.
+    pub fn box_art(&self) -> Option<String> {
+        if self.box_art_url.is_empty() {
+            None
+        } else {
+            Some(resolve_image_url(&self.box_art_url, 285, 380))
+        }
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -41,7 +54,8 @@ pub struct User {
     // pub kind: String,
     // pub broadcaster_type: String,
     // pub description: String,
-    // pub profile_image_url: String,
+    #[serde(default)]
+    pub profile_image_url: Box<str>,
     // pub offline_image_url: String,
     // pub view_count: u64,
 }
@@ -81,6 +95,12 @@ impl<'de> Deserialize<'de> for VideoType {
 #[derive(Deserialize, Clone, Debug)]
 pub struct Video {
     pub id: Box<str>,
+    #[serde(default)]
+    pub user_id: Box<str>,
+    #[serde(default)]
+    pub user_login: Box<str>,
+    #[serde(default)]
+    pub user_name: Box<str>,
     pub url: Box<str>,
     pub title: Box<str>,
     pub thumbnail_url: Box<str>,
@@ -112,12 +132,45 @@ pub struct Clip {
     pub created_at: eos::DateTime,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Deserialize, Clone, Debug)]
+pub struct StreamMarker {
+    pub id: Box<str>,
+    pub created_at: eos::DateTime,
+    #[serde(default)]
+    pub description: Box<str>,
+    pub position_seconds: u32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VideoMarkers {
+    pub video_id: Box<str>,
+    pub markers: Vec<StreamMarker>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UserMarkers {
+    pub videos: Vec<VideoMarkers>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum StreamType {
     Live,
+    Rerun,
+    Premiere,
     None,
 }
 
+impl StreamType {
+    /// The label to show in notifications for streams that aren't regular live broadcasts.
+    pub const fn label(&self) -> Option<&'static str> {
+        match self {
+            StreamType::Rerun => Some("Rerun"),
+            StreamType::Premiere => Some("Premiere"),
+            _ => None,
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for StreamType {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -126,6 +179,8 @@ impl<'de> Deserialize<'de> for StreamType {
         let s = String::deserialize(deserializer)?;
         match s.as_str() {
             "live" => Ok(StreamType::Live),
+            "rerun" | "playlist" => Ok(StreamType::Rerun),
+            "premiere" => Ok(StreamType::Premiere),
             _ => Ok(StreamType::None),
         }
     }
@@ -144,6 +199,8 @@ pub struct Stream {
     pub user_login: Box<str>,
     pub user_name: Box<str>,
     pub started_at: eos::DateTime,
+    #[serde(default)]
+    pub viewer_count: u32,
 }
 
 impl Stream {
@@ -160,14 +217,150 @@ impl Stream {
     }
 }
 
+#[derive(Deserialize, Clone, Debug)]
+pub struct Team {
+    pub team_name: Box<str>,
+    #[serde(default)]
+    pub users: Vec<TeamMember>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TeamMember {
+    pub user_id: Box<str>,
+    pub user_login: Box<str>,
+    pub user_name: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct FollowedChannel {
+    pub broadcaster_id: Box<str>,
+    pub broadcaster_login: Box<str>,
+    pub broadcaster_name: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Schedule {
+    pub broadcaster_id: Box<str>,
+    pub broadcaster_login: Box<str>,
+    pub broadcaster_name: Box<str>,
+    /// The upcoming segments, null if there are none
+    #[serde(default)]
+    pub segments: Option<Vec<ScheduleSegment>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ScheduleSegment {
+    pub id: Box<str>,
+    pub start_time: eos::DateTime,
+    #[serde(default)]
+    pub title: Box<str>,
+    /// Set if this occurrence of a recurring segment was canceled
+    #[serde(default)]
+    pub canceled_until: Option<Box<str>>,
+    #[serde(default)]
+    pub category: Option<Game>,
+    #[serde(default)]
+    pub is_recurring: bool,
+}
+
+/// An event of the most recent hype train of a channel.
+#[derive(Deserialize, Clone, Debug)]
+pub struct HypeTrainEvent {
+    pub id: Box<str>,
+    pub event_data: HypeTrain,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct HypeTrain {
+    /// The id of the hype train, which is the same for all of its events
+    pub id: Box<str>,
+    pub broadcaster_id: Box<str>,
+    pub level: u32,
+    /// The points contributed in the current level
+    pub total: u64,
+    /// The points required to reach the next level
+    pub goal: u64,
+    pub started_at: eos::DateTime,
+    /// When the hype train ends, unless it reaches the next level before
+    pub expires_at: eos::DateTime,
+}
+
+/// A creator goal, such as a follower or subscription goal.
+#[derive(Deserialize, Clone, Debug)]
+pub struct CreatorGoal {
+    pub id: Box<str>,
+    pub broadcaster_id: Box<str>,
+    pub broadcaster_login: Box<str>,
+    pub broadcaster_name: Box<str>,
+    /// The kind of goal, like `follower` or `subscription`
+    #[serde(rename = "type")]
+    pub kind: Box<str>,
+    #[serde(default)]
+    pub description: Box<str>,
+    pub current_amount: u64,
+    pub target_amount: u64,
+}
+
+impl CreatorGoal {
+    /// The progress towards the target in percent, which can exceed 100.
+    pub fn percent(&self) -> u64 {
+        if self.target_amount == 0 {
+            100
+        } else {
+            self.current_amount * 100 / self.target_amount
+        }
+    }
+}
+
+/// The `channel.raid` event of EventSub, which is sent when a broadcaster raids another channel.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RaidEvent {
+    pub from_broadcaster_user_id: Box<str>,
+    pub from_broadcaster_user_login: Box<str>,
+    pub from_broadcaster_user_name: Box<str>,
+    pub to_broadcaster_user_id: Box<str>,
+    pub to_broadcaster_user_login: Box<str>,
+    pub to_broadcaster_user_name: Box<str>,
+    pub viewers: u64,
+}
+
+/// Response of endpoints which report the total number of items, such as `/channels/followers`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct TwitchTotal {
+    pub total: u64,
+}
+
+/// Response wrapper for endpoints which return a single object as data, such as `/schedule`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct TwitchObject<T> {
+    pub data: T,
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct TwitchData<T> {
     pub data: Vec<T>,
+    #[serde(default)]
+    pub pagination: Pagination,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Pagination {
+    pub cursor: Option<Box<str>>,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct VideoDuration(u32);
 
+impl VideoDuration {
+    pub const fn from_secs(secs: u32) -> Self {
+        Self(secs)
+    }
+
+    pub const fn as_secs(&self) -> u32 {
+        self.0
+    }
+}
+
 impl Add<VideoDuration> for VideoDuration {
     type Output = VideoDuration;
 
@@ -217,7 +410,7 @@ impl<'de> Deserialize<'de> for VideoDuration {
 mod tests {
     use serde::Deserialize;
 
-    use super::VideoDuration;
+    use super::{Game, Schedule, StreamType, TwitchData, TwitchObject, VideoDuration};
     type Error = Box<dyn std::error::Error>;
 
     #[derive(Deserialize)]
@@ -225,6 +418,12 @@ mod tests {
         pub duration: VideoDuration,
     }
 
+    #[derive(Deserialize)]
+    struct TypeHolder {
+        #[serde(rename = "type")]
+        pub kind: StreamType,
+    }
+
     #[test]
     fn parse_duration() -> Result<(), Error> {
         let holder: Holder = serde_json::from_str(r#"{"duration": "1h02m3s"}"#)?;
@@ -233,4 +432,86 @@ mod tests {
         assert_eq!(VideoDuration(10).to_string(), "00h00m10s");
         Ok(())
     }
+    #[test]
+    fn parse_stream_type() -> Result<(), Error> {
+        let holder: TypeHolder = serde_json::from_str(r#"{"type": "rerun"}"#)?;
+        assert_eq!(holder.kind, StreamType::Rerun);
+        assert_eq!(holder.kind.label(), Some("Rerun"));
+        let holder: TypeHolder = serde_json::from_str(r#"{"type": ""}"#)?;
+        assert_eq!(holder.kind, StreamType::None);
+        assert_eq!(holder.kind.label(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_pagination() -> Result<(), Error> {
+        let page: TwitchData<u32> =
+            serde_json::from_str(r#"{"data": [1, 2], "pagination": {"cursor": "eyJiIjpudWxs"}}"#)?;
+        assert_eq!(page.data, vec![1, 2]);
+        assert_eq!(page.pagination.cursor.as_deref(), Some("eyJiIjpudWxs"));
+
+        let page: TwitchData<u32> = serde_json::from_str(r#"{"data": [], "pagination": {}}"#)?;
+        assert!(page.pagination.cursor.is_none());
+        let page: TwitchData<u32> = serde_json::from_str(r#"{"data": []}"#)?;
+        assert!(page.pagination.cursor.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_game() -> Result<(), Error> {
+        let game: Game = serde_json::from_str(
+            r#"{"id": "33214", "name": "Fortnite", "box_art_url": "https://static-cdn.jtvnw.net/ttv-boxart/33214-{width}x{height}.jpg"}"#,
+        )?;
+        assert_eq!(
+            game.box_art().as_deref(),
+            Some("https://static-cdn.jtvnw.net/ttv-boxart/33214-285x380.jpg")
+        );
+
+        // Games cached by older versions have no box art
+        let game: Game = serde_json::from_str(r#"{"id": "33214", "name": "Fortnite"}"#)?;
+        assert!(game.box_art().is_none());
+        assert!(Game::empty().box_art().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_schedule() -> Result<(), Error> {
+        let body: TwitchObject<Schedule> = serde_json::from_str(
+            r#"{
+                "data": {
+                    "segments": [
+                        {
+                            "id": "eyJzZWdtZW50SUQiOiJlNGFjYzcyNC0zNzFmLTQwMmMtODFjYS0yM2FkYTc5NzU5ZDQiLCJpc29ZZWFyIjoyMDIxLCJpc29XZWVrIjoyNn0=",
+                            "start_time": "2021-07-01T18:00:00Z",
+                            "end_time": "2021-07-01T19:00:00Z",
+                            "title": "TwitchDev Monthly Update // July 1, 2021",
+                            "canceled_until": null,
+                            "category": {"id": "509670", "name": "Science & Technology"},
+                            "is_recurring": false
+                        }
+                    ],
+                    "broadcaster_id": "141981764",
+                    "broadcaster_name": "TwitchDev",
+                    "broadcaster_login": "twitchdev",
+                    "vacation": null
+                },
+                "pagination": {}
+            }"#,
+        )?;
+
+        let segments = body.data.segments.unwrap_or_default();
+        assert_eq!(body.data.broadcaster_login.as_ref(), "twitchdev");
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].title.as_ref(), "TwitchDev Monthly Update // July 1, 2021");
+        assert_eq!(
+            segments[0].category.as_ref().map(|c| c.name.as_ref()),
+            Some("Science & Technology")
+        );
+
+        let body: TwitchObject<Schedule> = serde_json::from_str(
+            r#"{"data": {"segments": null, "broadcaster_id": "1", "broadcaster_name": "A", "broadcaster_login": "a"}}"#,
+        )?;
+        assert!(body.data.segments.is_none());
+        Ok(())
+    }
 }
@@ -1,23 +1,27 @@
+use async_trait::async_trait;
 use hashbrown::HashMap;
 use std::{
     borrow::Cow,
     str::FromStr,
-    time::{Duration, Instant},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tracing as log;
 
 use bytes::Bytes;
+use commons::retry::{retry, Retry, RetryPolicy};
 use log::{error, warn};
-use reqwest::{Client as HttpClient, Method};
-use serde::Deserialize;
+use reqwest::{header::HeaderMap, Client as HttpClient, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
 
-use crate::error::RequestError;
+use crate::error::{request_id, RequestError};
 
 const BASE_URL: &str = "https://api.twitch.tv/helix";
-
-fn get_url(endpoint: &str) -> String {
-    format!("{}/{}", BASE_URL, endpoint)
-}
+const AUTH_URL: &str = "https://id.twitch.tv/oauth2";
 
 pub enum QueryParams<'a> {
     None,
@@ -43,121 +47,423 @@ impl<'a> From<Box<[(&'a str, Cow<'a, str>)]>> for QueryParams<'a> {
     }
 }
 
+/// The rate limit budget of a client id, as reported by the `Ratelimit-*` headers of twitch.
+#[derive(Clone, Copy, Debug)]
+struct Budget {
+    remaining: u32,
+    reset: Instant,
+}
+
+impl Budget {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+        let remaining = header("Ratelimit-Remaining")?;
+        let reset = header("Ratelimit-Reset")?;
+        Some(Self {
+            remaining: u32::try_from(remaining).unwrap_or(u32::MAX),
+            reset: Instant::now() + Duration::from_secs(reset.saturating_sub(unix_now())),
+        })
+    }
+
+    fn is_exhausted(&self, now: Instant) -> bool {
+        self.remaining == 0 && now < self.reset
+    }
+}
+
+/// Credentials of another twitch application, with their own app access token.
+struct PooledCredentials {
+    params: ClientParams,
+    identity: tokio::sync::Mutex<Option<Identity>>,
+}
+
 pub struct OauthClient {
     pub params: ClientParams,
     pub http: HttpClient,
+    api_url: Box<str>,
+    auth_url: Box<str>,
+    /// Used while the rate limit budget of the primary credentials is exhausted
+    pool: Vec<PooledCredentials>,
+    /// The responses of the GET requests which are currently sent, by url
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<Option<Bytes>>>>>,
+    /// The last known budget, by client id
+    budgets: Mutex<HashMap<Box<str>, Budget>>,
+    /// Whether twitch rejected the app access token of the primary credentials
+    unauthorized: AtomicBool,
+}
+
+/// A failed attempt of a request.
+enum Attempt {
+    /// Retried after the backoff, such as for server and connection errors
+    Retry,
+    /// Retried right away, since the rate limit was already waited for or other credentials have budget left
+    RateLimited,
+    Failed(RequestError),
+}
+
+impl Attempt {
+    fn from_send_error(err: reqwest::Error) -> Self {
+        if err.is_connect() {
+            warn!("Connection error: {}", err);
+        } else if err.is_timeout() {
+            warn!("Request timeout: {}", err);
+        } else if err.is_request() {
+            warn!("Request error: {}", err);
+        } else {
+            error!("Request failed unexpectedly: {}", err);
+            return Self::Failed(err.into());
+        }
+        Self::Retry
+    }
+
+    fn retry(&self) -> Retry {
+        match self {
+            Self::Retry => Retry::Backoff,
+            Self::RateLimited => Retry::Immediately,
+            Self::Failed(_) => Retry::Stop,
+        }
+    }
+
+    /// The error of the last attempt, which is a timeout if the request kept failing.
+    fn into_error(self) -> RequestError {
+        match self {
+            Self::Failed(err) => err,
+            Self::Retry | Self::RateLimited => RequestError::Timeout,
+        }
+    }
+}
+
+impl From<RequestError> for Attempt {
+    fn from(err: RequestError) -> Self {
+        Self::Failed(err)
+    }
 }
 
 impl OauthClient {
-    const MAX_BACKOFF: Duration = Duration::from_secs(16);
-    const MIN_BACKOFF: Duration = Duration::from_secs(1);
+    const RETRY: RetryPolicy = RetryPolicy {
+        max_attempts: 10,
+        min_backoff: Duration::from_secs(1),
+        max_backoff: Duration::from_secs(16),
+        jitter: 0.2,
+    };
 
     pub fn new(params: ClientParams) -> Self {
+        Self::with_http(params, HttpClient::new())
+    }
+
+    /// Uses a preconfigured HTTP client, which is also used for the thumbnail downloads.
+    pub fn with_http(params: ClientParams, http: HttpClient) -> Self {
         Self {
             params,
-            http: HttpClient::new(),
+            http,
+            api_url: BASE_URL.into(),
+            auth_url: AUTH_URL.into(),
+            pool: Vec::new(),
+            in_flight: Mutex::default(),
+            budgets: Mutex::default(),
+            unauthorized: AtomicBool::new(false),
         }
     }
 
+    /// Adds the credentials of other twitch applications, which are used while the rate limit of the primary credentials is exhausted.
+    ///
+    /// Each application has its own budget of requests per minute, which is tracked separately.
+    pub fn with_credentials(mut self, credentials: Vec<ClientParams>) -> Self {
+        self.pool = credentials
+            .into_iter()
+            .map(|params| PooledCredentials {
+                params,
+                identity: tokio::sync::Mutex::default(),
+            })
+            .collect();
+        self
+    }
+
+    /// Sends the requests to other servers, such as a mock server in tests.
+    ///
+    /// The `api_url` replaces `https://api.twitch.tv/helix` and the `auth_url` replaces `https://id.twitch.tv/oauth2`.
+    pub fn with_base_urls(mut self, api_url: &str, auth_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').into();
+        self.auth_url = auth_url.trim_end_matches('/').into();
+        self
+    }
+
+    fn get_url(&self, endpoint: &str) -> String {
+        format!("{}/{}", self.api_url, endpoint)
+    }
+
+    fn auth_endpoint(&self, endpoint: &str) -> String {
+        format!("{}/{}", self.auth_url, endpoint)
+    }
+
     pub async fn authorize(&self) -> Result<Identity, RequestError> {
+        self.authorize_with(&self.params).await
+    }
+
+    async fn authorize_with(&self, params: &ClientParams) -> Result<Identity, RequestError> {
         let mut body = HashMap::with_capacity(3);
-        body.insert("client_id", self.params.client_id.clone());
-        body.insert("client_secret", self.params.client_secret.clone());
+        body.insert("client_id", params.client_id.clone());
+        body.insert("client_secret", params.client_secret.clone());
         body.insert("grant_type", "client_credentials".into());
 
-        let endpoint = "https://id.twitch.tv/oauth2/token".to_owned();
-
-        let mut backoff = Self::MIN_BACKOFF;
-        for _ in 0..10 {
-            let response = self.http.post(&endpoint).form(&body).send().await;
-
+        let endpoint = &self.auth_endpoint("token");
+        let body = &body;
+        let attempt = || async move {
+            let response = self.http.post(endpoint).form(body).send().await;
             match response {
-                Ok(res) if res.status().is_success() => {
-                    return Ok(res.json::<Identity>().await?);
-                }
+                Ok(res) if res.status().is_success() => Ok(res.json::<Identity>().await.map_err(RequestError::from)?),
                 Ok(res) if res.status().is_server_error() => {
-                    warn!("Server error: {}", res.status());
-                }
-                Ok(res) => {
-                    return Err(RequestError::from(res.status()));
-                }
-                Err(err) if err.is_connect() => {
-                    warn!("Connection error: {}", err);
-                }
-                Err(err) if err.is_timeout() => {
-                    warn!("Request timeout: {}", err);
+                    match request_id(res.headers()) {
+                        Some(id) => warn!("Server error: {} (request id {id})", res.status()),
+                        None => warn!("Server error: {}", res.status()),
+                    }
+                    Err(Attempt::Retry)
                 }
-                Err(err) if err.is_request() => {
-                    warn!("Request error: {}", err);
+                Ok(res) => Err(RequestError::from_response(res).await.into()),
+                Err(err) => Err(Attempt::from_send_error(err)),
+            }
+        };
+
+        retry(&Self::RETRY, attempt, Attempt::retry)
+            .await
+            .map_err(Attempt::into_error)
+    }
+
+    /// Starts the device code grant flow for a user access token with these scopes.
+    ///
+    /// The user has to open the verification uri and enter the user code, while [`poll_device_token`](Self::poll_device_token) waits for the token.
+    pub async fn request_device_code(&self, scopes: &[Box<str>]) -> Result<DeviceCode, RequestError> {
+        let scopes = scopes.join(" ");
+        let form = [
+            ("client_id", self.params.client_id.as_ref()),
+            ("scopes", scopes.as_str()),
+        ];
+        let response = self.http.post(self.auth_endpoint("device")).form(&form).send().await?;
+
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            Err(RequestError::from_response(response).await)
+        }
+    }
+
+    /// Waits until the user authorized the device code, polling at the interval required by twitch.
+    pub async fn poll_device_token(
+        &self,
+        code: &DeviceCode,
+        scopes: &[Box<str>],
+    ) -> Result<UserIdentity, RequestError> {
+        let scopes = scopes.join(" ");
+        let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+        let mut interval = Duration::from_secs(code.interval.max(1));
+
+        while Instant::now() < deadline {
+            tokio::time::sleep(interval).await;
+            let form = [
+                ("client_id", self.params.client_id.as_ref()),
+                ("scopes", scopes.as_str()),
+                ("device_code", code.device_code.as_ref()),
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ];
+
+            let response = self.http.post(self.auth_endpoint("token")).form(&form).send().await?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response.json().await?);
+            }
+
+            let message = response
+                .json::<ErrorBody>()
+                .await
+                .map(|b| b.message)
+                .unwrap_or_default();
+            match message.as_ref() {
+                "authorization_pending" => {}
+                "slow_down" => interval += Duration::from_secs(5),
+                _ => {
+                    error!("Device authorization failed: {} {}", status, message);
+                    return Err(RequestError::from(status));
                 }
-                Err(err) => {
-                    // error!("Request failed unexpectedly: {}", err);
-                    return Err(RequestError::from(err));
+            }
+        }
+
+        Err(RequestError::Timeout)
+    }
+
+    /// Exchanges the refresh token of a user for a new access token.
+    pub async fn refresh_user(&self, refresh_token: &str) -> Result<UserIdentity, RequestError> {
+        let form = [
+            ("client_id", self.params.client_id.as_ref()),
+            ("client_secret", self.params.client_secret.as_ref()),
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ];
+
+        let response = self.http.post(self.auth_endpoint("token")).form(&form).send().await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            Err(RequestError::from_response(response).await)
+        }
+    }
+
+    /// Checks whether the access token is still valid, returning its remaining lifetime.
+    ///
+    /// Returns `None` if the token was revoked or expired.
+    pub async fn validate(&self, access_token: &str) -> Result<Option<Duration>, RequestError> {
+        let response = self
+            .http
+            .get(self.auth_endpoint("validate"))
+            .header("Authorization", format!("OAuth {access_token}"))
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => {
+                let validation: Validation = response.json().await?;
+                Ok(Some(Duration::from_secs(validation.expires_in)))
+            }
+            StatusCode::UNAUTHORIZED => Ok(None),
+            status => Err(RequestError::from(status)),
+        }
+    }
+
+    fn is_exhausted(&self, client_id: &str) -> bool {
+        let budgets = self.budgets.lock().unwrap();
+        budgets
+            .get(client_id)
+            .map_or(false, |budget| budget.is_exhausted(Instant::now()))
+    }
+
+    /// Whether any of the pooled credentials can be used instead of the exhausted ones.
+    fn has_budget(&self) -> bool {
+        !self.pool.is_empty()
+            && std::iter::once(&self.params)
+                .chain(self.pool.iter().map(|pooled| &pooled.params))
+                .any(|params| !self.is_exhausted(&params.client_id))
+    }
+
+    /// Whether the app access token of the primary credentials was rejected since the last call.
+    ///
+    /// The token has to be authorized again, since it was revoked or the client secret was changed.
+    pub fn take_unauthorized(&self) -> bool {
+        self.unauthorized.swap(false, Ordering::Relaxed)
+    }
+
+    /// Forgets the rejected app access token of these credentials, so they are authorized again.
+    async fn invalidate(&self, client_id: &str) {
+        if *self.params.client_id == *client_id {
+            self.unauthorized.store(true, Ordering::Relaxed);
+        } else if let Some(pooled) = self.pool.iter().find(|pooled| *pooled.params.client_id == *client_id) {
+            *pooled.identity.lock().await = None;
+        }
+    }
+
+    /// The client id and access token for the next request.
+    ///
+    /// The pooled credentials are only used while the budget of the primary credentials is exhausted.
+    async fn select_credentials(&self, id: &Identity) -> (Box<str>, Box<str>) {
+        let primary = (self.params.client_id.clone(), id.access_token.clone());
+        if self.pool.is_empty() || !self.is_exhausted(&self.params.client_id) {
+            return primary;
+        }
+
+        for pooled in &self.pool {
+            let client_id = &pooled.params.client_id;
+            if self.is_exhausted(client_id) {
+                continue;
+            }
+
+            let mut identity = pooled.identity.lock().await;
+            if identity
+                .as_ref()
+                .map_or(true, |token| token.expires_at <= Instant::now())
+            {
+                match self.authorize_with(&pooled.params).await {
+                    Ok(authorized) => *identity = Some(authorized),
+                    Err(e) => {
+                        warn!("Failed to authorize pooled client {client_id}: {e}");
+                        continue;
+                    }
                 }
-            };
+            }
 
-            warn!("Retrying in {} seconds...", backoff.as_secs());
-            tokio::time::sleep(backoff).await;
-            backoff = Ord::clamp(backoff * 2, Self::MIN_BACKOFF, Self::MAX_BACKOFF);
+            if let Some(ref identity) = *identity {
+                log::debug!("Rate limit of the primary client is exhausted, using client {client_id}");
+                return (client_id.clone(), identity.access_token.clone());
+            }
         }
 
-        Err(RequestError::Timeout)
+        primary
     }
 
     /// Does not check if identity is expired, user error if so.
-    async fn make_request<U, T, F>(
+    ///
+    /// With `rotate`, the request may use the pooled credentials instead of the provided identity.
+    async fn make_request<T, F>(
         &self,
         id: &Identity,
+        rotate: bool,
         method: Method,
-        url: U,
-        params: QueryParams<'_>,
+        full_url: &str,
+        body: Option<&serde_json::Value>,
         handler: F,
     ) -> Result<T, RequestError>
     where
-        U: Into<String>,
         T: Sized + Send + Sync,
         F: FnOnce(Bytes) -> Result<T, RequestError>,
     {
-        let mut full_url: String = url.into();
-
-        if let QueryParams::With(vec) = params {
-            let mut query = vec.iter().fold(String::from("?"), |mut a, (name, value)| {
-                a.push_str(name);
-                a.push('=');
-                a.push_str(value.as_ref());
-                a.push('&');
-                a
-            });
-            query.truncate(query.len() - 1);
-            full_url.push_str(&query);
-        }
-
-        let mut backoff = Self::MIN_BACKOFF;
+        let method = &method;
+        let attempt = || async move {
+            let (client_id, access_token) = if rotate {
+                self.select_credentials(id).await
+            } else {
+                (self.params.client_id.clone(), id.access_token.clone())
+            };
 
-        for _ in 0..10 {
-            let request = self
+            let mut builder = self
                 .http
-                .request(method.clone(), full_url.clone())
-                .header("Client-ID", self.params.client_id.as_ref())
-                .bearer_auth(&id.access_token)
-                .build()?;
+                .request(method.clone(), full_url)
+                .header("Client-ID", client_id.as_ref())
+                .bearer_auth(&access_token);
+            if let Some(body) = body {
+                builder = builder.json(body);
+            }
+            let request = builder.build().map_err(RequestError::from)?;
 
             let response = self.http.execute(request).await;
-            match response {
-                Ok(res) if res.status().is_success() => {
-                    return handler(res.bytes().await?);
+            if let Ok(ref res) = response {
+                if let Some(budget) = Budget::from_headers(res.headers()) {
+                    self.budgets.lock().unwrap().insert(client_id.clone(), budget);
                 }
+            }
+
+            match response {
+                Ok(res) if res.status().is_success() => Ok(res.bytes().await.map_err(RequestError::from)?),
                 Ok(res) if res.status().is_server_error() => {
-                    warn!("Server error: {}", res.status());
+                    match request_id(res.headers()) {
+                        Some(id) => warn!("Server error: {} (request id {id})", res.status()),
+                        None => warn!("Server error: {}", res.status()),
+                    }
+                    Err(Attempt::Retry)
                 }
                 Ok(res) if res.status().as_u16() == 429 => {
+                    // The reset time is unknown without the headers, so the budget is retried after a while
+                    let reset = Budget::from_headers(res.headers())
+                        .map_or_else(|| Instant::now() + Duration::from_secs(10), |budget| budget.reset);
+                    let budget = Budget { remaining: 0, reset };
+                    self.budgets.lock().unwrap().insert(client_id.clone(), budget);
+                    if rotate && self.has_budget() {
+                        warn!("Rate limit of client {client_id} exceeded, retrying with other credentials...");
+                        return Err(Attempt::RateLimited);
+                    }
+
                     // skip standard exponential backoff for rate-limit retries since we already wait here
                     if let Some(header) = res.headers().get("Retry-After") {
-                        match header.to_str()?.parse() {
+                        match header.to_str().map_err(RequestError::from)?.parse() {
                             Ok(retry_after) => {
                                 warn!("Rate limit exceeded, retrying in {} seconds...", retry_after);
                                 tokio::time::sleep(Duration::from_secs(retry_after)).await;
-                                continue;
+                                return Err(Attempt::RateLimited);
                             }
                             Err(err) => {
                                 error!("Failed to parse Retry-After header {:?}: {}", header, err);
@@ -166,34 +472,79 @@ impl OauthClient {
                     }
                     warn!("Rate limit exceeded, retrying in 10 seconds...");
                     tokio::time::sleep(Duration::from_secs(10)).await;
-                    continue;
+                    Err(Attempt::RateLimited)
                 }
                 Ok(res) => {
-                    return Err(RequestError::from(res.status()));
-                }
-                Err(err) if err.is_connect() => {
-                    warn!("Connection error: {}", err);
-                }
-                Err(err) if err.is_timeout() => {
-                    warn!("Request timeout: {}", err);
-                }
-                Err(err) if err.is_request() => {
-                    warn!("Request error: {}", err);
+                    if rotate && res.status() == StatusCode::UNAUTHORIZED {
+                        self.invalidate(&client_id).await;
+                    }
+                    Err(RequestError::from_response(res).await.into())
                 }
-                Err(err) => {
-                    error!("Request failed unexpectedly: {}", err);
-                    return Err(RequestError::from(err));
+                Err(err) => Err(Attempt::from_send_error(err)),
+            }
+        };
+
+        let body = retry(&Self::RETRY, attempt, Attempt::retry)
+            .await
+            .map_err(Attempt::into_error)?;
+        handler(body)
+    }
+
+    /// Sends a GET request, which shares the response with concurrent requests for the same url.
+    pub async fn get<F, T>(
+        &self,
+        id: &Identity,
+        endpoint: &str,
+        params: QueryParams<'_>,
+        handler: F,
+    ) -> Result<T, RequestError>
+    where
+        T: Sized + Send + Sync,
+        F: FnOnce(Bytes) -> Result<T, RequestError>,
+    {
+        let url = with_query(self.get_url(endpoint), params);
+        let shared = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            Arc::clone(in_flight.entry(url.clone()).or_default())
+        };
+
+        let mut error = None;
+        let slot = &mut error;
+        let request_url = url.as_str();
+        let body = shared
+            .get_or_init(|| async move {
+                match self.make_request(id, true, Method::GET, request_url, None, Ok).await {
+                    Ok(body) => Some(body),
+                    Err(e) => {
+                        *slot = Some(e);
+                        None
+                    }
                 }
-            };
+            })
+            .await
+            .clone();
 
-            warn!("Retrying in {} seconds...", backoff.as_secs());
-            tokio::time::sleep(backoff).await;
-            backoff = Ord::clamp(backoff * 2, Self::MIN_BACKOFF, Self::MAX_BACKOFF);
+        // Only concurrent requests share the response, later requests are sent again
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if in_flight
+                .get(&url)
+                .map_or(false, |current| Arc::ptr_eq(current, &shared))
+            {
+                in_flight.remove(&url);
+            }
+        }
+
+        match (body, error) {
+            (Some(body), _) => handler(body),
+            (None, Some(e)) => Err(e),
+            // The error of the shared request is not shared, so this request is sent again on its own
+            (None, None) => handler(self.make_request(id, true, Method::GET, &url, None, Ok).await?),
         }
-        Err(RequestError::Timeout)
     }
 
-    pub async fn get<F, T>(
+    /// Sends the request with the provided token, which is required for user access tokens.
+    pub async fn get_with_token<F, T>(
         &self,
         id: &Identity,
         endpoint: &str,
@@ -204,11 +555,29 @@ impl OauthClient {
         T: Sized + Send + Sync,
         F: FnOnce(Bytes) -> Result<T, RequestError>,
     {
-        self.make_request(id, Method::GET, get_url(endpoint), params, handler)
+        let url = with_query(self.get_url(endpoint), params);
+        self.make_request(id, false, Method::GET, &url, None, handler).await
+    }
+
+    /// Sends a POST request with the JSON body and the provided token, which is never retried with other credentials.
+    pub async fn post_with_token<F, T>(
+        &self,
+        id: &Identity,
+        endpoint: &str,
+        body: &serde_json::Value,
+        handler: F,
+    ) -> Result<T, RequestError>
+    where
+        T: Sized + Send + Sync,
+        F: FnOnce(Bytes) -> Result<T, RequestError>,
+    {
+        let url = self.get_url(endpoint);
+        self.make_request(id, false, Method::POST, &url, Some(body), handler)
             .await
     }
 }
 
+#[derive(Deserialize, Clone, Debug)]
 pub struct ClientParams {
     pub client_id: Box<str>,
     pub client_secret: Box<str>,
@@ -223,6 +592,135 @@ pub struct Identity {
     pub token_type: Box<str>,
 }
 
+/// Response of the device authorization request, see https://dev.twitch.tv/docs/authentication/getting-tokens-oauth#device-code-grant-flow
+#[derive(Deserialize, Clone, Debug)]
+pub struct DeviceCode {
+    pub device_code: Box<str>,
+    pub user_code: Box<str>,
+    pub verification_uri: Box<str>,
+    /// Number of seconds until the device code expires
+    pub expires_in: u64,
+    /// Number of seconds to wait between polls for the token
+    pub interval: u64,
+}
+
+/// User access token, which can be refreshed without user interaction.
+#[derive(Deserialize, Clone)]
+pub struct UserIdentity {
+    #[serde(flatten)]
+    pub identity: Identity,
+    pub refresh_token: Box<str>,
+    #[serde(default)]
+    pub scope: Vec<Box<str>>,
+}
+
+impl UserIdentity {
+    pub fn from_stored(token: StoredToken, expires_in: Duration) -> Option<Self> {
+        let refresh_token = token.refresh_token.clone()?;
+        let scope = token.scopes.clone();
+        Some(Self {
+            identity: Identity::from_stored(token, expires_in),
+            refresh_token,
+            scope,
+        })
+    }
+
+    pub fn to_stored(&self) -> StoredToken {
+        StoredToken {
+            refresh_token: Some(self.refresh_token.clone()),
+            scopes: self.scope.clone(),
+            ..self.identity.to_stored()
+        }
+    }
+
+    /// Whether the token was granted all of these scopes.
+    pub fn has_scopes(&self, scopes: &[Box<str>]) -> bool {
+        scopes.iter().all(|scope| self.scope.contains(scope))
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    message: Box<str>,
+}
+
+impl Identity {
+    /// Restores an identity from the persisted token, with the remaining lifetime reported by twitch.
+    pub fn from_stored(token: StoredToken, expires_in: Duration) -> Self {
+        Self {
+            access_token: token.access_token,
+            expires_at: Instant::now() + expires_in,
+            token_type: token.token_type,
+        }
+    }
+
+    pub fn to_stored(&self) -> StoredToken {
+        let expires_in = self.expires_at.saturating_duration_since(Instant::now());
+        StoredToken {
+            access_token: self.access_token.clone(),
+            expires_at: unix_now() + expires_in.as_secs(),
+            token_type: self.token_type.clone(),
+            refresh_token: None,
+            scopes: Vec::new(),
+        }
+    }
+}
+
+/// Appends the query parameters to the url.
+fn with_query(mut url: String, params: QueryParams<'_>) -> String {
+    if let QueryParams::With(vec) = params {
+        let mut query = vec.iter().fold(String::from("?"), |mut a, (name, value)| {
+            a.push_str(name);
+            a.push('=');
+            a.push_str(value.as_ref());
+            a.push('&');
+            a
+        });
+        query.truncate(query.len() - 1);
+        url.push_str(&query);
+    }
+    url
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// An access token in a form that can be persisted across restarts.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StoredToken {
+    pub access_token: Box<str>,
+    /// Unix timestamp of the expiration
+    pub expires_at: u64,
+    pub token_type: Box<str>,
+    /// Only set for user access tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<Box<str>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<Box<str>>,
+}
+
+impl StoredToken {
+    /// The remaining lifetime according to the stored expiration.
+    pub fn expires_in(&self) -> Duration {
+        Duration::from_secs(self.expires_at.saturating_sub(unix_now()))
+    }
+}
+
+/// Storage for the access token, so restarts don't need a new client credentials grant.
+#[async_trait]
+pub trait TokenStore: Send + Sync {
+    async fn load(&self) -> Option<StoredToken>;
+    async fn save(&self, token: &StoredToken);
+}
+
+/// Response of the validate endpoint, see https://dev.twitch.tv/docs/authentication/validate-tokens
+#[derive(Deserialize)]
+struct Validation {
+    expires_in: u64,
+}
+
 impl FromStr for Identity {
     type Err = serde_json::Error;
 
@@ -248,5 +746,88 @@ mod tests {
 
         assert_eq!(identity.access_token.as_ref(), "jostpf5q0uzmxmkba9iyug38kjtgh");
         assert_eq!(identity.token_type.as_ref(), "bearer");
+
+        let stored = identity.to_stored();
+        assert_eq!(stored.access_token, identity.access_token);
+        let expires_in = stored.expires_in().as_secs();
+        assert!((5011270..=5011271).contains(&expires_in));
+    }
+
+    #[test]
+    fn test_user_identity() {
+        let user: UserIdentity = serde_json::from_str(
+            r#"{
+                "access_token": "rfx2uswqe8l4g1mkagrvg5tv0ks3",
+                "expires_in": 14124,
+                "refresh_token": "5b93chm6hdve3mycz05zfzatkfdenfspp1h1ar2xxdalen01",
+                "scope": ["channel:read:subscriptions"],
+                "token_type": "bearer"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(user.identity.access_token.as_ref(), "rfx2uswqe8l4g1mkagrvg5tv0ks3");
+        assert!(user.has_scopes(&["channel:read:subscriptions".into()]));
+        assert!(!user.has_scopes(&["moderator:read:chatters".into()]));
+
+        let stored = user.to_stored();
+        let restored = UserIdentity::from_stored(stored.clone(), stored.expires_in()).unwrap();
+        assert_eq!(restored.refresh_token, user.refresh_token);
+        assert_eq!(restored.scope, user.scope);
+
+        // App tokens cannot be restored as user tokens
+        assert!(UserIdentity::from_stored(user.identity.to_stored(), Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Ratelimit-Remaining", "0".parse().unwrap());
+        headers.insert("Ratelimit-Reset", (unix_now() + 30).to_string().parse().unwrap());
+
+        let budget = Budget::from_headers(&headers).unwrap();
+        assert!(budget.is_exhausted(Instant::now()));
+        assert!(!budget.is_exhausted(Instant::now() + Duration::from_secs(31)));
+
+        headers.insert("Ratelimit-Remaining", "799".parse().unwrap());
+        assert!(!Budget::from_headers(&headers).unwrap().is_exhausted(Instant::now()));
+
+        headers.remove("Ratelimit-Reset");
+        assert!(Budget::from_headers(&headers).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_requests() {
+        use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(path("/helix/games"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"{"data":[]}"#)
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .mount(&server)
+            .await;
+
+        let base = server.uri();
+        let client = OauthClient::new(ClientParams {
+            client_id: "id".into(),
+            client_secret: "secret".into(),
+        })
+        .with_base_urls(&format!("{base}/helix"), &format!("{base}/oauth2"));
+        let id =
+            Identity::from_str(r#"{"access_token": "token", "expires_in": 3600, "token_type": "bearer"}"#).unwrap();
+
+        let (first, second) = tokio::join!(
+            client.get(&id, "games", build_query!("id" => "1"), Ok),
+            client.get(&id, "games", build_query!("id" => "1"), Ok),
+        );
+        assert_eq!(first.unwrap(), second.unwrap());
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        // Requests after the response are sent again
+        client.get(&id, "games", build_query!("id" => "1"), Ok).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
     }
 }
@@ -0,0 +1,46 @@
+use async_trait::async_trait;
+use commons::source::{LiveStream, StreamSource};
+
+use crate::{client::resolve_thumbnail_url, TwitchClient};
+
+#[async_trait]
+impl StreamSource for TwitchClient {
+    fn platform(&self) -> &'static str {
+        "Twitch"
+    }
+
+    fn color(&self) -> u32 {
+        0x6441A4
+    }
+
+    fn stream_url(&self, channel: &str, _stream_id: &str) -> String {
+        format!("https://twitch.tv/{channel}")
+    }
+
+    async fn get_live_streams(&self, channels: &[Box<str>]) -> anyhow::Result<Vec<LiveStream>> {
+        let streams = self.get_streams_by_login(channels).await?;
+
+        let mut live = Vec::with_capacity(streams.len());
+        for stream in streams {
+            let game = stream.get_game(self).await?;
+            let category = if game.is_empty() { "".into() } else { game.name.clone() };
+            live.push(LiveStream {
+                thumbnail_url: resolve_thumbnail_url(&stream.thumbnail_url).into(),
+                category,
+                id: stream.id,
+                user_id: stream.user_id,
+                user_login: stream.user_login,
+                user_name: stream.user_name,
+                title: stream.title,
+                started_at: stream.started_at,
+                viewer_count: stream.viewer_count,
+            });
+        }
+
+        Ok(live)
+    }
+
+    async fn get_thumbnail(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(TwitchClient::get_thumbnail(self, url).await?)
+    }
+}
@@ -0,0 +1,101 @@
+use std::{io::Cursor, time::Duration};
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, io::Reader};
+use reqwest::{header::CONTENT_LENGTH, Response};
+use tokio::{task::spawn_blocking, time::timeout};
+
+use crate::error::RequestError;
+
+/// Thumbnails above this size are not downloaded, twitch previews are usually below 500 KiB
+pub const MAX_BYTES: usize = 8 * 1024 * 1024;
+/// Time to download the whole body, since a slow response would otherwise hold up the notification
+const READ_TIMEOUT: Duration = Duration::from_secs(15);
+/// Larger images are scaled down to fit, since discord shows them at most this large
+const MAX_WIDTH: u32 = 1920;
+const MAX_HEIGHT: u32 = 1080;
+
+/// Reads the body of the response in chunks, aborting once it exceeds `max_bytes` or takes too long.
+pub(crate) async fn read_limited(mut response: Response, max_bytes: usize) -> Result<Vec<u8>, RequestError> {
+    let length = response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+    if let Some(length) = length.filter(|length| *length > max_bytes) {
+        return Err(RequestError::TooLarge(length));
+    }
+
+    let read = async {
+        let mut body = Vec::with_capacity(length.unwrap_or(0));
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > max_bytes {
+                return Err(RequestError::TooLarge(body.len() + chunk.len()));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    };
+
+    timeout(READ_TIMEOUT, read).await.unwrap_or(Err(RequestError::Timeout))
+}
+
+/// Scales the image down to fit into 1920x1080, or returns it unchanged if it is small enough or not decodable.
+///
+/// The size is read from the image header, so only larger images are decoded.
+pub(crate) async fn shrink(data: Vec<u8>) -> Result<Vec<u8>, RequestError> {
+    match dimensions(&data) {
+        Some((width, height)) if width > MAX_WIDTH || height > MAX_HEIGHT => {}
+        _ => return Ok(data),
+    }
+
+    // Decoding and encoding the image takes a moment, which should not block the other requests
+    spawn_blocking(move || resize(&data).unwrap_or(data))
+        .await
+        .map_err(|e| RequestError::Unexpected(e.into()))
+}
+
+/// The size of the image, without decoding it.
+fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    Reader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+fn resize(data: &[u8]) -> Option<Vec<u8>> {
+    let image = image::load_from_memory(data).ok()?;
+    let resized = image.resize(MAX_WIDTH, MAX_HEIGHT, FilterType::Triangle).to_rgb8();
+    let mut output = Vec::new();
+    JpegEncoder::new_with_quality(&mut output, 85)
+        .encode_image(&resized)
+        .ok()?;
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, ImageOutputFormat, Rgb, RgbImage};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        RgbImage::from_pixel(width, height, Rgb([100, 65, 164]))
+            .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_shrink() {
+        let small = png(320, 180);
+        assert_eq!(dimensions(&small), Some((320, 180)));
+        assert_eq!(shrink(small.clone()).await.unwrap(), small);
+
+        let large = image::load_from_memory(&shrink(png(3840, 1080)).await.unwrap()).unwrap();
+        assert_eq!(large.dimensions(), (1920, 540));
+
+        assert_eq!(dimensions(b"not an image"), None);
+        assert_eq!(shrink(b"not an image".to_vec()).await.unwrap(), b"not an image");
+    }
+}
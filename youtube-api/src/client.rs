@@ -0,0 +1,182 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use commons::source::{LiveStream, SourceVideo, StreamSource};
+use reqwest::Client as HttpClient;
+use serde::de::DeserializeOwned;
+
+use crate::{error::RequestError, ListResponse, PlaylistItem, Video};
+
+const BASE_URL: &str = "https://www.googleapis.com/youtube/v3";
+/// Number of recent uploads of each channel, which are checked for live broadcasts
+const RECENT_UPLOADS: &str = "10";
+/// Maximum number of ids for one request of the videos endpoint
+const MAX_BATCH_SIZE: usize = 50;
+
+pub struct YoutubeClient {
+    api_key: Box<str>,
+    http: HttpClient,
+}
+
+impl YoutubeClient {
+    pub fn new(api_key: Box<str>) -> Self {
+        Self::with_http(api_key, HttpClient::new())
+    }
+
+    pub fn with_http(api_key: Box<str>, http: HttpClient) -> Self {
+        Self { api_key, http }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, endpoint: &str, query: &[(&str, &str)]) -> Result<T, RequestError> {
+        let response = self
+            .http
+            .get(format!("{}/{}", BASE_URL, endpoint))
+            .query(query)
+            .query(&[("key", self.api_key.as_ref())])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(RequestError::from(response.status()));
+        }
+
+        Ok(serde_json::from_slice(&response.bytes().await?)?)
+    }
+
+    /// The ids of the most recent uploads of a channel, which includes its current and upcoming broadcasts.
+    ///
+    /// Costs 1 quota unit, unlike the 100 units of a search for live broadcasts.
+    pub async fn get_recent_uploads(&self, channel_id: &str) -> Result<Vec<Box<str>>, RequestError> {
+        // The uploads playlist of a channel has the id of the channel, with UU instead of the UC prefix
+        let playlist = match channel_id.strip_prefix("UC") {
+            Some(id) => format!("UU{id}"),
+            None => channel_id.to_owned(),
+        };
+
+        let body: ListResponse<PlaylistItem> = self
+            .get(
+                "playlistItems",
+                &[
+                    ("part", "contentDetails"),
+                    ("playlistId", playlist.as_str()),
+                    ("maxResults", RECENT_UPLOADS),
+                ],
+            )
+            .await?;
+        Ok(body
+            .items
+            .into_iter()
+            .map(|item| item.content_details.video_id)
+            .collect())
+    }
+
+    /// Fetches the videos with their broadcast details, which costs 1 quota unit for every 50 ids.
+    pub async fn get_videos(&self, ids: &[Box<str>]) -> Result<Vec<Video>, RequestError> {
+        let mut videos = Vec::with_capacity(ids.len());
+        for batch in ids.chunks(MAX_BATCH_SIZE) {
+            let ids = batch.join(",");
+            let body: ListResponse<Video> = self
+                .get(
+                    "videos",
+                    &[
+                        ("part", "snippet,liveStreamingDetails,contentDetails"),
+                        ("id", ids.as_str()),
+                        ("maxResults", "50"),
+                    ],
+                )
+                .await?;
+            videos.extend(body.items);
+        }
+        Ok(videos)
+    }
+
+    pub async fn get_thumbnail(&self, url: &str) -> Result<Vec<u8>, RequestError> {
+        let response = self.http.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(RequestError::from(response.status()));
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[async_trait]
+impl StreamSource for YoutubeClient {
+    fn platform(&self) -> &'static str {
+        "YouTube"
+    }
+
+    fn color(&self) -> u32 {
+        0xFF0000
+    }
+
+    fn stream_url(&self, _channel: &str, stream_id: &str) -> String {
+        format!("https://www.youtube.com/watch?v={stream_id}")
+    }
+
+    async fn get_live_streams(&self, channels: &[Box<str>]) -> anyhow::Result<Vec<LiveStream>> {
+        let mut uploads = Vec::new();
+        for channel in channels {
+            // A failed channel fails the whole poll, so its stream is not considered offline,
+            // and the remaining requests don't use up more of the quota once it is exceeded.
+            let ids = self
+                .get_recent_uploads(channel)
+                .await
+                .with_context(|| format!("Failed to fetch youtube uploads of {channel}"))?;
+            uploads.extend(ids.into_iter().map(|id| (id, channel.clone())));
+        }
+
+        let ids: Vec<Box<str>> = uploads.iter().map(|(id, _)| id.clone()).collect();
+        let videos = self
+            .get_videos(&ids)
+            .await
+            .context("Failed to fetch youtube broadcasts")?;
+
+        let mut live = Vec::new();
+        for video in videos {
+            if !video.is_live() {
+                continue;
+            }
+            let Some(details) = video.live_streaming_details else {
+                continue;
+            };
+            let Some(started_at) = details.actual_start_time else {
+                continue;
+            };
+            let Some((_, channel)) = uploads.iter().find(|(id, _)| *id == video.id) else {
+                continue;
+            };
+
+            live.push(LiveStream {
+                thumbnail_url: video.snippet.thumbnails.best().unwrap_or_default().into(),
+                id: video.id,
+                user_id: video.snippet.channel_id,
+                user_login: channel.clone(),
+                user_name: video.snippet.channel_title,
+                title: video.snippet.title,
+                category: "".into(),
+                started_at,
+                viewer_count: details
+                    .concurrent_viewers
+                    .and_then(|viewers| viewers.parse().ok())
+                    .unwrap_or_default(),
+            });
+        }
+
+        Ok(live)
+    }
+
+    async fn get_video(&self, _channel: &str, stream_id: &str) -> anyhow::Result<Option<SourceVideo>> {
+        let video = self.get_videos(&[stream_id.into()]).await?.pop();
+        // The broadcast is kept as a video once it ended, unless the channel deleted or hid it
+        Ok(video.filter(Video::is_ended).map(|video| SourceVideo {
+            url: video.url().into(),
+            thumbnail_url: video.snippet.thumbnails.best().unwrap_or_default().into(),
+            duration: video.duration(),
+            id: video.id,
+            title: video.snippet.title,
+        }))
+    }
+
+    async fn get_thumbnail(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(YoutubeClient::get_thumbnail(self, url).await?)
+    }
+}
@@ -0,0 +1,194 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ListResponse<T> {
+    #[serde(default)]
+    pub items: Vec<T>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItem {
+    pub content_details: PlaylistItemDetails,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItemDetails {
+    pub video_id: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    pub id: Box<str>,
+    pub snippet: Snippet,
+    /// Only set for videos which are or were live broadcasts
+    #[serde(default)]
+    pub live_streaming_details: Option<LiveStreamingDetails>,
+    #[serde(default)]
+    pub content_details: Option<VideoDetails>,
+}
+
+impl Video {
+    pub fn url(&self) -> String {
+        format!("https://www.youtube.com/watch?v={}", self.id)
+    }
+
+    /// Whether the broadcast started and did not end yet, which excludes scheduled streams.
+    pub fn is_live(&self) -> bool {
+        self.live_streaming_details.as_ref().map_or(false, |live| {
+            live.actual_start_time.is_some() && live.actual_end_time.is_none()
+        })
+    }
+
+    /// Whether the video is the recording of a broadcast which already ended.
+    pub fn is_ended(&self) -> bool {
+        self.live_streaming_details
+            .as_ref()
+            .map_or(false, |live| live.actual_end_time.is_some())
+    }
+
+    /// The length of the video in seconds, which is zero while it is live.
+    pub fn duration(&self) -> u64 {
+        self.content_details
+            .as_ref()
+            .map_or(0, |details| parse_duration(&details.duration))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveStreamingDetails {
+    #[serde(default)]
+    pub actual_start_time: Option<eos::DateTime>,
+    #[serde(default)]
+    pub actual_end_time: Option<eos::DateTime>,
+    /// The number of viewers as a string, missing if the channel hides it
+    #[serde(default)]
+    pub concurrent_viewers: Option<Box<str>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VideoDetails {
+    /// ISO 8601 duration, like "PT1H2M3S"
+    #[serde(default)]
+    pub duration: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Snippet {
+    pub channel_id: Box<str>,
+    pub channel_title: Box<str>,
+    pub title: Box<str>,
+    #[serde(default)]
+    pub live_broadcast_content: Box<str>,
+    #[serde(default)]
+    pub thumbnails: Thumbnails,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Thumbnails {
+    pub high: Option<Thumbnail>,
+    pub medium: Option<Thumbnail>,
+    pub default: Option<Thumbnail>,
+}
+
+impl Thumbnails {
+    /// The url of the largest available thumbnail
+    pub fn best(&self) -> Option<&str> {
+        self.high
+            .as_ref()
+            .or(self.medium.as_ref())
+            .or(self.default.as_ref())
+            .map(|t| t.url.as_ref())
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Thumbnail {
+    pub url: Box<str>,
+}
+
+/// Converts an ISO 8601 duration of a video, like "PT1H2M3S", into seconds.
+///
+/// Videos longer than a day also have days, other units are not used by YouTube. Invalid durations are zero.
+pub fn parse_duration(duration: &str) -> u64 {
+    let Some(duration) = duration.strip_prefix('P') else {
+        return 0;
+    };
+
+    let mut seconds = 0;
+    let mut number = 0;
+    for c in duration.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            number = number * 10 + u64::from(digit);
+            continue;
+        }
+
+        let unit = match c {
+            'D' => 86400,
+            'H' => 3600,
+            'M' => 60,
+            'S' => 1,
+            'T' => 0,
+            _ => return 0,
+        };
+        seconds += number * unit;
+        number = 0;
+    }
+    seconds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_video_response() {
+        let response: ListResponse<Video> = serde_json::from_str(
+            r#"{
+                "kind": "youtube#videoListResponse",
+                "items": [{
+                    "id": "jfKfPfyJRdk",
+                    "snippet": {
+                        "channelId": "UCSJ4gkVC6NrvII8umztf0Ow",
+                        "channelTitle": "Lofi Girl",
+                        "title": "lofi hip hop radio",
+                        "liveBroadcastContent": "live",
+                        "thumbnails": {
+                            "default": { "url": "https://i.ytimg.com/vi/jfKfPfyJRdk/default_live.jpg" },
+                            "high": { "url": "https://i.ytimg.com/vi/jfKfPfyJRdk/hqdefault_live.jpg" }
+                        }
+                    },
+                    "contentDetails": { "duration": "P0D" },
+                    "liveStreamingDetails": {
+                        "actualStartTime": "2022-07-12T19:47:51Z",
+                        "concurrentViewers": "31235"
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let video = &response.items[0];
+        assert!(video.is_live());
+        assert!(!video.is_ended());
+        assert_eq!(video.duration(), 0);
+        assert_eq!(video.url(), "https://www.youtube.com/watch?v=jfKfPfyJRdk");
+        assert_eq!(
+            video.snippet.thumbnails.best(),
+            Some("https://i.ytimg.com/vi/jfKfPfyJRdk/hqdefault_live.jpg")
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT1H2M3S"), 3723);
+        assert_eq!(parse_duration("PT45S"), 45);
+        assert_eq!(parse_duration("P1DT2H"), 93600);
+        assert_eq!(parse_duration("P0D"), 0);
+        assert_eq!(parse_duration("1H"), 0);
+    }
+}